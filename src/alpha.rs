@@ -0,0 +1,127 @@
+//! Straight and premultiplied alpha. Documents are always stored in straight alpha; a document's
+//! alpha mode only decides how its files are read and written, for game engines that expect
+//! premultiplied textures. Whatever mixes neighbouring pixels works premultiplied either way, so
+//! the color of fully transparent pixels can't bleed into edges as dark halos: resampling, the
+//! blurs and smudging convert with the functions here, and layer compositing weights colors by
+//! alpha as it blends.
+
+use nannou::image::{imageops::FilterType, DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+use crate::filters::par_map;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlphaMode {
+    #[default]
+    Straight,
+    /// Color channels are stored already multiplied by alpha.
+    Premultiplied,
+}
+
+impl AlphaMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AlphaMode::Straight => "Straight",
+            AlphaMode::Premultiplied => "Premultiplied",
+        }
+    }
+
+    pub fn next(&self) -> AlphaMode {
+        match self {
+            AlphaMode::Straight => AlphaMode::Premultiplied,
+            AlphaMode::Premultiplied => AlphaMode::Straight,
+        }
+    }
+
+    /// Converts pixels read from a file in this mode to straight alpha.
+    pub fn import(&self, image: DynamicImage) -> DynamicImage {
+        match self {
+            AlphaMode::Straight => image,
+            AlphaMode::Premultiplied => DynamicImage::ImageRgba8(unpremultiply(&image.to_rgba8())),
+        }
+    }
+
+    /// Converts straight alpha pixels to this mode for writing to a file.
+    pub fn export(&self, image: &DynamicImage) -> DynamicImage {
+        match self {
+            AlphaMode::Straight => image.clone(),
+            AlphaMode::Premultiplied => DynamicImage::ImageRgba8(premultiply(&image.to_rgba8())),
+        }
+    }
+}
+
+pub fn premultiply(src: &RgbaImage) -> RgbaImage {
+    par_map(src, |x, y| {
+        premultiply_pixel(src.get_pixel(x, y).0).map(|c| (c * 255.0).round() as u8)
+    })
+}
+
+/// Inverse of [`premultiply`]. Color lost where alpha was 0 stays black.
+pub fn unpremultiply(src: &RgbaImage) -> RgbaImage {
+    par_map(src, |x, y| {
+        unpremultiply_pixel(src.get_pixel(x, y).0.map(|c| c as f32 / 255.0))
+    })
+}
+
+/// An 8-bit straight alpha pixel as 0-1 premultiplied, for blending that mustn't pull in the
+/// hidden color of transparent pixels.
+pub fn premultiply_pixel([r, g, b, a]: [u8; 4]) -> [f32; 4] {
+    let a = a as f32 / 255.0;
+    let channel = |c: u8| c as f32 / 255.0 * a;
+    [channel(r), channel(g), channel(b), a]
+}
+
+/// Inverse of [`premultiply_pixel`].
+pub fn unpremultiply_pixel([r, g, b, a]: [f32; 4]) -> [u8; 4] {
+    if a <= 0.0 {
+        return [0; 4];
+    }
+    let channel = |v: f32| (v * 255.0).round().clamp(0.0, 255.0) as u8;
+    [channel(r / a), channel(g / a), channel(b / a), channel(a)]
+}
+
+/// Resamples straight alpha pixels to `width` by `height` without dark fringes around
+/// transparency.
+pub fn resize_exact(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+) -> DynamicImage {
+    let premultiplied = DynamicImage::ImageRgba8(premultiply(&image.to_rgba8()));
+    let resized = premultiplied.resize_exact(width, height, filter);
+    DynamicImage::ImageRgba8(unpremultiply(&resized.to_rgba8()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::Rgba;
+
+    #[test]
+    fn opaque_pixels_round_trip() {
+        for c in 0..=255 {
+            let pixel = [c, 255 - c, c / 2, 255];
+            assert_eq!(unpremultiply_pixel(premultiply_pixel(pixel)), pixel);
+        }
+    }
+
+    #[test]
+    fn transparent_pixels_lose_their_color() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([0, 255, 0, 0]));
+        assert_eq!(premultiply(&image).get_pixel(0, 0).0, [0, 0, 0, 0]);
+        assert_eq!(unpremultiply(&image).get_pixel(0, 0).0, [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn images_match_pixels() {
+        let image = RgbaImage::from_pixel(1, 1, Rgba([200, 100, 50, 128]));
+        let [r, g, b, a] = premultiply(&image).get_pixel(0, 0).0;
+        assert_eq!([r, g, b, a], [100, 50, 25, 128]);
+        assert_eq!(
+            unpremultiply(&premultiply(&image)).get_pixel(0, 0).0,
+            [199, 100, 50, 128]
+        );
+    }
+}
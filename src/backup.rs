@@ -0,0 +1,203 @@
+//! Rolling backups of project files. While a document saved as a project (.ied) keeps changing,
+//! a copy of it is written to its backup directory every interval, along with a thumbnail, and
+//! only the newest few are kept. Versions are listed on the Workbench to be restored.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use nannou::image::{self, DynamicImage};
+use serde::{Deserialize, Serialize};
+
+use crate::layers::LayerStack;
+use crate::project::{self, Metadata};
+use crate::textures::Uploaded;
+
+/// Largest side of the thumbnails saved with each version.
+pub const THUMBNAIL_SIZE: u32 = 96;
+
+/// Set while backups are being written, so a slow write isn't overlapped by the next.
+static WRITING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackupOptions {
+    pub enabled: bool,
+    /// Versions kept for each project. Older ones are deleted.
+    pub count: usize,
+    /// Minutes between versions while the document keeps changing.
+    pub interval_minutes: f32,
+}
+
+impl Default for BackupOptions {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            count: 10,
+            interval_minutes: 5.0,
+        }
+    }
+}
+
+/// A backed up version of a project.
+pub struct Version {
+    pub path: PathBuf,
+    /// When it was written, in seconds since the Unix epoch.
+    pub time: u64,
+    pub thumbnail: Option<Uploaded>,
+}
+
+impl Version {
+    /// When the version was written, as `YYYY-MM-DD HH:MM` in UTC.
+    pub fn label(&self) -> String {
+        let (days, seconds) = ((self.time / 86400) as i64, self.time % 86400);
+        let (year, month, day) = civil_date(days);
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds / 60 % 60
+        )
+    }
+}
+
+/// Year, month and day of a count of days since 1970-01-01, in the proleptic Gregorian calendar.
+fn civil_date(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months counted from March, so the leap day comes last
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    (year, month, day)
+}
+
+/// Directory the backups of the project at `project` are kept in. The path's hash keeps projects
+/// with the same name apart.
+pub fn backup_dir(project: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    project.hash(&mut hasher);
+    let stem = project
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("image_editor")
+        .join("backups")
+        .join(format!("{}-{:016x}", stem, hasher.finish()))
+}
+
+/// Backed up versions of the project at `project`, newest first.
+pub fn versions(project: &Path) -> Vec<Version> {
+    let dir = backup_dir(project);
+    let mut versions: Vec<Version> = std::fs::read_dir(&dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "ied" {
+                return None;
+            }
+            let time = path.file_stem()?.to_str()?.parse().ok()?;
+            let thumbnail = image::open(path.with_extension("png"))
+                .ok()
+                .map(Uploaded::new);
+            Some(Version {
+                path,
+                time,
+                thumbnail,
+            })
+        })
+        .collect();
+    versions.sort_by_key(|v| std::cmp::Reverse(v.time));
+    versions
+}
+
+/// Timer deciding when the next round of backups is due.
+#[derive(Default)]
+pub struct Backups {
+    last: Option<Instant>,
+}
+
+impl Backups {
+    pub fn due(&self, options: &BackupOptions) -> bool {
+        let interval = Duration::from_secs_f32(options.interval_minutes.max(0.1) * 60.0);
+        options.enabled
+            && self.last.map_or(true, |t| t.elapsed() >= interval)
+            && !WRITING.load(Ordering::Acquire)
+    }
+
+    /// Writes a version of each project on a background thread, given with the path it is
+    /// saved at, then deletes all but the newest `count` of its versions.
+    pub fn run(&mut self, projects: Vec<(PathBuf, LayerStack, Metadata)>, count: usize) {
+        self.last = Some(Instant::now());
+        if projects.is_empty() {
+            return;
+        }
+        WRITING.store(true, Ordering::Release);
+        std::thread::spawn(move || {
+            for (path, layers, metadata) in projects {
+                if let Err(e) = write(&path, &layers, &metadata, count) {
+                    log::warn!("Failed to back up {}: {}", path.display(), e);
+                }
+            }
+            WRITING.store(false, Ordering::Release);
+        });
+    }
+}
+
+fn write(project: &Path, layers: &LayerStack, metadata: &Metadata, count: usize) -> io::Result<()> {
+    let dir = backup_dir(project);
+    std::fs::create_dir_all(&dir)?;
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    // Written aside and renamed so a half-written version is never listed
+    let partial = dir.join(format!("{}.partial", time));
+    project::save(layers, metadata, &partial)?;
+    std::fs::rename(&partial, dir.join(format!("{}.ied", time)))?;
+    layers
+        .composite()
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .save(dir.join(format!("{}.png", time)))
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    for old in versions(project).into_iter().skip(count.max(1)) {
+        std::fs::remove_file(&old.path)?;
+        let _ = std::fs::remove_file(old.path.with_extension("png"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn civil_date_starts_at_the_epoch() {
+        assert_eq!(civil_date(0), (1970, 1, 1));
+        assert_eq!(civil_date(-1), (1969, 12, 31));
+    }
+
+    #[test]
+    fn civil_date_handles_leap_days() {
+        // 2000 is a leap year although it's a century, 2100 isn't
+        assert_eq!(civil_date(11_016), (2000, 2, 29));
+        assert_eq!(civil_date(11_017), (2000, 3, 1));
+        assert_eq!(civil_date(47_540), (2100, 2, 28));
+        assert_eq!(civil_date(47_541), (2100, 3, 1));
+    }
+}
@@ -0,0 +1,100 @@
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use nannou::image::{self, imageops::FilterType, GenericImageView};
+use rayon::prelude::*;
+
+use crate::filters::Filter;
+use crate::palette;
+
+/// One step of a batch chain, applied to every file in order.
+#[derive(Clone)]
+pub enum BatchOp {
+    Resize { scale: f32 },
+    Filter(Filter),
+    Quantize { colors: usize },
+}
+
+/// Outcome for a single file, sent back to the UI thread as soon as it finishes.
+pub struct BatchResult {
+    pub path: PathBuf,
+    pub result: Result<(), String>,
+}
+
+/// A running batch. Results arrive on `results` in completion order.
+pub struct Batch {
+    pub total: usize,
+    pub done: Vec<BatchResult>,
+    results: Receiver<BatchResult>,
+}
+
+impl Batch {
+    /// Collects results that have arrived since the last poll.
+    pub fn poll(&mut self) {
+        self.done.extend(self.results.try_iter());
+    }
+
+    pub fn finished(&self) -> bool {
+        self.done.len() == self.total
+    }
+}
+
+fn is_image(path: &Path) -> bool {
+    matches!(
+        path.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+            .as_deref(),
+        Some("png" | "jpg" | "jpeg" | "bmp" | "gif" | "tga" | "tif" | "tiff" | "webp")
+    )
+}
+
+fn process(path: &Path, output_dir: &Path, ops: &[BatchOp]) -> Result<(), String> {
+    let mut img = image::open(path).map_err(|e| e.to_string())?;
+    for op in ops {
+        img = match op {
+            BatchOp::Resize { scale } => {
+                let w = ((img.width() as f32 * scale).round() as u32).max(1);
+                let h = ((img.height() as f32 * scale).round() as u32).max(1);
+                crate::alpha::resize_exact(&img, w, h, FilterType::Lanczos3)
+            }
+            BatchOp::Filter(filter) => filter.as_filter().apply(&img),
+            BatchOp::Quantize { colors } => palette::quantize(&img, *colors),
+        };
+    }
+
+    let name = path.file_name().ok_or("file has no name")?;
+    img.save(output_dir.join(name)).map_err(|e| e.to_string())
+}
+
+/// Runs `ops` over every image in `input_dir` on the rayon pool, writing results with the same
+/// file names into `output_dir`. Refuses to run with `output_dir` the same folder as `input_dir`,
+/// which would overwrite the originals.
+pub fn run(input_dir: &Path, output_dir: PathBuf, ops: Vec<BatchOp>) -> std::io::Result<Batch> {
+    let files: Vec<PathBuf> = std::fs::read_dir(input_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.is_file() && is_image(path))
+        .collect();
+    std::fs::create_dir_all(&output_dir)?;
+    if std::fs::canonicalize(input_dir)? == std::fs::canonicalize(&output_dir)? {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the output folder is the input folder, pick another so the originals are kept",
+        ));
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let total = files.len();
+    std::thread::spawn(move || {
+        files.into_par_iter().for_each_with(tx, |tx, path| {
+            let result = process(&path, &output_dir, &ops);
+            let _ = tx.send(BatchResult { path, result });
+        });
+    });
+
+    Ok(Batch {
+        total,
+        done: Vec::new(),
+        results: rx,
+    })
+}
@@ -0,0 +1,386 @@
+//! Brush tips and the stamping of dabs along a stroke. Shared by every tool that paints with the
+//! brush, so they all honor the brush size, tip, flow and selection the same way.
+
+use std::sync::Arc;
+
+use line_drawing::Bresenham;
+use nannou::image::{GenericImageView, GrayImage, Luma, Rgba};
+use nannou::prelude::Vec2;
+
+use crate::alpha::{premultiply_pixel, unpremultiply_pixel};
+use crate::canvas::Canvas;
+use crate::compositing::BrushBlend;
+use crate::stamp::Stamp;
+use crate::stroke::Stroke;
+use crate::symmetry::Symmetry;
+
+/// The shape of a single dab.
+pub trait Brush {
+    /// Strength (0-1) of a dab `size` pixels wide at offset `(dx, dy)` from its center.
+    fn coverage(&self, dx: f32, dy: f32, size: f32) -> f32;
+    /// Farthest a dab reaches from its center.
+    fn radius(&self, size: f32) -> f32 {
+        size / 2.0
+    }
+    /// Paints whole pixels at full strength: dabs land on every pixel the stroke crosses, ignoring
+    /// the spacing and flow.
+    fn pixel_snapped(&self) -> bool {
+        false
+    }
+}
+
+/// Solid disc with a one pixel anti-aliased edge.
+pub struct HardRound;
+
+impl Brush for HardRound {
+    fn coverage(&self, dx: f32, dy: f32, size: f32) -> f32 {
+        let dist = (dx * dx + dy * dy).sqrt();
+        (size / 2.0 + 0.5 - dist).clamp(0.0, 1.0)
+    }
+}
+
+/// Quadratic falloff from the center, reaching zero a little outside the nominal size.
+pub struct SoftRound;
+
+impl Brush for SoftRound {
+    fn coverage(&self, dx: f32, dy: f32, size: f32) -> f32 {
+        (1.0 - 2.0 * (dx * dx + dy * dy) / (size * size)).max(0.0)
+    }
+
+    fn radius(&self, size: f32) -> f32 {
+        size * std::f32::consts::FRAC_1_SQRT_2
+    }
+}
+
+pub struct Square;
+
+impl Brush for Square {
+    fn coverage(&self, dx: f32, dy: f32, size: f32) -> f32 {
+        let half = (size / 2.0).max(0.5);
+        if dx.abs() < half && dy.abs() < half {
+            1.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Square of whole pixels for pixel art, the brush size rounded to a whole number of pixels on a
+/// side. At size 1 it paints exactly the pixel under the cursor.
+pub struct Pencil;
+
+impl Brush for Pencil {
+    fn coverage(&self, dx: f32, dy: f32, size: f32) -> f32 {
+        let side = size.round().max(1.0);
+        // Even sizes put the extra pixel after the center
+        let first = -((side - 1.0) / 2.0).floor();
+        let inside = |d: f32| (first..first + side).contains(&d.round());
+        if inside(dx) && inside(dy) {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    fn radius(&self, size: f32) -> f32 {
+        size.round().max(1.0) / 2.0 + 1.0
+    }
+
+    fn pixel_snapped(&self) -> bool {
+        true
+    }
+}
+
+/// A grayscale tip scaled to the brush size, where white paints fully.
+#[derive(Clone)]
+pub struct Textured {
+    pub mask: Arc<GrayImage>,
+}
+
+impl Textured {
+    /// Uses a stamp as the tip: dark, opaque pixels paint and light or transparent ones don't.
+    pub fn from_stamp(stamp: &Stamp) -> Textured {
+        let mask = GrayImage::from_fn(stamp.image.width(), stamp.image.height(), |x, y| {
+            let [r, g, b, a] = stamp.image.get_pixel(x, y).0;
+            let luma = (r as f32 * 0.299 + g as f32 * 0.587 + b as f32 * 0.114) / 255.0;
+            Luma([((1.0 - luma) * a as f32).round() as u8])
+        });
+        Textured {
+            mask: Arc::new(mask),
+        }
+    }
+}
+
+impl Brush for Textured {
+    fn coverage(&self, dx: f32, dy: f32, size: f32) -> f32 {
+        let (w, h) = self.mask.dimensions();
+        let (u, v) = (dx / size + 0.5, dy / size + 0.5);
+        if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+            return 0.0;
+        }
+        self.mask
+            .get_pixel((u * w as f32) as u32, (v * h as f32) as u32)[0] as f32
+            / 255.0
+    }
+}
+
+/// Brush tips selectable on the Workbench.
+#[derive(Clone)]
+pub enum BrushType {
+    HardRound,
+    SoftRound,
+    Square,
+    Pencil,
+    Textured(Textured),
+}
+
+impl PartialEq for BrushType {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (BrushType::Textured(a), BrushType::Textured(b)) => Arc::ptr_eq(&a.mask, &b.mask),
+            _ => std::mem::discriminant(self) == std::mem::discriminant(other),
+        }
+    }
+}
+
+impl BrushType {
+    pub fn as_brush(&self) -> &dyn Brush {
+        match self {
+            BrushType::HardRound => &HardRound,
+            BrushType::SoftRound => &SoftRound,
+            BrushType::Square => &Square,
+            BrushType::Pencil => &Pencil,
+            BrushType::Textured(brush) => brush,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrushType::HardRound => "Hard Round",
+            BrushType::SoftRound => "Soft Round",
+            BrushType::Square => "Square",
+            BrushType::Pencil => "Pencil",
+            BrushType::Textured(_) => "Textured",
+        }
+    }
+}
+
+/// Edges of the pixels a dab `size` wide centered on `center` paints at more than `strength`, as
+/// segments in canvas pixel coordinates. Pixels are sampled the way `stamp` samples them, so this
+/// traces what a dab really covers rather than the nominal size.
+pub fn footprint(brush: &dyn Brush, center: Vec2, size: f32, strength: f32) -> Vec<(Vec2, Vec2)> {
+    // Pixel snapped strokes stamp on whole pixels
+    let center = if brush.pixel_snapped() {
+        center.floor()
+    } else {
+        center
+    };
+    let covered =
+        |x: i32, y: i32| brush.coverage(x as f32 - center.x, y as f32 - center.y, size) > strength;
+
+    let reach = brush.radius(size).ceil() as i32 + 1;
+    let (cx, cy) = (center.x.floor() as i32, center.y.floor() as i32);
+    let mut edges = Vec::new();
+    for y in cy - reach..=cy + reach {
+        for x in cx - reach..=cx + reach {
+            if !covered(x, y) {
+                continue;
+            }
+            let corner = |dx: i32, dy: i32| Vec2::new((x + dx) as f32, (y + dy) as f32);
+            if !covered(x - 1, y) {
+                edges.push((corner(0, 0), corner(0, 1)));
+            }
+            if !covered(x + 1, y) {
+                edges.push((corner(1, 0), corner(1, 1)));
+            }
+            if !covered(x, y - 1) {
+                edges.push((corner(0, 0), corner(1, 0)));
+            }
+            if !covered(x, y + 1) {
+                edges.push((corner(0, 1), corner(1, 1)));
+            }
+        }
+    }
+    edges
+}
+
+/// Settings for every dab of a stroke.
+#[derive(Clone)]
+pub struct Dab {
+    pub size: f32,
+    pub color: [u8; 4],
+    pub opacity: f32,
+    pub flow: f32,
+    pub blend: BrushBlend,
+    /// Distance between dabs as a fraction of the size. Never less than a pixel.
+    pub spacing: f32,
+    /// Where each dab is repeated. Smudging isn't repeated, as the colors it carries belong to
+    /// one place on the canvas.
+    pub symmetry: Symmetry,
+}
+
+/// Stamps dabs `dab.spacing` apart along the segment from `from` to `to`, writing the stroke's
+/// result onto `canvas`. The distance since the last dab carries over between segments, so a
+/// stroke drawn as many short segments is spaced evenly.
+pub fn stroke_segment(
+    canvas: &mut Canvas,
+    stroke: &mut Stroke,
+    brush: &dyn Brush,
+    from: Vec2,
+    to: Vec2,
+    dab: &Dab,
+) {
+    if brush.pixel_snapped() {
+        return pencil_segment(canvas, stroke, brush, from, to, dab);
+    }
+
+    let step = (dab.size * dab.spacing).max(1.0);
+    let length = from.distance(to);
+
+    let mut along = (step - stroke.travel).max(0.0);
+    while along <= length {
+        let center = if length > 0.0 {
+            from.lerp(to, along / length)
+        } else {
+            from
+        };
+        match dab.blend {
+            BrushBlend::Smudge => smudge(canvas, stroke, brush, center, dab),
+            _ => {
+                let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+                for center in dab.symmetry.positions(center, width, height) {
+                    stamp(canvas, stroke, brush, center, dab);
+                }
+            }
+        }
+        along += step;
+    }
+    stroke.travel = length - (along - step);
+}
+
+/// Stamps a full-strength dab on every pixel of the line from `from` to `to`, so pixel art lines
+/// come out one pixel wide without gaps or doubled corners.
+fn pencil_segment(
+    canvas: &mut Canvas,
+    stroke: &mut Stroke,
+    brush: &dyn Brush,
+    from: Vec2,
+    to: Vec2,
+    dab: &Dab,
+) {
+    let dab = Dab {
+        flow: 1.0,
+        ..dab.clone()
+    };
+    let (width, height) = (canvas.width() as f32, canvas.height() as f32);
+    let (from, to) = (from.floor(), to.floor());
+    for (x, y) in Bresenham::new((from.x as i32, from.y as i32), (to.x as i32, to.y as i32)) {
+        // Mirror pixel centers so mirrored dabs land on the mirrored pixel
+        let center = Vec2::new(x as f32, y as f32) + 0.5;
+        match dab.blend {
+            BrushBlend::Smudge => smudge(canvas, stroke, brush, center.floor(), &dab),
+            _ => {
+                for center in dab.symmetry.positions(center, width, height) {
+                    stamp(canvas, stroke, brush, center.floor(), &dab);
+                }
+            }
+        }
+    }
+    stroke.travel = 0.0;
+}
+
+/// Stamps a single dab centered on `center`.
+fn stamp(canvas: &mut Canvas, stroke: &mut Stroke, brush: &dyn Brush, center: Vec2, dab: &Dab) {
+    let radius = brush.radius(dab.size);
+    let (x0, x1) = (
+        (center.x - radius).floor() as i32,
+        (center.x + radius).ceil() as i32,
+    );
+    let (y0, y1) = (
+        (center.y - radius).floor() as i32,
+        (center.y + radius).ceil() as i32,
+    );
+    let (width, height) = ((x1 - x0 + 1) as u32, (y1 - y0 + 1) as u32);
+
+    canvas.stamp(x0, y0, width, height, |x, y, _| {
+        let strength = brush.coverage(x as f32 - center.x, y as f32 - center.y, dab.size);
+        if strength <= 0.0 {
+            return None;
+        }
+
+        stroke.dab(x, y, strength * dab.flow);
+        match dab.blend {
+            BrushBlend::Color(mode) => Some(stroke.paint(x, y, dab.color, dab.opacity, mode)),
+            BrushBlend::EraseAlpha => Some(stroke.erase(x, y, dab.opacity)),
+            BrushBlend::CloneStamp(offset) => stroke.clone_stamp(x, y, offset, dab.opacity),
+            BrushBlend::Smudge => unreachable!("smudge dabs are stamped by `smudge`"),
+        }
+    });
+}
+
+/// Premultiplied colors a smudge stroke carries, in a square around the dab center.
+pub struct Smudge {
+    radius: i32,
+    colors: Vec<[f32; 4]>,
+}
+
+/// Smudges a dab centered on `center`: the colors carried from the previous dab are laid down at
+/// the brush coverage, and the brush picks up what it covered. The brush opacity is how much of
+/// the carried color survives each dab, so at full opacity paint is dragged the whole stroke.
+///
+/// Unlike the other dabs this works on the current pixels rather than the stroke's original
+/// ones, since it has to push along paint laid by earlier dabs. The whole footprint is read
+/// before any of it is written, so a dab never picks up its own output.
+fn smudge(canvas: &mut Canvas, stroke: &mut Stroke, brush: &dyn Brush, center: Vec2, dab: &Dab) {
+    let (width, height) = (canvas.width() as i32, canvas.height() as i32);
+    let radius = brush.radius(dab.size).ceil() as i32;
+    let side = 2 * radius + 1;
+    let (cx, cy) = (center.x.round() as i32, center.y.round() as i32);
+    let position = |i: i32| (cx - radius + i % side, cy - radius + i / side);
+
+    let under: Vec<Option<[f32; 4]>> = (0..side * side)
+        .map(position)
+        .map(|(x, y)| {
+            (x >= 0 && y >= 0 && x < width && y < height)
+                .then(|| premultiply_pixel(canvas.pixels().get_pixel(x as u32, y as u32).0))
+        })
+        .collect();
+
+    if stroke.smudge.as_ref().map_or(true, |s| s.radius != radius) {
+        // The first dab, or the first after pressure changed the size, only picks up paint
+        stroke.smudge = Some(Smudge {
+            radius,
+            colors: under.iter().map(|c| c.unwrap_or([0.0; 4])).collect(),
+        });
+        return;
+    }
+    let carried = match &mut stroke.smudge {
+        Some(smudge) => &mut smudge.colors,
+        None => return,
+    };
+
+    let (left, top) = (cx - radius, cy - radius);
+    canvas.stamp(left, top, side as u32, side as u32, |x, y, _| {
+        let i = ((y as i32 - top) * side + (x as i32 - left)) as usize;
+        let color = under[i]?;
+        let strength =
+            brush.coverage(x as f32 - center.x, y as f32 - center.y, dab.size) * dab.flow;
+        (strength > 0.0).then(|| {
+            Rgba(unpremultiply_pixel(mix(
+                color,
+                carried[i],
+                strength.min(1.0),
+            )))
+        })
+    });
+    for (carried, color) in carried.iter_mut().zip(under) {
+        if let Some(color) = color {
+            *carried = mix(color, *carried, dab.opacity);
+        }
+    }
+}
+
+/// `a` moved `t` (0-1) of the way towards `b`.
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|c| a[c] + (b[c] - a[c]) * t)
+}
@@ -0,0 +1,76 @@
+//! The one way tools write to a layer. `Canvas::stamp` only visits the pixels of a rectangle that
+//! lie on the canvas and inside the selection, so tools describe what to paint and never bounds
+//! check or test the selection themselves.
+
+use nannou::image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+
+use crate::selection::Selection;
+
+pub struct Canvas<'a> {
+    pixels: &'a mut DynamicImage,
+    selection: Option<&'a Selection>,
+}
+
+impl<'a> Canvas<'a> {
+    /// Paints on `pixels`, only inside `selection` if there is one.
+    pub fn new(pixels: &'a mut DynamicImage, selection: Option<&'a Selection>) -> Canvas<'a> {
+        Canvas { pixels, selection }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixels.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixels.height()
+    }
+
+    /// The pixels as they are now, for tools that sample the layer. Reads aren't clipped to the
+    /// selection.
+    pub fn pixels(&self) -> &DynamicImage {
+        self.pixels
+    }
+
+    /// Whether `(x, y)` is on the canvas and inside the selection.
+    pub fn paintable(&self, x: u32, y: u32) -> bool {
+        x < self.width() && y < self.height() && self.selection.map_or(true, |s| s.contains(x, y))
+    }
+
+    /// Calls `paint` with the position and current color of every paintable pixel in the
+    /// `width` x `height` rectangle whose top-left corner is `(left, top)`, and writes back the
+    /// color it returns. `None` leaves the pixel as it is.
+    pub fn stamp<F>(&mut self, left: i32, top: i32, width: u32, height: u32, mut paint: F)
+    where
+        F: FnMut(u32, u32, Rgba<u8>) -> Option<Rgba<u8>>,
+    {
+        let clip = |start: i32, length: u32, size: u32| {
+            let start = start as i64;
+            (
+                start.clamp(0, size as i64) as u32,
+                (start + length as i64).clamp(0, size as i64) as u32,
+            )
+        };
+        let (x0, x1) = clip(left, width, self.pixels.width());
+        let (y0, y1) = clip(top, height, self.pixels.height());
+
+        for y in y0..y1 {
+            for x in x0..x1 {
+                if !self.paintable(x, y) {
+                    continue;
+                }
+                if let Some(pixel) = paint(x, y, self.pixels.get_pixel(x, y)) {
+                    self.pixels.put_pixel(x, y, pixel);
+                }
+            }
+        }
+    }
+
+    /// Stamps the whole canvas.
+    pub fn fill<F>(&mut self, paint: F)
+    where
+        F: FnMut(u32, u32, Rgba<u8>) -> Option<Rgba<u8>>,
+    {
+        let (width, height) = self.pixels.dimensions();
+        self.stamp(0, 0, width, height, paint);
+    }
+}
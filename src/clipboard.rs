@@ -0,0 +1,91 @@
+//! In-app clipboard for moving pixels between documents and layers.
+
+use nannou::image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+
+use crate::canvas::Canvas;
+use crate::layers::over;
+use crate::selection::Selection;
+
+/// Copied pixels and where they were copied from.
+pub struct Clip {
+    pub image: RgbaImage,
+    pub origin: (u32, u32),
+}
+
+/// How the clip is transformed when pasted.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PasteTransform {
+    None,
+    FlipHorizontal,
+    FlipVertical,
+    /// A quarter turn clockwise.
+    Rotate90,
+}
+
+impl PasteTransform {
+    pub const ALL: [PasteTransform; 4] = [
+        PasteTransform::None,
+        PasteTransform::FlipHorizontal,
+        PasteTransform::FlipVertical,
+        PasteTransform::Rotate90,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PasteTransform::None => "Paste",
+            PasteTransform::FlipHorizontal => "Paste Mirrored H",
+            PasteTransform::FlipVertical => "Paste Mirrored V",
+            PasteTransform::Rotate90 => "Paste Rotated 90",
+        }
+    }
+}
+
+impl Clip {
+    /// Copies the selected part of `image`, or all of it without a selection.
+    pub fn copy(image: &DynamicImage, selection: Option<&Selection>) -> Clip {
+        match selection {
+            Some(s) => Clip {
+                image: image.crop_imm(s.x, s.y, s.width, s.height).to_rgba8(),
+                origin: (s.x, s.y),
+            },
+            None => Clip {
+                image: image.to_rgba8(),
+                origin: (0, 0),
+            },
+        }
+    }
+
+    /// Blends the transformed clip over `image` at `at`, or where it was copied from, and returns
+    /// the area it covers so it can be selected. Only pixels inside `selection` are pasted over.
+    pub fn paste(
+        &self,
+        image: &mut DynamicImage,
+        at: Option<(u32, u32)>,
+        transform: PasteTransform,
+        selection: Option<&Selection>,
+    ) -> Option<Selection> {
+        let pixels = match transform {
+            PasteTransform::None => self.image.clone(),
+            PasteTransform::FlipHorizontal => imageops::flip_horizontal(&self.image),
+            PasteTransform::FlipVertical => imageops::flip_vertical(&self.image),
+            PasteTransform::Rotate90 => imageops::rotate90(&self.image),
+        };
+        let (x, y) = at.unwrap_or(self.origin);
+        let (width, height) = pixels.dimensions();
+        Canvas::new(image, selection).stamp(x as i32, y as i32, width, height, |px, py, dst| {
+            Some(over(dst, *pixels.get_pixel(px - x, py - y), 1.0))
+        });
+
+        let (w, h) = (image.width(), image.height());
+        Selection::from_corners(
+            (x as f32, y as f32).into(),
+            (
+                (x + pixels.width()) as f32 - 1.0,
+                (y + pixels.height()) as f32 - 1.0,
+            )
+                .into(),
+            w,
+            h,
+        )
+    }
+}
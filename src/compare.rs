@@ -0,0 +1,81 @@
+//! Comparing the canvas against a reference image, for checking exports and spotting changes in
+//! generated art. Images of different sizes are lined up at their top-left corners, and pixels
+//! covered by only one of them count as fully different.
+
+use nannou::image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CompareView {
+    /// Heatmap of the per-pixel difference in place of the canvas.
+    Difference,
+    /// Alternates between the canvas and the reference.
+    Blink,
+}
+
+impl CompareView {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompareView::Difference => "Difference",
+            CompareView::Blink => "Blink",
+        }
+    }
+
+    pub fn next(&self) -> CompareView {
+        match self {
+            CompareView::Difference => CompareView::Blink,
+            CompareView::Blink => CompareView::Difference,
+        }
+    }
+}
+
+/// Summary of a comparison.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct Stats {
+    /// Pixels that differ in any channel.
+    pub differing: usize,
+    /// Largest difference in any channel, 0-255.
+    pub max: u8,
+}
+
+/// Heatmap of how far apart the two images are at each pixel, going from black where they match
+/// through red and yellow to white, along with a summary.
+pub fn difference(canvas: &DynamicImage, reference: &DynamicImage) -> (DynamicImage, Stats) {
+    let width = canvas.width().max(reference.width());
+    let height = canvas.height().max(reference.height());
+    let pixel =
+        |image: &DynamicImage, x: u32, y: u32| image.in_bounds(x, y).then(|| image.get_pixel(x, y));
+
+    let mut stats = Stats::default();
+    let heatmap = RgbaImage::from_fn(width, height, |x, y| {
+        let delta = match (pixel(canvas, x, y), pixel(reference, x, y)) {
+            (Some(a), Some(b)) => (0..4)
+                .map(|c| (a[c] as i32 - b[c] as i32).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0),
+            _ => 255,
+        };
+        if delta > 0 {
+            stats.differing += 1;
+            stats.max = stats.max.max(delta);
+        }
+        heat(delta)
+    });
+    (DynamicImage::ImageRgba8(heatmap), stats)
+}
+
+/// Color of a difference on the heatmap. Small differences are boosted so single-step changes
+/// still show up.
+fn heat(delta: u8) -> Rgba<u8> {
+    if delta == 0 {
+        return Rgba([0, 0, 0, 255]);
+    }
+    let t = (delta as f32 / 255.0).sqrt();
+    let ramp = |start: f32| ((t - start) * 3.0).clamp(0.0, 1.0);
+    let red = ramp(0.0).max(0.25);
+    Rgba([
+        (red * 255.0) as u8,
+        (ramp(1.0 / 3.0) * 255.0) as u8,
+        (ramp(2.0 / 3.0) * 255.0) as u8,
+        255,
+    ])
+}
@@ -0,0 +1,116 @@
+//! Blend modes for laying one color over another, used by layers and brush strokes. Colors are
+//! in straight alpha; the blend mode decides the color where both are opaque and plain "over"
+//! covers the rest.
+
+use nannou::image::Rgba;
+use nannou::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::layers::over;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+}
+
+impl BlendMode {
+    pub const ALL: [BlendMode; 5] = [
+        BlendMode::Normal,
+        BlendMode::Multiply,
+        BlendMode::Screen,
+        BlendMode::Overlay,
+        BlendMode::Add,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlendMode::Normal => "Normal",
+            BlendMode::Multiply => "Multiply",
+            BlendMode::Screen => "Screen",
+            BlendMode::Overlay => "Overlay",
+            BlendMode::Add => "Add",
+        }
+    }
+
+    /// The mode after this one, wrapping around.
+    pub fn next(&self) -> BlendMode {
+        let i = BlendMode::ALL.iter().position(|m| m == self).unwrap();
+        BlendMode::ALL[(i + 1) % BlendMode::ALL.len()]
+    }
+
+    /// Blends one channel of the source `s` over the backdrop `d`, both 0-1.
+    fn channel(&self, d: f32, s: f32) -> f32 {
+        match self {
+            BlendMode::Normal => s,
+            BlendMode::Multiply => d * s,
+            BlendMode::Screen => d + s - d * s,
+            BlendMode::Overlay => {
+                if d <= 0.5 {
+                    2.0 * d * s
+                } else {
+                    1.0 - 2.0 * (1.0 - d) * (1.0 - s)
+                }
+            }
+            BlendMode::Add => (d + s).min(1.0),
+        }
+    }
+}
+
+/// Lays `src` with extra `opacity` over `dst` using `mode`.
+pub fn blend(dst: Rgba<u8>, src: Rgba<u8>, opacity: f32, mode: BlendMode) -> Rgba<u8> {
+    if mode == BlendMode::Normal {
+        return over(dst, src, opacity);
+    }
+
+    let sa = src[3] as f32 / 255.0 * opacity;
+    let da = dst[3] as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    if out_a <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let mut out = [0u8; 4];
+    for c in 0..3 {
+        let (d, s) = (dst[c] as f32 / 255.0, src[c] as f32 / 255.0);
+        let v = sa * (1.0 - da) * s + sa * da * mode.channel(d, s) + (1.0 - sa) * da * d;
+        out[c] = (v / out_a * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    out[3] = (out_a * 255.0).round() as u8;
+    Rgba(out)
+}
+
+/// How brush dabs combine with the layer: blending color, removing alpha, copying pixels from
+/// elsewhere on the layer or pushing the ones already there along the stroke.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BrushBlend {
+    Color(BlendMode),
+    EraseAlpha,
+    /// Copies the pixels this offset away from the dab.
+    CloneStamp(Vec2),
+    Smudge,
+}
+
+impl BrushBlend {
+    pub const ALL: [BrushBlend; 6] = [
+        BrushBlend::Color(BlendMode::Normal),
+        BrushBlend::Color(BlendMode::Multiply),
+        BrushBlend::Color(BlendMode::Screen),
+        BrushBlend::Color(BlendMode::Overlay),
+        BrushBlend::Color(BlendMode::Add),
+        BrushBlend::EraseAlpha,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BrushBlend::Color(mode) => mode.name(),
+            BrushBlend::EraseAlpha => "Erase Alpha",
+            BrushBlend::CloneStamp(_) => "Clone Stamp",
+            BrushBlend::Smudge => "Smudge",
+        }
+    }
+}
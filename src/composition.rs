@@ -0,0 +1,158 @@
+//! Composition overlays drawn over the canvas to frame a picture against: the rule of thirds, the
+//! golden spiral, a center cross and the action and title safe areas of video. They belong to the
+//! document and are saved with the project, but never exported.
+
+use nannou::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// Quarter turns of the golden spiral drawn before it gets too small to see.
+const SPIRAL_TURNS: usize = 12;
+/// Segments per quarter turn.
+const ARC_SEGMENTS: usize = 16;
+/// Share of the canvas inside the action and title safe areas.
+const SAFE_AREAS: [f32; 2] = [0.93, 0.9];
+
+/// Corner the golden spiral winds into.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+}
+
+impl Corner {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Corner::TopLeft => "Top Left",
+            Corner::TopRight => "Top Right",
+            Corner::BottomRight => "Bottom Right",
+            Corner::BottomLeft => "Bottom Left",
+        }
+    }
+
+    pub fn next(self) -> Corner {
+        match self {
+            Corner::TopLeft => Corner::TopRight,
+            Corner::TopRight => Corner::BottomRight,
+            Corner::BottomRight => Corner::BottomLeft,
+            Corner::BottomLeft => Corner::TopLeft,
+        }
+    }
+}
+
+/// Which overlays a document shows.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overlays {
+    pub thirds: bool,
+    pub golden_spiral: bool,
+    pub spiral_corner: Corner,
+    pub center: bool,
+    pub safe_areas: bool,
+}
+
+impl Default for Overlays {
+    fn default() -> Self {
+        Self {
+            thirds: false,
+            golden_spiral: false,
+            spiral_corner: Corner::BottomRight,
+            center: false,
+            safe_areas: false,
+        }
+    }
+}
+
+impl Overlays {
+    /// Straight lines of the enabled overlays on a `size` canvas, in canvas pixels.
+    pub fn lines(&self, size: Vec2) -> Vec<(Vec2, Vec2)> {
+        let mut lines = Vec::new();
+        if self.thirds {
+            for t in [1.0 / 3.0, 2.0 / 3.0] {
+                lines.push((Vec2::new(size.x * t, 0.0), Vec2::new(size.x * t, size.y)));
+                lines.push((Vec2::new(0.0, size.y * t), Vec2::new(size.x, size.y * t)));
+            }
+        }
+        if self.center {
+            let center = size / 2.0;
+            let arm = size.min_element() / 20.0;
+            lines.push((center - Vec2::X * arm, center + Vec2::X * arm));
+            lines.push((center - Vec2::Y * arm, center + Vec2::Y * arm));
+        }
+        if self.safe_areas {
+            for share in SAFE_AREAS {
+                let min = size * (1.0 - share) / 2.0;
+                let max = size - min;
+                let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)];
+                for i in 0..4 {
+                    lines.push((corners[i], corners[(i + 1) % 4]));
+                }
+            }
+        }
+        lines
+    }
+
+    /// The golden spiral as a polyline stretched over a `size` canvas, or nothing if it is off.
+    pub fn spiral(&self, size: Vec2) -> Vec<Vec2> {
+        if !self.golden_spiral {
+            return Vec::new();
+        }
+        // Built in a landscape golden rectangle by cutting a square off the left, top, right and
+        // bottom in turn, with a quarter circle in each. It winds into the bottom right.
+        let phi = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let (mut min, mut max) = (Vec2::ZERO, Vec2::new(phi, 1.0));
+        let mut points = vec![Vec2::new(0.0, 1.0)];
+        for turn in 0..SPIRAL_TURNS {
+            let side = (max - min).min_element();
+            // Center of the arc, then where it starts and ends relative to it
+            let (center, from, to) = match turn % 4 {
+                0 => {
+                    min.x += side;
+                    (Vec2::new(min.x, max.y), -Vec2::X, -Vec2::Y)
+                }
+                1 => {
+                    min.y += side;
+                    (min, -Vec2::Y, Vec2::X)
+                }
+                2 => {
+                    max.x -= side;
+                    (Vec2::new(max.x, min.y), Vec2::X, Vec2::Y)
+                }
+                _ => {
+                    max.y -= side;
+                    (max, Vec2::Y, -Vec2::X)
+                }
+            };
+            for i in 1..=ARC_SEGMENTS {
+                let angle = i as f32 / ARC_SEGMENTS as f32 * std::f32::consts::FRAC_PI_2;
+                points.push(center + (from * angle.cos() + to * angle.sin()) * side);
+            }
+        }
+
+        // Portrait canvases get the spiral turned on its side
+        let portrait = size.y > size.x;
+        let (flip_x, flip_y) = match self.spiral_corner {
+            Corner::BottomRight => (false, false),
+            Corner::BottomLeft => (true, false),
+            Corner::TopLeft => (true, true),
+            Corner::TopRight => (false, true),
+        };
+        points
+            .into_iter()
+            .map(|point| {
+                let mut unit = point / Vec2::new(phi, 1.0);
+                if portrait {
+                    unit = Vec2::new(unit.y, unit.x);
+                }
+                if flip_x {
+                    unit.x = 1.0 - unit.x;
+                }
+                if flip_y {
+                    unit.y = 1.0 - unit.y;
+                }
+                unit * size
+            })
+            .collect()
+    }
+}
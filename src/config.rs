@@ -0,0 +1,174 @@
+//! User configuration stored as TOML in the platform config directory.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use nannou::color::{rgb, Rgb};
+use serde::{Deserialize, Serialize};
+
+use crate::backup::BackupOptions;
+use crate::input::{MouseBindings, ScrollBindings};
+use crate::midi::MidiBindings;
+use crate::placement::WindowPlacement;
+use crate::workbench::Panel;
+use crate::workspace::WorkspacePreset;
+
+/// Most files kept in the recent files list.
+const RECENT_FILES: usize = 10;
+
+/// Color scheme of the window backgrounds.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Theme {
+    Dark,
+    Black,
+    Slate,
+}
+
+impl Theme {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Theme::Dark => "Dark",
+            Theme::Black => "Black",
+            Theme::Slate => "Slate",
+        }
+    }
+
+    /// The theme after this one, for cycling through them with a button.
+    pub fn next(self) -> Theme {
+        match self {
+            Theme::Dark => Theme::Black,
+            Theme::Black => Theme::Slate,
+            Theme::Slate => Theme::Dark,
+        }
+    }
+
+    /// Behind the canvas and the Workbench panels.
+    pub fn background(&self) -> Rgb {
+        match self {
+            Theme::Dark => rgb(0.15, 0.15, 0.15),
+            Theme::Black => rgb(0.04, 0.04, 0.04),
+            Theme::Slate => rgb(0.16, 0.18, 0.23),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Name of the active workspace preset.
+    pub workspace: String,
+    /// Keep the Workbench above other windows.
+    pub always_on_top: bool,
+    /// Collapse the Workbench into a strip of tool buttons.
+    pub compact: bool,
+    /// Brush size when the app was last closed.
+    pub brush_size: f32,
+    /// Brush color when the app was last closed.
+    pub brush_color: [u8; 4],
+    /// Canvas zoom when the app was last closed.
+    pub zoom: f32,
+    pub theme: Theme,
+    /// Seconds between autosaves of documents with unsaved changes.
+    pub autosave_seconds: f32,
+    /// Files opened or saved lately, newest first.
+    pub recent_files: Vec<PathBuf>,
+    pub presets: Vec<WorkspacePreset>,
+    /// Every Workbench panel there was when `presets` were saved, to tell panels added since
+    /// from ones taken out of a preset. Empty in configs from before it was recorded.
+    #[serde(default)]
+    pub panels: Vec<Panel>,
+    /// Last placement of each kind of window, keyed by [`crate::Window::role`].
+    pub windows: HashMap<String, WindowPlacement>,
+    /// What the scroll wheel does over the canvas with each modifier.
+    pub scroll: ScrollBindings,
+    /// What the middle, right and thumb mouse buttons do over the canvas.
+    pub mouse: MouseBindings,
+    /// Read gamepads for moving the cursor, painting and switching tools.
+    pub gamepad: bool,
+    /// MIDI controller knobs bound to brush and zoom parameters.
+    pub midi: MidiBindings,
+    /// Rolling backups of project files.
+    pub backup: BackupOptions,
+    /// Run the filters that have compute shaders on the GPU.
+    pub gpu_filters: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            workspace: "Painting".to_string(),
+            always_on_top: false,
+            compact: false,
+            brush_size: 1.0,
+            brush_color: [0, 0, 0, 255],
+            zoom: 1.75,
+            theme: Theme::Dark,
+            autosave_seconds: 30.0,
+            recent_files: Vec::new(),
+            presets: WorkspacePreset::builtin(),
+            panels: Panel::ALL.to_vec(),
+            windows: HashMap::new(),
+            scroll: ScrollBindings::default(),
+            mouse: MouseBindings::default(),
+            gamepad: false,
+            midi: MidiBindings::default(),
+            backup: BackupOptions::default(),
+            gpu_filters: true,
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        Some(dirs::config_dir()?.join("image_editor").join("config.toml"))
+    }
+
+    /// Reads the config file, falling back to defaults if it is missing or invalid.
+    pub fn load() -> Config {
+        let path = match Config::path() {
+            Some(path) if path.exists() => path,
+            _ => return Config::default(),
+        };
+
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| toml::from_str(&source).map_err(|e| e.to_string()))
+        {
+            Ok(mut config) => {
+                WorkspacePreset::merge(&mut config.presets, &config.panels);
+                config.panels = Panel::ALL.to_vec();
+                config
+            }
+            Err(e) => {
+                log::warn!("Ignoring invalid config {}: {}", path.display(), e);
+                Config::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        let path = Config::path()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        std::fs::create_dir_all(path.parent().unwrap())?;
+        let source =
+            toml::to_string_pretty(self).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        std::fs::write(path, source)
+    }
+
+    pub fn preset(&self, name: &str) -> Option<&WorkspacePreset> {
+        self.presets.iter().find(|p| p.name == name)
+    }
+
+    pub fn autosave_interval(&self) -> Duration {
+        Duration::from_secs_f32(self.autosave_seconds.max(5.0))
+    }
+
+    /// Moves `path` to the top of the recent files, dropping the oldest past the limit.
+    pub fn add_recent(&mut self, path: &Path) {
+        self.recent_files.retain(|p| p != path);
+        self.recent_files.insert(0, path.to_path_buf());
+        self.recent_files.truncate(RECENT_FILES);
+    }
+}
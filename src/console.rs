@@ -0,0 +1,79 @@
+//! Keeps recent log records in memory for the in-app console, alongside env_logger's usual
+//! terminal output. The editor's own records are kept down to debug level whatever `RUST_LOG`
+//! says, and the console panel picks which levels to show.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Number of records kept for the console.
+const CAPACITY: usize = 500;
+const CRATE: &str = env!("CARGO_CRATE_NAME");
+const CRATE_PREFIX: &str = concat!(env!("CARGO_CRATE_NAME"), "::");
+
+pub struct Entry {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+}
+
+static ENTRIES: Mutex<VecDeque<Entry>> = Mutex::new(VecDeque::new());
+
+struct ConsoleLogger {
+    inner: env_logger::Logger,
+}
+
+impl Log for ConsoleLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        // Dependencies only down to warnings, as the graphics stack logs every frame at debug
+        let own = metadata.target() == CRATE || metadata.target().starts_with(CRATE_PREFIX);
+        metadata.level() <= if own { Level::Debug } else { Level::Warn }
+            || self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.inner.enabled(record.metadata()) {
+            self.inner.log(record);
+        }
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut entries = ENTRIES.lock().unwrap();
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(Entry {
+            level: record.level(),
+            target: record.target().to_string(),
+            message: record.args().to_string(),
+        });
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+/// Installs the console logger in place of `env_logger::init()`.
+pub fn init() {
+    let inner = env_logger::Builder::from_default_env().build();
+    let max = inner.filter().max(LevelFilter::Debug);
+    if log::set_boxed_logger(Box::new(ConsoleLogger { inner })).is_ok() {
+        log::set_max_level(max);
+    }
+}
+
+/// Calls `f` with up to `count` of the most recent entries at or above `level`, oldest first.
+pub fn with_recent<R>(level: LevelFilter, count: usize, f: impl FnOnce(&[&Entry]) -> R) -> R {
+    let entries = ENTRIES.lock().unwrap();
+    let mut recent: Vec<_> = entries
+        .iter()
+        .rev()
+        .filter(|e| e.level <= level)
+        .take(count)
+        .collect();
+    recent.reverse();
+    f(&recent)
+}
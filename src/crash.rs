@@ -0,0 +1,90 @@
+//! Panic hook that writes a crash report before the process goes down. Unsaved documents are
+//! already kept by the session autosave, which offers them back on the next launch, so the report
+//! only points there.
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::{Mutex, TryLockError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::session;
+
+/// Number of recent commands included in crash reports.
+const COMMAND_HISTORY: usize = 20;
+
+static COMMANDS: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+
+/// Directory crash reports are written to.
+pub fn recovery_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("image_editor")
+        .join("recovery")
+}
+
+/// Records a command for the crash report.
+pub fn record_command(command: String) {
+    let mut commands = COMMANDS.lock().unwrap_or_else(|e| e.into_inner());
+    if commands.len() == COMMAND_HISTORY {
+        commands.pop_front();
+    }
+    commands.push_back(command);
+}
+
+/// Installs the panic hook. The default hook still runs afterwards.
+pub fn install() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(&info.to_string()) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(e) => eprintln!("Failed to write crash report: {}", e),
+        }
+        default_hook(info);
+    }));
+}
+
+fn write_report(panic: &str) -> std::io::Result<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let dir = recovery_dir();
+    std::fs::create_dir_all(&dir)?;
+
+    let mut report = String::new();
+    let _ = writeln!(report, "{}\n", panic);
+    let _ = writeln!(report, "Recent commands:");
+    // The panic may have happened while the history was locked on this thread, where waiting
+    // would deadlock
+    let commands = match COMMANDS.try_lock() {
+        Ok(commands) => Some(commands),
+        Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    };
+    match commands {
+        Some(commands) => {
+            for command in commands.iter() {
+                let _ = writeln!(report, "  {}", command);
+            }
+        }
+        None => {
+            let _ = writeln!(report, "  (unavailable)");
+        }
+    }
+    let _ = writeln!(
+        report,
+        "\nUnsaved documents from the last autosave are in {} and are offered back on the next \
+         launch.",
+        session::session_dir().display()
+    );
+    let _ = writeln!(
+        report,
+        "\nBacktrace:\n{}",
+        std::backtrace::Backtrace::force_capture()
+    );
+
+    let path = dir.join(format!("crash-{}.txt", stamp));
+    std::fs::write(&path, report)?;
+    Ok(path)
+}
@@ -0,0 +1,181 @@
+//! A Workbench widget for editing a [`Curve`]: click to add a control point, drag points to move
+//! them and right click to remove one. Input runs left to right and output bottom to top.
+
+use nannou_conrod::prelude::*;
+
+use crate::filters::curves::Curve;
+
+/// How far a control point can be grabbed from, in points.
+const GRAB_RADIUS: f64 = 8.0;
+/// Samples along the drawn curve.
+const CURVE_SAMPLES: usize = 64;
+
+widget_ids! {
+    pub struct Ids {
+        background,
+        grid[],
+        diagonal,
+        curve,
+        points[],
+    }
+}
+
+pub struct CurveEditor<'a> {
+    common: widget::CommonBuilder,
+    curve: &'a Curve,
+    rgb: (f32, f32, f32),
+}
+
+impl<'a> CurveEditor<'a> {
+    pub fn new(curve: &'a Curve) -> Self {
+        Self {
+            common: widget::CommonBuilder::default(),
+            curve,
+            rgb: (0.9, 0.9, 0.9),
+        }
+    }
+
+    /// Color of the curve and its points.
+    pub fn rgb(mut self, r: f32, g: f32, b: f32) -> Self {
+        self.rgb = (r, g, b);
+        self
+    }
+}
+
+impl widget::Common for CurveEditor<'_> {
+    fn common(&self) -> &widget::CommonBuilder {
+        &self.common
+    }
+
+    fn common_mut(&mut self) -> &mut widget::CommonBuilder {
+        &mut self.common
+    }
+}
+
+impl Widget for CurveEditor<'_> {
+    type State = Ids;
+    type Style = ();
+    /// The edited curve, when it changed.
+    type Event = Option<Curve>;
+
+    fn init_state(&self, id_gen: widget::id::Generator) -> Self::State {
+        Ids::new(id_gen)
+    }
+
+    fn style(&self) -> Self::Style {}
+
+    fn update(self, args: widget::UpdateArgs<Self>) -> Self::Event {
+        let widget::UpdateArgs {
+            id,
+            state,
+            rect,
+            ui,
+            ..
+        } = args;
+        let (left, bottom, w, h) = (rect.left(), rect.bottom(), rect.w(), rect.h());
+        let to_screen = |p: [f32; 2]| {
+            [
+                left + p[0] as f64 / 255.0 * w,
+                bottom + p[1] as f64 / 255.0 * h,
+            ]
+        };
+        // Input positions are relative to the widget's center
+        let to_curve = |xy: [f64; 2]| {
+            [
+                ((xy[0] + w / 2.0) / w * 255.0) as f32,
+                ((xy[1] + h / 2.0) / h * 255.0) as f32,
+            ]
+        };
+        let grab = (GRAB_RADIUS / w.min(h) * 255.0) as f32;
+
+        let mut curve = self.curve.clone();
+        let input = ui.widget_input(id);
+        // Each drag step moves the point under its previous position, which is the one being
+        // dragged since it follows the mouse
+        for drag in input.drags().left() {
+            let [x, y] = to_curve(drag.from);
+            if let Some(i) = curve.nearest(x, y, grab) {
+                let [x, y] = to_curve(drag.to);
+                curve.move_point(i, x, y);
+            }
+        }
+        // Clicks also end drags, which leave a point under the mouse, so only empty spots add one
+        for click in input.clicks().left() {
+            let [x, y] = to_curve(click.xy);
+            if curve.nearest(x, y, grab).is_none() {
+                curve.insert(x, y);
+            }
+        }
+        for click in input.clicks().right() {
+            let [x, y] = to_curve(click.xy);
+            if let Some(i) = curve.nearest(x, y, grab) {
+                curve.remove(i);
+            }
+        }
+
+        widget::Rectangle::fill(rect.dim())
+            .middle_of(id)
+            .graphics_for(id)
+            .rgb(0.1, 0.1, 0.1)
+            .set(state.background, ui);
+
+        // Quarter lines and the identity diagonal, for reference
+        if state.grid.len() < 6 {
+            state.update(|ids| ids.grid.resize(6, &mut ui.widget_id_generator()));
+        }
+        for i in 0..3 {
+            let v = (i + 1) as f32 * 255.0 / 4.0;
+            for (j, (start, end)) in [
+                (to_screen([v, 0.0]), to_screen([v, 255.0])),
+                (to_screen([0.0, v]), to_screen([255.0, v])),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                widget::Line::abs(start, end)
+                    .parent(id)
+                    .graphics_for(id)
+                    .rgb(0.25, 0.25, 0.25)
+                    .set(state.grid[i * 2 + j], ui);
+            }
+        }
+        widget::Line::abs(to_screen([0.0, 0.0]), to_screen([255.0, 255.0]))
+            .parent(id)
+            .graphics_for(id)
+            .rgb(0.35, 0.35, 0.35)
+            .set(state.diagonal, ui);
+
+        let (r, g, b) = self.rgb;
+        let lut = curve.lut();
+        let path: Vec<[f64; 2]> = (0..=CURVE_SAMPLES)
+            .map(|i| {
+                let x = (i * 255 / CURVE_SAMPLES) as u8;
+                to_screen([x as f32, lut[x as usize] as f32])
+            })
+            .collect();
+        widget::PointPath::abs(path)
+            .parent(id)
+            .graphics_for(id)
+            .rgb(r, g, b)
+            .thickness(1.5)
+            .set(state.curve, ui);
+
+        if state.points.len() < curve.points.len() {
+            state.update(|ids| {
+                ids.points
+                    .resize(curve.points.len(), &mut ui.widget_id_generator())
+            });
+        }
+        for (i, point) in curve.points.iter().enumerate() {
+            let [x, y] = to_screen(*point);
+            widget::Circle::fill(4.0)
+                .x_y(x, y)
+                .parent(id)
+                .graphics_for(id)
+                .rgb(r, g, b)
+                .set(state.points[i], ui);
+        }
+
+        (curve != *self.curve).then(|| curve)
+    }
+}
@@ -0,0 +1,222 @@
+//! Background removal from scribbles. The user scribbles over some of the foreground and, if
+//! needed, some of the background. Every other pixel goes to whichever scribbles it is nearer,
+//! measured along the image: steps between similar colors are cheap, steps across edges are
+//! expensive, and so are pixels unlike the colors under that kind of scribble. Without
+//! background scribbles the border of the layer stands in for them, which is enough for
+//! screenshots and product photos on a plain backdrop.
+//!
+//! The result is cut into the layer's alpha, with a soft edge where the two distances are close.
+//! Undoing, adding scribbles where it went wrong and running it again refines the cutout.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use nannou::image::{DynamicImage, GenericImageView};
+use nannou::prelude::Vec2;
+use rayon::prelude::*;
+
+use crate::canvas::Canvas;
+use crate::jobs::Progress;
+use crate::selection::Selection;
+
+/// Colors sampled from each kind of scribble to judge how alike a pixel is to it.
+const MODEL_COLORS: usize = 12;
+/// Weight of the color model against the edges crossed on the way.
+const MODEL_WEIGHT: f32 = 0.5;
+
+/// A brush stroke marking foreground or background.
+#[derive(Clone)]
+pub struct Scribble {
+    pub background: bool,
+    pub radius: f32,
+    /// Canvas positions along the stroke.
+    pub points: Vec<Vec2>,
+}
+
+#[derive(Clone, Default)]
+pub struct Scribbles {
+    pub strokes: Vec<Scribble>,
+}
+
+impl Scribbles {
+    /// Starts a stroke `size` pixels wide at canvas position `pos`.
+    pub fn begin(&mut self, pos: Vec2, size: f32, background: bool) {
+        self.strokes.push(Scribble {
+            background,
+            radius: (size / 2.0).max(0.5),
+            points: vec![pos],
+        });
+    }
+
+    /// Continues the last stroke to `pos`.
+    pub fn extend(&mut self, pos: Vec2) {
+        if let Some(stroke) = self.strokes.last_mut() {
+            stroke.points.push(pos);
+        }
+    }
+
+    /// Pixels covered by the foreground or background strokes, row by row.
+    fn mask(&self, width: u32, height: u32, background: bool) -> Vec<bool> {
+        let mut mask = vec![false; (width * height) as usize];
+        for stroke in self.strokes.iter().filter(|s| s.background == background) {
+            let mut mark = |center: Vec2| {
+                let r = stroke.radius;
+                let y0 = (center.y - r).floor().max(0.0) as u32;
+                let x0 = (center.x - r).floor().max(0.0) as u32;
+                let y1 = ((center.y + r).ceil().max(0.0) as u32).min(height);
+                let x1 = ((center.x + r).ceil().max(0.0) as u32).min(width);
+                for y in y0..y1 {
+                    for x in x0..x1 {
+                        let pixel = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                        if pixel.distance(center) <= r {
+                            mask[(y * width + x) as usize] = true;
+                        }
+                    }
+                }
+            };
+            mark(stroke.points[0]);
+            for pair in stroke.points.windows(2) {
+                let steps = pair[0].distance(pair[1]).ceil().max(1.0) as usize;
+                for i in 1..=steps {
+                    mark(pair[0].lerp(pair[1], i as f32 / steps as f32));
+                }
+            }
+        }
+        mask
+    }
+}
+
+/// `image` with the background cut out of its alpha, inside `selection` if there is one.
+/// `softness` (0-1) widens the partly transparent edge.
+pub fn remove_background(
+    image: &DynamicImage,
+    scribbles: &Scribbles,
+    softness: f32,
+    selection: Option<Selection>,
+    progress: &Progress,
+) -> Result<DynamicImage, String> {
+    let (width, height) = image.dimensions();
+    let foreground = scribbles.mask(width, height, false);
+    if !foreground.contains(&true) {
+        return Err("scribble over the foreground first".to_string());
+    }
+    let mut background = scribbles.mask(width, height, true);
+    if !background.contains(&true) {
+        for y in 0..height {
+            for x in 0..width {
+                if x == 0 || y == 0 || x == width - 1 || y == height - 1 {
+                    background[(y * width + x) as usize] = true;
+                }
+            }
+        }
+    }
+
+    let colors: Vec<[f32; 4]> = image
+        .to_rgba8()
+        .pixels()
+        .map(|p| p.0.map(|c| c as f32))
+        .collect();
+    let graph = Graph {
+        width,
+        height,
+        colors: &colors,
+    };
+    let to_foreground = graph.distances(&foreground, |f| progress.set(f * 0.5));
+    let to_background = graph.distances(&background, |f| progress.set(0.5 + f * 0.5));
+
+    let edge = softness.clamp(0.0, 1.0) * 0.5;
+    let mut result = image.clone();
+    Canvas::new(&mut result, selection.as_ref()).fill(|x, y, mut pixel| {
+        let i = (y * width + x) as usize;
+        let (f, b) = (to_foreground[i] as f32, to_background[i] as f32);
+        // Share of the way from the foreground to the background
+        let t = if f + b > 0.0 { f / (f + b) } else { 0.0 };
+        let alpha = if edge > 0.0 {
+            ((0.5 - t) / edge * 0.5 + 0.5).clamp(0.0, 1.0)
+        } else if t <= 0.5 {
+            1.0
+        } else {
+            0.0
+        };
+        pixel.0[3] = (pixel.0[3] as f32 * alpha).round() as u8;
+        Some(pixel)
+    });
+    Ok(result)
+}
+
+struct Graph<'a> {
+    width: u32,
+    height: u32,
+    colors: &'a [[f32; 4]],
+}
+
+impl Graph<'_> {
+    /// Cheapest way from each pixel to the nearest `seeds` pixel, found with Dijkstra's
+    /// algorithm. Entering a pixel costs its color difference from the pixel it was entered from
+    /// plus how unlike it is to the colors under the seeds. `progress` gets the settled fraction.
+    fn distances(&self, seeds: &[bool], progress: impl Fn(f32)) -> Vec<u64> {
+        let model = self.model(seeds);
+        let (width, height) = (self.width as i64, self.height as i64);
+        let size = seeds.len();
+        let mut cost = vec![u64::MAX; size];
+        let mut queue = BinaryHeap::new();
+        for (i, _) in seeds.iter().enumerate().filter(|&(_, &seed)| seed) {
+            cost[i] = 0;
+            queue.push(Reverse((0, i)));
+        }
+
+        let mut settled = 0;
+        while let Some(Reverse((c, i))) = queue.pop() {
+            if c > cost[i] {
+                continue;
+            }
+            settled += 1;
+            if settled % 65536 == 0 {
+                progress(settled as f32 / size as f32);
+            }
+            let (x, y) = (i as i64 % width, i as i64 / width);
+            for (nx, ny) in [(x - 1, y), (x + 1, y), (x, y - 1), (x, y + 1)] {
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let n = (ny * width + nx) as usize;
+                let step = 1.0 + difference(self.colors[i], self.colors[n]) + model[n];
+                let c = c + (step * 16.0) as u64;
+                if c < cost[n] {
+                    cost[n] = c;
+                    queue.push(Reverse((c, n)));
+                }
+            }
+        }
+        cost
+    }
+
+    /// How far each pixel's color is from the nearest of a handful of colors under `seeds`.
+    fn model(&self, seeds: &[bool]) -> Vec<f32> {
+        let under: Vec<[f32; 4]> = self
+            .colors
+            .iter()
+            .zip(seeds)
+            .filter(|&(_, &seed)| seed)
+            .map(|(&color, _)| color)
+            .collect();
+        let stride = (under.len() / MODEL_COLORS).max(1);
+        let samples: Vec<[f32; 4]> = under.into_iter().step_by(stride).collect();
+
+        self.colors
+            .par_iter()
+            .map(|&color| {
+                samples
+                    .iter()
+                    .map(|&sample| difference(color, sample))
+                    .fold(f32::MAX, f32::min)
+                    * MODEL_WEIGHT
+            })
+            .collect()
+    }
+}
+
+/// Distance between two RGBA colors.
+fn difference(a: [f32; 4], b: [f32; 4]) -> f32 {
+    (0..4).map(|c| (a[c] - b[c]).powi(2)).sum::<f32>().sqrt()
+}
@@ -0,0 +1,66 @@
+//! Tracking which part of the canvas changed since its texture was last uploaded.
+
+/// Pixel bounds of a changed area, `x0..x1` by `y0..y1`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DirtyRect {
+    pub x0: u32,
+    pub y0: u32,
+    pub x1: u32,
+    pub y1: u32,
+}
+
+impl DirtyRect {
+    /// The square of `radius` around `(x, y)`, clipped to a `width` x `height` canvas.
+    pub fn around(x: i32, y: i32, radius: i32, width: u32, height: u32) -> DirtyRect {
+        let clip = |v: i32, max: u32| v.clamp(0, max as i32) as u32;
+        DirtyRect {
+            x0: clip(x - radius, width),
+            y0: clip(y - radius, height),
+            x1: clip(x + radius + 1, width),
+            y1: clip(y + radius + 1, height),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> u32 {
+        self.y1 - self.y0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.x0 >= self.x1 || self.y0 >= self.y1
+    }
+
+    pub fn union(&self, other: &DirtyRect) -> DirtyRect {
+        DirtyRect {
+            x0: self.x0.min(other.x0),
+            y0: self.y0.min(other.y0),
+            x1: self.x1.max(other.x1),
+            y1: self.y1.max(other.y1),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Dirty {
+    Clean,
+    Region(DirtyRect),
+    /// Everything must be re-uploaded, e.g. after undo or a layer change.
+    All,
+}
+
+impl Dirty {
+    /// Grows the dirty area to include `rect`.
+    pub fn include(self, rect: DirtyRect) -> Dirty {
+        if rect.is_empty() {
+            return self;
+        }
+        match self {
+            Dirty::Clean => Dirty::Region(rect),
+            Dirty::Region(r) => Dirty::Region(r.union(&rect)),
+            Dirty::All => Dirty::All,
+        }
+    }
+}
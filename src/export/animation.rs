@@ -0,0 +1,284 @@
+//! Animated GIF and APNG export. Documents have no frame timeline, so the visible layers are the
+//! frames, bottom first, the way GIMP exports animations. A layer whose name ends in a delay in
+//! parentheses, like `Jump (250ms)`, stays up that long instead of the default delay.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use nannou::image::{
+    codecs::png::PngEncoder, ColorType, DynamicImage, GenericImageView, ImageEncoder,
+};
+
+use crate::jobs::Progress;
+use crate::layers::LayerStack;
+use crate::palette;
+
+const PNG_SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub fn from_path(path: &Path) -> Option<AnimationFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "gif" => Some(AnimationFormat::Gif),
+            "png" | "apng" => Some(AnimationFormat::Apng),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AnimationOptions {
+    /// Milliseconds each frame is shown unless its layer name says otherwise.
+    pub delay: f32,
+    /// Times the animation plays, or 0 to loop forever.
+    pub plays: f32,
+    /// Palette size of GIF frames, at most 256.
+    pub colors: f32,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        Self {
+            delay: 100.0,
+            plays: 0.0,
+            colors: 256.0,
+        }
+    }
+}
+
+pub struct Frame {
+    /// Name of the layer the frame was taken from.
+    pub name: String,
+    pub image: DynamicImage,
+    /// Milliseconds the frame is shown.
+    pub delay: u32,
+}
+
+/// The visible layers as frames, each on its own with its opacity applied.
+pub fn frames(layers: &LayerStack, default_delay: u32) -> Vec<Frame> {
+    layers
+        .layers
+        .iter()
+        .filter(|layer| layer.visible)
+        .map(|layer| {
+            let mut image = layer.pixels.to_rgba8();
+            for pixel in image.pixels_mut() {
+                pixel.0[3] = (pixel.0[3] as f32 * layer.opacity).round() as u8;
+            }
+            Frame {
+                name: layer.name.clone(),
+                image: DynamicImage::ImageRgba8(image),
+                delay: name_delay(&layer.name).unwrap_or(default_delay),
+            }
+        })
+        .collect()
+}
+
+/// The delay a layer name ends in, as in `Jump (250ms)`.
+fn name_delay(name: &str) -> Option<u32> {
+    let (_, delay) = name.trim_end().strip_suffix(')')?.rsplit_once('(')?;
+    delay.trim().strip_suffix("ms")?.trim().parse().ok()
+}
+
+/// Writes `frames` to `path` as a GIF or APNG, going by the extension.
+pub fn export_animation(
+    frames: &[Frame],
+    path: &Path,
+    options: &AnimationOptions,
+    progress: &Progress,
+) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message);
+    if frames.is_empty() {
+        return Err(invalid("no visible layers to animate"));
+    }
+    let format = AnimationFormat::from_path(path)
+        .ok_or_else(|| invalid("animations are exported as .gif or .png"))?;
+
+    let mut out = BufWriter::new(File::create(path)?);
+    match format {
+        AnimationFormat::Gif => write_gif(frames, options, &mut out, progress)?,
+        AnimationFormat::Apng => write_apng(frames, options, &mut out, progress)?,
+    }
+    out.flush()
+}
+
+fn write_gif(
+    frames: &[Frame],
+    options: &AnimationOptions,
+    out: &mut impl Write,
+    progress: &Progress,
+) -> io::Result<()> {
+    let (width, height) = frames[0].image.dimensions();
+    let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(width), Ok(height)) => (width, height),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "GIFs are at most 65535 pixels across",
+            ))
+        }
+    };
+
+    let mut encoder = gif::Encoder::new(out, width, height, &[]).map_err(other)?;
+    match options.plays.round() as u16 {
+        0 => encoder.set_repeat(gif::Repeat::Infinite).map_err(other)?,
+        // Without a loop extension, a GIF plays once
+        1 => (),
+        plays => encoder
+            .set_repeat(gif::Repeat::Finite(plays - 1))
+            .map_err(other)?,
+    }
+
+    let colors = (options.colors.round() as usize).clamp(2, 256);
+    for (i, frame) in frames.iter().enumerate() {
+        // The encoder keeps the colors of frames that already fit in a GIF palette
+        let mut pixels = palette::quantize(&frame.image, colors)
+            .to_rgba8()
+            .into_raw();
+        let mut gif_frame = gif::Frame::from_rgba_speed(width, height, &mut pixels, 10);
+        // GIF delays are in hundredths of a second
+        gif_frame.delay = (frame.delay / 10).min(u16::MAX as u32) as u16;
+        // Cleared before the next frame, so transparent layers don't pile up
+        gif_frame.dispose = gif::DisposalMethod::Background;
+        encoder.write_frame(&gif_frame).map_err(other)?;
+        progress.set((i + 1) as f32 / frames.len() as f32);
+    }
+    Ok(())
+}
+
+/// Encodes each frame as a PNG of its own and rewraps its chunks: the first frame's image data
+/// stays as `IDAT` so viewers without APNG support show it, later frames go into `fdAT`.
+fn write_apng(
+    frames: &[Frame],
+    options: &AnimationOptions,
+    out: &mut impl Write,
+    progress: &Progress,
+) -> io::Result<()> {
+    let (width, height) = frames[0].image.dimensions();
+    out.write_all(&PNG_SIGNATURE)?;
+
+    // Shared by fcTL and fdAT chunks, in the order they are written
+    let mut sequence = 0u32;
+    for (i, frame) in frames.iter().enumerate() {
+        let mut png = Vec::new();
+        PngEncoder::new(&mut png)
+            .write_image(&frame.image.to_rgba8(), width, height, ColorType::Rgba8)
+            .map_err(other)?;
+        let chunks = chunks(&png)?;
+
+        if i == 0 {
+            if let Some((_, header)) = chunks.iter().find(|(kind, _)| kind == b"IHDR") {
+                write_chunk(out, b"IHDR", header)?;
+            }
+            let mut control = Vec::new();
+            control.extend((frames.len() as u32).to_be_bytes());
+            control.extend((options.plays.round() as u32).to_be_bytes());
+            write_chunk(out, b"acTL", &control)?;
+        }
+
+        let mut control = Vec::new();
+        control.extend(sequence.to_be_bytes());
+        control.extend(width.to_be_bytes());
+        control.extend(height.to_be_bytes());
+        control.extend(0u32.to_be_bytes());
+        control.extend(0u32.to_be_bytes());
+        // Delay as a fraction, milliseconds over 1000
+        control.extend((frame.delay.min(u16::MAX as u32) as u16).to_be_bytes());
+        control.extend(1000u16.to_be_bytes());
+        // Cleared to transparent afterwards, and drawn over nothing rather than blended
+        control.extend([1, 0]);
+        write_chunk(out, b"fcTL", &control)?;
+        sequence += 1;
+
+        for (_, data) in chunks.iter().filter(|(kind, _)| kind == b"IDAT") {
+            if i == 0 {
+                write_chunk(out, b"IDAT", data)?;
+            } else {
+                let mut frame_data = sequence.to_be_bytes().to_vec();
+                frame_data.extend_from_slice(data);
+                write_chunk(out, b"fdAT", &frame_data)?;
+                sequence += 1;
+            }
+        }
+        progress.set((i + 1) as f32 / frames.len() as f32);
+    }
+
+    write_chunk(out, b"IEND", &[])
+}
+
+/// The type and data of each chunk in a PNG file.
+fn chunks(png: &[u8]) -> io::Result<Vec<([u8; 4], &[u8])>> {
+    let mut chunks = Vec::new();
+    let mut rest = png.get(PNG_SIGNATURE.len()..).unwrap_or_default();
+    // Length, type and CRC take 12 bytes around the data
+    while rest.len() >= 12 {
+        let length = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let end = 8 + length;
+        if rest.len() < end + 4 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "truncated PNG chunk",
+            ));
+        }
+        chunks.push(([rest[4], rest[5], rest[6], rest[7]], &rest[8..end]));
+        rest = &rest[end + 4..];
+    }
+    Ok(chunks)
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc.finalize().to_be_bytes())
+}
+
+fn other<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        for (kind, data) in chunks {
+            write_chunk(&mut png, kind, data).unwrap();
+        }
+        png
+    }
+
+    #[test]
+    fn chunks_reads_back_written_chunks() {
+        let written: [(&[u8; 4], &[u8]); 3] =
+            [(b"IHDR", &[1, 2, 3]), (b"IDAT", &[4; 20]), (b"IEND", &[])];
+        let png = png(&written);
+        let read: Vec<_> = chunks(&png)
+            .unwrap()
+            .into_iter()
+            .map(|(kind, data)| (kind, data.to_vec()))
+            .collect();
+        let expected: Vec<_> = written
+            .iter()
+            .map(|(kind, data)| (**kind, data.to_vec()))
+            .collect();
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn chunks_rejects_a_truncated_chunk() {
+        let mut png = png(&[(b"IHDR", &[1, 2, 3][..]), (b"IDAT", &[4; 20][..])]);
+        png.truncate(png.len() - 5);
+        assert_eq!(chunks(&png).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
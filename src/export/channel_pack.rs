@@ -0,0 +1,135 @@
+//! Channel packing for game engines, which often want several grayscale maps in one texture
+//! (roughness in red, metallic in green and so on). Each output channel is read from one channel
+//! of a layer or of the composite, or filled with a constant.
+
+use nannou::image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::layers::LayerStack;
+
+/// Where an output channel is read from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Source {
+    Composite,
+    /// A layer's own pixels, ignoring its opacity, visibility and blend mode.
+    Layer(usize),
+    Black,
+    White,
+}
+
+impl Source {
+    /// The source after this one when cycling through a stack of `layer_count` layers.
+    pub fn next(&self, layer_count: usize) -> Source {
+        match self {
+            Source::Composite if layer_count > 0 => Source::Layer(0),
+            Source::Layer(i) if i + 1 < layer_count => Source::Layer(i + 1),
+            Source::Composite | Source::Layer(_) => Source::Black,
+            Source::Black => Source::White,
+            Source::White => Source::Composite,
+        }
+    }
+
+    /// Whether the output is a constant, so the channel picked doesn't matter.
+    pub fn is_constant(&self) -> bool {
+        matches!(self, Source::Black | Source::White)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Channel {
+    Red,
+    Green,
+    Blue,
+    Alpha,
+    Luminance,
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Red => "R",
+            Channel::Green => "G",
+            Channel::Blue => "B",
+            Channel::Alpha => "A",
+            Channel::Luminance => "Luma",
+        }
+    }
+
+    pub fn next(&self) -> Channel {
+        match self {
+            Channel::Red => Channel::Green,
+            Channel::Green => Channel::Blue,
+            Channel::Blue => Channel::Alpha,
+            Channel::Alpha => Channel::Luminance,
+            Channel::Luminance => Channel::Red,
+        }
+    }
+
+    fn read(&self, px: Rgba<u8>) -> u8 {
+        match self {
+            Channel::Red => px[0],
+            Channel::Green => px[1],
+            Channel::Blue => px[2],
+            Channel::Alpha => px[3],
+            Channel::Luminance => {
+                (0.2126 * px[0] as f32 + 0.7152 * px[1] as f32 + 0.0722 * px[2] as f32).round()
+                    as u8
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ChannelSource {
+    pub source: Source,
+    pub channel: Channel,
+}
+
+/// Sources of the red, green, blue and alpha channels of the packed texture.
+pub struct PackOptions {
+    pub outputs: [ChannelSource; 4],
+}
+
+impl Default for PackOptions {
+    /// Passes the composite through unchanged.
+    fn default() -> Self {
+        let composite = |channel| ChannelSource {
+            source: Source::Composite,
+            channel,
+        };
+        Self {
+            outputs: [
+                composite(Channel::Red),
+                composite(Channel::Green),
+                composite(Channel::Blue),
+                composite(Channel::Alpha),
+            ],
+        }
+    }
+}
+
+/// Builds the packed texture. A layer that no longer exists reads as black.
+pub fn pack(layers: &LayerStack, options: &PackOptions) -> DynamicImage {
+    let composite = options
+        .outputs
+        .iter()
+        .any(|o| o.source == Source::Composite)
+        .then(|| layers.composite());
+
+    let mut out = RgbaImage::new(layers.width(), layers.height());
+    for (c, output) in options.outputs.iter().enumerate() {
+        let image = match output.source {
+            Source::Composite => composite.as_ref(),
+            Source::Layer(i) => layers.layers.get(i).map(|layer| &layer.pixels),
+            Source::Black | Source::White => None,
+        };
+        let fill = if output.source == Source::White {
+            255
+        } else {
+            0
+        };
+        for (x, y, px) in out.enumerate_pixels_mut() {
+            px[c] = image.map_or(fill, |image| output.channel.read(image.get_pixel(x, y)));
+        }
+    }
+    DynamicImage::ImageRgba8(out)
+}
@@ -0,0 +1,101 @@
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+
+/// Number of output lines kept for display.
+const LOG_LIMIT: usize = 200;
+
+/// Output collected from post-export commands.
+#[derive(Default)]
+pub struct HookLog {
+    pub lines: Vec<String>,
+    runs: Vec<Receiver<String>>,
+}
+
+impl HookLog {
+    /// Runs `command` through the platform shell with `{file}` replaced by the quoted exported
+    /// path. Output is streamed back and collected by [`HookLog::poll`].
+    pub fn run(&mut self, command: &str, file: &Path) {
+        let command = command.replace("{file}", &quote(file));
+        let (tx, rx) = mpsc::channel();
+        self.runs.push(rx);
+        std::thread::spawn(move || run_command(&command, tx));
+    }
+
+    /// Moves output that arrived since the last poll into `lines`.
+    pub fn poll(&mut self) {
+        let lines = &mut self.lines;
+        // A run is finished once its sender has been dropped and drained
+        self.runs.retain(|run| loop {
+            match run.try_recv() {
+                Ok(line) => lines.push(line),
+                Err(mpsc::TryRecvError::Empty) => break true,
+                Err(mpsc::TryRecvError::Disconnected) => break false,
+            }
+        });
+        if self.lines.len() > LOG_LIMIT {
+            self.lines.drain(..self.lines.len() - LOG_LIMIT);
+        }
+    }
+}
+
+fn quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    if cfg!(windows) {
+        format!("\"{}\"", path)
+    } else {
+        format!("'{}'", path.replace('\'', r"'\''"))
+    }
+}
+
+fn run_command(command: &str, tx: Sender<String>) {
+    let mut shell = if cfg!(windows) {
+        let mut c = Command::new("cmd");
+        c.arg("/C");
+        c
+    } else {
+        let mut c = Command::new("sh");
+        c.arg("-c");
+        c
+    };
+
+    let _ = tx.send(format!("$ {}", command));
+    let child = shell
+        .arg(command)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = tx.send(format!("failed to start: {}", e));
+            return;
+        }
+    };
+
+    let stderr = child.stderr.take().unwrap();
+    let err_tx = tx.clone();
+    let stderr_thread = std::thread::spawn(move || {
+        for line in BufReader::new(stderr).lines().flatten() {
+            let _ = err_tx.send(line);
+        }
+    });
+    for line in BufReader::new(child.stdout.take().unwrap())
+        .lines()
+        .flatten()
+    {
+        let _ = tx.send(line);
+    }
+    let _ = stderr_thread.join();
+
+    match child.wait() {
+        Ok(status) if status.success() => (),
+        Ok(status) => {
+            let _ = tx.send(format!("exited with {}", status));
+        }
+        Err(e) => {
+            let _ = tx.send(format!("failed: {}", e));
+        }
+    }
+}
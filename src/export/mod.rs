@@ -0,0 +1,9 @@
+pub mod animation;
+pub mod channel_pack;
+pub mod hook;
+pub mod naming;
+pub mod options;
+pub mod pdf;
+pub mod svg;
+pub mod video;
+pub mod watermark;
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+/// Values available to `{token}` placeholders in export file names.
+pub struct NameContext<'a> {
+    pub name: &'a str,
+    pub frame: usize,
+    pub layer: &'a str,
+    pub scale: f32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Expands `{name}`, `{frame}`, `{layer}`, `{scale}`, `{width}` and `{height}` in `template`.
+/// Numeric tokens accept a zero-padded width, e.g. `{frame:03}`. `{{` and `}}` are literal
+/// braces.
+pub fn expand(template: &str, ctx: &NameContext) -> Result<String, String> {
+    let mut out = String::with_capacity(template.len());
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let token: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let (key, width) = match token.split_once(':') {
+                    Some((key, width)) => (
+                        key,
+                        width
+                            .parse::<usize>()
+                            .map_err(|_| format!("invalid width in {{{}}}", token))?,
+                    ),
+                    None => (token.as_str(), 0),
+                };
+                let number = |n: u64| format!("{:0width$}", n, width = width);
+                out.push_str(&match key {
+                    "name" => ctx.name.to_string(),
+                    "layer" => ctx.layer.to_string(),
+                    "frame" => number(ctx.frame as u64),
+                    "width" => number(ctx.width as u64),
+                    "height" => number(ctx.height as u64),
+                    // Whole scales print without a trailing ".0" so `{scale}x` reads as `2x`
+                    "scale" if ctx.scale.fract() == 0.0 => number(ctx.scale as u64),
+                    "scale" => ctx.scale.to_string(),
+                    _ => return Err(format!("unknown token {{{}}}", key)),
+                });
+            }
+            c => out.push(c),
+        }
+    }
+
+    Ok(out)
+}
+
+/// Expands tokens in the file name of `path`, leaving its directory untouched.
+pub fn expand_path(path: &Path, ctx: &NameContext) -> Result<PathBuf, String> {
+    match path.file_name().and_then(|n| n.to_str()) {
+        Some(file_name) => Ok(path.with_file_name(expand(file_name, ctx)?)),
+        None => Ok(path.to_path_buf()),
+    }
+}
@@ -0,0 +1,251 @@
+//! Export settings applied on top of the canvas: scaling, palette reduction and the file format
+//! with its compression: PNG color type and level, JPEG and lossy WebP quality, or lossless
+//! WebP. The soft proof runs the same steps and decodes the bytes back, so it
+//! shows exactly what the written file will contain.
+
+use nannou::image::{
+    self,
+    codecs::png::{self, CompressionType, PngEncoder},
+    imageops::FilterType,
+    DynamicImage, GenericImageView, ImageEncoder, ImageOutputFormat, ImageResult,
+};
+
+use crate::alpha::{self, AlphaMode};
+use crate::textures::Uploaded;
+use crate::{formats, palette};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ExportFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl ExportFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "PNG",
+            ExportFormat::Jpeg => "JPEG",
+            ExportFormat::WebP => "WebP",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Png => "png",
+            ExportFormat::Jpeg => "jpg",
+            ExportFormat::WebP => "webp",
+        }
+    }
+
+    pub fn next(&self) -> ExportFormat {
+        match self {
+            ExportFormat::Png => ExportFormat::Jpeg,
+            ExportFormat::Jpeg => ExportFormat::WebP,
+            ExportFormat::WebP => ExportFormat::Png,
+        }
+    }
+}
+
+/// Channels and bit depth of PNG exports.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PngColor {
+    Rgba8,
+    Rgb8,
+    GrayAlpha8,
+    Gray8,
+    Rgba16,
+    Gray16,
+}
+
+impl PngColor {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PngColor::Rgba8 => "RGBA 8-bit",
+            PngColor::Rgb8 => "RGB 8-bit",
+            PngColor::GrayAlpha8 => "Gray + Alpha 8-bit",
+            PngColor::Gray8 => "Gray 8-bit",
+            PngColor::Rgba16 => "RGBA 16-bit",
+            PngColor::Gray16 => "Gray 16-bit",
+        }
+    }
+
+    pub fn next(&self) -> PngColor {
+        match self {
+            PngColor::Rgba8 => PngColor::Rgb8,
+            PngColor::Rgb8 => PngColor::GrayAlpha8,
+            PngColor::GrayAlpha8 => PngColor::Gray8,
+            PngColor::Gray8 => PngColor::Rgba16,
+            PngColor::Rgba16 => PngColor::Gray16,
+            PngColor::Gray16 => PngColor::Rgba8,
+        }
+    }
+
+    fn convert(&self, image: &DynamicImage) -> DynamicImage {
+        match self {
+            PngColor::Rgba8 => DynamicImage::ImageRgba8(image.to_rgba8()),
+            PngColor::Rgb8 => DynamicImage::ImageRgb8(image.to_rgb8()),
+            PngColor::GrayAlpha8 => DynamicImage::ImageLumaA8(image.to_luma_alpha8()),
+            PngColor::Gray8 => DynamicImage::ImageLuma8(image.to_luma8()),
+            PngColor::Rgba16 => DynamicImage::ImageRgba16(image.to_rgba16()),
+            PngColor::Gray16 => DynamicImage::ImageLuma16(image.to_luma16()),
+        }
+    }
+}
+
+/// How hard the PNG encoder works to shrink the file. Every level decodes to the same pixels.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+impl PngCompression {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PngCompression::Fast => "Fast",
+            PngCompression::Default => "Default",
+            PngCompression::Best => "Best",
+        }
+    }
+
+    pub fn next(&self) -> PngCompression {
+        match self {
+            PngCompression::Fast => PngCompression::Default,
+            PngCompression::Default => PngCompression::Best,
+            PngCompression::Best => PngCompression::Fast,
+        }
+    }
+
+    fn compression_type(&self) -> CompressionType {
+        match self {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    pub png_color: PngColor,
+    pub png_compression: PngCompression,
+    /// JPEG and lossy WebP quality, 1-100.
+    pub quality: f32,
+    pub webp_lossless: bool,
+    /// Size of the export relative to the canvas.
+    pub scale: f32,
+    /// Palette size to quantize to, or 0 to keep every color.
+    pub colors: f32,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            format: ExportFormat::Png,
+            png_color: PngColor::Rgba8,
+            png_compression: PngCompression::Default,
+            quality: 90.0,
+            webp_lossless: false,
+            scale: 1.0,
+            colors: 0.0,
+        }
+    }
+}
+
+/// What an export will look like once written and opened again.
+pub struct Proof {
+    pub image: Uploaded,
+    /// Size of the encoded file.
+    pub bytes: usize,
+}
+
+impl ExportOptions {
+    /// The canvas scaled and quantized, before encoding.
+    fn prepare(&self, image: &DynamicImage) -> DynamicImage {
+        let mut image = if (self.scale - 1.0).abs() > f32::EPSILON {
+            let w = ((image.width() as f32 * self.scale).round() as u32).max(1);
+            let h = ((image.height() as f32 * self.scale).round() as u32).max(1);
+            alpha::resize_exact(image, w, h, FilterType::Lanczos3)
+        } else {
+            image.clone()
+        };
+        let colors = self.colors.round() as usize;
+        if colors > 0 {
+            image = palette::quantize(&image, colors);
+        }
+        image
+    }
+
+    /// The bytes of the file an export of straight alpha `image` writes, storing alpha as
+    /// `alpha`.
+    pub fn encode(&self, image: &DynamicImage, alpha: AlphaMode) -> ImageResult<Vec<u8>> {
+        let image = alpha.export(&self.prepare(image));
+        let quality = self.quality.round().clamp(1.0, 100.0);
+        let mut bytes = Vec::new();
+        match self.format {
+            ExportFormat::Png => {
+                let image = self.png_color.convert(&image);
+                PngEncoder::new_with_quality(
+                    &mut bytes,
+                    self.png_compression.compression_type(),
+                    png::FilterType::Adaptive,
+                )
+                .write_image(
+                    image.as_bytes(),
+                    image.width(),
+                    image.height(),
+                    image.color(),
+                )?;
+            }
+            // JPEG has no alpha channel
+            ExportFormat::Jpeg => DynamicImage::ImageRgb8(image.to_rgb8())
+                .write_to(&mut bytes, ImageOutputFormat::Jpeg(quality as u8))?,
+            ExportFormat::WebP => {
+                bytes = formats::encode_webp(&image, (!self.webp_lossless).then(|| quality))?
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Encodes `image` and decodes it again, so compression artifacts show. The result is back
+    /// in straight alpha, as an engine reading the file in `alpha` would see it.
+    pub fn proof(&self, image: &DynamicImage, alpha: AlphaMode) -> ImageResult<Proof> {
+        let bytes = self.encode(image, alpha)?;
+        let decoded = match self.format {
+            ExportFormat::WebP => formats::decode_webp(&bytes)?,
+            _ => image::load_from_memory(&bytes)?,
+        };
+        Ok(Proof {
+            // 16-bit PNGs are shown at the 8 bits the preview texture holds
+            image: alpha
+                .import(DynamicImage::ImageRgba8(decoded.to_rgba8()))
+                .into(),
+            bytes: bytes.len(),
+        })
+    }
+
+    /// Format, size and file size of a proof, for the preview's status bar.
+    pub fn summary(&self, proof: &Proof) -> String {
+        let format = match self.format {
+            ExportFormat::Png => format!("PNG {}", self.png_color.name()),
+            ExportFormat::Jpeg => format!("JPEG {}", self.quality.round()),
+            ExportFormat::WebP if self.webp_lossless => "WebP lossless".to_string(),
+            ExportFormat::WebP => format!("WebP {}", self.quality.round()),
+        };
+        let colors = match self.colors.round() as usize {
+            0 => String::new(),
+            n => format!(", {} colors", n),
+        };
+        format!(
+            "{}{}, {}x{}, {:.1} KB",
+            format,
+            colors,
+            proof.image.width(),
+            proof.image.height(),
+            proof.bytes as f32 / 1024.0
+        )
+    }
+}
@@ -0,0 +1,146 @@
+//! PDF export with one page per image, for sharing annotated screenshots. Pages are sized to the
+//! image at 96 DPI and hold the image as an embedded JPEG, flattened onto white. Printing writes
+//! pages of a paper size instead, with the image placed inside the margins.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use nannou::image::{DynamicImage, GenericImageView, ImageOutputFormat, RgbImage};
+
+const JPEG_QUALITY: u8 = 92;
+/// PDF points per pixel at 96 DPI.
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+/// A page and where its image is drawn, in PDF points from the bottom left corner.
+pub struct Page<'a> {
+    pub image: &'a DynamicImage,
+    pub size: (f32, f32),
+    /// x, y, width and height of the image on the page.
+    pub placement: (f32, f32, f32, f32),
+}
+
+pub fn export(images: &[DynamicImage], path: &Path) -> io::Result<()> {
+    let pages: Vec<Page> = images
+        .iter()
+        .map(|image| {
+            let (width, height) = (
+                image.width() as f32 * POINTS_PER_PIXEL,
+                image.height() as f32 * POINTS_PER_PIXEL,
+            );
+            Page {
+                image,
+                size: (width, height),
+                placement: (0.0, 0.0, width, height),
+            }
+        })
+        .collect();
+    write_pages(&pages, path)
+}
+
+pub fn write_pages(pages: &[Page], path: &Path) -> io::Result<()> {
+    if pages.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no pages to export",
+        ));
+    }
+
+    // Objects 1 and 2 are the catalog and page tree, then three per page: the page, its
+    // content stream and its image.
+    let page_id = |i: usize| 3 + i * 3;
+    let mut objects: Vec<Vec<u8>> = vec![
+        b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            (0..pages.len())
+                .map(|i| format!("{} 0 R", page_id(i)))
+                .collect::<Vec<_>>()
+                .join(" "),
+            pages.len()
+        )
+        .into_bytes(),
+    ];
+
+    for (i, page) in pages.iter().enumerate() {
+        let image = page.image;
+        let (width, height) = page.size;
+        let (contents, xobject) = (page_id(i) + 1, page_id(i) + 2);
+
+        objects.push(
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {w} {h}] /Contents {c} 0 R \
+                 /Resources << /XObject << /Im0 {x} 0 R >> >> >>",
+                w = width,
+                h = height,
+                c = contents,
+                x = xobject,
+            )
+            .into_bytes(),
+        );
+
+        let (x, y, w, h) = page.placement;
+        let draw = format!("q {} 0 0 {} {} {} cm /Im0 Do Q", w, h, x, y);
+        objects.push(stream(String::new(), draw.as_bytes()));
+
+        let mut jpeg = Vec::new();
+        DynamicImage::ImageRgb8(flatten(image))
+            .write_to(&mut jpeg, ImageOutputFormat::Jpeg(JPEG_QUALITY))
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        objects.push(stream(
+            format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+                 /BitsPerComponent 8 /Filter /DCTDecode",
+                image.width(),
+                image.height()
+            ),
+            &jpeg,
+        ));
+    }
+
+    let mut pdf = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::with_capacity(objects.len());
+    for (i, object) in objects.iter().enumerate() {
+        offsets.push(pdf.len());
+        pdf.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+        pdf.extend_from_slice(object);
+        pdf.extend_from_slice(b"\nendobj\n");
+    }
+
+    let xref = pdf.len();
+    pdf.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+    );
+    for offset in offsets {
+        pdf.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    pdf.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        )
+        .as_bytes(),
+    );
+
+    fs::write(path, pdf)
+}
+
+/// A stream object with extra dictionary entries.
+fn stream(dictionary: String, data: &[u8]) -> Vec<u8> {
+    let mut object = format!("<< {} /Length {} >>\nstream\n", dictionary, data.len()).into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+/// Lays the image over white, since JPEG has no alpha channel.
+fn flatten(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let a = a as f32 / 255.0;
+        let mix = |c: u8| (c as f32 * a + 255.0 * (1.0 - a)).round() as u8;
+        nannou::image::Rgb([mix(r), mix(g), mix(b)])
+    })
+}
@@ -0,0 +1,118 @@
+//! SVG export of a layered document. Layers only hold pixels, so this is raster-in-SVG: each
+//! layer is written as an embedded PNG inside its own group, keeping its name, opacity and
+//! visibility, so the layers stay separate when a mockup is refined in a vector tool.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use nannou::image::ImageOutputFormat;
+
+use crate::layers::LayerStack;
+
+pub fn export(layers: &LayerStack, path: &Path) -> io::Result<()> {
+    fs::write(path, document(layers)?)
+}
+
+fn document(layers: &LayerStack) -> io::Result<String> {
+    let (width, height) = (layers.width(), layers.height());
+    let mut svg = String::new();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape" width="{w}" height="{h}" viewBox="0 0 {w} {h}">"#,
+        w = width,
+        h = height,
+    )
+    .unwrap();
+
+    for (index, layer) in layers.layers.iter().enumerate() {
+        let mut png = Vec::new();
+        layer
+            .pixels
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+        let hidden = if layer.visible {
+            ""
+        } else {
+            r#" visibility="hidden""#
+        };
+        // Layer names can repeat and hold anything, so they go in the label vector tools show
+        // and the id is generated
+        writeln!(
+            svg,
+            r#"  <g id="layer-{index}" inkscape:groupmode="layer" inkscape:label="{name}" opacity="{opacity}"{hidden}>"#,
+            index = index + 1,
+            name = escape(&layer.name),
+            opacity = layer.opacity,
+            hidden = hidden,
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            r#"    <image width="{}" height="{}" xlink:href="data:image/png;base64,{}"/>"#,
+            width,
+            height,
+            base64::encode(&png),
+        )
+        .unwrap();
+        writeln!(svg, "  </g>").unwrap();
+    }
+
+    writeln!(svg, "</svg>").unwrap();
+    Ok(svg)
+}
+
+/// Escapes text for use in an XML attribute.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::{DynamicImage, RgbaImage};
+
+    use crate::layers::Layer;
+
+    fn stack(names: &[&str]) -> LayerStack {
+        let mut layers = LayerStack::new(DynamicImage::ImageRgba8(RgbaImage::new(2, 2)));
+        layers.layers = names
+            .iter()
+            .map(|name| {
+                Layer::new(
+                    name.to_string(),
+                    DynamicImage::ImageRgba8(RgbaImage::new(2, 2)),
+                )
+            })
+            .collect();
+        layers
+    }
+
+    #[test]
+    fn ids_are_unique_whatever_the_names() {
+        let svg = document(&stack(&["Layer 1", "Layer 1", "a\"<b>&"])).unwrap();
+        assert!(svg.contains(r#"id="layer-1""#));
+        assert!(svg.contains(r#"id="layer-2""#));
+        assert!(svg.contains(r#"id="layer-3""#));
+        assert!(svg.contains(r#"inkscape:label="a&quot;&lt;b&gt;&amp;""#));
+    }
+
+    #[test]
+    fn layers_embed_their_png() {
+        let layers = stack(&["Only"]);
+        let mut png = Vec::new();
+        layers.layers[0]
+            .pixels
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let svg = document(&layers).unwrap();
+        let start = svg.find("base64,").unwrap() + "base64,".len();
+        let end = start + svg[start..].find('"').unwrap();
+        assert_eq!(base64::decode(&svg[start..end]).unwrap(), png);
+    }
+}
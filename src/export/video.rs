@@ -0,0 +1,127 @@
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use nannou::image::{imageops::FilterType, DynamicImage, GenericImageView};
+
+use crate::alpha;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VideoFormat {
+    Mp4,
+    WebM,
+}
+
+impl VideoFormat {
+    pub fn from_path(path: &Path) -> Option<VideoFormat> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "mp4" => Some(VideoFormat::Mp4),
+            "webm" => Some(VideoFormat::WebM),
+            _ => None,
+        }
+    }
+
+    fn codec_args(&self) -> &'static [&'static str] {
+        match self {
+            VideoFormat::Mp4 => &[
+                "-c:v",
+                "libx264",
+                "-pix_fmt",
+                "yuv420p",
+                "-movflags",
+                "+faststart",
+            ],
+            VideoFormat::WebM => &[
+                "-c:v",
+                "libvpx-vp9",
+                "-pix_fmt",
+                "yuva420p",
+                "-b:v",
+                "0",
+                "-crf",
+                "30",
+            ],
+        }
+    }
+}
+
+pub struct VideoOptions {
+    pub fps: f32,
+    pub scale: f32,
+}
+
+impl Default for VideoOptions {
+    fn default() -> Self {
+        Self {
+            fps: 12.0,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Encodes `frames` to `path` by piping raw RGBA frames into `ffmpeg`, which must be on `PATH`.
+/// The container is picked from the file extension (`.mp4` or `.webm`).
+pub fn export_video(
+    frames: &[DynamicImage],
+    path: &Path,
+    options: &VideoOptions,
+) -> io::Result<()> {
+    let first = frames
+        .first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no frames to export"))?;
+    let format = VideoFormat::from_path(path).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "video path must end in .mp4 or .webm",
+        )
+    })?;
+
+    // Chroma subsampled pixel formats need even dimensions
+    let even = |v: f32| ((v.round() as u32).max(2) + 1) & !1;
+    let width = even(first.width() as f32 * options.scale);
+    let height = even(first.height() as f32 * options.scale);
+
+    let mut child = Command::new("ffmpeg")
+        .args([
+            "-y",
+            "-loglevel",
+            "error",
+            "-f",
+            "rawvideo",
+            "-pix_fmt",
+            "rgba",
+        ])
+        .arg("-s")
+        .arg(format!("{}x{}", width, height))
+        .arg("-r")
+        .arg(options.fps.to_string())
+        .args(["-i", "-"])
+        .args(format.codec_args())
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()?;
+
+    let mut stdin = child.stdin.take().unwrap();
+    let written = frames.iter().try_for_each(|frame| {
+        // Nearest keeps hard pixel edges when scaling up sprite work, and shrinking is filtered
+        let frame = if options.scale >= 1.0 {
+            frame.resize_exact(width, height, FilterType::Nearest)
+        } else {
+            alpha::resize_exact(frame, width, height, FilterType::Lanczos3)
+        };
+        stdin.write_all(&frame.to_rgba8())
+    });
+    // Closing stdin ends the stream, and ffmpeg is waited on even when writing failed so it
+    // isn't left running
+    drop(stdin);
+
+    let status = child.wait()?;
+    if !status.success() {
+        // Writes fail once ffmpeg has quit, so its exit says more than the broken pipe
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("ffmpeg exited with {}", status),
+        ));
+    }
+    written
+}
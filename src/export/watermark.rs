@@ -0,0 +1,130 @@
+use nannou::image::{DynamicImage, GenericImageView, Pixel, Rgba, RgbaImage};
+use rusttype::{point, Scale};
+
+/// Corner or edge of the output the watermark is placed against.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    Center,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Anchor {
+    pub const ALL: [Anchor; 5] = [
+        Anchor::TopLeft,
+        Anchor::TopRight,
+        Anchor::Center,
+        Anchor::BottomLeft,
+        Anchor::BottomRight,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Anchor::TopLeft => "Top Left",
+            Anchor::TopRight => "Top Right",
+            Anchor::Center => "Center",
+            Anchor::BottomLeft => "Bottom Left",
+            Anchor::BottomRight => "Bottom Right",
+        }
+    }
+
+    /// The anchor after this one, wrapping around.
+    pub fn next(&self) -> Anchor {
+        let i = Anchor::ALL.iter().position(|a| a == self).unwrap();
+        Anchor::ALL[(i + 1) % Anchor::ALL.len()]
+    }
+}
+
+/// Image or text composited over exported output. The document itself is never modified.
+pub struct Watermark {
+    pub enabled: bool,
+    pub image: Option<DynamicImage>,
+    /// Rendered instead when no image is set.
+    pub text: String,
+    pub anchor: Anchor,
+    pub opacity: f32,
+    pub margin: f32,
+}
+
+impl Default for Watermark {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            image: None,
+            text: String::new(),
+            anchor: Anchor::BottomRight,
+            opacity: 0.5,
+            margin: 8.0,
+        }
+    }
+}
+
+impl Watermark {
+    fn render_text(&self, height: u32) -> Option<RgbaImage> {
+        if self.text.is_empty() {
+            return None;
+        }
+
+        let font = nannou::text::font::default_notosans();
+        // Text is sized relative to the output so it reads the same at any export scale
+        let scale = Scale::uniform((height as f32 / 16.0).max(8.0));
+        let ascent = font.v_metrics(scale).ascent;
+        let glyphs: Vec<_> = font.layout(&self.text, scale, point(0.0, ascent)).collect();
+        let width = glyphs
+            .iter()
+            .filter_map(|g| g.pixel_bounding_box())
+            .map(|b| b.max.x)
+            .max()?;
+
+        let mut out = RgbaImage::new(width.max(1) as u32, scale.y.ceil() as u32);
+        for glyph in &glyphs {
+            if let Some(bb) = glyph.pixel_bounding_box() {
+                glyph.draw(|x, y, v| {
+                    let (x, y) = (x as i32 + bb.min.x, y as i32 + bb.min.y);
+                    if x >= 0 && y >= 0 && (x as u32) < out.width() && (y as u32) < out.height() {
+                        out.put_pixel(x as u32, y as u32, Rgba([255, 255, 255, (v * 255.0) as u8]));
+                    }
+                });
+            }
+        }
+        Some(out)
+    }
+
+    /// Returns a copy of `image` with the watermark composited on top.
+    pub fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mark = match &self.image {
+            Some(mark) => mark.to_rgba8(),
+            None => match self.render_text(image.height()) {
+                Some(mark) => mark,
+                None => return image.clone(),
+            },
+        };
+
+        let (w, h) = image.dimensions();
+        let (mw, mh) = mark.dimensions();
+        let margin = self.margin as i64;
+        let (left, right) = (margin, w as i64 - mw as i64 - margin);
+        let (top, bottom) = (margin, h as i64 - mh as i64 - margin);
+        let (x, y) = match self.anchor {
+            Anchor::TopLeft => (left, top),
+            Anchor::TopRight => (right, top),
+            Anchor::Center => ((w as i64 - mw as i64) / 2, (h as i64 - mh as i64) / 2),
+            Anchor::BottomLeft => (left, bottom),
+            Anchor::BottomRight => (right, bottom),
+        };
+
+        let mut out = image.to_rgba8();
+        for (mx, my, px) in mark.enumerate_pixels() {
+            let (ox, oy) = (x + mx as i64, y + my as i64);
+            if ox < 0 || oy < 0 || ox >= w as i64 || oy >= h as i64 {
+                continue;
+            }
+            let mut px = *px;
+            px.0[3] = (px.0[3] as f32 * self.opacity) as u8;
+            out.get_pixel_mut(ox as u32, oy as u32).blend(&px);
+        }
+        DynamicImage::ImageRgba8(out)
+    }
+}
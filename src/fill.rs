@@ -0,0 +1,184 @@
+//! Bucket fill.
+
+use nannou::image::Rgba;
+
+use crate::canvas::Canvas;
+use crate::layers::over;
+
+/// Scanline flood fill from `(x, y)`, covering every connected pixel whose channels all differ
+/// from the clicked pixel by at most `tolerance`. `color` is laid over each filled pixel at
+/// `opacity`. With a selection, the fill never leaves it.
+pub fn flood_fill(
+    canvas: &mut Canvas,
+    start: (u32, u32),
+    color: [u8; 4],
+    opacity: f32,
+    tolerance: u8,
+) {
+    let filled = match fill_mask(canvas, start, tolerance) {
+        Some(filled) => filled,
+        None => return,
+    };
+    let width = canvas.width();
+    canvas.fill(|x, y, pixel| {
+        filled[(y * width + x) as usize].then(|| over(pixel, Rgba(color), opacity))
+    });
+}
+
+/// Which pixels a fill from `(x, y)` covers, row by row. `None` if the start is off the canvas.
+fn fill_mask(canvas: &Canvas, (x, y): (u32, u32), tolerance: u8) -> Option<Vec<bool>> {
+    // Layers are RGBA8 unless imported otherwise, which are compared as RGBA8
+    let converted;
+    let image = match canvas.pixels().as_rgba8() {
+        Some(image) => image,
+        None => {
+            converted = canvas.pixels().to_rgba8();
+            &converted
+        }
+    };
+    let (width, height) = image.dimensions();
+    if x >= width || y >= height {
+        return None;
+    }
+
+    let target = *image.get_pixel(x, y);
+    let matches = |p: &Rgba<u8>| {
+        p.0.iter()
+            .zip(target.0.iter())
+            .all(|(a, b)| (*a as i16 - *b as i16).unsigned_abs() <= tolerance as u16)
+    };
+    let mut filled = vec![false; (width * height) as usize];
+    let fillable = |filled: &[bool], x: u32, y: u32| {
+        !filled[(y * width + x) as usize]
+            && canvas.paintable(x, y)
+            && matches(image.get_pixel(x, y))
+    };
+
+    let mut seeds = vec![(x, y)];
+    while let Some((x, y)) = seeds.pop() {
+        if !fillable(&filled, x, y) {
+            continue;
+        }
+
+        let mut x0 = x;
+        while x0 > 0 && fillable(&filled, x0 - 1, y) {
+            x0 -= 1;
+        }
+        let mut x1 = x;
+        while x1 + 1 < width && fillable(&filled, x1 + 1, y) {
+            x1 += 1;
+        }
+
+        // Queue one seed per run of fillable pixels in the rows above and below the span
+        for row in [y.checked_sub(1), Some(y + 1).filter(|&r| r < height)]
+            .into_iter()
+            .flatten()
+        {
+            let mut in_run = false;
+            for sx in x0..=x1 {
+                let open = fillable(&filled, sx, row);
+                if open && !in_run {
+                    seeds.push((sx, row));
+                }
+                in_run = open;
+            }
+        }
+
+        for sx in x0..=x1 {
+            filled[(y * width + sx) as usize] = true;
+        }
+    }
+    Some(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::Selection;
+    use nannou::image::{DynamicImage, RgbaImage};
+
+    const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
+    const BLACK: Rgba<u8> = Rgba([0, 0, 0, 255]);
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    /// `rows` drawn with `#` for black and anything else for white.
+    fn image(rows: &[&str]) -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(rows[0].len() as u32, rows.len() as u32, WHITE);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    image.put_pixel(x as u32, y as u32, BLACK);
+                }
+            }
+        }
+        DynamicImage::ImageRgba8(image)
+    }
+
+    /// `rows` with `r` where the pixel is red, `#` where black and `.` where white.
+    fn render(image: &DynamicImage) -> Vec<String> {
+        let image = image.to_rgba8();
+        image
+            .rows()
+            .map(|row| {
+                row.map(|p| match p.0 {
+                    [255, 0, 0, 255] => 'r',
+                    [0, 0, 0, 255] => '#',
+                    _ => '.',
+                })
+                .collect()
+            })
+            .collect()
+    }
+
+    fn fill(pixels: &mut DynamicImage, selection: Option<&Selection>, start: (u32, u32)) {
+        flood_fill(&mut Canvas::new(pixels, selection), start, RED, 1.0, 0);
+    }
+
+    #[test]
+    fn stops_at_walls() {
+        let mut pixels = image(&["..#..", "..#..", "..#.."]);
+        fill(&mut pixels, None, (0, 1));
+        assert_eq!(render(&pixels), ["rr#..", "rr#..", "rr#.."]);
+    }
+
+    #[test]
+    fn reaches_around_corners() {
+        // Only reachable by going down, along and back up
+        let mut pixels = image(&[".#...", ".#.#.", ".#.#.", "...#."]);
+        fill(&mut pixels, None, (0, 0));
+        assert_eq!(render(&pixels), ["r#rrr", "r#r#r", "r#r#r", "rrr#r"]);
+    }
+
+    #[test]
+    fn stays_in_the_selection() {
+        let mut pixels = image(&["....", "....", "...."]);
+        let selection = Selection {
+            x: 1,
+            y: 0,
+            width: 2,
+            height: 2,
+        };
+        fill(&mut pixels, Some(&selection), (1, 1));
+        assert_eq!(render(&pixels), [".rr.", ".rr.", "...."]);
+    }
+
+    #[test]
+    fn tolerance_takes_in_similar_colors() {
+        let mut pixels = image(&["..."]);
+        if let DynamicImage::ImageRgba8(image) = &mut pixels {
+            image.put_pixel(2, 0, Rgba([250, 250, 250, 255]));
+        }
+        let mut strict = pixels.clone();
+        flood_fill(&mut Canvas::new(&mut strict, None), (0, 0), RED, 1.0, 0);
+        assert_eq!(render(&strict), ["rr."]);
+        flood_fill(&mut Canvas::new(&mut pixels, None), (0, 0), RED, 1.0, 5);
+        assert_eq!(render(&pixels), ["rrr"]);
+    }
+
+    #[test]
+    fn ignores_a_start_off_the_canvas() {
+        let mut pixels = image(&["..", ".."]);
+        fill(&mut pixels, None, (2, 0));
+        assert_eq!(render(&pixels), ["..", ".."]);
+    }
+}
@@ -0,0 +1,168 @@
+use nannou::image::DynamicImage;
+
+use super::{par_map, ImageFilter};
+
+/// Shifts brightness and stretches contrast around mid-gray, both from -100 to 100.
+#[derive(Clone, PartialEq)]
+pub struct BrightnessContrast {
+    pub brightness: f32,
+    pub contrast: f32,
+}
+
+impl Default for BrightnessContrast {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 0.0,
+        }
+    }
+}
+
+impl BrightnessContrast {
+    /// The adjustment of each channel value, shared by the CPU and GPU filters.
+    pub fn lut(&self) -> [u8; 256] {
+        // Contrast of -100 flattens to gray and +100 is close to a hard threshold
+        let contrast = (self.contrast.clamp(-100.0, 99.0) / 100.0 + 1.0)
+            / (1.0 - self.contrast.clamp(-100.0, 99.0) / 100.0);
+        let brightness = self.brightness / 100.0 * 255.0;
+        let mut lut = [0; 256];
+        for (v, out) in lut.iter_mut().enumerate() {
+            let v = (v as f32 - 127.5) * contrast + 127.5 + brightness;
+            *out = v.round().clamp(0.0, 255.0) as u8;
+        }
+        lut
+    }
+}
+
+impl ImageFilter for BrightnessContrast {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let lut = self.lut();
+
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            [lut[r as usize], lut[g as usize], lut[b as usize], a]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Brightness", &mut self.brightness, -100.0, 100.0),
+            ("Contrast", &mut self.contrast, -100.0, 100.0),
+        ]
+    }
+}
+
+/// Scales how far each pixel is from its own gray. 0 is grayscale, 1 leaves the image unchanged.
+#[derive(Clone, PartialEq)]
+pub struct Saturation {
+    pub amount: f32,
+}
+
+impl Default for Saturation {
+    fn default() -> Self {
+        Self { amount: 1.0 }
+    }
+}
+
+impl ImageFilter for Saturation {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            let gray = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+            let mix = |c: u8| {
+                (gray + (c as f32 - gray) * self.amount)
+                    .round()
+                    .clamp(0.0, 255.0) as u8
+            };
+            [mix(r), mix(g), mix(b), a]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Saturation", &mut self.amount, 0.0, 3.0)]
+    }
+}
+
+/// Remaps the black and white points to the ends of the range, with gamma bending the values in
+/// between. A gamma above 1 brightens the midtones.
+#[derive(Clone, PartialEq)]
+pub struct Levels {
+    pub black: f32,
+    pub white: f32,
+    pub gamma: f32,
+}
+
+impl Default for Levels {
+    fn default() -> Self {
+        Self {
+            black: 0.0,
+            white: 255.0,
+            gamma: 1.0,
+        }
+    }
+}
+
+impl Levels {
+    /// The adjustment of each channel value, shared by the CPU and GPU filters.
+    pub fn lut(&self) -> [u8; 256] {
+        // Keep at least one step between the points so the range never collapses
+        let black = self.black.min(254.0);
+        let white = self.white.max(black + 1.0);
+        let mut lut = [0; 256];
+        for (v, out) in lut.iter_mut().enumerate() {
+            let t = ((v as f32 - black) / (white - black)).clamp(0.0, 1.0);
+            *out = (t.powf(1.0 / self.gamma.max(0.01)) * 255.0).round() as u8;
+        }
+        lut
+    }
+}
+
+impl ImageFilter for Levels {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let lut = self.lut();
+
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            [lut[r as usize], lut[g as usize], lut[b as usize], a]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Black Point", &mut self.black, 0.0, 255.0),
+            ("White Point", &mut self.white, 0.0, 255.0),
+            ("Gamma", &mut self.gamma, 0.1, 4.0),
+        ]
+    }
+}
+
+/// Turns pixels white where their luminance reaches `level` and black elsewhere, keeping alpha.
+#[derive(Clone, PartialEq)]
+pub struct Threshold {
+    pub level: f32,
+}
+
+impl Default for Threshold {
+    fn default() -> Self {
+        Self { level: 128.0 }
+    }
+}
+
+impl ImageFilter for Threshold {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            let gray = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+            let v = if gray >= self.level { 255 } else { 0 };
+            [v, v, v, a]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Level", &mut self.level, 0.0, 255.0)]
+    }
+}
@@ -0,0 +1,127 @@
+use nannou::image::{imageops, DynamicImage, RgbaImage};
+use rayon::prelude::*;
+
+use super::ImageFilter;
+use crate::alpha::{premultiply, unpremultiply};
+
+/// Gaussian blur with standard deviation `radius`.
+#[derive(Clone, PartialEq)]
+pub struct GaussianBlur {
+    pub radius: f32,
+}
+
+impl Default for GaussianBlur {
+    fn default() -> Self {
+        Self { radius: 2.0 }
+    }
+}
+
+impl ImageFilter for GaussianBlur {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        // Averaged premultiplied, so transparent pixels add no color of their own
+        let blurred = imageops::blur(&premultiply(&image.to_rgba8()), self.radius);
+        DynamicImage::ImageRgba8(unpremultiply(&blurred))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Radius", &mut self.radius, 0.1, 50.0)]
+    }
+}
+
+/// Averages each pixel with the square of pixels `radius` around it. Cheap at any radius since
+/// it runs as a horizontal then a vertical sliding sum.
+#[derive(Clone, PartialEq)]
+pub struct BoxBlur {
+    pub radius: f32,
+}
+
+impl Default for BoxBlur {
+    fn default() -> Self {
+        Self { radius: 3.0 }
+    }
+}
+
+impl ImageFilter for BoxBlur {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let radius = self.radius.round() as usize;
+        let src = image.to_rgba8();
+        let (w, h) = (src.width() as usize, src.height() as usize);
+        if radius == 0 || w == 0 || h == 0 {
+            return DynamicImage::ImageRgba8(src);
+        }
+        let src = premultiply(&src);
+
+        let rows = box_rows(src.as_raw(), w, h, radius);
+        let transposed = transpose(&rows, w, h);
+        let cols = box_rows(&transposed, h, w, radius);
+        let out = transpose(&cols, h, w);
+        let out = RgbaImage::from_raw(w as u32, h as u32, out).unwrap();
+        DynamicImage::ImageRgba8(unpremultiply(&out))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Radius", &mut self.radius, 0.0, 50.0)]
+    }
+}
+
+/// Box-averages every row of an RGBA buffer `w` pixels wide, clamping at the edges.
+fn box_rows(src: &[u8], w: usize, h: usize, radius: usize) -> Vec<u8> {
+    let mut out = vec![0u8; w * h * 4];
+    let window = (2 * radius + 1) as u32;
+    out.par_chunks_mut(w * 4)
+        .zip(src.par_chunks(w * 4))
+        .for_each(|(out, row)| {
+            let at = |x: isize, c: usize| row[x.clamp(0, w as isize - 1) as usize * 4 + c] as u32;
+            for c in 0..4 {
+                let mut sum: u32 = (-(radius as isize)..=radius as isize)
+                    .map(|x| at(x, c))
+                    .sum();
+                for x in 0..w {
+                    out[x * 4 + c] = ((sum + window / 2) / window) as u8;
+                    let x = x as isize;
+                    sum = sum + at(x + radius as isize + 1, c) - at(x - radius as isize, c);
+                }
+            }
+        });
+    out
+}
+
+fn transpose(src: &[u8], w: usize, h: usize) -> Vec<u8> {
+    let mut out = vec![0u8; w * h * 4];
+    for y in 0..h {
+        for x in 0..w {
+            let (from, to) = ((y * w + x) * 4, (x * h + y) * 4);
+            out[to..to + 4].copy_from_slice(&src[from..from + 4]);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::Rgba;
+
+    /// Opaque red beside transparent pixels that hold green.
+    fn edge() -> DynamicImage {
+        let mut image = RgbaImage::from_pixel(5, 1, Rgba([0, 255, 0, 0]));
+        image.put_pixel(2, 0, Rgba([255, 0, 0, 255]));
+        DynamicImage::ImageRgba8(image)
+    }
+
+    #[test]
+    fn box_blur_takes_no_color_from_transparent_pixels() {
+        let blurred = BoxBlur { radius: 1.0 }.apply(&edge()).to_rgba8();
+        let [r, g, _, a] = blurred.get_pixel(1, 0).0;
+        assert_eq!((r, g), (255, 0));
+        assert!(a > 0 && a < 255);
+    }
+
+    #[test]
+    fn gaussian_blur_takes_no_color_from_transparent_pixels() {
+        let blurred = GaussianBlur { radius: 1.0 }.apply(&edge()).to_rgba8();
+        let [r, g, _, a] = blurred.get_pixel(1, 0).0;
+        assert!(r >= 250 && g <= 5, "{:?}", blurred.get_pixel(1, 0));
+        assert!(a > 0 && a < 255);
+    }
+}
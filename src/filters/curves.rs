@@ -0,0 +1,198 @@
+use nannou::image::DynamicImage;
+
+use super::{par_map, ImageFilter};
+
+/// A mapping from input to output values, both 0-255, through control points joined by a
+/// monotone cubic spline, so the curve never overshoots between its points.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Curve {
+    /// Control points as `[input, output]`, sorted by input. The first and last are pinned to
+    /// inputs 0 and 255.
+    pub points: Vec<[f32; 2]>,
+}
+
+impl Default for Curve {
+    fn default() -> Self {
+        Self {
+            points: vec![[0.0, 0.0], [255.0, 255.0]],
+        }
+    }
+}
+
+impl Curve {
+    pub fn is_identity(&self) -> bool {
+        *self == Curve::default()
+    }
+
+    /// Adds a point at input `x`, keeping the points sorted, and returns its index. Inputs
+    /// already taken by a point, or at the pinned ends, are refused.
+    pub fn insert(&mut self, x: f32, y: f32) -> Option<usize> {
+        let x = x.round();
+        let i = self.points.iter().position(|p| p[0] >= x)?;
+        if i == 0 || self.points[i][0] == x {
+            return None;
+        }
+        self.points.insert(i, [x, y.round().clamp(0.0, 255.0)]);
+        Some(i)
+    }
+
+    /// Removes a point. The end points stay.
+    pub fn remove(&mut self, i: usize) {
+        if i > 0 && i + 1 < self.points.len() {
+            self.points.remove(i);
+        }
+    }
+
+    /// Moves a point, keeping it between its neighbors. The end points only move up and down.
+    pub fn move_point(&mut self, i: usize, x: f32, y: f32) {
+        let last = self.points.len() - 1;
+        let x = match i {
+            0 => 0.0,
+            _ if i == last => 255.0,
+            _ => x
+                .round()
+                .clamp(self.points[i - 1][0] + 1.0, self.points[i + 1][0] - 1.0),
+        };
+        self.points[i] = [x, y.round().clamp(0.0, 255.0)];
+    }
+
+    /// The point closest to `[x, y]` no further than `within` away.
+    pub fn nearest(&self, x: f32, y: f32, within: f32) -> Option<usize> {
+        let distance = |p: &[f32; 2]| ((p[0] - x).powi(2) + (p[1] - y).powi(2)).sqrt();
+        self.points
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| distance(p) <= within)
+            .min_by(|(_, a), (_, b)| distance(a).total_cmp(&distance(b)))
+            .map(|(i, _)| i)
+    }
+
+    /// Slopes at each point for a Fritsch-Carlson monotone spline.
+    fn tangents(&self) -> Vec<f32> {
+        let p = &self.points;
+        let secants: Vec<f32> = p
+            .windows(2)
+            .map(|w| (w[1][1] - w[0][1]) / (w[1][0] - w[0][0]))
+            .collect();
+        let n = p.len();
+        let mut m = vec![0.0; n];
+        m[0] = secants[0];
+        m[n - 1] = secants[n - 2];
+        for k in 1..n - 1 {
+            // Flat at local extremes, so the curve doesn't bulge past them
+            if secants[k - 1] * secants[k] > 0.0 {
+                m[k] = (secants[k - 1] + secants[k]) / 2.0;
+            }
+        }
+        for (k, &d) in secants.iter().enumerate() {
+            if d == 0.0 {
+                m[k] = 0.0;
+                m[k + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (m[k] / d, m[k + 1] / d);
+            let s = a * a + b * b;
+            if s > 9.0 {
+                let t = 3.0 / s.sqrt();
+                m[k] = t * a * d;
+                m[k + 1] = t * b * d;
+            }
+        }
+        m
+    }
+
+    /// The curve's output for every input, for use as a lookup table.
+    pub fn lut(&self) -> [u8; 256] {
+        let (p, m) = (&self.points, self.tangents());
+        let mut lut = [0; 256];
+        let mut k = 0;
+        for (x, out) in lut.iter_mut().enumerate() {
+            let x = x as f32;
+            while k + 2 < p.len() && x > p[k + 1][0] {
+                k += 1;
+            }
+            let h = p[k + 1][0] - p[k][0];
+            let t = ((x - p[k][0]) / h).clamp(0.0, 1.0);
+            let (t2, t3) = (t * t, t * t * t);
+            let y = (2.0 * t3 - 3.0 * t2 + 1.0) * p[k][1]
+                + (t3 - 2.0 * t2 + t) * h * m[k]
+                + (-2.0 * t3 + 3.0 * t2) * p[k + 1][1]
+                + (t3 - t2) * h * m[k + 1];
+            *out = y.round().clamp(0.0, 255.0) as u8;
+        }
+        lut
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Channel {
+    /// All three color channels together.
+    Rgb,
+    Red,
+    Green,
+    Blue,
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Rgb => "RGB",
+            Channel::Red => "Red",
+            Channel::Green => "Green",
+            Channel::Blue => "Blue",
+        }
+    }
+
+    pub fn next(&self) -> Channel {
+        match self {
+            Channel::Rgb => Channel::Red,
+            Channel::Red => Channel::Green,
+            Channel::Green => Channel::Blue,
+            Channel::Blue => Channel::Rgb,
+        }
+    }
+}
+
+/// A curve per color channel, applied after a curve shared by all three.
+#[derive(Clone, PartialEq, Default)]
+pub struct Curves {
+    pub rgb: Curve,
+    pub red: Curve,
+    pub green: Curve,
+    pub blue: Curve,
+}
+
+impl Curves {
+    pub fn curve_mut(&mut self, channel: Channel) -> &mut Curve {
+        match channel {
+            Channel::Rgb => &mut self.rgb,
+            Channel::Red => &mut self.red,
+            Channel::Green => &mut self.green,
+            Channel::Blue => &mut self.blue,
+        }
+    }
+
+    /// Red, green and blue tables, each channel's curve composed with the shared one.
+    pub fn luts(&self) -> [[u8; 256]; 3] {
+        let rgb = self.rgb.lut();
+        let table = |curve: &Curve| curve.lut().map(|v| rgb[v as usize]);
+        [table(&self.red), table(&self.green), table(&self.blue)]
+    }
+}
+
+impl ImageFilter for Curves {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let [red, green, blue] = self.luts();
+
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let [r, g, b, a] = src.get_pixel(x, y).0;
+            [red[r as usize], green[g as usize], blue[b as usize], a]
+        }))
+    }
+
+    /// Curves are edited on their own widget rather than with sliders.
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,159 @@
+use nannou::image::{DynamicImage, RgbaImage};
+
+use super::{par_map, ImageFilter};
+
+/// Channel values of the `(2 * radius + 1)²` window around `(x, y)`, clamped at the edges.
+fn window(src: &RgbaImage, x: u32, y: u32, radius: i32) -> impl Iterator<Item = [u8; 4]> + '_ {
+    let (w, h) = (src.width() as i32, src.height() as i32);
+    (-radius..=radius).flat_map(move |dy| {
+        (-radius..=radius).map(move |dx| {
+            let sx = (x as i32 + dx).clamp(0, w - 1);
+            let sy = (y as i32 + dy).clamp(0, h - 1);
+            src.get_pixel(sx as u32, sy as u32).0
+        })
+    })
+}
+
+fn median(src: &RgbaImage, x: u32, y: u32, radius: i32) -> [u8; 4] {
+    let mut channels: [Vec<u8>; 4] = Default::default();
+    for px in window(src, x, y, radius) {
+        for c in 0..4 {
+            channels[c].push(px[c]);
+        }
+    }
+    channels.map(|mut values| {
+        values.sort_unstable();
+        values[values.len() / 2]
+    })
+}
+
+/// Replaces every pixel with the per-channel median of its neighbourhood.
+#[derive(Clone, PartialEq)]
+pub struct Median {
+    pub radius: f32,
+}
+
+impl Default for Median {
+    fn default() -> Self {
+        Self { radius: 1.0 }
+    }
+}
+
+impl ImageFilter for Median {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let radius = self.radius.round() as i32;
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| median(&src, x, y, radius)))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Radius", &mut self.radius, 1.0, 5.0)]
+    }
+}
+
+/// Median filter applied only to pixels that stand out from their neighbourhood by more than
+/// `threshold`, removing isolated specks while leaving texture intact.
+#[derive(Clone, PartialEq)]
+pub struct Despeckle {
+    pub radius: f32,
+    pub threshold: f32,
+}
+
+impl Default for Despeckle {
+    fn default() -> Self {
+        Self {
+            radius: 1.0,
+            threshold: 40.0,
+        }
+    }
+}
+
+impl ImageFilter for Despeckle {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let radius = self.radius.round() as i32;
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let orig = src.get_pixel(x, y).0;
+            let med = median(&src, x, y, radius);
+            let diff = (0..3)
+                .map(|c| (orig[c] as i32 - med[c] as i32).abs())
+                .max()
+                .unwrap_or(0);
+            if diff as f32 > self.threshold {
+                med
+            } else {
+                orig
+            }
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Radius", &mut self.radius, 1.0, 5.0),
+            ("Threshold", &mut self.threshold, 0.0, 255.0),
+        ]
+    }
+}
+
+/// Edge-preserving smoothing: neighbours are weighted by both distance and color similarity.
+#[derive(Clone, PartialEq)]
+pub struct Bilateral {
+    pub radius: f32,
+    pub sigma_color: f32,
+    pub sigma_space: f32,
+}
+
+impl Default for Bilateral {
+    fn default() -> Self {
+        Self {
+            radius: 3.0,
+            sigma_color: 30.0,
+            sigma_space: 2.0,
+        }
+    }
+}
+
+impl ImageFilter for Bilateral {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let (w, h) = (src.width() as i32, src.height() as i32);
+        let radius = self.radius.round() as i32;
+        let space = -0.5 / (self.sigma_space * self.sigma_space);
+        let color = -0.5 / (self.sigma_color * self.sigma_color);
+
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let center = src.get_pixel(x, y).0;
+            let mut sum = [0.0f32; 3];
+            let mut total = 0.0;
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    let sx = (x as i32 + dx).clamp(0, w - 1) as u32;
+                    let sy = (y as i32 + dy).clamp(0, h - 1) as u32;
+                    let px = src.get_pixel(sx, sy).0;
+                    let dist = (0..3)
+                        .map(|c| (px[c] as f32 - center[c] as f32).powi(2))
+                        .sum::<f32>();
+                    let weight = (((dx * dx + dy * dy) as f32) * space + dist * color).exp();
+                    for c in 0..3 {
+                        sum[c] += px[c] as f32 * weight;
+                    }
+                    total += weight;
+                }
+            }
+            [
+                (sum[0] / total).round() as u8,
+                (sum[1] / total).round() as u8,
+                (sum[2] / total).round() as u8,
+                center[3],
+            ]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Radius", &mut self.radius, 1.0, 8.0),
+            ("Color Sigma", &mut self.sigma_color, 1.0, 100.0),
+            ("Space Sigma", &mut self.sigma_space, 0.5, 10.0),
+        ]
+    }
+}
@@ -0,0 +1,281 @@
+//! Compute-shader versions of the filters that dominate on large canvases: the blurs, threshold and
+//! the color adjustments. They run on the device nannou already opened for the windows. Filters
+//! without a shader, devices without compute support and images past the device's buffer limits
+//! fall back to the CPU.
+
+use std::sync::Arc;
+
+use nannou::image::{DynamicImage, RgbaImage};
+use nannou::wgpu::{self, util::DeviceExt};
+
+use super::{blur, Filter};
+use crate::alpha::{premultiply, unpremultiply};
+
+/// Side of the square of pixels each workgroup covers, matching `workgroup_size` in the shader.
+const WORKGROUP_SIZE: u32 = 16;
+
+/// One dispatch of a shader entry point.
+struct Pass<'a> {
+    pipeline: &'a wgpu::ComputePipeline,
+    radius: i32,
+    amount: f32,
+    /// Blur weights or lookup tables, depending on the entry point.
+    table: Vec<f32>,
+}
+
+pub struct GpuFilters {
+    device_queue: Arc<wgpu::DeviceQueuePair>,
+    layout: wgpu::BindGroupLayout,
+    blur_horizontal: wgpu::ComputePipeline,
+    blur_vertical: wgpu::ComputePipeline,
+    lookup: wgpu::ComputePipeline,
+    saturation: wgpu::ComputePipeline,
+    threshold: wgpu::ComputePipeline,
+}
+
+impl GpuFilters {
+    /// Builds the pipelines on a window's device. `None` if the device can't run them.
+    pub fn new(device_queue: Arc<wgpu::DeviceQueuePair>) -> Option<GpuFilters> {
+        let device = device_queue.device();
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("filters"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("gpu.wgsl").into()),
+        });
+        let buffer = |binding, ty| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("filters"),
+            entries: &[
+                buffer(0, wgpu::BufferBindingType::Uniform),
+                buffer(1, wgpu::BufferBindingType::Storage { read_only: true }),
+                buffer(2, wgpu::BufferBindingType::Storage { read_only: false }),
+                buffer(3, wgpu::BufferBindingType::Storage { read_only: true }),
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("filters"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(&pipeline_layout),
+                module: &module,
+                entry_point,
+            })
+        };
+        let (blur_horizontal, blur_vertical) =
+            (pipeline("blur_horizontal"), pipeline("blur_vertical"));
+        let (lookup, saturation, threshold) = (
+            pipeline("lookup"),
+            pipeline("saturation"),
+            pipeline("threshold"),
+        );
+
+        if let Some(e) = pollster::block_on(device.pop_error_scope()) {
+            log::warn!("GPU filters unavailable, using the CPU: {}", e);
+            return None;
+        }
+
+        Some(GpuFilters {
+            device_queue,
+            layout,
+            blur_horizontal,
+            blur_vertical,
+            lookup,
+            saturation,
+            threshold,
+        })
+    }
+
+    /// Runs `filter` on the GPU. `None` if it has no shader or the GPU run failed.
+    pub fn apply(&self, filter: &Filter, image: &DynamicImage) -> Option<DynamicImage> {
+        let lookup = |tables: [[u8; 256]; 3]| Pass {
+            pipeline: &self.lookup,
+            radius: 0,
+            amount: 0.0,
+            table: tables.iter().flatten().map(|&v| v as f32 / 255.0).collect(),
+        };
+        let blur = |weights: Vec<f32>| {
+            let radius = weights.len() as i32 / 2;
+            [&self.blur_horizontal, &self.blur_vertical].map(|pipeline| Pass {
+                pipeline,
+                radius,
+                amount: 0.0,
+                table: weights.clone(),
+            })
+        };
+
+        let passes: Vec<Pass> = match filter {
+            Filter::GaussianBlur(f) => blur(gaussian_weights(f)).into(),
+            Filter::BoxBlur(f) => {
+                let radius = f.radius.round() as usize;
+                blur(vec![1.0 / (2 * radius + 1) as f32; 2 * radius + 1]).into()
+            }
+            Filter::BrightnessContrast(f) => vec![lookup([f.lut(); 3])],
+            Filter::Levels(f) => vec![lookup([f.lut(); 3])],
+            Filter::Curves(f) => vec![lookup(f.luts())],
+            Filter::Saturation(f) => vec![Pass {
+                pipeline: &self.saturation,
+                radius: 0,
+                amount: f.amount,
+                table: Vec::new(),
+            }],
+            Filter::Threshold(f) => vec![Pass {
+                pipeline: &self.threshold,
+                radius: 0,
+                amount: f.level / 255.0,
+                table: Vec::new(),
+            }],
+            _ => return None,
+        };
+        // Blurs average premultiplied pixels, like their CPU versions
+        if let Filter::GaussianBlur(_) | Filter::BoxBlur(_) = filter {
+            return self
+                .run(&premultiply(&image.to_rgba8()), &passes)
+                .map(|out| DynamicImage::ImageRgba8(unpremultiply(&out)));
+        }
+        self.run(&image.to_rgba8(), &passes)
+            .map(DynamicImage::ImageRgba8)
+    }
+
+    /// Runs the passes one after another, each reading the previous one's output.
+    fn run(&self, src: &RgbaImage, passes: &[Pass]) -> Option<RgbaImage> {
+        let (device, queue) = (self.device_queue.device(), self.device_queue.queue());
+        let (width, height) = src.dimensions();
+        let size = src.as_raw().len() as wgpu::BufferAddress;
+        if size == 0 || size > device.limits().max_storage_buffer_binding_size as u64 {
+            return None;
+        }
+
+        device.push_error_scope(wgpu::ErrorFilter::OutOfMemory);
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+
+        let usage = wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC;
+        let pixels = [
+            device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("filter pixels"),
+                contents: src.as_raw(),
+                usage,
+            }),
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("filter pixels"),
+                size,
+                usage,
+                mapped_at_creation: false,
+            }),
+        ];
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("filters"),
+        });
+        for (i, pass) in passes.iter().enumerate() {
+            let params: Vec<u8> = [
+                width.to_le_bytes(),
+                height.to_le_bytes(),
+                pass.radius.to_le_bytes(),
+                pass.amount.to_le_bytes(),
+            ]
+            .concat();
+            // Bindings can't be empty, so passes without a table get a dummy one
+            let table: Vec<u8> = if pass.table.is_empty() {
+                vec![0; 4]
+            } else {
+                pass.table.iter().flat_map(|v| v.to_le_bytes()).collect()
+            };
+            let params = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("filter params"),
+                contents: &params,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+            let table = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("filter table"),
+                contents: &table,
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("filters"),
+                layout: &self.layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: params.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: pixels[i % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: pixels[(i + 1) % 2].as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: table.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let mut compute = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("filters"),
+            });
+            compute.set_pipeline(pass.pipeline);
+            compute.set_bind_group(0, &bind_group, &[]);
+            compute.dispatch(
+                (width + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                (height + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE,
+                1,
+            );
+        }
+
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("filter readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        encoder.copy_buffer_to_buffer(&pixels[passes.len() % 2], 0, &readback, 0, size);
+        queue.submit(Some(encoder.finish()));
+
+        let validation = pollster::block_on(device.pop_error_scope());
+        let memory = pollster::block_on(device.pop_error_scope());
+        if let Some(e) = validation.or(memory) {
+            log::warn!("GPU filter failed, using the CPU: {}", e);
+            return None;
+        }
+
+        let slice = readback.slice(..);
+        let mapped = slice.map_async(wgpu::MapMode::Read);
+        device.poll(wgpu::Maintain::Wait);
+        if let Err(e) = pollster::block_on(mapped) {
+            log::warn!("GPU filter failed, using the CPU: {}", e);
+            return None;
+        }
+        let out = slice.get_mapped_range().to_vec();
+        readback.unmap();
+        RgbaImage::from_raw(width, height, out)
+    }
+}
+
+/// Normalized weights of a Gaussian with standard deviation `blur.radius`, cut off at twice that
+/// like the image crate's CPU blur.
+fn gaussian_weights(blur: &blur::GaussianBlur) -> Vec<f32> {
+    let sigma = if blur.radius <= 0.0 { 1.0 } else { blur.radius };
+    let radius = (2.0 * sigma).ceil() as i32;
+    let weights: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = weights.iter().sum();
+    weights.into_iter().map(|w| w / total).collect()
+}
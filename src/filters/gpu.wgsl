@@ -0,0 +1,110 @@
+// Filters run by `GpuFilters`. Pixels are RGBA8 packed into one u32 each, red in the low byte,
+// which is how the image crate lays them out in memory.
+
+[[block]]
+struct Params {
+    width: u32;
+    height: u32;
+    // Pixels either side of the center for the blurs.
+    radius: i32;
+    // Saturation factor, or the threshold level from 0 to 1.
+    amount: f32;
+};
+
+[[block]]
+struct Pixels {
+    data: array<u32>;
+};
+
+[[block]]
+struct Table {
+    data: array<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> params: Params;
+[[group(0), binding(1)]]
+var<storage, read> src: Pixels;
+[[group(0), binding(2)]]
+var<storage, read_write> dst: Pixels;
+// Blur weights, or the red, green and blue lookup tables one after another.
+[[group(0), binding(3)]]
+var<storage, read> table: Table;
+
+fn inside(id: vec3<u32>) -> bool {
+    return id.x < params.width && id.y < params.height;
+}
+
+// Clamps to the edges, like the CPU filters.
+fn load(x: i32, y: i32) -> vec4<f32> {
+    let cx = clamp(x, 0, i32(params.width) - 1);
+    let cy = clamp(y, 0, i32(params.height) - 1);
+    return unpack4x8unorm(src.data[u32(cy) * params.width + u32(cx)]);
+}
+
+fn store(id: vec3<u32>, color: vec4<f32>) {
+    dst.data[id.y * params.width + id.x] = pack4x8unorm(color);
+}
+
+fn convolve(id: vec3<u32>, step: vec2<i32>) {
+    if (!inside(id)) {
+        return;
+    }
+    let center = vec2<i32>(i32(id.x), i32(id.y));
+    var sum = vec4<f32>(0.0, 0.0, 0.0, 0.0);
+    for (var i: i32 = -params.radius; i <= params.radius; i = i + 1) {
+        let at = center + step * i;
+        sum = sum + load(at.x, at.y) * table.data[i + params.radius];
+    }
+    store(id, sum);
+}
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn blur_horizontal([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    convolve(id, vec2<i32>(1, 0));
+}
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn blur_vertical([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    convolve(id, vec2<i32>(0, 1));
+}
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn lookup([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (!inside(id)) {
+        return;
+    }
+    let color = load(i32(id.x), i32(id.y));
+    let index = vec3<u32>(floor(color.rgb * 255.0 + vec3<f32>(0.5, 0.5, 0.5)));
+    store(id, vec4<f32>(
+        table.data[index.x],
+        table.data[256u + index.y],
+        table.data[512u + index.z],
+        color.a
+    ));
+}
+
+fn luminance(color: vec4<f32>) -> f32 {
+    return dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+}
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn saturation([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (!inside(id)) {
+        return;
+    }
+    let color = load(i32(id.x), i32(id.y));
+    let gray = luminance(color);
+    let rgb = vec3<f32>(gray, gray, gray) + (color.rgb - vec3<f32>(gray, gray, gray)) * params.amount;
+    store(id, vec4<f32>(rgb, color.a));
+}
+
+[[stage(compute), workgroup_size(16, 16)]]
+fn threshold([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (!inside(id)) {
+        return;
+    }
+    let color = load(i32(id.x), i32(id.y));
+    let v = select(0.0, 1.0, luminance(color) >= params.amount);
+    store(id, vec4<f32>(v, v, v, color.a));
+}
@@ -0,0 +1,168 @@
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use nannou::image::DynamicImage;
+
+use super::{par_map, ImageFilter};
+
+/// A 3D color lookup table as read from an Adobe/Resolve `.cube` file.
+pub struct Lut3d {
+    pub title: Option<String>,
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Output colors with red changing fastest, then green, then blue.
+    table: Vec<[f32; 3]>,
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl Lut3d {
+    pub fn load(path: &Path) -> io::Result<Lut3d> {
+        Lut3d::parse(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn parse(source: &str) -> io::Result<Lut3d> {
+        let mut lut = Lut3d {
+            title: None,
+            size: 0,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            table: Vec::new(),
+        };
+
+        let triple = |parts: &[&str], line: usize| -> io::Result<[f32; 3]> {
+            let mut out = [0.0; 3];
+            if parts.len() != 3 {
+                return Err(invalid(format!("line {}: expected 3 values", line)));
+            }
+            for (o, p) in out.iter_mut().zip(parts) {
+                *o = p
+                    .parse()
+                    .map_err(|_| invalid(format!("line {}: invalid number {:?}", line, p)))?;
+            }
+            Ok(out)
+        };
+
+        for (i, line) in source.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<_> = line.split_whitespace().collect();
+            match parts[0] {
+                "TITLE" => lut.title = Some(line[5..].trim().trim_matches('"').to_string()),
+                "LUT_3D_SIZE" => {
+                    lut.size = parts
+                        .get(1)
+                        .and_then(|s| s.parse().ok())
+                        .filter(|s| (2..=256).contains(s))
+                        .ok_or_else(|| invalid(format!("line {}: invalid LUT size", i + 1)))?;
+                }
+                "DOMAIN_MIN" => lut.domain_min = triple(&parts[1..], i + 1)?,
+                "DOMAIN_MAX" => lut.domain_max = triple(&parts[1..], i + 1)?,
+                "LUT_1D_SIZE" => return Err(invalid("1D LUTs are not supported".into())),
+                // Other keywords (e.g. LUT_3D_INPUT_RANGE) don't affect the table
+                key if key.chars().next().map_or(false, char::is_alphabetic) => (),
+                _ => lut.table.push(triple(&parts, i + 1)?),
+            }
+        }
+
+        if lut.size == 0 {
+            return Err(invalid("missing LUT_3D_SIZE".into()));
+        }
+        if lut.table.len() != lut.size.pow(3) {
+            return Err(invalid(format!(
+                "expected {} table entries, found {}",
+                lut.size.pow(3),
+                lut.table.len()
+            )));
+        }
+        Ok(lut)
+    }
+
+    fn at(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[r + g * self.size + b * self.size * self.size]
+    }
+
+    /// Looks up a color with components in `0..1` using trilinear interpolation.
+    pub fn sample(&self, color: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+        let mut idx = [0usize; 3];
+        let mut frac = [0.0f32; 3];
+        for c in 0..3 {
+            let range = (self.domain_max[c] - self.domain_min[c]).max(f32::EPSILON);
+            let v = ((color[c] - self.domain_min[c]) / range).clamp(0.0, 1.0) * max;
+            idx[c] = (v.floor() as usize).min(self.size - 2);
+            frac[c] = v - idx[c] as f32;
+        }
+
+        let lerp = |a: [f32; 3], b: [f32; 3], t: f32| {
+            [
+                a[0] + (b[0] - a[0]) * t,
+                a[1] + (b[1] - a[1]) * t,
+                a[2] + (b[2] - a[2]) * t,
+            ]
+        };
+        let [r, g, b] = idx;
+        let c00 = lerp(self.at(r, g, b), self.at(r + 1, g, b), frac[0]);
+        let c10 = lerp(self.at(r, g + 1, b), self.at(r + 1, g + 1, b), frac[0]);
+        let c01 = lerp(self.at(r, g, b + 1), self.at(r + 1, g, b + 1), frac[0]);
+        let c11 = lerp(
+            self.at(r, g + 1, b + 1),
+            self.at(r + 1, g + 1, b + 1),
+            frac[0],
+        );
+        lerp(lerp(c00, c10, frac[1]), lerp(c01, c11, frac[1]), frac[2])
+    }
+}
+
+/// Color grade through a loaded LUT, mixed with the original by `intensity`.
+#[derive(Clone)]
+pub struct LutGrade {
+    pub lut: Arc<Lut3d>,
+    pub intensity: f32,
+}
+
+impl LutGrade {
+    pub fn new(lut: Lut3d) -> LutGrade {
+        LutGrade {
+            lut: Arc::new(lut),
+            intensity: 1.0,
+        }
+    }
+}
+
+impl PartialEq for LutGrade {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.lut, &other.lut) && self.intensity == other.intensity
+    }
+}
+
+impl ImageFilter for LutGrade {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let px = src.get_pixel(x, y).0;
+            let graded = self.lut.sample([
+                px[0] as f32 / 255.0,
+                px[1] as f32 / 255.0,
+                px[2] as f32 / 255.0,
+            ]);
+            let mut out = px;
+            for c in 0..3 {
+                let v = px[c] as f32 / 255.0;
+                out[c] =
+                    ((v + (graded[c] - v) * self.intensity).clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+            out
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Intensity", &mut self.intensity, 0.0, 1.0)]
+    }
+}
@@ -0,0 +1,112 @@
+use nannou::image::{DynamicImage, RgbaImage};
+use rayon::prelude::*;
+
+pub mod adjust;
+pub mod blur;
+pub mod curves;
+pub mod denoise;
+pub mod gpu;
+pub mod lut;
+pub mod normal_map;
+pub mod sharpen;
+pub mod stylize;
+
+/// A whole-image operation with adjustable numeric parameters.
+pub trait ImageFilter {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage;
+
+    /// Adjustable parameters as `(label, value, min, max)`, shown as sliders on the Workbench.
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)>;
+}
+
+/// The filters that can be picked from the Workbench. Compared by value so previews are only
+/// recomputed when a parameter changes.
+#[derive(Clone, PartialEq)]
+pub enum Filter {
+    GaussianBlur(blur::GaussianBlur),
+    BoxBlur(blur::BoxBlur),
+    BrightnessContrast(adjust::BrightnessContrast),
+    Saturation(adjust::Saturation),
+    Levels(adjust::Levels),
+    Threshold(adjust::Threshold),
+    Curves(curves::Curves),
+    UnsharpMask(sharpen::UnsharpMask),
+    HighPass(sharpen::HighPass),
+    Median(denoise::Median),
+    Despeckle(denoise::Despeckle),
+    Bilateral(denoise::Bilateral),
+    ChromaticAberration(stylize::ChromaticAberration),
+    Vignette(stylize::Vignette),
+    Grain(stylize::Grain),
+    Lut(lut::LutGrade),
+    NormalMap(normal_map::NormalMap),
+}
+
+impl Filter {
+    /// Runs the filter on the GPU when `gpu` is given and supports it, or on the CPU otherwise.
+    pub fn apply(&self, image: &DynamicImage, gpu: Option<&gpu::GpuFilters>) -> DynamicImage {
+        gpu.and_then(|gpu| gpu.apply(self, image))
+            .unwrap_or_else(|| self.as_filter().apply(image))
+    }
+
+    pub fn as_filter(&self) -> &dyn ImageFilter {
+        match self {
+            Filter::GaussianBlur(f) => f,
+            Filter::BoxBlur(f) => f,
+            Filter::BrightnessContrast(f) => f,
+            Filter::Saturation(f) => f,
+            Filter::Levels(f) => f,
+            Filter::Threshold(f) => f,
+            Filter::Curves(f) => f,
+            Filter::UnsharpMask(f) => f,
+            Filter::HighPass(f) => f,
+            Filter::Median(f) => f,
+            Filter::Despeckle(f) => f,
+            Filter::Bilateral(f) => f,
+            Filter::ChromaticAberration(f) => f,
+            Filter::Vignette(f) => f,
+            Filter::Grain(f) => f,
+            Filter::Lut(f) => f,
+            Filter::NormalMap(f) => f,
+        }
+    }
+
+    pub fn as_filter_mut(&mut self) -> &mut dyn ImageFilter {
+        match self {
+            Filter::GaussianBlur(f) => f,
+            Filter::BoxBlur(f) => f,
+            Filter::BrightnessContrast(f) => f,
+            Filter::Saturation(f) => f,
+            Filter::Levels(f) => f,
+            Filter::Threshold(f) => f,
+            Filter::Curves(f) => f,
+            Filter::UnsharpMask(f) => f,
+            Filter::HighPass(f) => f,
+            Filter::Median(f) => f,
+            Filter::Despeckle(f) => f,
+            Filter::Bilateral(f) => f,
+            Filter::ChromaticAberration(f) => f,
+            Filter::Vignette(f) => f,
+            Filter::Grain(f) => f,
+            Filter::Lut(f) => f,
+            Filter::NormalMap(f) => f,
+        }
+    }
+}
+
+/// Builds an image the size of `src` by evaluating `f` for every pixel, one row per task.
+pub(crate) fn par_map<F>(src: &RgbaImage, f: F) -> RgbaImage
+where
+    F: Fn(u32, u32) -> [u8; 4] + Sync,
+{
+    let width = src.width();
+    let mut out = RgbaImage::new(width, src.height());
+    out.par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, px) in row.chunks_exact_mut(4).enumerate() {
+                px.copy_from_slice(&f(x as u32, y as u32));
+            }
+        });
+    out
+}
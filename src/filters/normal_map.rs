@@ -0,0 +1,52 @@
+use nannou::image::DynamicImage;
+
+use super::{par_map, ImageFilter};
+
+/// Reads the image as a height map, brighter being higher, and replaces it with the tangent-space
+/// normal map of that surface. Green points up the image (OpenGL convention) unless `invert_y`
+/// flips it for DirectX.
+#[derive(Clone, PartialEq)]
+pub struct NormalMap {
+    pub strength: f32,
+    pub invert_y: bool,
+}
+
+impl Default for NormalMap {
+    fn default() -> Self {
+        Self {
+            strength: 2.0,
+            invert_y: false,
+        }
+    }
+}
+
+impl ImageFilter for NormalMap {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let height = image.to_luma8();
+        let (w, h) = height.dimensions();
+        let sample = |x: i64, y: i64| {
+            let x = x.clamp(0, w as i64 - 1) as u32;
+            let y = y.clamp(0, h as i64 - 1) as u32;
+            height.get_pixel(x, y)[0] as f32 / 255.0
+        };
+
+        DynamicImage::ImageRgba8(par_map(&image.to_rgba8(), |x, y| {
+            let (x, y) = (x as i64, y as i64);
+            // Sobel gradients, with y growing down the image
+            let dx = (sample(x + 1, y - 1) + 2.0 * sample(x + 1, y) + sample(x + 1, y + 1))
+                - (sample(x - 1, y - 1) + 2.0 * sample(x - 1, y) + sample(x - 1, y + 1));
+            let dy = (sample(x - 1, y + 1) + 2.0 * sample(x, y + 1) + sample(x + 1, y + 1))
+                - (sample(x - 1, y - 1) + 2.0 * sample(x, y - 1) + sample(x + 1, y - 1));
+
+            let ny = if self.invert_y { -dy } else { dy };
+            let (nx, ny, nz) = (-dx * self.strength, ny * self.strength, 1.0);
+            let len = (nx * nx + ny * ny + nz * nz).sqrt();
+            let encode = |n: f32| ((n / len * 0.5 + 0.5) * 255.0).round() as u8;
+            [encode(nx), encode(ny), encode(nz), 255]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Strength", &mut self.strength, 0.1, 10.0)]
+    }
+}
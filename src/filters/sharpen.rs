@@ -0,0 +1,96 @@
+use nannou::image::{imageops, DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use super::ImageFilter;
+
+/// Classic unsharp mask: adds back the difference between the image and a blurred copy wherever
+/// that difference exceeds `threshold`.
+#[derive(Clone, PartialEq)]
+pub struct UnsharpMask {
+    pub amount: f32,
+    pub radius: f32,
+    pub threshold: f32,
+}
+
+impl Default for UnsharpMask {
+    fn default() -> Self {
+        Self {
+            amount: 1.0,
+            radius: 1.5,
+            threshold: 4.0,
+        }
+    }
+}
+
+impl ImageFilter for UnsharpMask {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let blurred = imageops::blur(image, self.radius);
+        let out = RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+            let orig = image.get_pixel(x, y).0;
+            let blur = blurred.get_pixel(x, y).0;
+            let mut px = orig;
+            for c in 0..3 {
+                let diff = orig[c] as f32 - blur[c] as f32;
+                if diff.abs() >= self.threshold {
+                    px[c] = (orig[c] as f32 + diff * self.amount).clamp(0.0, 255.0) as u8;
+                }
+            }
+            Rgba(px)
+        });
+        DynamicImage::ImageRgba8(out)
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Amount", &mut self.amount, 0.0, 5.0),
+            ("Radius", &mut self.radius, 0.1, 20.0),
+            ("Threshold", &mut self.threshold, 0.0, 255.0),
+        ]
+    }
+}
+
+/// High-pass sharpening: extracts detail above the blur `radius` and overlays it onto the image.
+#[derive(Clone, PartialEq)]
+pub struct HighPass {
+    pub radius: f32,
+    pub strength: f32,
+}
+
+impl Default for HighPass {
+    fn default() -> Self {
+        Self {
+            radius: 3.0,
+            strength: 1.0,
+        }
+    }
+}
+
+impl ImageFilter for HighPass {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let blurred = imageops::blur(image, self.radius);
+        let out = RgbaImage::from_fn(image.width(), image.height(), |x, y| {
+            let orig = image.get_pixel(x, y).0;
+            let blur = blurred.get_pixel(x, y).0;
+            let mut px = orig;
+            for c in 0..3 {
+                let base = orig[c] as f32 / 255.0;
+                let detail = ((orig[c] as f32 - blur[c] as f32) * self.strength / 255.0 + 0.5)
+                    .clamp(0.0, 1.0);
+                let overlay = if base < 0.5 {
+                    2.0 * base * detail
+                } else {
+                    1.0 - 2.0 * (1.0 - base) * (1.0 - detail)
+                };
+                px[c] = (overlay * 255.0).round() as u8;
+            }
+            Rgba(px)
+        });
+        DynamicImage::ImageRgba8(out)
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Radius", &mut self.radius, 0.1, 30.0),
+            ("Strength", &mut self.strength, 0.0, 5.0),
+        ]
+    }
+}
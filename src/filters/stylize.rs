@@ -0,0 +1,135 @@
+use nannou::image::DynamicImage;
+use rand::{Rng, SeedableRng};
+
+use super::{par_map, ImageFilter};
+
+/// Shifts the red and blue channels in opposite directions away from the image center, like the
+/// color fringing of a cheap lens.
+#[derive(Clone, PartialEq)]
+pub struct ChromaticAberration {
+    pub offset: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self { offset: 2.0 }
+    }
+}
+
+impl ImageFilter for ChromaticAberration {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let (w, h) = src.dimensions();
+        let (cx, cy) = (w as f32 / 2.0, h as f32 / 2.0);
+        let max = (cx * cx + cy * cy).sqrt();
+
+        let sample = |x: f32, y: f32| {
+            let sx = (x.round() as i32).clamp(0, w as i32 - 1) as u32;
+            let sy = (y.round() as i32).clamp(0, h as i32 - 1) as u32;
+            src.get_pixel(sx, sy).0
+        };
+
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            // Fringing grows towards the corners
+            let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+            let shift = self.offset / max;
+            let (ox, oy) = (dx * shift, dy * shift);
+            let px = src.get_pixel(x, y).0;
+            [
+                sample(x as f32 + ox, y as f32 + oy)[0],
+                px[1],
+                sample(x as f32 - ox, y as f32 - oy)[2],
+                px[3],
+            ]
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![("Offset", &mut self.offset, 0.0, 20.0)]
+    }
+}
+
+/// Darkens the image towards its edges. `roundness` 1 gives a circle, 0 follows the image aspect.
+#[derive(Clone, PartialEq)]
+pub struct Vignette {
+    pub strength: f32,
+    pub size: f32,
+    pub roundness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            strength: 0.5,
+            size: 0.7,
+            roundness: 1.0,
+        }
+    }
+}
+
+impl ImageFilter for Vignette {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let src = image.to_rgba8();
+        let (w, h) = (src.width() as f32, src.height() as f32);
+        let aspect = 1.0 + (w / h - 1.0) * self.roundness;
+
+        DynamicImage::ImageRgba8(par_map(&src, |x, y| {
+            let nx = (x as f32 + 0.5) / w * 2.0 - 1.0;
+            let ny = ((y as f32 + 0.5) / h * 2.0 - 1.0) / aspect;
+            let dist = (nx * nx + ny * ny).sqrt() / std::f32::consts::SQRT_2;
+            let t = ((dist - self.size) / (1.0 - self.size).max(0.01)).clamp(0.0, 1.0);
+            let factor = 1.0 - self.strength * t * t * (3.0 - 2.0 * t);
+
+            let mut px = src.get_pixel(x, y).0;
+            for c in &mut px[..3] {
+                *c = (*c as f32 * factor).round() as u8;
+            }
+            px
+        }))
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Strength", &mut self.strength, 0.0, 1.0),
+            ("Size", &mut self.size, 0.0, 1.0),
+            ("Roundness", &mut self.roundness, 0.0, 1.0),
+        ]
+    }
+}
+
+/// Monochrome film grain. The noise is seeded so the preview matches what gets applied.
+#[derive(Clone, PartialEq)]
+pub struct Grain {
+    pub amount: f32,
+    pub seed: f32,
+}
+
+impl Default for Grain {
+    fn default() -> Self {
+        Self {
+            amount: 20.0,
+            seed: 0.0,
+        }
+    }
+}
+
+impl ImageFilter for Grain {
+    fn apply(&self, image: &DynamicImage) -> DynamicImage {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(self.seed as u64);
+        let mut out = image.to_rgba8();
+        for px in out.pixels_mut() {
+            let noise = rng.gen_range(-1.0..1.0) * self.amount;
+            for c in &mut px.0[..3] {
+                *c = (*c as f32 + noise).clamp(0.0, 255.0) as u8;
+            }
+        }
+        DynamicImage::ImageRgba8(out)
+    }
+
+    fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        vec![
+            ("Amount", &mut self.amount, 0.0, 100.0),
+            ("Seed", &mut self.seed, 0.0, 100.0),
+        ]
+    }
+}
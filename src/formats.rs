@@ -0,0 +1,191 @@
+//! Reading and writing formats the image crate doesn't handle. Each codec wraps a system library,
+//! so it is behind a cargo feature: `avif` for AVIF, `jxl` for JPEG XL and `webp` for writing
+//! WebP and reading the lossless and transparent WebP files the image crate can't. Without the
+//! feature, those files fail with an unsupported-format error.
+
+use std::path::Path;
+
+use nannou::image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use nannou::image::{DynamicImage, ImageError, ImageResult};
+
+/// Formats handled here rather than by the image crate.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Avif,
+    JpegXl,
+    #[cfg(feature = "webp")]
+    WebP,
+}
+
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension()?.to_str()?.to_lowercase().as_str() {
+            "avif" => Some(Format::Avif),
+            "jxl" => Some(Format::JpegXl),
+            #[cfg(feature = "webp")]
+            "webp" => Some(Format::WebP),
+            _ => None,
+        }
+    }
+
+    fn unsupported(&self) -> ImageError {
+        match self {
+            Format::Avif => unsupported("AVIF", "avif"),
+            Format::JpegXl => unsupported("JPEG XL", "jxl"),
+            #[cfg(feature = "webp")]
+            Format::WebP => unsupported("WebP", "webp"),
+        }
+    }
+}
+
+fn unsupported(name: &str, feature: &str) -> ImageError {
+    let hint = ImageFormatHint::Name(name.to_string());
+    log::warn!(
+        "{} support requires building with the `{}` feature",
+        name,
+        feature
+    );
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        hint.clone(),
+        UnsupportedErrorKind::Format(hint),
+    ))
+}
+
+pub fn open(path: &Path) -> ImageResult<DynamicImage> {
+    match Format::from_path(path) {
+        #[cfg(feature = "avif")]
+        Some(Format::Avif) => avif::read(&std::fs::read(path)?),
+        #[cfg(feature = "jxl")]
+        Some(Format::JpegXl) => jxl::read(&std::fs::read(path)?),
+        #[cfg(feature = "webp")]
+        Some(Format::WebP) => webp::read(&std::fs::read(path)?),
+        #[allow(unreachable_patterns)]
+        Some(format) => Err(format.unsupported()),
+        None => nannou::image::open(path),
+    }
+}
+
+/// Writes `pixels` to `path` if it is one of the formats handled here. Returns `None` for
+/// anything else, which the image crate saves.
+#[cfg_attr(
+    not(any(feature = "avif", feature = "jxl", feature = "webp")),
+    allow(unused_variables)
+)]
+pub fn save(pixels: &DynamicImage, path: &Path) -> Option<ImageResult<()>> {
+    let format = Format::from_path(path)?;
+    let data = match format {
+        #[cfg(feature = "avif")]
+        Format::Avif => avif::write(pixels),
+        #[cfg(feature = "jxl")]
+        Format::JpegXl => jxl::write(pixels),
+        #[cfg(feature = "webp")]
+        Format::WebP => webp::write(pixels, None),
+        #[allow(unreachable_patterns)]
+        _ => Err(format.unsupported()),
+    };
+    Some(data.and_then(|data| std::fs::write(path, data).map_err(ImageError::from)))
+}
+
+/// WebP bytes of `pixels`, lossy at `quality` (0-100) or lossless without one.
+#[cfg_attr(not(feature = "webp"), allow(unused_variables))]
+pub fn encode_webp(pixels: &DynamicImage, quality: Option<f32>) -> ImageResult<Vec<u8>> {
+    #[cfg(feature = "webp")]
+    return webp::write(pixels, quality);
+    #[cfg(not(feature = "webp"))]
+    Err(unsupported("WebP", "webp"))
+}
+
+/// Decodes WebP bytes, including the lossless and transparent files the image crate can't read.
+pub fn decode_webp(data: &[u8]) -> ImageResult<DynamicImage> {
+    #[cfg(feature = "webp")]
+    return webp::read(data);
+    #[cfg(not(feature = "webp"))]
+    nannou::image::load_from_memory(data)
+}
+
+#[cfg(feature = "avif")]
+mod avif {
+    use nannou::image::{DynamicImage, ImageResult};
+
+    pub fn read(data: &[u8]) -> ImageResult<DynamicImage> {
+        libavif_image::read(data)
+    }
+
+    pub fn write(pixels: &DynamicImage) -> ImageResult<Vec<u8>> {
+        libavif_image::save(pixels).map(|data| data.to_vec())
+    }
+}
+
+#[cfg(feature = "jxl")]
+mod jxl {
+    use nannou::image::error::{
+        DecodingError, EncodingError, ImageError, ImageFormatHint, ImageResult,
+    };
+    use nannou::image::{DynamicImage, GenericImageView, RgbaImage};
+
+    fn hint() -> ImageFormatHint {
+        ImageFormatHint::Name("JPEG XL".to_string())
+    }
+
+    pub fn read(data: &[u8]) -> ImageResult<DynamicImage> {
+        let decode = || -> Result<DynamicImage, Box<dyn std::error::Error + Send + Sync>> {
+            let mut decoder = jpegxl_rs::decoder_builder()
+                .pixel_format(jpegxl_rs::decode::PixelFormat {
+                    num_channels: 4,
+                    ..Default::default()
+                })
+                .build()?;
+            let (metadata, pixels) = decoder.decode_with::<u8>(data)?;
+            RgbaImage::from_raw(metadata.width, metadata.height, pixels)
+                .map(DynamicImage::ImageRgba8)
+                .ok_or_else(|| "decoded buffer has the wrong size".into())
+        };
+        decode().map_err(|e| ImageError::Decoding(DecodingError::new(hint(), e)))
+    }
+
+    pub fn write(pixels: &DynamicImage) -> ImageResult<Vec<u8>> {
+        let rgba = pixels.to_rgba8();
+        let encode = || -> Result<Vec<u8>, Box<dyn std::error::Error + Send + Sync>> {
+            let mut encoder = jpegxl_rs::encoder_builder().has_alpha(true).build()?;
+            let result: jpegxl_rs::encode::EncoderResult<u8> =
+                encoder.encode(&rgba, pixels.width(), pixels.height())?;
+            Ok(result.data)
+        };
+        encode().map_err(|e| ImageError::Encoding(EncodingError::new(hint(), e)))
+    }
+}
+
+#[cfg(feature = "webp")]
+mod webp {
+    use nannou::image::error::{DecodingError, ImageError, ImageFormatHint, ImageResult};
+    use nannou::image::{DynamicImage, RgbImage, RgbaImage};
+
+    fn hint() -> ImageFormatHint {
+        ImageFormatHint::Name("WebP".to_string())
+    }
+
+    pub fn read(data: &[u8]) -> ImageResult<DynamicImage> {
+        let decoding =
+            |message: &str| ImageError::Decoding(DecodingError::new(hint(), message.to_string()));
+        let image = ::webp::Decoder::new(data)
+            .decode()
+            .ok_or_else(|| decoding("not a WebP image"))?;
+        let (width, height) = (image.width(), image.height());
+        let pixels = if image.is_alpha() {
+            RgbaImage::from_raw(width, height, image.to_vec()).map(DynamicImage::ImageRgba8)
+        } else {
+            RgbImage::from_raw(width, height, image.to_vec()).map(DynamicImage::ImageRgb8)
+        };
+        pixels.ok_or_else(|| decoding("decoded buffer has the wrong size"))
+    }
+
+    pub fn write(pixels: &DynamicImage, quality: Option<f32>) -> ImageResult<Vec<u8>> {
+        let rgba = pixels.to_rgba8();
+        let encoder = ::webp::Encoder::from_rgba(&rgba, rgba.width(), rgba.height());
+        let data = match quality {
+            Some(quality) => encoder.encode(quality.clamp(0.0, 100.0)),
+            None => encoder.encode_lossless(),
+        };
+        Ok(data.to_vec())
+    }
+}
@@ -0,0 +1,233 @@
+//! Free transform: the selection, or the whole layer, lifted off the canvas to be moved, scaled and
+//! rotated with handles. While dragging it is only drawn as an overlay; the pixels are resampled
+//! into the layer once, when the transform is committed.
+
+use nannou::image::{DynamicImage, GenericImageView, Rgba};
+use nannou::prelude::Vec2;
+
+use crate::canvas::Canvas;
+use crate::layers::over;
+use crate::selection::Selection;
+use crate::textures::Uploaded;
+
+/// How far handles can be grabbed from, in screen points.
+pub const HANDLE_RADIUS: f32 = 8.0;
+/// Distance of the rotation handle above the top edge, in screen points.
+pub const ROTATE_HANDLE_OFFSET: f32 = 24.0;
+/// Rotation step while Shift is held, in degrees.
+pub const ROTATE_SNAP: f32 = 15.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Handle {
+    /// Anywhere inside the box.
+    Move,
+    /// A corner, given by the signs of its offset from the center before transforming. Scales
+    /// around the center.
+    Corner(f32, f32),
+    /// The knob above the top edge. Rotates around the center.
+    Rotate,
+}
+
+#[derive(Clone)]
+pub struct FreeTransform {
+    /// The lifted pixels, untransformed.
+    pub pixels: Uploaded,
+    /// Where the center of the pixels is placed, in canvas pixels.
+    pub center: Vec2,
+    /// Scale along the pixels' own axes. Negative values flip.
+    pub scale: Vec2,
+    /// Rotation in radians, clockwise as seen on screen.
+    pub angle: f32,
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+impl FreeTransform {
+    /// Cuts the selected pixels, or the whole layer without a selection, out of `layer`, leaving
+    /// transparency behind.
+    pub fn lift(layer: &mut DynamicImage, selection: Option<&Selection>) -> FreeTransform {
+        let area = selection.copied().unwrap_or(Selection {
+            x: 0,
+            y: 0,
+            width: layer.width(),
+            height: layer.height(),
+        });
+        let pixels = layer.crop_imm(area.x, area.y, area.width, area.height);
+        Canvas::new(layer, Some(&area)).fill(|_, _, _| Some(Rgba([0, 0, 0, 0])));
+
+        FreeTransform {
+            pixels: pixels.into(),
+            center: Vec2::new(
+                area.x as f32 + area.width as f32 / 2.0,
+                area.y as f32 + area.height as f32 / 2.0,
+            ),
+            scale: Vec2::ONE,
+            angle: 0.0,
+        }
+    }
+
+    /// Size of the lifted pixels before scaling.
+    pub fn size(&self) -> Vec2 {
+        Vec2::new(self.pixels.width() as f32, self.pixels.height() as f32)
+    }
+
+    /// Canvas position of a point given relative to the center of the untransformed pixels.
+    pub fn to_canvas(&self, local: Vec2) -> Vec2 {
+        self.center + rotate(local * self.scale, self.angle)
+    }
+
+    /// Inverse of [`FreeTransform::to_canvas`]. Infinite when flattened to no width or height.
+    pub fn to_local(&self, canvas: Vec2) -> Vec2 {
+        rotate(canvas - self.center, -self.angle) / self.scale
+    }
+
+    /// Corners of the transformed box, clockwise from the top left.
+    pub fn corners(&self) -> [Vec2; 4] {
+        let half = self.size() / 2.0;
+        [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+            .map(|(sx, sy)| self.to_canvas(Vec2::new(sx, sy) * half))
+    }
+
+    /// Canvas position of the rotation handle at canvas zoom `zoom`.
+    pub fn rotate_handle(&self, zoom: f32) -> Vec2 {
+        let top = self.to_canvas(Vec2::new(0.0, -self.size().y / 2.0));
+        let up = rotate(Vec2::new(0.0, -self.scale.y.signum()), self.angle);
+        top + up * ROTATE_HANDLE_OFFSET / zoom
+    }
+
+    /// The handle under canvas position `pos` at canvas zoom `zoom`.
+    pub fn handle_at(&self, pos: Vec2, zoom: f32) -> Option<Handle> {
+        let reach = HANDLE_RADIUS / zoom;
+        if pos.distance(self.rotate_handle(zoom)) <= reach {
+            return Some(Handle::Rotate);
+        }
+        let signs = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        for (corner, (sx, sy)) in self.corners().into_iter().zip(signs) {
+            if pos.distance(corner) <= reach {
+                return Some(Handle::Corner(sx, sy));
+            }
+        }
+        let local = self.to_local(pos).abs();
+        let half = self.size() / 2.0;
+        (local.x <= half.x && local.y <= half.y).then(|| Handle::Move)
+    }
+
+    /// Updates the transform for `handle` dragged from canvas position `from` to `to`, starting
+    /// from the transform as it was when the drag began. `constrain` keeps the aspect ratio while
+    /// scaling and snaps the angle while rotating.
+    pub fn drag(
+        &mut self,
+        start: &FreeTransform,
+        handle: Handle,
+        from: Vec2,
+        to: Vec2,
+        constrain: bool,
+    ) {
+        match handle {
+            Handle::Move => self.center = start.center + (to - from),
+            Handle::Corner(sx, sy) => {
+                let half = start.size().max(Vec2::ONE) / 2.0;
+                let local = rotate(to - start.center, -start.angle);
+                let scale = Vec2::new(local.x / (sx * half.x), local.y / (sy * half.y));
+                self.scale = if constrain {
+                    let ratio = scale / start.scale;
+                    let k = if ratio.x.abs() > ratio.y.abs() {
+                        ratio.x
+                    } else {
+                        ratio.y
+                    };
+                    start.scale * k
+                } else {
+                    scale
+                };
+            }
+            Handle::Rotate => {
+                let (a, b) = (from - start.center, to - start.center);
+                let turn = (a.x * b.y - a.y * b.x).atan2(a.dot(b));
+                let mut angle = start.angle + turn;
+                if constrain {
+                    let step = ROTATE_SNAP.to_radians();
+                    angle = (angle / step).round() * step;
+                }
+                self.angle = angle;
+            }
+        }
+    }
+
+    /// Resamples the transformed pixels over `layer`, with bilinear filtering when `smooth` or
+    /// nearest neighbor otherwise. Returns the area covered, to become the selection. The
+    /// selection the pixels were lifted from went with them, so nothing clips where they land.
+    pub fn apply(&self, layer: &mut DynamicImage, smooth: bool) -> Option<Selection> {
+        let corners = self.corners();
+        let min = corners.iter().fold(Vec2::splat(f32::MAX), |m, c| m.min(*c));
+        let max = corners.iter().fold(Vec2::splat(f32::MIN), |m, c| m.max(*c));
+        // Both corner pixels are included, so stop short of the far edge
+        let area = Selection::from_corners(min, max - 0.5, layer.width(), layer.height())?;
+
+        let half = self.size() / 2.0;
+        let (left, top) = (area.x as i32, area.y as i32);
+        Canvas::new(layer, None).stamp(left, top, area.width, area.height, |x, y, dst| {
+            // Sample at the pixel center, in the lifted pixels' own coordinates
+            let source = self.to_local(Vec2::new(x as f32, y as f32) + 0.5) + half;
+            let pixel = if smooth {
+                self.bilinear(source - 0.5)
+            } else {
+                self.nearest(source)
+            };
+            pixel.map(|pixel| over(dst, pixel, 1.0))
+        });
+        Some(area)
+    }
+
+    fn nearest(&self, at: Vec2) -> Option<Rgba<u8>> {
+        let (w, h) = self.pixels.dimensions();
+        if !(at.x >= 0.0 && at.y >= 0.0 && at.x < w as f32 && at.y < h as f32) {
+            return None;
+        }
+        Some(self.pixels.get_pixel(at.x as u32, at.y as u32))
+    }
+
+    /// Bilinear sample with `at` in pixel-center coordinates, fading to transparent past the
+    /// edges. Colors are weighted by alpha so transparent pixels don't darken the edges.
+    fn bilinear(&self, at: Vec2) -> Option<Rgba<u8>> {
+        let (w, h) = (self.pixels.width() as i64, self.pixels.height() as i64);
+        if !at.is_finite() || at.x <= -1.0 || at.y <= -1.0 || at.x >= w as f32 || at.y >= h as f32 {
+            return None;
+        }
+        let (x0, y0) = (at.x.floor(), at.y.floor());
+        let (fx, fy) = (at.x - x0, at.y - y0);
+
+        let mut sum = [0.0f32; 4];
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let (x, y) = (x0 as i64 + dx, y0 as i64 + dy);
+            if x < 0 || y < 0 || x >= w || y >= h {
+                continue;
+            }
+            let pixel = self.pixels.get_pixel(x as u32, y as u32);
+            let alpha = pixel[3] as f32 * weight;
+            for c in 0..3 {
+                sum[c] += pixel[c] as f32 * alpha;
+            }
+            sum[3] += alpha;
+        }
+
+        if sum[3] <= 0.0 {
+            return None;
+        }
+        let color = |c: usize| (sum[c] / sum[3]).round().clamp(0.0, 255.0) as u8;
+        Some(Rgba([
+            color(0),
+            color(1),
+            color(2),
+            sum[3].round().min(255.0) as u8,
+        ]))
+    }
+}
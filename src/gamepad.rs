@@ -0,0 +1,121 @@
+//! Gamepad input for couch painting and accessibility: the left stick moves a virtual cursor, the
+//! right trigger paints with pressure and the face buttons switch tools. Reading controllers needs
+//! the `gamepad` feature; without it the toggle in the Workbench only logs a warning.
+
+use nannou::prelude::Vec2;
+
+#[cfg(feature = "gamepad")]
+use crate::tools::TOOLS;
+use crate::Mode;
+
+/// Cursor speed in screen points per second with the stick fully pushed.
+pub const CURSOR_SPEED: f32 = 600.0;
+/// Stick deflection below this is treated as resting, so worn sticks don't drift the cursor.
+pub const DEAD_ZONE: f32 = 0.15;
+/// Trigger travel needed before the brush touches the canvas.
+pub const TRIGGER_THRESHOLD: f32 = 0.05;
+
+/// What the controllers did since the last poll.
+#[derive(Default, Debug)]
+pub struct GamepadInput {
+    /// How far to move the cursor, in screen points with y pointing up.
+    pub motion: Vec2,
+    /// Trigger travel while painting, 0-1.
+    pub pressure: Option<f32>,
+    /// Tool picked with a face or shoulder button.
+    pub mode: Option<Mode>,
+    /// The brush was put down (`true`) or lifted (`false`).
+    pub button: Option<bool>,
+    pub undo: bool,
+}
+
+pub struct Gamepad {
+    #[cfg(feature = "gamepad")]
+    gilrs: Option<gilrs::Gilrs>,
+    /// Whether the trigger is currently held past the threshold.
+    #[cfg(feature = "gamepad")]
+    painting: bool,
+}
+
+impl Gamepad {
+    pub fn new() -> Gamepad {
+        #[cfg(feature = "gamepad")]
+        let gilrs = match gilrs::Gilrs::new() {
+            Ok(gilrs) => Some(gilrs),
+            Err(e) => {
+                log::warn!("Gamepads unavailable: {}", e);
+                None
+            }
+        };
+        #[cfg(not(feature = "gamepad"))]
+        log::warn!("Gamepad input needs the editor to be built with the `gamepad` feature");
+
+        Gamepad {
+            #[cfg(feature = "gamepad")]
+            gilrs,
+            #[cfg(feature = "gamepad")]
+            painting: false,
+        }
+    }
+
+    /// Drains controller events and reads the sticks, `dt` seconds after the last poll.
+    #[cfg(feature = "gamepad")]
+    pub fn poll(&mut self, current: Mode, dt: f32) -> GamepadInput {
+        use gilrs::{Axis, Button, EventType};
+
+        let mut input = GamepadInput::default();
+        let gilrs = match &mut self.gilrs {
+            Some(gilrs) => gilrs,
+            None => return input,
+        };
+
+        let mut mode = current;
+        while let Some(event) = gilrs.next_event() {
+            if let EventType::ButtonPressed(button, _) = event.event {
+                match button {
+                    Button::South => input.mode = Some(Mode::Paint),
+                    Button::East => input.mode = Some(Mode::Erase),
+                    Button::West => input.mode = Some(Mode::Eyedropper),
+                    Button::North => input.mode = Some(Mode::Fill),
+                    Button::LeftTrigger => input.mode = Some(cycle_tool(mode, -1)),
+                    Button::RightTrigger => input.mode = Some(cycle_tool(mode, 1)),
+                    Button::Select => input.undo = true,
+                    _ => (),
+                }
+                mode = input.mode.unwrap_or(mode);
+            }
+        }
+
+        let mut trigger: f32 = 0.0;
+        for (_, pad) in gilrs.gamepads() {
+            let stick = Vec2::new(pad.value(Axis::LeftStickX), pad.value(Axis::LeftStickY));
+            if stick.length() > DEAD_ZONE {
+                input.motion += stick * CURSOR_SPEED * dt;
+            }
+            if let Some(data) = pad.button_data(Button::RightTrigger2) {
+                trigger = trigger.max(data.value());
+            }
+        }
+
+        let painting = trigger > TRIGGER_THRESHOLD;
+        if painting != self.painting {
+            self.painting = painting;
+            input.button = Some(painting);
+        }
+        input.pressure = painting.then(|| trigger);
+        input
+    }
+
+    #[cfg(not(feature = "gamepad"))]
+    pub fn poll(&mut self, _current: Mode, _dt: f32) -> GamepadInput {
+        GamepadInput::default()
+    }
+}
+
+/// The tool `step` places away from `mode` in the tool registry, wrapping around.
+#[cfg(feature = "gamepad")]
+fn cycle_tool(mode: Mode, step: isize) -> Mode {
+    let index = TOOLS.iter().position(|tool| tool.mode == mode).unwrap_or(0);
+    let next = (index as isize + step).rem_euclid(TOOLS.len() as isize);
+    TOOLS[next as usize].mode
+}
@@ -0,0 +1,106 @@
+//! Linear and radial gradients through a list of color stops, dragged out on the canvas.
+
+use nannou::image::Rgba;
+use nannou::prelude::Vec2;
+
+use crate::canvas::Canvas;
+use crate::layers::over;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GradientKind {
+    /// Bands perpendicular to the drag, from its start to its end.
+    Linear,
+    /// Rings around the drag's start, reaching the last stop at its end.
+    Radial,
+}
+
+impl GradientKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            GradientKind::Linear => "Linear",
+            GradientKind::Radial => "Radial",
+        }
+    }
+
+    pub fn next(&self) -> GradientKind {
+        match self {
+            GradientKind::Linear => GradientKind::Radial,
+            GradientKind::Radial => GradientKind::Linear,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ColorStop {
+    /// Where along the gradient the color is reached, 0-1.
+    pub position: f32,
+    pub color: [u8; 4],
+}
+
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<ColorStop>,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            kind: GradientKind::Linear,
+            stops: vec![
+                ColorStop {
+                    position: 0.0,
+                    color: [0, 0, 0, 255],
+                },
+                ColorStop {
+                    position: 1.0,
+                    color: [255, 255, 255, 255],
+                },
+            ],
+        }
+    }
+}
+
+impl Gradient {
+    /// Color at `t` along the gradient, holding the end colors beyond the first and last stops.
+    /// Stops can be in any order.
+    pub fn color_at(&self, t: f32) -> [u8; 4] {
+        let below = self
+            .stops
+            .iter()
+            .filter(|s| s.position <= t)
+            .max_by(|a, b| a.position.total_cmp(&b.position));
+        let above = self
+            .stops
+            .iter()
+            .filter(|s| s.position > t)
+            .min_by(|a, b| a.position.total_cmp(&b.position));
+
+        match (below, above) {
+            (Some(a), Some(b)) => {
+                let f = (t - a.position) / (b.position - a.position);
+                let mut color = [0u8; 4];
+                for (c, channel) in color.iter_mut().enumerate() {
+                    let (from, to) = (a.color[c] as f32, b.color[c] as f32);
+                    *channel = (from + (to - from) * f).round() as u8;
+                }
+                color
+            }
+            (Some(stop), None) | (None, Some(stop)) => stop.color,
+            (None, None) => [0, 0, 0, 0],
+        }
+    }
+
+    /// Lays the gradient dragged from `start` to `end` over `canvas` with `opacity`.
+    pub fn fill(&self, canvas: &mut Canvas, start: Vec2, end: Vec2, opacity: f32) {
+        let axis = end - start;
+        let length = axis.length().max(f32::EPSILON);
+        canvas.fill(|x, y, pixel| {
+            let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - start;
+            let t = match self.kind {
+                GradientKind::Linear => offset.dot(axis) / (length * length),
+                GradientKind::Radial => offset.length() / length,
+            };
+            Some(over(pixel, Rgba(self.color_at(t)), opacity))
+        });
+    }
+}
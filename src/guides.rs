@@ -0,0 +1,157 @@
+//! Guides: vertical and horizontal lines across the document, placed at given pixel positions or
+//! dividing it into equal rows and columns. They also cut the document into cells for slice
+//! export.
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use nannou::image::{DynamicImage, GenericImageView};
+use nannou::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Orientation {
+    /// A line at an x position, running the height of the canvas.
+    Vertical,
+    /// A line at a y position, running the width of the canvas.
+    Horizontal,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Guide {
+    pub orientation: Orientation,
+    /// Pixel offset from the left or top edge.
+    pub position: u32,
+}
+
+/// Settings of the guides panel.
+pub struct GuideOptions {
+    pub columns: f32,
+    pub rows: f32,
+    /// Comma-separated x positions to add vertical guides at.
+    pub vertical: String,
+    /// Comma-separated y positions to add horizontal guides at.
+    pub horizontal: String,
+    /// Show rulers along the editor's edges, which guides can be dragged out of.
+    pub rulers: bool,
+    /// Pull selections and shapes onto guides near the cursor.
+    pub snap: bool,
+}
+
+impl Default for GuideOptions {
+    fn default() -> Self {
+        Self {
+            columns: 2.0,
+            rows: 2.0,
+            vertical: String::new(),
+            horizontal: String::new(),
+            rulers: true,
+            snap: true,
+        }
+    }
+}
+
+/// Guides splitting a `width` x `height` canvas into `columns` by `rows` equal cells. Sizes that
+/// don't divide evenly leave the remainder spread over the cells.
+pub fn divide(width: u32, height: u32, columns: u32, rows: u32) -> Vec<Guide> {
+    let split = |size: u32, count: u32, orientation| {
+        let count = count.max(1);
+        (1..count).map(move |i| Guide {
+            orientation,
+            position: (size as u64 * i as u64 / count as u64) as u32,
+        })
+    };
+    split(width, columns, Orientation::Vertical)
+        .chain(split(height, rows, Orientation::Horizontal))
+        .collect()
+}
+
+impl Guide {
+    /// Distance from `pos` to the guide, across it.
+    fn distance(&self, pos: Vec2) -> f32 {
+        match self.orientation {
+            Orientation::Vertical => (pos.x - self.position as f32).abs(),
+            Orientation::Horizontal => (pos.y - self.position as f32).abs(),
+        }
+    }
+}
+
+/// `pos` moved onto the nearest vertical and nearest horizontal guide within `distance`, each
+/// axis on its own.
+pub fn snap(guides: &[Guide], pos: Vec2, distance: f32) -> Vec2 {
+    let nearest = |orientation| {
+        guides
+            .iter()
+            .filter(|g| g.orientation == orientation && g.distance(pos) <= distance)
+            .min_by(|a, b| a.distance(pos).partial_cmp(&b.distance(pos)).unwrap())
+            .map(|g| g.position as f32)
+    };
+    Vec2::new(
+        nearest(Orientation::Vertical).unwrap_or(pos.x),
+        nearest(Orientation::Horizontal).unwrap_or(pos.y),
+    )
+}
+
+/// Index of the guide nearest `pos`, if any is within `distance`.
+pub fn find(guides: &[Guide], pos: Vec2, distance: f32) -> Option<usize> {
+    guides
+        .iter()
+        .enumerate()
+        .filter(|(_, g)| g.distance(pos) <= distance)
+        .min_by(|(_, a), (_, b)| a.distance(pos).partial_cmp(&b.distance(pos)).unwrap())
+        .map(|(i, _)| i)
+}
+
+/// Reads a list of pixel positions like `32, 64, 96`.
+pub fn parse_positions(text: &str) -> Result<Vec<u32>, String> {
+    text.split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            part.parse::<u32>()
+                .map_err(|_| format!("{:?} is not a pixel position", part))
+        })
+        .collect()
+}
+
+/// Cell boundaries along one axis: the canvas edges plus every guide strictly inside them.
+fn cuts(guides: &[Guide], orientation: Orientation, size: u32) -> Vec<u32> {
+    let mut cuts: Vec<u32> = guides
+        .iter()
+        .filter(|g| g.orientation == orientation && g.position > 0 && g.position < size)
+        .map(|g| g.position)
+        .collect();
+    cuts.push(0);
+    cuts.push(size);
+    cuts.sort_unstable();
+    cuts.dedup();
+    cuts
+}
+
+/// Writes every cell between the guides as its own PNG, named after `path` with the cell's row
+/// and column appended, e.g. `sheet_r0_c1.png`. Returns the files written.
+pub fn export_slices(
+    image: &DynamicImage,
+    guides: &[Guide],
+    path: &Path,
+) -> io::Result<Vec<PathBuf>> {
+    let (width, height) = image.dimensions();
+    let xs = cuts(guides, Orientation::Vertical, width);
+    let ys = cuts(guides, Orientation::Horizontal, height);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "slice".to_string());
+
+    let mut files = Vec::new();
+    for (row, y) in ys.windows(2).enumerate() {
+        for (col, x) in xs.windows(2).enumerate() {
+            let cell = image.crop_imm(x[0], y[0], x[1] - x[0], y[1] - y[0]);
+            let file = path.with_file_name(format!("{}_r{}_c{}.png", stem, row, col));
+            cell.save(&file)
+                .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+            files.push(file);
+        }
+    }
+    Ok(files)
+}
@@ -0,0 +1,46 @@
+//! Per-channel histograms of the canvas, shown on the Workbench while adjusting levels.
+
+use nannou::image::DynamicImage;
+
+/// Counts of pixels at each value, 0-255, of the red, green and blue channels and of the
+/// luminance. Fully transparent pixels are left out, as their color isn't seen.
+#[derive(Clone)]
+pub struct Histogram {
+    pub red: [u32; 256],
+    pub green: [u32; 256],
+    pub blue: [u32; 256],
+    pub luminance: [u32; 256],
+}
+
+impl Histogram {
+    pub fn of(image: &DynamicImage) -> Histogram {
+        let mut histogram = Histogram {
+            red: [0; 256],
+            green: [0; 256],
+            blue: [0; 256],
+            luminance: [0; 256],
+        };
+        for pixel in image.to_rgba8().pixels() {
+            let [r, g, b, a] = pixel.0;
+            if a == 0 {
+                continue;
+            }
+            histogram.red[r as usize] += 1;
+            histogram.green[g as usize] += 1;
+            histogram.blue[b as usize] += 1;
+            let luma = 0.2126 * r as f32 + 0.7152 * g as f32 + 0.0722 * b as f32;
+            histogram.luminance[luma.round() as usize] += 1;
+        }
+        histogram
+    }
+
+    /// The largest count in any channel, which the plots are scaled to.
+    pub fn peak(&self) -> u32 {
+        [&self.red, &self.green, &self.blue, &self.luminance]
+            .iter()
+            .flat_map(|channel| channel.iter())
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+}
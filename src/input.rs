@@ -0,0 +1,126 @@
+//! Mapping of canvas input to editor actions, configurable in the user config.
+
+use nannou::event::MouseButton;
+use nannou::prelude::Key;
+use nannou::winit::event::ModifiersState;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScrollAction {
+    None,
+    PanVertical,
+    PanHorizontal,
+    /// Zoom in or out keeping the canvas point under the cursor in place.
+    Zoom,
+    BrushSize,
+}
+
+/// What the scroll wheel does over a canvas, for each modifier.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrollBindings {
+    pub plain: ScrollAction,
+    pub ctrl: ScrollAction,
+    pub shift: ScrollAction,
+    pub alt: ScrollAction,
+}
+
+impl Default for ScrollBindings {
+    fn default() -> Self {
+        Self {
+            plain: ScrollAction::PanVertical,
+            ctrl: ScrollAction::Zoom,
+            shift: ScrollAction::PanHorizontal,
+            alt: ScrollAction::BrushSize,
+        }
+    }
+}
+
+impl ScrollBindings {
+    /// The action for the held modifiers. With several held, Ctrl wins over Shift over Alt.
+    pub fn action(&self, mods: ModifiersState) -> ScrollAction {
+        if mods.ctrl() {
+            self.ctrl
+        } else if mods.shift() {
+            self.shift
+        } else if mods.alt() {
+            self.alt
+        } else {
+            self.plain
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MouseAction {
+    None,
+    /// Pan the canvas by dragging while the button is held.
+    Pan,
+    Undo,
+    /// Pick the color under the cursor, and keep picking while the button is held.
+    Eyedropper,
+}
+
+/// What the mouse buttons other than the left one do over a canvas. The left button always uses
+/// the active tool.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MouseBindings {
+    pub middle: MouseAction,
+    pub right: MouseAction,
+    /// The thumb button browsers use for "back".
+    pub back: MouseAction,
+    pub forward: MouseAction,
+}
+
+impl Default for MouseBindings {
+    fn default() -> Self {
+        Self {
+            middle: MouseAction::Pan,
+            right: MouseAction::None,
+            back: MouseAction::Undo,
+            forward: MouseAction::Eyedropper,
+        }
+    }
+}
+
+/// Numbers the back and forward thumb buttons are reported as, which differ by platform.
+#[cfg(target_os = "windows")]
+const SIDE_BUTTONS: (u16, u16) = (1, 2);
+#[cfg(target_os = "macos")]
+const SIDE_BUTTONS: (u16, u16) = (3, 4);
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const SIDE_BUTTONS: (u16, u16) = (8, 9);
+
+impl MouseBindings {
+    pub fn action(&self, button: MouseButton) -> MouseAction {
+        match button {
+            MouseButton::Left => MouseAction::None,
+            MouseButton::Middle => self.middle,
+            MouseButton::Right => self.right,
+            MouseButton::Other(n) if n == SIDE_BUTTONS.0 => self.back,
+            MouseButton::Other(n) if n == SIDE_BUTTONS.1 => self.forward,
+            MouseButton::Other(_) => MouseAction::None,
+        }
+    }
+}
+
+/// Tool option value for a number key: `1` is 10% through `9` at 90%, and `0` is 100%.
+pub fn digit_percent(key: Key) -> Option<f32> {
+    let digit = match key {
+        Key::Key1 | Key::Numpad1 => 1,
+        Key::Key2 | Key::Numpad2 => 2,
+        Key::Key3 | Key::Numpad3 => 3,
+        Key::Key4 | Key::Numpad4 => 4,
+        Key::Key5 | Key::Numpad5 => 5,
+        Key::Key6 | Key::Numpad6 => 6,
+        Key::Key7 | Key::Numpad7 => 7,
+        Key::Key8 | Key::Numpad8 => 8,
+        Key::Key9 | Key::Numpad9 => 9,
+        Key::Key0 | Key::Numpad0 => 10,
+        _ => return None,
+    };
+    Some(digit as f32 / 10.0)
+}
@@ -0,0 +1,119 @@
+//! Single-instance support: a second launch forwards its file arguments to the running instance
+//! over a localhost socket and exits.
+//!
+//! The running instance listens on a port picked by the system and writes it, along with a random
+//! token, to a file only the user can read. Connections that don't start with the token are
+//! dropped, so other users and programs on the machine can't make the editor open files.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Ipv4Addr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+
+use nannou::app::Proxy;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+/// Most bytes read from one connection, so a stuck or hostile client can't grow a line forever.
+const MESSAGE_LIMIT: u64 = 64 * 1024;
+
+/// Where the running instance leaves its port and token: the user's runtime directory where the
+/// system has one, which is private to the user and cleared on logout.
+fn lock_path() -> Option<PathBuf> {
+    dirs::runtime_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("image_editor").join("instance"))
+}
+
+/// Sends `paths` to an already running instance. Returns `Ok(false)` if none is listening.
+pub fn forward(paths: &[PathBuf]) -> io::Result<bool> {
+    let lock = match lock_path().map(fs::read_to_string) {
+        Some(Ok(lock)) => lock,
+        _ => return Ok(false),
+    };
+    let mut lines = lock.lines();
+    let (port, token) = match (lines.next().map(str::parse::<u16>), lines.next()) {
+        (Some(Ok(port)), Some(token)) => (port, token),
+        _ => return Ok(false),
+    };
+
+    let mut stream = match TcpStream::connect((Ipv4Addr::LOCALHOST, port)) {
+        Ok(stream) => stream,
+        // Left behind by an instance that didn't exit cleanly
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    writeln!(stream, "{}", token)?;
+    for path in paths {
+        // Relative paths mean nothing to the other process's working directory
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.clone());
+        writeln!(stream, "{}", path.display())?;
+    }
+    Ok(true)
+}
+
+/// Starts accepting open requests from later launches. Each received path is sent on the
+/// returned channel and the event loop is woken through `proxy` to pick it up.
+pub fn listen(proxy: Proxy) -> io::Result<Receiver<PathBuf>> {
+    let path = lock_path()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no directory for the lock file"))?;
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect();
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut lock = private_file(&path)?;
+    writeln!(lock, "{}", listener.local_addr()?.port())?;
+    writeln!(lock, "{}", token)?;
+
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut lines = BufReader::new(stream.take(MESSAGE_LIMIT)).lines();
+            match lines.next() {
+                Some(Ok(line)) if line == token => (),
+                _ => {
+                    log::warn!("Ignored an open request without the instance token");
+                    continue;
+                }
+            }
+            for line in lines.flatten() {
+                let path = PathBuf::from(line);
+                if !path.is_absolute() || !path.is_file() {
+                    log::warn!("Ignored open request for {}", path.display());
+                    continue;
+                }
+                if tx.send(path).is_err() {
+                    return;
+                }
+                let _ = proxy.wakeup();
+            }
+        }
+    });
+
+    Ok(rx)
+}
+
+/// Creates or truncates `path` readable and writable by the user only.
+fn private_file(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // `mode` only applies to new files
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
@@ -0,0 +1,90 @@
+//! Filters, resizes and file writes that take long enough to stall the event loop run as jobs on
+//! worker threads. A job reports its progress and its result over a channel; the main thread
+//! polls it every update and applies the result to the document the job was started for.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+
+use nannou::image::DynamicImage;
+use nannou::prelude::WindowId;
+
+/// What a finished job hands back to its document.
+pub enum Output {
+    /// New pixels for the layer at the index.
+    Layer(usize, DynamicImage),
+    /// A layer to add above the active one, with its name.
+    NewLayer(String, DynamicImage),
+    /// New pixels for every layer, in stack order.
+    Layers(Vec<DynamicImage>),
+    /// The document was saved to the path.
+    Saved(PathBuf),
+    /// The canvas was exported to the path.
+    Exported(PathBuf),
+    /// The canvas was sent to the printer.
+    Printed,
+    /// Preview images, for jobs polled by the cache that started them rather than `poll_jobs`.
+    Previews(Vec<DynamicImage>),
+}
+
+enum Message {
+    Progress(f32),
+    Done(Result<Output, String>),
+}
+
+/// Lets the work report how far along it is.
+pub struct Progress(Sender<Message>);
+
+impl Progress {
+    /// Sets the completed fraction, from 0 to 1.
+    pub fn set(&self, fraction: f32) {
+        let _ = self.0.send(Message::Progress(fraction.clamp(0.0, 1.0)));
+    }
+}
+
+pub struct Job {
+    /// Shown on the Workbench progress bar.
+    pub label: String,
+    /// Editor window of the document the result is for.
+    pub target: WindowId,
+    /// The document's edit count when the job started. Pixel results for a document edited since
+    /// are dropped rather than overwriting the newer edits.
+    pub edits: u64,
+    pub progress: f32,
+    messages: Receiver<Message>,
+}
+
+impl Job {
+    pub fn spawn<F>(label: &str, target: WindowId, edits: u64, work: F) -> Job
+    where
+        F: FnOnce(&Progress) -> Result<Output, String> + Send + 'static,
+    {
+        let (sender, messages) = mpsc::channel();
+        std::thread::spawn(move || {
+            let progress = Progress(sender);
+            let result = work(&progress);
+            let _ = progress.0.send(Message::Done(result));
+        });
+        Job {
+            label: label.to_string(),
+            target,
+            edits,
+            progress: 0.0,
+            messages,
+        }
+    }
+
+    /// Takes the progress reported since the last poll, and the result once the work is done.
+    pub fn poll(&mut self) -> Option<Result<Output, String>> {
+        loop {
+            match self.messages.try_recv() {
+                Ok(Message::Progress(progress)) => self.progress = progress,
+                Ok(Message::Done(result)) => return Some(result),
+                Err(TryRecvError::Empty) => return None,
+                // The worker panicked before sending a result
+                Err(TryRecvError::Disconnected) => {
+                    return Some(Err("the worker thread stopped".to_string()))
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use nannou::image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::compositing::{blend, BlendMode};
+use crate::dirty::DirtyRect;
+
+/// Source of layer ids, unique for the run.
+static NEXT_LAYER_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Clone)]
+pub struct Layer {
+    /// Stays with the layer when it moves in the stack, and in the undo history.
+    pub id: u64,
+    pub name: String,
+    /// Always RGBA8 and the same size as every other layer in the stack.
+    pub pixels: DynamicImage,
+    pub opacity: f32,
+    pub visible: bool,
+    pub blend: BlendMode,
+}
+
+impl Layer {
+    pub fn new(name: String, pixels: DynamicImage) -> Layer {
+        Layer {
+            id: NEXT_LAYER_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            pixels: DynamicImage::ImageRgba8(pixels.to_rgba8()),
+            opacity: 1.0,
+            visible: true,
+            blend: BlendMode::Normal,
+        }
+    }
+}
+
+/// What the Workbench layers panel shows about a layer.
+pub struct LayerInfo {
+    pub name: String,
+    pub opacity: f32,
+    pub visible: bool,
+    pub blend: BlendMode,
+}
+
+/// Edits to the layer stack requested from the Workbench.
+#[derive(Debug)]
+pub enum LayerOp {
+    Add,
+    Delete,
+    Select(usize),
+    MoveUp,
+    MoveDown,
+    SetOpacity(f32),
+    SetVisible(bool),
+    SetBlend(BlendMode),
+}
+
+/// The layers of a document, bottom first. Tools paint only on the active layer.
+#[derive(Clone)]
+pub struct LayerStack {
+    pub layers: Vec<Layer>,
+    pub active: usize,
+}
+
+impl LayerStack {
+    pub fn new(background: DynamicImage) -> LayerStack {
+        LayerStack {
+            layers: vec![Layer::new("Background".to_string(), background)],
+            active: 0,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.layers[0].pixels.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.layers[0].pixels.height()
+    }
+
+    /// The layer with id `id`, if it is still in the stack.
+    pub fn by_id_mut(&mut self, id: u64) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.id == id)
+    }
+
+    pub fn active(&self) -> &Layer {
+        &self.layers[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut Layer {
+        &mut self.layers[self.active]
+    }
+
+    pub fn info(&self) -> Vec<LayerInfo> {
+        self.layers
+            .iter()
+            .map(|l| LayerInfo {
+                name: l.name.clone(),
+                opacity: l.opacity,
+                visible: l.visible,
+                blend: l.blend,
+            })
+            .collect()
+    }
+
+    /// Flattens the visible layers into a single image.
+    pub fn composite(&self) -> DynamicImage {
+        self.composite_with(None)
+    }
+
+    /// Flattens the visible layers, drawing `active` in place of the active layer's pixels. Used
+    /// to preview an operation on the active layer without committing it.
+    pub fn composite_with(&self, active: Option<&DynamicImage>) -> DynamicImage {
+        let mut out = RgbaImage::new(self.width(), self.height());
+        for (i, layer) in self.layers.iter().enumerate() {
+            if !layer.visible || layer.opacity <= 0.0 {
+                continue;
+            }
+            let pixels = match active {
+                Some(pixels) if i == self.active => pixels,
+                _ => &layer.pixels,
+            };
+            for (dst, (_, _, src)) in out.pixels_mut().zip(pixels.pixels()) {
+                *dst = blend(*dst, src, layer.opacity, layer.blend);
+            }
+        }
+        DynamicImage::ImageRgba8(out)
+    }
+
+    /// Flattens the visible layers within `rect` only, with `active` standing in for the active
+    /// layer as in [`LayerStack::composite_with`].
+    pub fn composite_region(&self, active: Option<&DynamicImage>, rect: &DirtyRect) -> RgbaImage {
+        RgbaImage::from_fn(rect.width(), rect.height(), |x, y| {
+            let (x, y) = (rect.x0 + x, rect.y0 + y);
+            self.layers
+                .iter()
+                .enumerate()
+                .filter(|(_, l)| l.visible)
+                .fold(Rgba([0, 0, 0, 0]), |dst, (i, l)| {
+                    let src = match active {
+                        Some(pixels) if i == self.active => pixels.get_pixel(x, y),
+                        _ => l.pixels.get_pixel(x, y),
+                    };
+                    blend(dst, src, l.opacity, l.blend)
+                })
+        })
+    }
+
+    /// The flattened color of a single pixel.
+    pub fn composite_pixel(&self, x: u32, y: u32) -> Rgba<u8> {
+        self.layers
+            .iter()
+            .filter(|l| l.visible)
+            .fold(Rgba([0, 0, 0, 0]), |dst, l| {
+                blend(dst, l.pixels.get_pixel(x, y), l.opacity, l.blend)
+            })
+    }
+
+    pub fn apply(&mut self, op: LayerOp) {
+        match op {
+            LayerOp::Add => {
+                let pixels = DynamicImage::ImageRgba8(RgbaImage::new(self.width(), self.height()));
+                let name = format!("Layer {}", self.layers.len());
+                self.active += 1;
+                self.layers.insert(self.active, Layer::new(name, pixels));
+            }
+            LayerOp::Delete => {
+                // A document always keeps at least one layer
+                if self.layers.len() > 1 {
+                    self.layers.remove(self.active);
+                    self.active = self.active.saturating_sub(1);
+                }
+            }
+            LayerOp::Select(i) => self.active = i.min(self.layers.len() - 1),
+            LayerOp::MoveUp => {
+                if self.active + 1 < self.layers.len() {
+                    self.layers.swap(self.active, self.active + 1);
+                    self.active += 1;
+                }
+            }
+            LayerOp::MoveDown => {
+                if self.active > 0 {
+                    self.layers.swap(self.active, self.active - 1);
+                    self.active -= 1;
+                }
+            }
+            LayerOp::SetOpacity(opacity) => self.active_mut().opacity = opacity.clamp(0.0, 1.0),
+            LayerOp::SetVisible(visible) => self.active_mut().visible = visible,
+            LayerOp::SetBlend(mode) => self.active_mut().blend = mode,
+        }
+    }
+}
+
+/// Porter-Duff "over" of `src` with extra `opacity` onto `dst`, in straight alpha. Colors are
+/// weighted by their alpha as they mix, the same as compositing premultiplied, so the hidden color
+/// of transparent pixels never shows.
+pub fn over(dst: Rgba<u8>, src: Rgba<u8>, opacity: f32) -> Rgba<u8> {
+    let sa = src[3] as f32 / 255.0 * opacity;
+    let da = dst[3] as f32 / 255.0;
+    let out_a = sa + da * (1.0 - sa);
+    if out_a <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+
+    let mut out = [0u8; 4];
+    for c in 0..3 {
+        let v = (src[c] as f32 * sa + dst[c] as f32 * da * (1.0 - sa)) / out_a;
+        out[c] = v.round() as u8;
+    }
+    out[3] = (out_a * 255.0).round() as u8;
+    Rgba(out)
+}
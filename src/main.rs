@@ -1,11 +1,77 @@
-use line_drawing::{Bresenham, XiaolinWu};
-use nannou::image::{DynamicImage, GenericImage, GenericImageView, Pixel, RgbaImage};
+use nannou::image::{
+    imageops::FilterType, DynamicImage, GenericImage, GenericImageView, RgbaImage,
+};
 use nannou::prelude::Rect;
 use nannou::prelude::*;
+use nannou::winit::window::CursorIcon;
 use nannou_conrod as ui;
 use nannou_conrod::prelude::*;
 use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
+use std::path::PathBuf;
+
+mod alpha;
+mod backup;
+mod batch;
+mod brush;
+mod canvas;
+mod clipboard;
+mod compare;
+mod compositing;
+mod composition;
+mod config;
+mod console;
+mod crash;
+mod curve_editor;
+mod cutout;
+mod dirty;
+mod export;
+mod fill;
+mod filters;
+mod formats;
+mod free_transform;
+mod gamepad;
+mod gradient;
+mod guides;
+mod histogram;
+mod input;
+mod instance;
+mod jobs;
+mod layers;
+mod measure;
+mod midi;
+mod nine_slice;
+mod nodes;
+mod notes;
+mod ora;
+mod painter;
+mod palette;
+mod perspective;
+mod pixel_grid;
+mod placement;
+mod print;
+mod project;
+mod raw;
+mod resize;
+mod retouch;
+mod scissors;
+mod selection;
+mod session;
+mod shapes;
+mod sprite_sheet;
+mod stamp;
+mod stroke;
+mod symmetry;
+mod templates;
+mod textures;
+mod tiles;
+mod tools;
+mod viewport;
+mod workbench;
+mod workspace;
+mod zoom;
 
 struct Window {
     pub id: WindowId,
@@ -13,19 +79,54 @@ struct Window {
     pub widget_ids: WindowType,
 }
 
+/// Size in points of windows that don't ask for one, the same as nannou's default.
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (1024, 768);
+
+thread_local! {
+    /// Windows closed by `close_window`, hidden and waiting to be reused by `open_window`.
+    static SPARE_WINDOWS: RefCell<Vec<WindowId>> = RefCell::new(Vec::new());
+}
+
+/// Builds a window, reusing one `close_window` hid if there is one.
+fn open_window(app: &App, title: &str, (width, height): (u32, u32), decorations: bool) -> WindowId {
+    let spare = SPARE_WINDOWS.with(|spare| spare.borrow_mut().pop());
+    if let Some(window) = spare.and_then(|id| app.window(id)) {
+        window.set_title(title);
+        window.set_inner_size_points(width as f32, height as f32);
+        window.set_decorations(decorations);
+        window.set_cursor_visible(true);
+        window.set_visible(true);
+        return window.id();
+    }
+    app.new_window()
+        .title(title)
+        .size(width, height)
+        .decorations(decorations)
+        .raw_event(raw_window_event)
+        .view(view)
+        .build()
+        .unwrap()
+}
+
+/// Takes a window out of the model. nannou only destroys windows the user closes from the title
+/// bar, so a window closed from code is hidden and kept for `open_window` to reuse instead of
+/// piling up.
+fn close_window(app: &App, model: &mut Model, id: WindowId) -> Option<Window> {
+    let window = model.windows.remove(&id);
+    if let Some(hidden) = app.window(id) {
+        hidden.set_visible(false);
+        SPARE_WINDOWS.with(|spare| spare.borrow_mut().push(id));
+    }
+    window
+}
+
 trait Init<T> {
     fn new(app: &App, title: &str) -> Self;
 }
 
 impl Init<EditorIds> for Window {
     fn new(app: &App, title: &str) -> Window {
-        let w_id = app
-            .new_window()
-            .title(title)
-            .raw_event(raw_window_event)
-            .view(view)
-            .build()
-            .unwrap();
+        let w_id = open_window(app, title, DEFAULT_WINDOW_SIZE, true);
 
         let mut ui = ui::builder(app).window(w_id).build().unwrap();
         let generator = ui.widget_id_generator();
@@ -38,15 +139,194 @@ impl Init<EditorIds> for Window {
     }
 }
 
+impl Window {
+    /// Key the window's placement is remembered under. All editor windows share one.
+    fn role(&self) -> String {
+        match &self.widget_ids {
+            WindowType::Editor(_, _) => "editor".to_string(),
+            WindowType::Workbench(_, _) => "workbench".to_string(),
+            WindowType::Panel(_, panel) => format!("panel-{}", panel.name()),
+            WindowType::Output(_) => "output".to_string(),
+            WindowType::Proof(_) => "proof".to_string(),
+        }
+    }
+
+    /// Moves the window to where this kind of window was last closed, if known.
+    fn restore_placement(&self, app: &App, config: &config::Config) {
+        if let (Some(placement), Some(window)) =
+            (config.windows.get(&self.role()), app.window(self.id))
+        {
+            placement::restore(&window, placement);
+        }
+    }
+
+    /// Opens an Editor window on an image or project file. Projects also return the tool
+    /// settings they were saved with.
+    fn open_editor(
+        app: &App,
+        path: &std::path::Path,
+        raw_options: &raw::RawOptions,
+        alpha: alpha::AlphaMode,
+    ) -> nannou::image::ImageResult<(Window, Option<project::ToolSettings>)> {
+        let is_ext = |ext: &str| {
+            path.extension()
+                .map_or(false, |e| e.eq_ignore_ascii_case(ext))
+        };
+        let (layers, metadata) = if is_ext("ied") {
+            let (layers, metadata) = project::open(path)?;
+            (Some(layers), Some(metadata))
+        } else if is_ext("ora") {
+            (Some(ora::open(path)?), None)
+        } else {
+            (None, None)
+        };
+        let pixels = match &layers {
+            Some(layers) => layers.layers[0].pixels.clone(),
+            None if raw::is_raw(path) => raw::decode(path, raw_options)?,
+            None => alpha.import(formats::open(path)?),
+        };
+        let name = path
+            .file_stem()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+
+        let mut window = <Window as Init<EditorIds>>::new(app, &name);
+        if let WindowType::Editor(_, state) = &mut window.widget_ids {
+            *state = EditorState::with_image(name, pixels);
+            if let Some(layers) = layers {
+                state.layers = layers;
+            }
+            if let Some(metadata) = &metadata {
+                state.selection = metadata.selection;
+                state.guides = metadata.guides.clone();
+                state.overlays = metadata.overlays;
+                state.notes = metadata.notes.clone();
+                state.dpi = metadata.dpi;
+            }
+            state.alpha_mode = metadata.as_ref().map_or(alpha, |m| m.alpha);
+            state.path = Some(path.to_path_buf());
+        }
+        Ok((window, metadata.map(|m| m.tools)))
+    }
+
+    /// Opens an Editor window on a document left unsaved by the previous session.
+    fn open_session_document(
+        app: &App,
+        document: &session::SessionDocument,
+    ) -> std::io::Result<Window> {
+        let layers = session::restore(document)?;
+        let mut window = <Window as Init<EditorIds>>::new(app, &document.name);
+        if let WindowType::Editor(_, state) = &mut window.widget_ids {
+            *state =
+                EditorState::with_image(document.name.clone(), layers.layers[0].pixels.clone());
+            state.layers = layers;
+            state.path = document.path.clone();
+            // Still unsaved, and kept in the session under its new id
+            state.modified = true;
+            state.autosave_stale.set(true);
+        }
+        Ok(window)
+    }
+
+    /// Opens an Editor window on a blank `width` x `height` canvas at `dpi`, filled with
+    /// `background`.
+    fn new_image(app: &App, width: u32, height: u32, dpi: f32, background: [u8; 4]) -> Window {
+        let pixels = RgbaImage::from_pixel(width, height, nannou::image::Rgba(background));
+        let mut window = <Window as Init<EditorIds>>::new(app, "untitled");
+        if let WindowType::Editor(_, state) = &mut window.widget_ids {
+            *state =
+                EditorState::with_image("untitled".to_string(), DynamicImage::ImageRgba8(pixels));
+            state.dpi = dpi;
+        }
+        window
+    }
+
+    /// Opens an Editor window with a layer for each cell of the sprite sheet at `path`.
+    fn open_sprite_sheet(
+        app: &App,
+        path: &std::path::Path,
+        options: &sprite_sheet::SpriteSheetOptions,
+        alpha: alpha::AlphaMode,
+    ) -> nannou::image::ImageResult<Window> {
+        let sheet = alpha.import(formats::open(path)?);
+        let cells = sprite_sheet::slice(&sheet, options);
+        if cells.is_empty() {
+            return Err(nannou::image::ImageError::IoError(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "the sprite sheet is empty",
+            )));
+        }
+        let name = path
+            .file_stem()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "untitled".to_string());
+
+        let mut window = <Window as Init<EditorIds>>::new(app, &name);
+        if let WindowType::Editor(_, state) = &mut window.widget_ids {
+            *state = EditorState::with_image(name, DynamicImage::ImageRgba8(cells[0].clone()));
+            // Not saved over the sheet, which holds every frame
+            state.layers.layers = cells
+                .into_iter()
+                .enumerate()
+                .map(|(i, cell)| {
+                    layers::Layer::new(format!("Frame {}", i + 1), DynamicImage::ImageRgba8(cell))
+                })
+                .collect();
+            state.modified = true;
+        }
+        Ok(window)
+    }
+}
+
+impl Window {
+    /// Opens a window showing a single Workbench panel.
+    fn new_panel(app: &App, panel: workbench::Panel) -> Window {
+        let w_id = open_window(app, panel.name(), (260, 400), true);
+
+        let mut ui = ui::builder(app).window(w_id).build().unwrap();
+        let generator = ui.widget_id_generator();
+
+        Window {
+            id: w_id,
+            widget_ids: WindowType::Panel(WorkbenchIds::new(generator), panel),
+            ui,
+        }
+    }
+}
+
+impl Window {
+    /// Opens a borderless window mirroring the canvas of the editor `source`, without cursors,
+    /// overlays or widgets, for capturing in streaming software.
+    fn new_output(app: &App, source: WindowId) -> Window {
+        let w_id = open_window(app, "Stream Output", (1280, 720), false);
+        if let Some(window) = app.window(w_id) {
+            window.set_cursor_visible(false);
+        }
+
+        let ui = ui::builder(app).window(w_id).build().unwrap();
+        Window {
+            id: w_id,
+            widget_ids: WindowType::Output(source),
+            ui,
+        }
+    }
+
+    /// A window showing the document of the editor `source` as the export options would write it.
+    fn new_proof(app: &App, source: WindowId) -> Window {
+        let w_id = open_window(app, "Export Preview", (800, 600), true);
+
+        let ui = ui::builder(app).window(w_id).build().unwrap();
+        Window {
+            id: w_id,
+            widget_ids: WindowType::Proof(source),
+            ui,
+        }
+    }
+}
+
 impl Init<WorkbenchIds> for Window {
     fn new(app: &App, title: &str) -> Window {
-        let w_id = app
-            .new_window()
-            .title(title)
-            .raw_event(raw_window_event)
-            .view(view)
-            .build()
-            .unwrap();
+        let w_id = open_window(app, title, DEFAULT_WINDOW_SIZE, true);
 
         let mut ui = ui::builder(app).window(w_id).build().unwrap();
         let generator = ui.widget_id_generator();
@@ -60,26 +340,498 @@ impl Init<WorkbenchIds> for Window {
 }
 
 fn main() {
-    env_logger::init();
+    console::init();
+    crash::install();
+
+    let args = Args::parse();
+    if args.single_instance {
+        match instance::forward(&args.files) {
+            Ok(true) => return,
+            Ok(false) => (),
+            Err(e) => log::warn!("Failed to reach running instance: {}", e),
+        }
+    }
 
-    nannou::app(model).update(update).run();
+    nannou::app(model).update(update).exit(exit).run();
+}
+
+/// Command-line arguments: image files to open, plus `--single-instance` to hand them to an
+/// already running editor instead of starting a new one.
+struct Args {
+    files: Vec<PathBuf>,
+    single_instance: bool,
+}
+
+impl Args {
+    fn parse() -> Args {
+        let mut args = Args {
+            files: Vec::new(),
+            single_instance: false,
+        };
+        for arg in std::env::args_os().skip(1) {
+            match arg.to_str() {
+                Some("--single-instance") => args.single_instance = true,
+                _ => args.files.push(PathBuf::from(arg)),
+            }
+        }
+        args
+    }
 }
 
 struct Model {
     windows: HashMap<WindowId, Window>,
     global_state: GlobalState,
+    /// Files forwarded by later launches in single-instance mode.
+    open_requests: Option<std::sync::mpsc::Receiver<PathBuf>>,
 }
 
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
 enum Mode {
     Move,
     Paint,
+    /// Drag out a rectangle, or Alt-click to select the opaque island under the cursor.
+    Select,
+    Stamp,
+    RedEye,
+    Heal,
+    /// Paints with pixels copied from the source set with Alt-click.
+    CloneStamp,
+    /// Drags the pixels under the brush along the stroke.
+    Smudge,
+    Eyedropper,
+    /// Paints transparency with the brush instead of color.
+    Erase,
+    Fill,
+    /// Places the stamp snapped to the tile grid.
+    TileStamp,
+    /// Drags out a shape, drawn into the layer on release.
+    Shape(shapes::Shape),
+    /// Drags out the gradient's direction and length, filled in on release.
+    Gradient,
+    /// Drags a line and reports its length and angle in the status bar.
+    Measure,
+    /// Lifts the selection, or the whole layer, to move, scale and rotate with handles.
+    Transform,
+    /// Pins the note typed in the notes panel where clicked, or selects the note clicked.
+    Note,
+    /// Traces an outline that snaps to edges while dragging, and selects its bounds on release.
+    Scissors,
+    /// Scribbles over the foreground, or the background with Alt held, for background removal.
+    Cutout,
+}
+
+impl Mode {
+    fn cursor(&self) -> Cursor {
+        match self {
+            Mode::Move | Mode::Transform => Cursor::System(CursorIcon::Move),
+            Mode::Paint
+            | Mode::Erase
+            | Mode::RedEye
+            | Mode::Heal
+            | Mode::CloneStamp
+            | Mode::Smudge => Cursor::BrushOutline,
+            Mode::Select
+            | Mode::Eyedropper
+            | Mode::Shape(_)
+            | Mode::Gradient
+            | Mode::Measure
+            | Mode::Note
+            | Mode::Scissors
+            | Mode::Cutout => Cursor::System(CursorIcon::Crosshair),
+            Mode::Fill => Cursor::Bucket,
+            Mode::Stamp => Cursor::System(CursorIcon::Copy),
+            Mode::TileStamp => Cursor::System(CursorIcon::Cell),
+        }
+    }
+}
+
+/// How the pointer looks over the canvas, so the active mode is obvious without the Workbench.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Cursor {
+    System(CursorIcon),
+    /// The system cursor is hidden and the brush outline drawn in its place.
+    BrushOutline,
+    /// A crosshair with a paint bucket drawn beside it.
+    Bucket,
+}
+
+impl Cursor {
+    fn apply(&self, window: &nannou::window::Window) {
+        match self {
+            Cursor::System(icon) => {
+                window.set_cursor_visible(true);
+                window.set_cursor_icon(*icon);
+            }
+            Cursor::BrushOutline => window.set_cursor_visible(false),
+            Cursor::Bucket => {
+                window.set_cursor_visible(true);
+                window.set_cursor_icon(CursorIcon::Crosshair);
+            }
+        }
+    }
+}
+
+/// Work requested from the Workbench that needs access to editor windows. Queued while widgets
+/// are set and run once the window loop in `update` is done.
+#[derive(Debug)]
+enum Command {
+    Open(PathBuf),
+    Save(PathBuf),
+    /// Moves a panel out of the Workbench into its own window, placed where it was dropped if it
+    /// was dragged out: a point in the Workbench window, from its center with y up.
+    DetachPanel(workbench::Panel, Option<[f64; 2]>),
+    Layer(layers::LayerOp),
+    /// Closes a detached panel window, returning the panel to the Workbench.
+    DockPanel(WindowId),
+    ExportVideo(PathBuf),
+    /// Exports the visible layers as the frames of an animated GIF or APNG.
+    ExportAnimation(PathBuf),
+    /// Opens a sprite sheet as a new document with a layer per cell.
+    ImportSpriteSheet(PathBuf),
+    /// Packs the visible layers into a sprite sheet.
+    ExportSpriteSheet(PathBuf),
+    ExportNineSlice(PathBuf),
+    CaptureStamp,
+    BakeNodeGraph,
+    ApplyFilter,
+    CancelFilter,
+    Undo,
+    LoadLut(PathBuf),
+    RunBatch(PathBuf, PathBuf),
+    LoadWatermark(PathBuf),
+    /// Opens an editor on a blank canvas using the New Image options.
+    NewImage,
+    /// Copies the selection, or the whole active layer, to the clipboard.
+    Copy,
+    Paste(clipboard::PasteTransform),
+    /// Redraws the last stroke with post-processing applied.
+    StrokeFx(stroke::StrokeFx),
+    /// Applies the always-on-top and compact settings to the Workbench window.
+    UpdateWorkbenchWindow,
+    /// Makes an editor window the document Workbench commands apply to.
+    FocusDocument(WindowId),
+    /// Closes an editor window, offering to save its unsaved changes first.
+    CloseDocument(WindowId),
+    /// Lists the backed up versions of the focused document's project file.
+    BrowseBackups,
+    /// Replaces the focused document's layers with a backed up version.
+    RestoreBackup(PathBuf),
+    /// Opens a stream output window mirroring an editor's canvas.
+    OpenOutput(WindowId),
+    CloseOutput(WindowId),
+    /// Opens the export preview of an editor.
+    OpenProof(WindowId),
+    CloseProof(WindowId),
+    /// Writes the canvas with the export options.
+    Export(PathBuf),
+    /// Counts the colors and bit depth the document uses.
+    AnalyzeColors,
+    /// Reduces every layer to the palette panel's color count.
+    Posterize,
+    /// Changes how the current document's files store alpha.
+    SetAlphaMode(alpha::AlphaMode),
+    /// Changes which composition overlays the current document shows.
+    SetOverlays(composition::Overlays),
+    /// Glides the current document's view to fit, 100% or the selection.
+    Zoom(zoom::Target),
+    /// Prints the document with the print options.
+    Print,
+    /// Decodes the document's camera RAW file again with the current RAW options, into a new
+    /// layer at the document's size.
+    ReprocessRaw,
+    /// Resamples every layer to the size in the resize options.
+    ScaleImage,
+    /// Changes the canvas to the size in the resize options without scaling the layers.
+    ResizeCanvas,
+    /// Writes the texture described by the channel pack options.
+    ExportChannelPack(PathBuf),
+    /// Offsets the active layer by half its size to bring its edges into the middle.
+    MakeSeamless,
+    /// Loads the image documents are compared against.
+    LoadReference(PathBuf),
+    /// Replaces the guides with ones dividing the canvas into the chosen rows and columns.
+    DivideGuides,
+    /// Adds guides at the positions typed into the guides panel.
+    AddGuides,
+    ClearGuides,
+    /// Exports each cell between the guides as its own file.
+    SliceByGuides(PathBuf),
+    /// Removes the measurements left on the canvas.
+    ClearMeasurements,
+    /// Cuts the background out of the active layer's alpha, going by the cutout scribbles.
+    RemoveBackground,
+    ClearScribbles,
+    /// Resamples the free transform in progress into the layer.
+    CommitTransform,
+    /// Puts the pixels lifted for a free transform back where they were.
+    CancelTransform,
+    Note(notes::NoteOp),
 }
 
 struct GlobalState {
+    /// Zoom of the focused editor, refreshed every update for the scale slider and the brush
+    /// HUD. Each editor keeps its own zoom in its view.
     scale: f32,
     brush_size: f32,
+    /// When the brush size was last changed from the canvas, to show the size HUD.
+    brush_hud: Option<std::time::Instant>,
+    brush_color: [u8; 4],
+    brush: brush::BrushType,
+    /// Most a single stroke can cover, 0-1.
+    brush_opacity: f32,
+    /// How much each dab adds towards the opacity, 0-1.
+    brush_flow: f32,
+    /// Distance between dabs as a fraction of the brush size.
+    brush_spacing: f32,
+    brush_blend: compositing::BrushBlend,
+    /// How many previous cursor positions strokes are averaged with to steady them.
+    stabilizer: f32,
+    /// Where brush dabs are repeated around the canvas center.
+    symmetry: symmetry::Symmetry,
+    /// Pen pressure of the stylus currently touching the canvas, 0-1. `None` for the mouse.
+    pressure: Option<f32>,
+    /// Scale the brush size with pen pressure.
+    pressure_size: bool,
+    /// Scale how much each dab adds with pen pressure.
+    pressure_opacity: bool,
+    /// Largest per-channel difference from the clicked color that the bucket fill spreads to.
+    fill_tolerance: f32,
+    /// Width (0-1) of the partly transparent edge background removal leaves.
+    cutout_softness: f32,
+    /// Draw shape outlines with anti-aliased edges.
+    shape_antialias: bool,
+    /// Leave each finished measurement drawn on the canvas until they are cleared.
+    keep_measurements: bool,
+    /// Resample free transforms bilinearly rather than keeping hard pixel edges.
+    transform_smooth: bool,
+    /// Open controllers while gamepad input is on in the config.
+    gamepad: Option<gamepad::Gamepad>,
+    /// Connected MIDI controller while MIDI input is on in the config.
+    midi: Option<midi::MidiInput>,
+    /// Parameter the next turned MIDI knob is bound to.
+    midi_learn: Option<midi::MidiParam>,
+    gradient: gradient::Gradient,
+    guides: guides::GuideOptions,
+    perspective: perspective::PerspectiveGrid,
+    /// The next note placed with the Note tool.
+    note: notes::NoteOptions,
     mode: Mode,
     last_mouse: Option<Vec2>,
+    video: export::video::VideoOptions,
+    animation: export::animation::AnimationOptions,
+    sprite_sheet: sprite_sheet::SpriteSheetOptions,
+    channel_pack: export::channel_pack::PackOptions,
+    print: print::PrintOptions,
+    /// Used when opening camera RAW files.
+    raw: raw::RawOptions,
+    resize: resize::ResizeOptions,
+    commands: Vec<Command>,
+    tile_mode: bool,
+    tile_size: f32,
+    /// Pixels Alt+arrow keys shift the layer by.
+    wrap_step: f32,
+    /// Pixels either side of the seams Make Seamless cross-fades over.
+    seam_blend: f32,
+    /// Draw the canvas repeated around itself in tile mode, with the seams marked.
+    wrap_preview: bool,
+    nine_slice_mode: bool,
+    nine_slice: nine_slice::NineSlice,
+    nine_slice_size: (f32, f32),
+    stamp: Option<stamp::Stamp>,
+    stamp_jitter: stamp::StampJitter,
+    node_graph: nodes::NodeGraph,
+    /// Canvas position healing samples from, set with Alt-click.
+    heal_source: Option<Vec2>,
+    /// Canvas position the clone stamp copies from, set with Alt-click.
+    clone_source: Option<Vec2>,
+    /// Offset from the brush to the clone source, fixed by the first stroke after the source is
+    /// set so later strokes stay aligned with it.
+    clone_offset: Option<Vec2>,
+    /// Image the canvas is compared against. Comparison is off while this is `None`.
+    reference: Option<textures::Uploaded>,
+    compare_view: compare::CompareView,
+    /// How the current document differs from the reference, refreshed every update for the
+    /// compare panel.
+    compare_stats: Option<compare::Stats>,
+    /// Show the focused document's histogram on the Workbench.
+    show_histogram: bool,
+    /// Histogram of the focused document, including the filter being previewed.
+    histogram: Option<histogram::Histogram>,
+    /// Filter being previewed on the canvas, committed with "Apply".
+    filter: Option<filters::Filter>,
+    /// Compute pipelines for filters, built on the windows' device. `None` without GPU compute.
+    gpu: Option<std::sync::Arc<filters::gpu::GpuFilters>>,
+    /// Channel whose curve is shown while previewing Curves.
+    curve_channel: filters::curves::Channel,
+    batch_options: BatchOptions,
+    export: export::options::ExportOptions,
+    /// Filters, resizes and file writes running on worker threads.
+    jobs: Vec<jobs::Job>,
+    /// Last color analysis of the focused document, cleared when it posterizes.
+    color_report: Option<palette::ColorReport>,
+    posterize: palette::PosterizeOptions,
+    /// How alpha is stored in the image files opened next.
+    import_alpha: alpha::AlphaMode,
+    /// Alpha mode of the current document, refreshed with the layer list.
+    document_alpha: alpha::AlphaMode,
+    /// Composition overlays of the current document, refreshed with the layer list.
+    document_overlays: composition::Overlays,
+    new_image: NewImageOptions,
+    clipboard: Option<clipboard::Clip>,
+    batch: Option<batch::Batch>,
+    watermark: export::watermark::Watermark,
+    /// Shell command run after each successful export, with `{file}` replaced by its path.
+    post_export_command: String,
+    hook_log: export::hook::HookLog,
+    autosave: session::Autosave,
+    backups: backup::Backups,
+    /// Backed up versions listed on the Workbench, along with the project file they are of.
+    backup_versions: Option<(PathBuf, Vec<backup::Version>)>,
+    console_visible: bool,
+    /// Least severe level shown in the console.
+    console_level: log::LevelFilter,
+    /// When a slow update was last reported, and how many have gone unreported since.
+    slow_updates: (Option<std::time::Instant>, u32),
+    /// Workbench panels shown in the current workspace.
+    panels: Vec<workbench::Panel>,
+    /// Panels currently shown in their own window instead of the Workbench.
+    detached: Vec<workbench::Panel>,
+    /// Layers of the current document and the active index, refreshed every update for the
+    /// layers panel.
+    layer_list: (Vec<layers::LayerInfo>, usize),
+    /// Notes of the current document and the selected one, refreshed with the layer list.
+    note_list: (Vec<notes::Note>, Option<usize>),
+    /// Size of the current document, refreshed with the layer list.
+    document_size: (u32, u32),
+    /// Editor window last focused. Workbench commands apply to its document.
+    focused_editor: Option<WindowId>,
+    /// Open documents and their editor windows, refreshed every update for the documents panel.
+    documents: Vec<(WindowId, String)>,
+    smooth_sampling: bool,
+    pixel_grid: pixel_grid::PixelGrid,
+    /// Show the document at favicon sizes in the corner of the editor.
+    icon_preview: bool,
+    /// Workbench size before it was collapsed, restored when it is expanded again.
+    expanded_size: Option<(u32, u32)>,
+    config: config::Config,
+}
+
+impl GlobalState {
+    /// The GPU filters, unless they are unavailable or turned off.
+    fn gpu_filters(&self) -> Option<std::sync::Arc<filters::gpu::GpuFilters>> {
+        self.gpu.clone().filter(|_| self.config.gpu_filters)
+    }
+
+    /// Brush size after pen pressure is applied.
+    fn pressure_brush_size(&self) -> f32 {
+        match (self.pressure, self.pressure_size) {
+            (Some(pressure), true) => (self.brush_size * pressure).max(1.0),
+            _ => self.brush_size,
+        }
+    }
+
+    /// How dabs combine with the layer. The erase tool always removes alpha.
+    fn dab_blend(&self) -> compositing::BrushBlend {
+        match self.mode {
+            Mode::Erase => compositing::BrushBlend::EraseAlpha,
+            Mode::CloneStamp => {
+                compositing::BrushBlend::CloneStamp(self.clone_offset.unwrap_or_default())
+            }
+            Mode::Smudge => compositing::BrushBlend::Smudge,
+            _ => self.brush_blend,
+        }
+    }
+
+    /// Brush opacity after pen pressure is applied.
+    fn pressure_brush_opacity(&self) -> f32 {
+        match (self.pressure, self.pressure_opacity) {
+            (Some(pressure), true) => self.brush_opacity * pressure,
+            _ => self.brush_opacity,
+        }
+    }
+}
+
+/// Settings of the New Image dialog.
+struct NewImageOptions {
+    width: f32,
+    height: f32,
+    /// Resolution in pixels per inch.
+    dpi: f32,
+    background: Background,
+    /// Templates offered, built-in ones first.
+    templates: Vec<templates::Template>,
+    /// Name the current size is saved as a template under.
+    template_name: String,
+}
+
+impl Default for NewImageOptions {
+    fn default() -> Self {
+        Self {
+            width: DEFAULT_CANVAS_SIZE as f32,
+            height: DEFAULT_CANVAS_SIZE as f32,
+            dpi: 72.0,
+            background: Background::White,
+            templates: templates::load(),
+            template_name: String::new(),
+        }
+    }
+}
+
+/// Fill of a new canvas.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Background {
+    White,
+    Black,
+    Transparent,
+    BrushColor,
+}
+
+impl Background {
+    fn name(&self) -> &'static str {
+        match self {
+            Background::White => "White",
+            Background::Black => "Black",
+            Background::Transparent => "Transparent",
+            Background::BrushColor => "Brush Color",
+        }
+    }
+
+    fn next(&self) -> Background {
+        match self {
+            Background::White => Background::Black,
+            Background::Black => Background::Transparent,
+            Background::Transparent => Background::BrushColor,
+            Background::BrushColor => Background::White,
+        }
+    }
+
+    fn color(&self, brush_color: [u8; 4]) -> [u8; 4] {
+        match self {
+            Background::White => [255, 255, 255, 255],
+            Background::Black => [0, 0, 0, 255],
+            Background::Transparent => [0, 0, 0, 0],
+            Background::BrushColor => brush_color,
+        }
+    }
+}
+
+/// Chain applied by the batch dialog: resize, then the current filter, then palette quantize.
+struct BatchOptions {
+    scale: f32,
+    use_filter: bool,
+    /// Palette size to quantize to, or 0 to skip quantization.
+    colors: f32,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            use_filter: false,
+            colors: 0.0,
+        }
+    }
 }
 
 widget_ids! {
@@ -88,39 +840,788 @@ widget_ids! {
 }
 
 struct EditorState {
+    /// Document name, used for `{name}` in export paths.
+    name: String,
+    /// File the document was opened from or last saved to.
+    path: Option<PathBuf>,
+    /// Resolution in pixels per inch, kept in project files.
+    dpi: f32,
+    /// Edited since it was opened or last saved.
+    modified: bool,
+    /// Changed since the last autosave wrote it.
+    autosave_stale: Cell<bool>,
+    /// Changed since the last backup of its project file.
+    backup_stale: Cell<bool>,
+    /// Names the document's file in the autosave session.
+    session_id: u64,
     offset: Point2,
     selected: bool,
-    pixels: DynamicImage,
+    layers: layers::LayerStack,
+    /// Arrangement preview shown in tile mode, and when and for which revision and tile size it
+    /// was rendered.
+    tile_preview: Option<(std::time::Instant, u64, u32, textures::Uploaded)>,
+    /// Arrangement being rendered, with the revision and tile size it is for.
+    tile_job: Option<(u64, u32, jobs::Job)>,
+    /// Stretched 9-slice preview, along with the guides and size it was rendered for.
+    nine_slice_preview: Option<(nine_slice::NineSlice, (u32, u32), textures::Uploaded)>,
+    selection: Option<selection::Selection>,
+    guides: Vec<guides::Guide>,
+    /// Guide being dragged out of a ruler or moved. It joins `guides` when dropped on the canvas.
+    dragged_guide: Option<guides::Guide>,
+    /// Index of the perspective vanishing point being dragged with the Move tool.
+    dragged_vanishing_point: Option<usize>,
+    /// Composition overlays shown over this document. Saved in projects, never exported.
+    overlays: composition::Overlays,
+    /// Zoom transition the view is in the middle of.
+    zoom: Option<zoom::Transition>,
+    /// Notes pinned to the canvas. Saved in projects, never exported.
+    notes: Vec<notes::Note>,
+    /// How the document's image files store alpha. The pixels here are always straight.
+    alpha_mode: alpha::AlphaMode,
+    /// Note whose text is shown beside its marker.
+    selected_note: Option<usize>,
+    /// Canvas position the current selection drag started from.
+    selection_anchor: Option<Vec2>,
+    /// Edge-snapping outline being traced with the scissors.
+    scissors: Option<scissors::Scissors>,
+    /// Foreground and background marked for background removal.
+    scribbles: cutout::Scribbles,
+    /// Offset from the cursor to the heal source and the canvas as it was when the stroke began.
+    heal_stroke: Option<(Vec2, DynamicImage)>,
+    /// Canvas position the shape, gradient or measurement being dragged out starts from.
+    shape_anchor: Option<Vec2>,
+    /// Most recent measurement, shown in the status bar.
+    measurement: Option<measure::Measurement>,
+    /// Measurements kept on the canvas. Only drawn, never saved.
+    annotations: Vec<measure::Measurement>,
+    /// Pixels lifted for a free transform, drawn as an overlay until committed.
+    transform: Option<free_transform::FreeTransform>,
+    /// Handle being dragged, where the drag started and the transform before it.
+    transform_drag: Option<(free_transform::Handle, Vec2, free_transform::FreeTransform)>,
+    /// Last cursor position while panning with the middle button or Space+drag.
+    pan: Option<Point2>,
+    /// A mouse button bound to the eyedropper is held.
+    picking: bool,
+    /// Paint or erase strokes rasterized on a worker thread, oldest first. Only the first paints;
+    /// the others wait for it to land so they start from its pixels.
+    painters: std::collections::VecDeque<painter::Painter>,
+    /// Path of the most recent stroke, for stroke post-processing. Dropped by the next edit.
+    last_stroke: Option<stroke::StrokePath>,
+    /// Result of the previewed filter on the active layer, along with the settings it was
+    /// computed with.
+    filter_preview: Option<(filters::Filter, DynamicImage)>,
+    /// Layer stack snapshots taken before each edit, most recent last.
+    history: Vec<layers::LayerStack>,
+    /// Document downscaled to each of `ICON_SIZES`, and when and for which revision they were
+    /// rendered.
+    icon_previews: Option<(std::time::Instant, u64, Vec<textures::Uploaded>)>,
+    /// Icon previews being rendered, with the revision they are for.
+    icon_job: Option<(u64, jobs::Job)>,
+    /// Soft proof for the export preview, the options it was made with and when it was made.
+    proof: Option<(
+        std::time::Instant,
+        u64,
+        export::options::ExportOptions,
+        export::options::Proof,
+    )>,
+    /// Soft proof being rendered, with the revision and options it is for.
+    proof_job: Option<(
+        u64,
+        export::options::ExportOptions,
+        std::sync::mpsc::Receiver<Result<export::options::Proof, String>>,
+    )>,
+    /// Histogram of the canvas, and when and for which revision it was computed.
+    histogram: Option<(std::time::Instant, u64, histogram::Histogram)>,
+    /// Difference from the compare reference, along with the revision it was computed for.
+    compare_preview: Option<(u64, textures::Uploaded, compare::Stats)>,
+    /// Bumped whenever the canvas changes, so caches built from it can tell they are out of
+    /// date.
+    revision: Cell<u64>,
+    /// Bumped by every edit to the pixels, unlike `revision`, which also moves when the canvas is
+    /// only re-uploaded. Jobs use it to tell whether their result is still wanted.
+    edits: Cell<u64>,
+    /// Mode the window's cursor was last set for.
+    cursor_mode: Option<Mode>,
+    /// Composited canvas on the GPU and the view it is drawn with, created on first draw and
+    /// updated in place afterwards.
+    texture: RefCell<Option<(wgpu::Texture, wgpu::TextureView)>>,
+    /// Part of the canvas that changed since `texture` was last written.
+    dirty: Cell<dirty::Dirty>,
 
-    rect: Rect<f32>,
+    /// Where the canvas is shown in the window. Every conversion between window and canvas
+    /// coordinates goes through it.
+    view: viewport::Viewport,
 }
 
+/// Size of the blank canvas an editor starts with when no image is given.
+const DEFAULT_CANVAS_SIZE: u32 = 256;
+
 impl Default for EditorState {
     fn default() -> Self {
         // let mut rng = rand::thread_rng();
-        // let mut img = RgbaImage::new(256, 256);
-        let mut img = RgbaImage::new(256, 256);
-        for (_, _, pixel) in img.enumerate_pixels_mut() {
-            // pixel.0 = [rng.gen(), rng.gen(), 255, 255];
-            pixel.0 = [255, 255, 255, 255];
-        }
+        let img = RgbaImage::from_pixel(
+            DEFAULT_CANVAS_SIZE,
+            DEFAULT_CANVAS_SIZE,
+            nannou::image::Rgba([255, 255, 255, 255]),
+        );
         Self {
+            name: "untitled".to_string(),
+            path: None,
+            dpi: 72.0,
+            modified: false,
+            autosave_stale: Cell::new(false),
+            backup_stale: Cell::new(false),
+            session_id: session::next_id(),
             offset: Point2::new(0.0, 0.0),
             selected: false,
-            pixels: DynamicImage::ImageRgba8(img),
-            rect: nannou::prelude::Rect::from_x_y_w_h(0.0, 0.0, 256.0, 256.0),
+            layers: layers::LayerStack::new(DynamicImage::ImageRgba8(img)),
+            tile_preview: None,
+            tile_job: None,
+            nine_slice_preview: None,
+            selection: None,
+            guides: Vec::new(),
+            dragged_guide: None,
+            dragged_vanishing_point: None,
+            overlays: Default::default(),
+            zoom: None,
+            notes: Vec::new(),
+            selected_note: None,
+            alpha_mode: Default::default(),
+            selection_anchor: None,
+            scissors: None,
+            scribbles: Default::default(),
+            heal_stroke: None,
+            shape_anchor: None,
+            measurement: None,
+            annotations: Vec::new(),
+            transform: None,
+            transform_drag: None,
+            pan: None,
+            picking: false,
+            painters: Default::default(),
+            last_stroke: None,
+            filter_preview: None,
+            history: Vec::new(),
+            icon_previews: None,
+            icon_job: None,
+            proof: None,
+            proof_job: None,
+            histogram: None,
+            compare_preview: None,
+            revision: Cell::new(0),
+            edits: Cell::new(0),
+            cursor_mode: None,
+            texture: RefCell::new(None),
+            dirty: Cell::new(dirty::Dirty::All),
+            view: viewport::Viewport::new(Vec2::splat(DEFAULT_CANVAS_SIZE as _)),
+        }
+    }
+}
+
+impl EditorState {
+    fn with_image(name: String, pixels: DynamicImage) -> EditorState {
+        EditorState {
+            name,
+            view: viewport::Viewport::new(Vec2::new(pixels.width() as _, pixels.height() as _)),
+            layers: layers::LayerStack::new(pixels),
+            ..Default::default()
+        }
+    }
+}
+
+/// Sizes in pixels the icon preview shows the document at.
+const ICON_SIZES: [u32; 3] = [16, 24, 32];
+/// Shortest time between icon preview renders while the canvas keeps changing.
+const ICON_PREVIEW_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// Number of snapshots kept for undo.
+const HISTORY_LIMIT: usize = 20;
+
+impl EditorState {
+    /// Records the current layers so the next edit can be undone.
+    fn checkpoint(&mut self) {
+        if self.history.len() == HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        self.history.push(self.layers.clone());
+        self.last_stroke = None;
+        self.modified = true;
+        self.edits.set(self.edits.get() + 1);
+        self.autosave_stale.set(true);
+        self.backup_stale.set(true);
+    }
+
+    /// Marks document settings outside the undo history, like notes, as needing a save.
+    fn settings_changed(&mut self) {
+        self.modified = true;
+        self.autosave_stale.set(true);
+        self.backup_stale.set(true);
+    }
+
+    fn undo(&mut self) {
+        if self.stroking() {
+            log::warn!("Let the stroke finish before undoing");
+            return;
+        }
+        if let Some(layers) = self.history.pop() {
+            self.layers = layers;
+            self.edits.set(self.edits.get() + 1);
+            // The lifted pixels are back in the layer
+            self.transform = None;
+            self.transform_drag = None;
+            self.last_stroke = None;
+            self.modified = true;
+            self.autosave_stale.set(true);
+            self.backup_stale.set(true);
+            self.nine_slice_preview = None;
+            self.filter_preview = None;
+            self.invalidate();
+        }
+    }
+
+    /// Resamples the free transform in progress into the active layer and selects the result.
+    fn commit_transform(&mut self, smooth: bool) {
+        if let Some(transform) = self.transform.take() {
+            self.transform_drag = None;
+            self.selection = transform.apply(&mut self.layers.active_mut().pixels, smooth);
+            self.nine_slice_preview = None;
+            self.filter_preview = None;
+            self.invalidate();
+        }
+    }
+
+    /// Marks the whole canvas for re-upload.
+    fn invalidate(&self) {
+        self.dirty.set(dirty::Dirty::All);
+        self.revision.set(self.revision.get() + 1);
+    }
+
+    /// Marks the square of `radius` around canvas pixel `(x, y)` for re-upload.
+    fn mark_dirty(&self, x: i32, y: i32, radius: i32) {
+        let rect =
+            dirty::DirtyRect::around(x, y, radius, self.layers.width(), self.layers.height());
+        self.mark_dirty_rect(rect);
+    }
+
+    /// Marks the box spanned by canvas positions `a` and `b`, grown by `padding` on each side,
+    /// for re-upload.
+    fn mark_dirty_span(&self, a: Vec2, b: Vec2, padding: f32) {
+        let (min, max) = (a.min(b) - Vec2::splat(padding), a.max(b) + Vec2::splat(padding));
+        let clip = |v: f32, size: u32| v.clamp(0.0, size as f32) as u32;
+        let (width, height) = (self.layers.width(), self.layers.height());
+        self.mark_dirty_rect(dirty::DirtyRect {
+            x0: clip(min.x.floor(), width),
+            y0: clip(min.y.floor(), height),
+            x1: clip(max.x.ceil() + 1.0, width),
+            y1: clip(max.y.ceil() + 1.0, height),
+        });
+    }
+
+    /// Drops the filter and 9-slice previews, marking the canvas for re-upload if one was shown.
+    fn clear_previews(&mut self) {
+        if self.nine_slice_preview.take().is_some() | self.filter_preview.take().is_some() {
+            self.invalidate();
+        }
+    }
+
+    /// Marks `rect` for re-upload.
+    fn mark_dirty_rect(&self, rect: dirty::DirtyRect) {
+        self.dirty.set(self.dirty.get().include(rect));
+        self.revision.set(self.revision.get() + 1);
+        self.edits.set(self.edits.get() + 1);
+        // Strokes keep changing the layer after their checkpoint
+        self.autosave_stale.set(true);
+        self.backup_stale.set(true);
+    }
+
+    /// Whether a stroke is still painting. The layers are locked until it lands.
+    fn stroking(&self) -> bool {
+        !self.painters.is_empty()
+    }
+
+    /// Merges what the stroke workers painted into their layers, and starts the next stroke once
+    /// the one before it has landed.
+    fn poll_painters(&mut self) {
+        while let Some(painter) = self.painters.front_mut() {
+            let id = painter.layer;
+            if !painter.started() {
+                let pixels = match self.layers.by_id_mut(id) {
+                    Some(layer) => layer.pixels.clone(),
+                    // Deleted before the stroke got its turn
+                    None => {
+                        self.painters.pop_front();
+                        continue;
+                    }
+                };
+                // Taken here rather than on press, so it holds the strokes before this one
+                self.checkpoint();
+                if let Some(painter) = self.painters.front_mut() {
+                    painter.start(pixels);
+                }
+                continue;
+            }
+
+            let (patches, done) = painter.poll();
+            for patch in patches {
+                if let Some(layer) = self.layers.by_id_mut(id) {
+                    patch.merge(&mut layer.pixels);
+                }
+                self.mark_dirty_rect(patch.rect);
+            }
+            if !done {
+                break;
+            }
+            if let Some(painter) = self.painters.pop_front() {
+                self.last_stroke = Some(painter.into_path());
+            }
+        }
+    }
+
+    /// Ends the stroke being drawn. It keeps painting until the samples so far have landed.
+    fn end_stroke(&mut self) {
+        if let Some(painter) = self.painters.back_mut() {
+            painter.end();
+        }
+    }
+
+    /// Re-renders the icon previews if the canvas changed, at most once per
+    /// `ICON_PREVIEW_INTERVAL`. Resizing runs as a job so painting stays responsive, and the last
+    /// previews stay up until it's done.
+    fn refresh_icon_previews(&mut self, target: WindowId) {
+        if let Some((revision, job)) = &mut self.icon_job {
+            match job.poll() {
+                None => return,
+                Some(Ok(jobs::Output::Previews(icons))) => {
+                    let icons = icons.into_iter().map(Into::into).collect();
+                    self.icon_previews = Some((std::time::Instant::now(), *revision, icons));
+                }
+                Some(Ok(_)) => (),
+                Some(Err(e)) => log::error!("Failed to render icon previews: {}", e),
+            }
+            self.icon_job = None;
+        }
+
+        let revision = self.revision.get();
+        let due = self
+            .icon_previews
+            .as_ref()
+            .map_or(true, |(rendered, r, _)| {
+                *r != revision && rendered.elapsed() >= ICON_PREVIEW_INTERVAL
+            });
+        if !due {
+            return;
+        }
+
+        let preview = self.filter_preview.as_ref().map(|(_, preview)| preview);
+        let canvas = self.layers.composite_with(preview);
+        let job = jobs::Job::spawn("Rendering icons", target, self.edits.get(), move |_| {
+            let icons = ICON_SIZES
+                .iter()
+                .map(|size| canvas.resize(*size, *size, FilterType::Lanczos3))
+                .collect();
+            Ok(jobs::Output::Previews(icons))
+        });
+        self.icon_job = Some((revision, job));
+    }
+
+    /// Re-renders the tile arrangement preview when the tile size changes, or when the sheet
+    /// changed at most once per `ICON_PREVIEW_INTERVAL` like the icon previews. Arranging
+    /// compares every tile with every other, so it runs as a job, one at a time, and the last
+    /// preview stays up until it's done.
+    fn refresh_tile_preview(&mut self, target: WindowId, tile_size: u32) {
+        if let Some((revision, size, job)) = &mut self.tile_job {
+            match job.poll() {
+                None => return,
+                Some(Ok(jobs::Output::Previews(arrangement))) => {
+                    if let Some(arrangement) = arrangement.into_iter().next() {
+                        let rendered = std::time::Instant::now();
+                        self.tile_preview = Some((rendered, *revision, *size, arrangement.into()));
+                    }
+                }
+                Some(Ok(_)) => (),
+                Some(Err(e)) => log::error!("Failed to arrange tiles: {}", e),
+            }
+            self.tile_job = None;
+        }
+
+        let revision = self.revision.get();
+        let due = self
+            .tile_preview
+            .as_ref()
+            .map_or(true, |(rendered, r, size, _)| {
+                *size != tile_size
+                    || (*r != revision && rendered.elapsed() >= ICON_PREVIEW_INTERVAL)
+            });
+        if !due {
+            return;
+        }
+
+        let sheet = self.layers.composite();
+        let job = jobs::Job::spawn("Arranging tiles", target, self.edits.get(), move |_| {
+            let (cols, rows) = tiles::grid_size(&sheet, tile_size);
+            let arrangement = tiles::render_arrangement(&sheet, tile_size, cols + 2, rows + 2);
+            Ok(jobs::Output::Previews(vec![arrangement]))
+        });
+        self.tile_job = Some((revision, tile_size, job));
+    }
+
+    /// Re-renders the soft proof when the export options change, or when the canvas changed at
+    /// most once per `ICON_PREVIEW_INTERVAL` like the icon previews.
+    ///
+    /// Encoding a large canvas takes a while, so proofs are rendered on a worker thread, one at a
+    /// time, and picked up by a later call.
+    fn refresh_proof(&mut self, global: &GlobalState) {
+        if let Some((revision, options, job)) = &self.proof_job {
+            match job.try_recv() {
+                Ok(Ok(proof)) => {
+                    self.proof =
+                        Some((std::time::Instant::now(), *revision, options.clone(), proof))
+                }
+                Ok(Err(e)) => {
+                    log::error!("Failed to preview export: {}", e);
+                    self.proof = None;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => return,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => (),
+            }
+            self.proof_job = None;
+        }
+
+        let revision = self.revision.get();
+        let due = self
+            .proof
+            .as_ref()
+            .map_or(true, |(rendered, r, options, _)| {
+                *options != global.export
+                    || (*r != revision && rendered.elapsed() >= ICON_PREVIEW_INTERVAL)
+            });
+        if !due {
+            return;
+        }
+
+        let preview = self.filter_preview.as_ref().map(|(_, preview)| preview);
+        let canvas = export_pixels(global, &self.layers.composite_with(preview));
+        let (options, alpha) = (global.export.clone(), self.alpha_mode);
+        let (sender, job) = std::sync::mpsc::channel();
+        std::thread::spawn({
+            let options = options.clone();
+            move || {
+                let _ = sender.send(options.proof(&canvas, alpha).map_err(|e| e.to_string()));
+            }
+        });
+        self.proof_job = Some((revision, options, job));
+    }
+
+    /// Recomputes the histogram if the canvas changed, at most once per `ICON_PREVIEW_INTERVAL`
+    /// like the icon previews.
+    fn refresh_histogram(&mut self) {
+        let revision = self.revision.get();
+        let due = self.histogram.as_ref().map_or(true, |(computed, r, _)| {
+            *r != revision && computed.elapsed() >= ICON_PREVIEW_INTERVAL
+        });
+        if due {
+            let preview = self.filter_preview.as_ref().map(|(_, preview)| preview);
+            let histogram = histogram::Histogram::of(&self.layers.composite_with(preview));
+            self.histogram = Some((std::time::Instant::now(), revision, histogram));
+        }
+    }
+
+    /// Recomputes the difference from `reference` if the canvas changed. Waits for the stroke
+    /// in progress to finish rather than redoing the whole canvas on every dab.
+    fn refresh_compare_preview(&mut self, reference: &DynamicImage) {
+        let revision = self.revision.get();
+        let stale = self
+            .compare_preview
+            .as_ref()
+            .map_or(true, |(r, _, _)| *r != revision);
+        if !stale || self.stroking() || self.heal_stroke.is_some() {
+            return;
         }
+
+        let preview = self.filter_preview.as_ref().map(|(_, preview)| preview);
+        let (heatmap, stats) = compare::difference(&self.layers.composite_with(preview), reference);
+        self.compare_preview = Some((revision, heatmap.into(), stats));
+    }
+
+    /// Brings the canvas texture up to date with the layers, uploading only what changed.
+    fn canvas_texture(&self, app: &App, queue: &wgpu::Queue) -> wgpu::TextureView {
+        let preview = self.filter_preview.as_ref().map(|(_, preview)| preview);
+        let mut texture = self.texture.borrow_mut();
+        let size = [self.layers.width(), self.layers.height()];
+
+        match (texture.as_ref(), self.dirty.replace(dirty::Dirty::Clean)) {
+            (Some((t, _)), dirty::Dirty::Clean) if t.size() == size => {}
+            (Some((t, _)), dirty::Dirty::Region(rect)) if t.size() == size => {
+                let pixels = self.layers.composite_region(preview, &rect);
+                textures::write(queue, t, [rect.x0, rect.y0], &pixels);
+            }
+            // A full invalidation writes over the texture it already has
+            (Some((t, _)), dirty::Dirty::All) if t.size() == size => {
+                let pixels = self.layers.composite_with(preview).to_rgba8();
+                textures::write(queue, t, [0, 0], &pixels);
+            }
+            // First draw or a resized document
+            _ => {
+                let t = wgpu::Texture::from_image(app, &self.layers.composite_with(preview));
+                let view = t.view().build();
+                *texture = Some((t, view));
+            }
+        }
+
+        texture.as_ref().map(|(_, view)| view.clone()).unwrap()
     }
 }
 
 widget_ids! {
     struct WorkbenchIds {
+        workspace_button,
+        compact_button,
+        compact_tools[],
+        tool_buttons[],
+        pin_button,
+        panel_headers[],
+        dock_button,
+        job_progress,
+        job_progress_bar,
+        job_progress_label,
+        new_image_templates[],
+        new_image_width,
+        new_image_height,
+        new_image_dpi,
+        new_image_background,
+        new_image_button,
+        new_image_template_name,
+        new_image_save_template,
+        copy_button,
+        paste_buttons[],
+        commit_transform_button,
+        cancel_transform_button,
+        transform_smooth_toggle,
+        document_buttons[],
+        document_close,
+        document_alpha_button,
+        import_alpha_button,
+        open_output_button,
+        export_format_button,
+        export_png_color,
+        export_png_compression,
+        export_webp_lossless,
+        export_quality,
+        export_scale,
+        export_colors,
+        export_preview_button,
+        export_button,
+        backup_browse_button,
+        backup_buttons[],
+        backup_close_button,
+        print_paper,
+        print_landscape,
+        print_dpi,
+        print_margin,
+        print_fit,
+        print_scale,
+        print_page,
+        print_area,
+        print_image,
+        print_button,
+        raw_exposure,
+        raw_white_balance,
+        raw_reprocess,
+        resize_width,
+        resize_height,
+        resize_current,
+        resize_resample,
+        resize_anchors[],
+        scale_image_button,
+        resize_canvas_button,
+        tile_stamp_button,
+        pixel_grid_toggle,
+        pixel_grid_color,
+        pixel_grid_major,
+        wrap_step,
+        seam_blend,
+        wrap_preview_toggle,
+        make_seamless_button,
+        gradient_kind,
+        gradient_stop_colors[],
+        gradient_stop_positions[],
+        gradient_stop_removes[],
+        gradient_add_stop,
+        perspective_toggle,
+        perspective_points,
+        perspective_density,
+        perspective_snap,
+        composition_thirds,
+        composition_spiral,
+        composition_spiral_corner,
+        composition_center,
+        composition_safe_areas,
+        guide_columns,
+        guide_rows,
+        guide_divide,
+        guide_vertical_label,
+        guide_vertical,
+        guide_horizontal_label,
+        guide_horizontal,
+        guide_rulers,
+        guide_snap,
+        guide_add,
+        guide_clear,
+        guide_slice,
+        note_text,
+        note_todo,
+        note_buttons[],
+        note_done,
+        note_delete,
+        note_clear_done,
+        palette_analyze,
+        palette_report,
+        palette_swatches[],
+        palette_colors,
+        palette_method,
+        palette_posterize,
+        shape_antialias_toggle,
+        pressure_size_toggle,
+        pressure_opacity_toggle,
+        fill_tolerance,
+        cutout_softness,
+        remove_background_button,
+        clear_scribbles_button,
+        icon_preview_toggle,
+        keep_measurements_toggle,
+        gamepad_toggle,
+        midi_toggle,
+        midi_learn_buttons[],
+        clear_measurements_button,
+        color_swatch,
+        brush_opacity,
+        brush_flow,
+        brush_type_buttons[],
+        brush_blend,
+        brush_spacing,
+        stabilizer,
+        symmetry_button,
+        symmetry_spokes,
+        stroke_fx_buttons[],
+        color_red,
+        color_green,
+        color_blue,
+        color_alpha,
+        layer_add,
+        layer_delete,
+        layer_up,
+        layer_down,
+        layer_visible,
+        layer_opacity,
+        layer_blend,
+        layer_buttons[],
         scale,
+        zoom_fit_button,
+        zoom_actual_button,
+        zoom_selection_button,
+        open_button,
+        save_button,
         brush_size,
         brush_size_labels,
-        move_mode_button,
-        paint_mode_button,
         modes,
+        video_fps,
+        video_scale,
+        export_video_button,
+        animation_delay,
+        animation_plays,
+        animation_colors,
+        export_animation_button,
+        sprite_by_size,
+        sprite_cell_width,
+        sprite_cell_height,
+        sprite_grid_columns,
+        sprite_grid_rows,
+        sprite_skip_empty,
+        import_sprite_sheet_button,
+        sprite_columns,
+        sprite_padding,
+        sprite_metadata,
+        export_sprite_sheet_button,
+        channel_pack_sources[],
+        channel_pack_channels[],
+        channel_pack_export,
+        tile_mode_toggle,
+        tile_size,
+        nine_slice_toggle,
+        nine_slice_left,
+        nine_slice_right,
+        nine_slice_top,
+        nine_slice_bottom,
+        nine_slice_width,
+        nine_slice_height,
+        export_nine_slice_button,
+        capture_stamp_button,
+        stamp_rotate_toggle,
+        stamp_flip_toggle,
+        bake_nodes_button,
+        node_output,
+        node_labels[],
+        node_removes[],
+        node_params[],
+        node_inputs[],
+        node_actions[],
+        node_add_buttons[],
+        unsharp_mask_button,
+        high_pass_button,
+        median_button,
+        despeckle_button,
+        bilateral_button,
+        filter_params[],
+        apply_filter_button,
+        cancel_filter_button,
+        chromatic_aberration_button,
+        vignette_button,
+        grain_button,
+        gaussian_blur_button,
+        box_blur_button,
+        brightness_contrast_button,
+        levels_button,
+        threshold_button,
+        gpu_filters_toggle,
+        curves_button,
+        curve_channel_button,
+        curve_editor,
+        curve_reset_button,
+        histogram_toggle,
+        histogram_background,
+        histogram_plots[],
+        saturation_button,
+        undo_button,
+        load_lut_button,
+        compare_load,
+        compare_view,
+        compare_stats,
+        compare_clear,
+        normal_map_button,
+        normal_map_invert_y,
+        batch_scale,
+        batch_filter_toggle,
+        batch_colors,
+        run_batch_button,
+        batch_progress,
+        batch_results[],
+        watermark_toggle,
+        watermark_image_button,
+        watermark_text,
+        watermark_anchor_button,
+        watermark_opacity,
+        watermark_margin,
+        post_export_command_label,
+        post_export_command,
+        hook_log_lines[],
+        console_toggle,
+        console_level_button,
+        console_lines[],
+        settings_theme,
+        settings_autosave,
+        settings_recent_label,
+        settings_recent[],
+        settings_clear_recent,
     }
 }
 
@@ -135,357 +1636,3575 @@ impl Default for WorkBenchState {
 enum WindowType {
     Editor(EditorIds, EditorState),
     Workbench(WorkbenchIds, WorkBenchState),
+    /// A Workbench panel detached into its own window.
+    Panel(WorkbenchIds, workbench::Panel),
+    /// Borderless mirror of the canvas of the editor with this id.
+    Output(WindowId),
+    /// Soft proof of the export of the editor with this id.
+    Proof(WindowId),
 }
 
 fn model(app: &App) -> Model {
     // Set the loop mode to wait for events, an energy-efficient option for pure-GUI apps.
     app.set_loop_mode(LoopMode::Wait);
 
-    let editor_window = <Window as Init<EditorIds>>::new(app, "Editor");
+    let mut map = HashMap::default();
+    let args = Args::parse();
+    // Tool settings of the first project opened, applied once the state exists
+    let mut tools = None;
+
+    // Files passed on the command line (e.g. from a file manager's "Open with") each get an
+    // Editor window
+    for path in args.files {
+        match Window::open_editor(app, &path, &Default::default(), Default::default()) {
+            Ok((window, project_tools)) => {
+                map.insert(window.id, window);
+                tools = tools.or(project_tools);
+            }
+            Err(e) => log::error!("Failed to open {}: {}", path.display(), e),
+        }
+    }
+
+    // Work left unsaved when the last run crashed or was closed
+    let unsaved = session::pending();
+    if !unsaved.is_empty() {
+        let restore = rfd::MessageDialog::new()
+            .set_title("Restore Session")
+            .set_description(&format!(
+                "{} document(s) had unsaved changes when the editor last closed. Restore them?",
+                unsaved.len()
+            ))
+            .set_buttons(rfd::MessageButtons::YesNo)
+            .show();
+        if restore {
+            // The files of documents that didn't restore are moved out of the session, as the
+            // next autosave removes everything it doesn't list
+            let mut failed = Vec::new();
+            for document in &unsaved {
+                match Window::open_session_document(app, document) {
+                    Ok(window) => {
+                        map.insert(window.id, window);
+                    }
+                    Err(e) => {
+                        log::error!("Failed to restore {}: {}", document.name, e);
+                        let kept = match session::set_aside(document) {
+                            Ok(path) => format!("kept at {}", path.display()),
+                            Err(e) => format!("could not be moved aside: {}", e),
+                        };
+                        failed.push(format!("{}: {} ({})", document.name, e, kept));
+                    }
+                }
+            }
+            if !failed.is_empty() {
+                rfd::MessageDialog::new()
+                    .set_title("Restore Session")
+                    .set_level(rfd::MessageLevel::Warning)
+                    .set_description(&format!(
+                        "Some documents could not be restored:\n\n{}",
+                        failed.join("\n")
+                    ))
+                    .set_buttons(rfd::MessageButtons::Ok)
+                    .show();
+            }
+        } else if let Err(e) = session::clear() {
+            log::warn!("Failed to discard the previous session: {}", e);
+        }
+    }
+
+    if map.is_empty() {
+        let editor_window = <Window as Init<EditorIds>>::new(app, "Editor");
+        map.insert(editor_window.id, editor_window);
+    }
+
     let workbench_window = <Window as Init<WorkbenchIds>>::new(app, "Workbench");
+    let workbench_id = workbench_window.id;
+    map.insert(workbench_id, workbench_window);
 
-    let mut map = HashMap::default();
-    map.insert(editor_window.id, editor_window);
-    map.insert(workbench_window.id, workbench_window);
+    let open_requests = if args.single_instance {
+        instance::listen(app.create_proxy())
+            .map_err(|e| log::warn!("Single-instance listener unavailable: {}", e))
+            .ok()
+    } else {
+        None
+    };
+
+    let config = config::Config::load();
+    for window in map.values() {
+        window.restore_placement(app, &config);
+    }
+    let mut expanded_size = None;
+    if config.always_on_top || config.compact {
+        update_workbench_window(app, &map, &config, &mut expanded_size);
+    }
+    // Every window shares one device, so any of them can lend it to the filters
+    let gpu = app
+        .window(workbench_id)
+        .and_then(|window| filters::gpu::GpuFilters::new(window.device_queue_pair().clone()))
+        .map(std::sync::Arc::new);
+
+    let preset = config
+        .preset(&config.workspace)
+        .cloned()
+        .unwrap_or_else(|| workspace::WorkspacePreset::builtin().remove(0));
 
-    Model {
+    let mut model = Model {
         windows: map,
+        open_requests,
         global_state: GlobalState {
-            scale: 1.75,
-            brush_size: 1.0,
-            mode: Mode::Move,
+            scale: config.zoom,
+            brush_size: config.brush_size,
+            brush_hud: None,
+            brush_color: config.brush_color,
+            brush: brush::BrushType::SoftRound,
+            brush_opacity: 1.0,
+            brush_flow: 1.0,
+            brush_spacing: 0.1,
+            brush_blend: compositing::BrushBlend::Color(compositing::BlendMode::Normal),
+            stabilizer: 0.0,
+            symmetry: symmetry::Symmetry::Off,
+            pressure: None,
+            pressure_size: true,
+            pressure_opacity: false,
+            fill_tolerance: 32.0,
+            cutout_softness: 0.2,
+            shape_antialias: true,
+            keep_measurements: false,
+            transform_smooth: true,
+            gamepad: config.gamepad.then(gamepad::Gamepad::new),
+            midi: config
+                .midi
+                .enabled
+                .then(|| midi::MidiInput::connect(&config.midi.port))
+                .flatten(),
+            midi_learn: None,
+            gradient: Default::default(),
+            guides: Default::default(),
+            perspective: Default::default(),
+            note: Default::default(),
+            mode: preset.mode,
             last_mouse: None,
+            video: Default::default(),
+            animation: Default::default(),
+            sprite_sheet: Default::default(),
+            channel_pack: Default::default(),
+            print: Default::default(),
+            raw: Default::default(),
+            resize: Default::default(),
+            commands: Vec::new(),
+            tile_mode: preset.tile_grid,
+            tile_size: preset.tile_size,
+            wrap_step: 1.0,
+            seam_blend: 0.0,
+            wrap_preview: false,
+            nine_slice_mode: false,
+            nine_slice: Default::default(),
+            nine_slice_size: (96.0, 64.0),
+            stamp: None,
+            stamp_jitter: Default::default(),
+            node_graph: Default::default(),
+            heal_source: None,
+            clone_source: None,
+            clone_offset: None,
+            reference: None,
+            compare_view: compare::CompareView::Difference,
+            compare_stats: None,
+            show_histogram: false,
+            histogram: None,
+            filter: None,
+            gpu,
+            curve_channel: filters::curves::Channel::Rgb,
+            batch_options: Default::default(),
+            export: Default::default(),
+            jobs: Vec::new(),
+            color_report: None,
+            posterize: Default::default(),
+            import_alpha: Default::default(),
+            document_alpha: Default::default(),
+            document_overlays: Default::default(),
+            new_image: Default::default(),
+            clipboard: None,
+            batch: None,
+            watermark: Default::default(),
+            post_export_command: String::new(),
+            hook_log: Default::default(),
+            autosave: Default::default(),
+            backups: Default::default(),
+            backup_versions: None,
+            console_visible: false,
+            console_level: log::LevelFilter::Info,
+            slow_updates: (None, 0),
+            panels: preset.panels,
+            detached: Vec::new(),
+            layer_list: (Vec::new(), 0),
+            note_list: (Vec::new(), None),
+            document_size: (0, 0),
+            focused_editor: None,
+            documents: Vec::new(),
+            smooth_sampling: preset.smooth_sampling,
+            pixel_grid: Default::default(),
+            icon_preview: false,
+            expanded_size,
+            config,
         },
+    };
+    if let Some(tools) = tools {
+        tools.apply(&mut model.global_state);
+    }
+    // Documents open at the zoom the editor was last left at
+    for window in model.windows.values_mut() {
+        if let WindowType::Editor(_, state) = &mut window.widget_ids {
+            state.view = state.view.with_zoom(model.global_state.scale);
+        }
     }
+    model
 }
 
-fn raw_window_event(app: &App, model: &mut Model, event: &ui::RawWindowEvent, id: WindowId) {
-    model.windows.get_mut(&id).map(|window| {
-        match &mut window.widget_ids {
-            WindowType::Editor(_, state) => match &event {
-                ui::RawWindowEvent::MouseWheel { delta, .. } => match delta {
-                    MouseScrollDelta::PixelDelta(d) => {
-                        model.global_state.scale = (model.global_state.scale
-                            + d.y as f32 / 10.0 * model.global_state.scale)
-                            .clamp(1.0, 100.0);
-                    }
-                    MouseScrollDelta::LineDelta(_, y) => {
-                        model.global_state.scale = (model.global_state.scale
-                            + *y as f32 / 10.0 * model.global_state.scale)
-                            .clamp(1.0, 100.0);
-                    }
-                },
-                ui::RawWindowEvent::MouseInput {
-                    button: nannou::event::MouseButton::Left,
-                    state: bstate,
-                    ..
-                } => {
-                    state.selected = match bstate {
-                        nannou::event::ElementState::Pressed => true,
-                        nannou::event::ElementState::Released => false,
-                    };
-                    model.global_state.last_mouse = None;
-                    state.offset = translate_mouse_center(app, state.rect);
-                }
-                ui::RawWindowEvent::CursorMoved { .. } => match model.global_state.mode {
+/// Size of the Workbench when collapsed to its tool strip.
+const COMPACT_SIZE: (u32, u32) = (560, 50);
+
+/// Applies `config.always_on_top` and `config.compact` to the Workbench window. `expanded_size`
+/// keeps the full size while the Workbench is collapsed.
+fn update_workbench_window(
+    app: &App,
+    windows: &HashMap<WindowId, Window>,
+    config: &config::Config,
+    expanded_size: &mut Option<(u32, u32)>,
+) {
+    let window = windows
+        .values()
+        .find(|w| matches!(w.widget_ids, WindowType::Workbench(_, _)))
+        .and_then(|w| app.window(w.id));
+    let window = match window {
+        Some(window) => window,
+        None => return,
+    };
+
+    window.set_always_on_top(config.always_on_top);
+    if config.compact {
+        if expanded_size.is_none() {
+            *expanded_size = Some(window.inner_size_pixels());
+        }
+        window.set_inner_size_pixels(COMPACT_SIZE.0, COMPACT_SIZE.1);
+    } else if let Some((w, h)) = expanded_size.take() {
+        window.set_inner_size_pixels(w, h);
+    }
+}
+
+/// Remembers window placements and the brush and zoom for the next launch.
+fn exit(app: &App, mut model: Model) {
+    let global = &mut model.global_state;
+    global.config.brush_size = global.brush_size;
+    global.config.brush_color = global.brush_color;
+    global.config.zoom = global.scale;
+
+    for window in model.windows.values() {
+        remember_placement(app, window, &mut model.global_state);
+    }
+
+    if let Err(e) = model.global_state.config.save() {
+        log::warn!("Failed to save config: {}", e);
+    }
+}
+
+/// Records where `window` is in the config. Done as windows move and close, since windows closed
+/// before the app exits are gone by the time `exit` runs.
+fn remember_placement(app: &App, window: &Window, global: &mut GlobalState) {
+    if let Some(mut placement) = app.window(window.id).and_then(|w| placement::capture(&w)) {
+        // A collapsed Workbench is remembered at its full size and collapsed again on startup
+        if let (WindowType::Workbench(_, _), Some((w, h))) =
+            (&window.widget_ids, global.expanded_size)
+        {
+            placement.width = w;
+            placement.height = h;
+        }
+        global.config.windows.insert(window.role(), placement);
+    }
+}
+
+fn raw_window_event(app: &App, model: &mut Model, event: &ui::RawWindowEvent, id: WindowId) {
+    if let ui::RawWindowEvent::Moved(_)
+    | ui::RawWindowEvent::Resized(_)
+    | ui::RawWindowEvent::CloseRequested = event
+    {
+        if let Some(window) = model.windows.get(&id) {
+            remember_placement(app, window, &mut model.global_state);
+        }
+    }
+    model.windows.get_mut(&id).map(|window| {
+        match &mut window.widget_ids {
+            WindowType::Editor(_, state) => match &event {
+                ui::RawWindowEvent::Focused(true) => model.global_state.focused_editor = Some(id),
+                ui::RawWindowEvent::CloseRequested => {
+                    model.global_state.commands.push(Command::CloseDocument(id))
+                }
+                // Pens are reported as touches. The cursor still follows them as a mouse, so only
+                // the pressure is taken from here.
+                ui::RawWindowEvent::Touch(touch) => {
+                    model.global_state.pressure = match touch.phase {
+                        TouchPhase::Started | TouchPhase::Moved => {
+                            touch.force.map(|force| force.normalized() as f32)
+                        }
+                        TouchPhase::Ended | TouchPhase::Cancelled => None,
+                    };
+                }
+                ui::RawWindowEvent::MouseWheel { delta, .. } => {
+                    let steps = match delta {
+                        MouseScrollDelta::PixelDelta(d) => d.y as f32 / 10.0,
+                        MouseScrollDelta::LineDelta(_, y) => *y,
+                    };
+                    let action = model.global_state.config.scroll.action(app.keys.mods);
+                    // Scrolling takes over from a zoom transition
+                    state.zoom = None;
+                    match action {
+                        input::ScrollAction::None => (),
+                        input::ScrollAction::PanVertical => {
+                            state.view = state.view.panned(Vec2::new(0.0, -steps * PAN_STEP));
+                        }
+                        input::ScrollAction::PanHorizontal => {
+                            state.view = state.view.panned(Vec2::new(steps * PAN_STEP, 0.0));
+                        }
+                        input::ScrollAction::Zoom => {
+                            let old = state.view.zoom();
+                            let scale = (old + steps / 10.0 * old)
+                                .clamp(viewport::MIN_ZOOM, viewport::MAX_ZOOM);
+                            // Keep the canvas point under the cursor where it is
+                            state.view = state.view.zoomed(scale, app.mouse.position());
+                        }
+                        input::ScrollAction::BrushSize => {
+                            adjust_brush_size(&mut model.global_state, steps)
+                        }
+                    }
+                }
+                // Panning works in every mode: drag with the button bound to it (the middle one
+                // unless rebound), or hold Space and drag
+                ui::RawWindowEvent::MouseInput {
+                    button,
+                    state: bstate,
+                    ..
+                } if model.global_state.config.mouse.action(*button) == input::MouseAction::Pan
+                    || (*button == nannou::event::MouseButton::Left
+                        && match bstate {
+                            nannou::event::ElementState::Pressed => {
+                                app.keys.down.contains(&Key::Space)
+                            }
+                            nannou::event::ElementState::Released => state.pan.is_some(),
+                        }) =>
+                {
+                    state.pan = match bstate {
+                        nannou::event::ElementState::Pressed => Some(app.mouse.position()),
+                        nannou::event::ElementState::Released => None,
+                    };
+                }
+                // Guides are dragged out of the rulers, and moved with the Move tool
+                ui::RawWindowEvent::MouseInput {
+                    button: nannou::event::MouseButton::Left,
+                    state: nannou::event::ElementState::Pressed,
+                    ..
+                } if guide_grab(app, id, state, &model.global_state).is_some() => {
+                    if let Some((index, guide)) = guide_grab(app, id, state, &model.global_state) {
+                        if let Some(index) = index {
+                            state.guides.remove(index);
+                        }
+                        state.dragged_guide = Some(guide);
+                    }
+                }
+                // Vanishing points are moved with the Move tool while the grid is shown
+                ui::RawWindowEvent::MouseInput {
+                    button: nannou::event::MouseButton::Left,
+                    state: nannou::event::ElementState::Pressed,
+                    ..
+                } if vanishing_point_grab(app, state, &model.global_state).is_some() => {
+                    state.dragged_vanishing_point =
+                        vanishing_point_grab(app, state, &model.global_state);
+                }
+                ui::RawWindowEvent::MouseInput {
+                    button: nannou::event::MouseButton::Left,
+                    state: nannou::event::ElementState::Released,
+                    ..
+                } if state.dragged_vanishing_point.is_some() => {
+                    state.dragged_vanishing_point = None;
+                }
+                ui::RawWindowEvent::MouseInput {
+                    button: nannou::event::MouseButton::Left,
+                    state: nannou::event::ElementState::Released,
+                    ..
+                } if state.dragged_guide.is_some() => {
+                    let guide = state.dragged_guide.take().unwrap();
+                    let pos = canvas_position(app, state);
+                    let over_ruler = app
+                        .window(id)
+                        .and_then(|w| ruler_at(w.rect(), app.mouse.position()))
+                        .is_some();
+                    let (value, size) = match guide.orientation {
+                        guides::Orientation::Vertical => (pos.x, state.layers.width()),
+                        guides::Orientation::Horizontal => (pos.y, state.layers.height()),
+                    };
+                    // Dropping a guide back on a ruler or off the canvas removes it
+                    if !over_ruler && value >= 0.0 && value <= size as f32 {
+                        state.guides.push(guide);
+                    }
+                }
+                ui::RawWindowEvent::MouseInput {
+                    button,
+                    state: bstate,
+                    ..
+                } if *button != nannou::event::MouseButton::Left => {
+                    let pressed = *bstate == nannou::event::ElementState::Pressed;
+                    match model.global_state.config.mouse.action(*button) {
+                        input::MouseAction::Undo if pressed => state.undo(),
+                        input::MouseAction::Eyedropper => {
+                            state.picking = pressed;
+                            if pressed {
+                                let pos = canvas_position(app, state);
+                                pick_color(&mut model.global_state, state, pos);
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+                ui::RawWindowEvent::MouseInput {
+                    button: nannou::event::MouseButton::Left,
+                    state: bstate,
+                    ..
+                } => {
+                    let pressed = *bstate == nannou::event::ElementState::Pressed;
+                    canvas_button(app, &mut model.global_state, state, pressed);
+                }
+                ui::RawWindowEvent::CursorMoved { .. } if state.dragged_guide.is_some() => {
+                    let pos = canvas_position(app, state);
+                    if let Some(guide) = &mut state.dragged_guide {
+                        guide.position = match guide.orientation {
+                            guides::Orientation::Vertical => pos.x,
+                            guides::Orientation::Horizontal => pos.y,
+                        }
+                        .round()
+                        .max(0.0) as u32;
+                    }
+                }
+                ui::RawWindowEvent::CursorMoved { .. }
+                    if state.dragged_vanishing_point.is_some() =>
+                {
+                    let pos = canvas_position(app, state);
+                    if let Some(index) = state.dragged_vanishing_point {
+                        model
+                            .global_state
+                            .perspective
+                            .move_point(index, pos, canvas_size(state));
+                    }
+                }
+                ui::RawWindowEvent::CursorMoved { .. } if state.picking => {
+                    let pos = canvas_position(app, state);
+                    pick_color(&mut model.global_state, state, pos);
+                }
+                ui::RawWindowEvent::CursorMoved { .. } if state.pan.is_some() => {
+                    let mouse = app.mouse.position();
+                    if let Some(last) = state.pan.replace(mouse) {
+                        state.view = state.view.panned(mouse - last);
+                    }
+                }
+                ui::RawWindowEvent::CursorMoved { .. } => match model.global_state.mode {
                     Mode::Move => {
                         if state.selected {
-                            state.rect = Rect::from_xy_wh(
-                                Point2::new(
-                                    app.mouse.position().x as _,
-                                    app.mouse.position().y as _,
-                                ) - state.offset,
-                                state.rect.wh(),
-                            );
+                            let center = app.mouse.position() - state.offset;
+                            state.view = state.view.centered_at(center);
                         }
                     }
-                    Mode::Paint => {
-                        if state.rect.contains(app.mouse.position()) && state.selected {
-                            let mousef = (app.mouse.position() - state.rect.xy())
-                                / model.global_state.scale
-                                + Vec2::new(state.pixels.width() as _, state.pixels.height() as _)
-                                    / 2.0;
-                            let mousef =
-                                Vec2::new(mousef.x, state.pixels.height() as f32 - mousef.y);
+                    Mode::Paint | Mode::Erase | Mode::Smudge | Mode::CloneStamp => {
+                        // The clone stamp has nothing to copy until a source is set
+                        let ready = model.global_state.mode != Mode::CloneStamp
+                            || model.global_state.clone_offset.is_some();
+                        if state.view.contains(app.mouse.position()) && state.selected && ready {
+                            let mousef = canvas_position(app, state);
 
+                            let (width, height) = (state.layers.width(), state.layers.height());
                             let mouse = Vec2::new(
-                                mousef.x.round().min(255.0) as _,
-                                mousef.y.round().min(255.0) as _,
+                                mousef.x.clamp(0.0, width as f32 - 1.0),
+                                mousef.y.clamp(0.0, height as f32 - 1.0),
                             );
-                            // state.pixels.put_pixel(
-                            //     mouse.0,
-                            //     mouse.1,
-                            //     nannou::image::Rgba::<u8>::from_channels(0, 0, 0, 255),
-                            // );
-
-                            match model.global_state.last_mouse {
-                                Some(m) => {
-                                    let size = model.global_state.brush_size.round() as i32;
-                                    let rad = (model.global_state.brush_size / 2.0).round() as i32;
-
-                                    for (x, y) in Bresenham::<i32>::new(
-                                        (m.x as _, m.y as _),
-                                        (mouse.x as _, mouse.y as _),
-                                    ) {
-                                        for i in -rad * 2..rad * 2 {
-                                            for j in -rad * 2..rad * 2 {
-                                                let dist = mousef.distance(Vec2::new(
-                                                    (i + x) as _,
-                                                    (y + j) as _,
-                                                ));
-
-                                                let opac = ((255.0)
-                                                    * (-1.0
-                                                        / (model.global_state.brush_size
-                                                            * model.global_state.brush_size)
-                                                        * (dist * dist) * 2.0
-                                                        + 1.0))
-                                                    .max(0.0);
-                                                let mut pix = state
-                                                    .pixels
-                                                    .get_pixel((x + i) as u32, (y + j) as u32);
-                                                pix.blend(
-                                                    &nannou::image::Rgba::<u8>::from_channels(
-                                                        0, 0, 0, opac as u8,
-                                                    ),
-                                                );
-
-                                                state.pixels.put_pixel(
-                                                    (x + i) as u32,
-                                                    (y + j) as u32,
-                                                    pix,
-                                                );
-                                            }
-                                        }
-                                    }
-
-                                    // for ((x, y), value) in XiaolinWu::<f32, i32>::new(
-                                    //     (m.x, m.y),
-                                    //     (mouse.0 as _, mouse.1 as _),
-                                    // ) {
-                                    //     for i in -size / 2..size / 2 {
-                                    //         for j in -size / 2..size / 2 {
-                                    //             state.pixels.put_pixel(
-                                    //                 (x + i) as u32,
-                                    //                 (y + j) as u32,
-                                    //                 nannou::image::Rgba::<u8>::from_channels(
-                                    //                     0,
-                                    //                     0,
-                                    //                     0,
-                                    //                     (255.0 * value) as _,
-                                    //                 ),
-                                    //             )
-                                    //         }
-                                    //     }
-                                    // }
-                                }
-                                None => (),
-                            }
 
+                            let global = &model.global_state;
+                            let dab = brush::Dab {
+                                size: global.pressure_brush_size(),
+                                color: global.brush_color,
+                                opacity: global.pressure_brush_opacity(),
+                                flow: global.brush_flow,
+                                blend: global.dab_blend(),
+                                spacing: global.brush_spacing,
+                                symmetry: global.symmetry,
+                            };
+                            // Painted on the worker, and merged into the layer by `update`
+                            if let Some(painter) = state.painters.back() {
+                                painter.send(painter::Sample {
+                                    pos: mouse,
+                                    dab,
+                                    pressure: global.pressure.unwrap_or(1.0),
+                                });
+                            }
                             model.global_state.last_mouse = Some(mousef);
-                            // for angle in (0.0 .. 2.0 * f32::PI()) {
-
-                            // }
-
-                            // let mut angle = 0.0;
-
-                            // for r in 0..(model.global_state.brush_size as u32) {
-                            //     let r = r as f32;
-                            //     while angle < 2.0 * f32::PI() {
-                            //         state.pixels.put_pixel(
-                            //             ((mouse.0 as f32 + (r * angle.cos()).round()) as u32)
-                            //                 .min(255),
-                            //             ((mouse.1 as f32 + (r * angle.sin()).round()) as u32)
-                            //                 .min(255),
-                            //             nannou::image::Rgba::<u8>::from_channels(0, 0, 0, 0),
-                            //         );
-                            //         angle += 0.00002;
-                            //     }
-                            //     angle = 0.0;
-                            // }
                         }
                     }
+                    Mode::Select => {
+                        if let Some(anchor) = state.selection_anchor {
+                            let pos = snapped_canvas_position(app, state, &model.global_state);
+                            state.selection = selection::Selection::from_corners(
+                                anchor,
+                                pos,
+                                state.layers.width(),
+                                state.layers.height(),
+                            );
+                        }
+                    }
+                    Mode::Scissors => {
+                        let pos = canvas_position(app, state);
+                        if let Some(scissors) = &mut state.scissors {
+                            scissors.trace(pos);
+                        }
+                    }
+                    Mode::Cutout if state.selected => {
+                        let pos = canvas_position(app, state);
+                        state.scribbles.extend(pos);
+                    }
+                    Mode::Stamp => {
+                        if let (true, Some(stamp), Some(last)) = (
+                            state.selected,
+                            &model.global_state.stamp,
+                            model.global_state.last_mouse,
+                        ) {
+                            let pos = canvas_position(app, state);
+                            if pos.distance(last) >= stamp.spacing() {
+                                stamp.place(
+                                    &mut canvas::Canvas::new(
+                                        &mut state.layers.active_mut().pixels,
+                                        state.selection.as_ref(),
+                                    ),
+                                    pos.x as i32,
+                                    pos.y as i32,
+                                    &model.global_state.stamp_jitter,
+                                    &mut rand::thread_rng(),
+                                );
+                                let (w, h) = stamp.image.dimensions();
+                                state.mark_dirty(pos.x as i32, pos.y as i32, w.max(h) as i32);
+                                model.global_state.last_mouse = Some(pos);
+                            }
+                        }
+                    }
+                    Mode::Heal => {
+                        if let Some((offset, snapshot)) = &state.heal_stroke {
+                            let pos = canvas_position(app, state);
+                            heal_dab(
+                                &mut canvas::Canvas::new(
+                                    &mut state.layers.active_mut().pixels,
+                                    state.selection.as_ref(),
+                                ),
+                                snapshot,
+                                pos + *offset,
+                                pos,
+                                model.global_state.brush_size,
+                            );
+                            state.mark_dirty(
+                                pos.x as i32,
+                                pos.y as i32,
+                                model.global_state.brush_size.ceil() as i32,
+                            );
+                        }
+                    }
+                    Mode::Eyedropper => {
+                        if state.selected {
+                            let pos = canvas_position(app, state);
+                            pick_color(&mut model.global_state, state, pos);
+                        }
+                    }
+                    Mode::TileStamp => {
+                        if state.selected {
+                            let pos = canvas_position(app, state);
+                            tile_stamp(&mut model.global_state, state, pos);
+                        }
+                    }
+                    // Shapes, gradients and measurements are previewed in `view` until the button
+                    // is released
+                    Mode::RedEye
+                    | Mode::Fill
+                    | Mode::Shape(_)
+                    | Mode::Gradient
+                    | Mode::Measure
+                    | Mode::Note => (),
+                    // Only the overlay changes until the transform is committed
+                    Mode::Transform => {
+                        let pos = canvas_position(app, state);
+                        if let (Some(transform), Some((handle, from, start))) =
+                            (&mut state.transform, &state.transform_drag)
+                        {
+                            transform.drag(start, *handle, *from, pos, app.keys.mods.shift());
+                        }
+                    }
+                },
+                ui::RawWindowEvent::KeyboardInput {
+                    input:
+                        nannou::winit::event::KeyboardInput {
+                            state: nannou::event::ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } if app.keys.mods.ctrl() => match key {
+                    Key::Z => state.undo(),
+                    Key::Key0 => model
+                        .global_state
+                        .commands
+                        .push(Command::Zoom(zoom::Target::Fit)),
+                    Key::Key1 => model
+                        .global_state
+                        .commands
+                        .push(Command::Zoom(zoom::Target::ActualSize)),
+                    Key::Key2 => model
+                        .global_state
+                        .commands
+                        .push(Command::Zoom(zoom::Target::Selection)),
+                    Key::LBracket => model
+                        .global_state
+                        .commands
+                        .push(Command::Zoom(zoom::Target::Rotate(VIEW_TURN))),
+                    Key::RBracket => model
+                        .global_state
+                        .commands
+                        .push(Command::Zoom(zoom::Target::Rotate(-VIEW_TURN))),
+                    Key::D => {
+                        state.selection = None;
+                        state.filter_preview = None;
+                    }
+                    Key::C => model.global_state.commands.push(Command::Copy),
+                    Key::V => model
+                        .global_state
+                        .commands
+                        .push(Command::Paste(clipboard::PasteTransform::None)),
+                    Key::O => {
+                        if let Some(path) = pick_image_file() {
+                            model.global_state.commands.push(Command::Open(path));
+                        }
+                    }
+                    Key::S => {
+                        let path = match (&state.path, app.keys.mods.shift()) {
+                            (Some(path), false) => Some(path.clone()),
+                            _ => pick_save_file(),
+                        };
+                        if let Some(path) = path {
+                            model.global_state.commands.push(Command::Save(path));
+                        }
+                    }
+                    _ => (),
                 },
+                ui::RawWindowEvent::KeyboardInput {
+                    input:
+                        nannou::winit::event::KeyboardInput {
+                            state: nannou::event::ElementState::Pressed,
+                            virtual_keycode:
+                                Some(key @ (Key::Left | Key::Right | Key::Up | Key::Down)),
+                            ..
+                        },
+                    ..
+                } if app.keys.mods.alt() => {
+                    // Alt+arrows nudge the layer (or selection) with wrap-around, for seamless
+                    // tiles. Shift moves a whole tile at a time.
+                    let step = if app.keys.mods.shift() {
+                        model.global_state.tile_size
+                    } else {
+                        model.global_state.wrap_step
+                    } as i32;
+                    let (dx, dy) = match key {
+                        Key::Left => (-step, 0),
+                        Key::Right => (step, 0),
+                        Key::Up => (0, -step),
+                        _ => (0, step),
+                    };
+                    if state.stroking() {
+                        log::warn!("Still finishing the last stroke");
+                    } else {
+                        state.checkpoint();
+                        let selection = state.selection;
+                        let layer = state.layers.active_mut();
+                        tiles::wrap_shift(&mut layer.pixels, dx, dy, selection.as_ref());
+                        state.filter_preview = None;
+                        state.invalidate();
+                    }
+                }
+                ui::RawWindowEvent::KeyboardInput {
+                    input:
+                        nannou::winit::event::KeyboardInput {
+                            state: nannou::event::ElementState::Pressed,
+                            virtual_keycode: Some(key),
+                            ..
+                        },
+                    ..
+                } if input::digit_percent(*key).is_some() => {
+                    // Like other editors: "5" is 50%, "0" is 100%, Shift sets flow instead
+                    let value = input::digit_percent(*key).unwrap();
+                    if app.keys.mods.shift() {
+                        model.global_state.brush_flow = value;
+                    } else {
+                        model.global_state.brush_opacity = value;
+                    }
+                    model.global_state.brush_hud = Some(std::time::Instant::now());
+                }
+                ui::RawWindowEvent::KeyboardInput {
+                    input:
+                        nannou::winit::event::KeyboardInput {
+                            state: nannou::event::ElementState::Pressed,
+                            virtual_keycode: Some(key @ (Key::Return | Key::Escape)),
+                            ..
+                        },
+                    ..
+                } if state.transform.is_some() => {
+                    model.global_state.commands.push(if *key == Key::Return {
+                        Command::CommitTransform
+                    } else {
+                        Command::CancelTransform
+                    });
+                }
+                ui::RawWindowEvent::KeyboardInput {
+                    input:
+                        nannou::winit::event::KeyboardInput {
+                            state: nannou::event::ElementState::Pressed,
+                            virtual_keycode: Some(key @ (Key::LBracket | Key::RBracket)),
+                            ..
+                        },
+                    ..
+                } => {
+                    let steps = if *key == Key::LBracket { -1.0 } else { 1.0 };
+                    adjust_brush_size(&mut model.global_state, steps);
+                }
                 _ => (),
             },
             WindowType::Workbench(_, _) => {}
+            // Without a title bar the window is moved by dragging anywhere and closed with Escape
+            WindowType::Output(_) => match event {
+                ui::RawWindowEvent::MouseInput {
+                    state: nannou::event::ElementState::Pressed,
+                    button: nannou::event::MouseButton::Left,
+                    ..
+                } => {
+                    if let Some(window) = app.window(id) {
+                        let _ = window.winit_window().drag_window();
+                    }
+                }
+                ui::RawWindowEvent::KeyboardInput {
+                    input:
+                        nannou::winit::event::KeyboardInput {
+                            state: nannou::event::ElementState::Pressed,
+                            virtual_keycode: Some(Key::Escape),
+                            ..
+                        },
+                    ..
+                }
+                | ui::RawWindowEvent::CloseRequested => {
+                    model.global_state.commands.push(Command::CloseOutput(id));
+                }
+                _ => (),
+            },
+            WindowType::Proof(_) => {
+                if let ui::RawWindowEvent::CloseRequested = event {
+                    model.global_state.commands.push(Command::CloseProof(id));
+                }
+            }
+            WindowType::Panel(_, _) => {
+                if let ui::RawWindowEvent::CloseRequested = event {
+                    model.global_state.commands.push(Command::DockPanel(id));
+                }
+            }
         }
         window.ui.handle_raw_event(app, event);
         Some(0)
     });
+}
 
-    // match &event {
-    //     ui::RawWindowEvent::CursorMoved { .. } => {
-    //         model.global_state.last_mouse = app.mouse.position();
-    //     }
-    //     _ => (),
-    // }
-}
-
-// fn line(p: &mut DynamicImage, p1: Vec2, p2: Vec2) {
-//     let dx = (p2.x - p1.x).abs();
-//     let sx = if p1.x < p2.x { 1.0 } else { -1.0 };
-//     let dy = -(p2.y - p1.y).abs();
-//     let sy = if p1.y < p2.y { 1.0 } else { -1.0 };
-//     let mut error = dx + dy;
-
-//     let (mut x0, x1) = (p1.x, p2.x);
-//     let (mut y0, y1) = (p1.y, p2.y);
-
-//     loop {
-//         p.put_pixel(
-//             x0 as _,
-//             y0 as _,
-//             nannou::image::Rgba::<u8>::from_channels(0, 0, 0, 255),
-//         );
-
-//         if x0 == x1 && y0 == y1 {
-//             break;
-//         }
-
-//         let e2 = 2.0 * error;
-//         if e2 >= dy {
-//             if x0 == x1 {
-//                 break;
-//             }
-//             error += dy;
-//             x0 += sx;
-//         }
-
-//         if e2 <= dx {
-//             if y0 == y1 {
-//                 break;
-//             }
-//             error += dx;
-//             y0 += sy;
-//         }
-//     }
-// }
-
-fn update(_app: &App, model: &mut Model, _update: Update) {
-    // Calling `set_widgets` allows us to instantiate some widgets.
-    for window in model.windows.values_mut() {
-        let ui = &mut window.ui.set_widgets();
-        match &mut window.widget_ids {
-            WindowType::Editor(_, state) => {
-                state.rect = Rect::from_xy_wh(
-                    state.rect.xy(),
-                    Point2::new(
-                        state.pixels.as_rgba8().unwrap().width() as f32 * model.global_state.scale,
-                        state.pixels.as_rgba8().unwrap().height() as f32 * model.global_state.scale,
-                    ),
-                );
-            }
-            WindowType::Workbench(ids, _) => {
-                fn slider(val: f32, min: f32, max: f32) -> widget::Slider<'static, f32> {
-                    widget::Slider::new(val, min, max)
-                        .w_h(200.0, 30.0)
-                        .label_font_size(15)
-                        .rgb(0.3, 0.3, 0.3)
-                        .label_rgb(1.0, 1.0, 1.0)
-                        .border(0.0)
-                }
-
-                if let Some(value) = slider(model.global_state.scale, 1.0, 100.0)
-                    .top_left_with_margin(20.0)
-                    .label("Scale")
-                    .set(ids.scale, ui)
-                {
-                    model.global_state.scale = value;
-                }
 
-                if let Some(value) = slider(model.global_state.brush_size, 1.0, 100.0)
-                    .down(10.0)
-                    .label("Brush Size")
-                    .set(ids.brush_size, ui)
-                {
-                    model.global_state.brush_size = value;
-                }
+/// The document Workbench commands apply to: the focused editor, or the first open document if
+/// none has been focused yet.
+fn editor_id(windows: &HashMap<WindowId, Window>, focused: Option<WindowId>) -> Option<WindowId> {
+    let is_editor = |window: &Window| matches!(window.widget_ids, WindowType::Editor(..));
+    match focused {
+        Some(id) if windows.get(&id).map_or(false, is_editor) => Some(id),
+        // The first document in the Documents panel, rather than whichever the map yields first
+        _ => windows
+            .iter()
+            .filter(|(_, window)| is_editor(window))
+            .map(|(id, _)| *id)
+            .min(),
+    }
+}
+
+/// Whether any document still has a stroke landing.
+fn strokes_landing(windows: &HashMap<WindowId, Window>) -> bool {
+    windows.values().any(|window| match &window.widget_ids {
+        WindowType::Editor(_, state) => state.stroking(),
+        _ => false,
+    })
+}
 
-                widget::Text::new(format!("{}", model.global_state.brush_size).as_str())
-                    .right_from(ids.brush_size, 10.0)
-                    .set(ids.brush_size_labels, ui);
+/// The state of the document Workbench commands apply to, see `editor_id`.
+fn editor_state(
+    windows: &mut HashMap<WindowId, Window>,
+    focused: Option<WindowId>,
+) -> Option<&mut EditorState> {
+    let id = editor_id(windows, focused)?;
+    match &mut windows.get_mut(&id)?.widget_ids {
+        WindowType::Editor(_, state) => Some(state),
+        _ => None,
+    }
+}
 
-                for _click in widget::Button::new()
-                    .down_from(ids.brush_size, 10.0)
-                    .label("Move")
-                    .set(ids.move_mode_button, ui)
-                {
-                    model.global_state.mode = Mode::Move;
+fn run_command(app: &App, model: &mut Model, command: Command) {
+    match command {
+        Command::Open(path) => match Window::open_editor(
+            app,
+            &path,
+            &model.global_state.raw,
+            model.global_state.import_alpha,
+        ) {
+            Ok((window, tools)) => {
+                if let Some(tools) = tools {
+                    tools.apply(&mut model.global_state);
                 }
-
-                for _click in widget::Button::new()
-                    .label("Paint")
-                    .set(ids.paint_mode_button, ui)
+                model.global_state.config.add_recent(&path);
+                model.global_state.focused_editor = Some(window.id);
+                model.windows.insert(window.id, window);
+            }
+            Err(e) => log::error!("Failed to open {}: {}", path.display(), e),
+        },
+        Command::Layer(op) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                let edit = !matches!(op, layers::LayerOp::Select(_));
+                if edit {
+                    state.checkpoint();
+                }
+                state.layers.apply(op);
+                state.clear_previews();
+                if edit {
+                    state.invalidate();
+                }
+            }
+        }
+        Command::Note(op) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                if !matches!(op, notes::NoteOp::Select(_)) {
+                    state.settings_changed();
+                }
+                op.apply(&mut state.notes, &mut state.selected_note);
+            }
+        }
+        Command::DetachPanel(panel, drop) => {
+            let window = Window::new_panel(app, panel);
+            window.restore_placement(app, &model.global_state.config);
+            let workbench = model
+                .windows
+                .values()
+                .find(|w| matches!(w.widget_ids, WindowType::Workbench(_, _)))
+                .and_then(|w| app.window(w.id));
+            if let (Some([x, y]), Some(workbench), Some(detached)) =
+                (drop, workbench, app.window(window.id))
+            {
+                let workbench = workbench.winit_window();
+                if let Ok(origin) = workbench.inner_position() {
+                    let (size, scale) = (workbench.inner_size(), workbench.scale_factor());
+                    let x = origin.x as f64 + size.width as f64 / 2.0 + x * scale;
+                    let y = origin.y as f64 + size.height as f64 / 2.0 - y * scale;
+                    detached
+                        .winit_window()
+                        .set_outer_position(nannou::winit::dpi::PhysicalPosition::new(x, y));
+                }
+            }
+            model.windows.insert(window.id, window);
+            model.global_state.detached.push(panel);
+        }
+        Command::UpdateWorkbenchWindow => {
+            let state = &mut model.global_state;
+            update_workbench_window(app, &model.windows, &state.config, &mut state.expanded_size);
+            if let Err(e) = state.config.save() {
+                log::warn!("Failed to save config: {}", e);
+            }
+        }
+        Command::DockPanel(id) => {
+            if let Some(Window {
+                widget_ids: WindowType::Panel(_, panel),
+                ..
+            }) = close_window(app, model, id)
+            {
+                model.global_state.detached.retain(|p| *p != panel);
+            }
+        }
+        Command::Save(path) => {
+            let label = format!("Saving {}", file_label(&path));
+            start_job(model, &label, |global, state| {
+                let layers = state.layers.clone();
+                let alpha = state.alpha_mode;
+                let metadata = project::Metadata {
+                    selection: state.selection,
+                    dpi: state.dpi,
+                    guides: state.guides.clone(),
+                    overlays: state.overlays,
+                    notes: state.notes.clone(),
+                    alpha,
+                    tools: project::ToolSettings::capture(global),
+                };
+                Some(move |progress: &jobs::Progress| {
+                    let ext = path
+                        .extension()
+                        .and_then(|e| e.to_str())
+                        .map(|e| e.to_lowercase());
+                    let result = match ext.as_deref() {
+                        // SVG keeps the layers apart instead of flattening them
+                        Some("svg") => {
+                            export::svg::export(&layers, &path).map_err(|e| e.to_string())
+                        }
+                        Some("ora") => ora::save(&layers, &path).map_err(|e| e.to_string()),
+                        Some("ied") => {
+                            project::save(&layers, &metadata, &path).map_err(|e| e.to_string())
+                        }
+                        // Documents have no frames yet, so the PDF is a single page
+                        Some("pdf") => export::pdf::export(&[layers.composite()], &path)
+                            .map_err(|e| e.to_string()),
+                        _ => {
+                            let canvas = alpha.export(&layers.composite());
+                            progress.set(0.5);
+                            save_image(&canvas, &path).map_err(|e| e.to_string())
+                        }
+                    };
+                    result.map(|()| jobs::Output::Saved(path))
+                })
+            });
+        }
+        Command::ExportVideo(path) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                // Documents don't have a frame timeline yet, so the canvas is held for one second
+                let count = model.global_state.video.fps.round().max(1.0) as usize;
+                let frames =
+                    vec![export_pixels(&model.global_state, &state.layers.composite()); count];
+                let path = match export_path(state, &path, model.global_state.video.scale) {
+                    Some(path) => path,
+                    None => return,
+                };
+                if let Err(e) =
+                    export::video::export_video(&frames, &path, &model.global_state.video)
                 {
-                    model.global_state.mode = Mode::Paint;
+                    log::error!("Failed to export video to {}: {}", path.display(), e);
+                } else {
+                    post_export(&mut model.global_state, &path);
                 }
-
-                // widget::Tabs::new(&[(ids.move_mode_button, "Move"), (
-                //     ids.paint_mode_button,
-                //     "Paint",
-                // )]);
-                // .set(ids.modes, ui);
             }
         }
-    }
-}
-
-// Draw the state of your `Model` into the given `Frame` here.
-fn view(app: &App, model: &Model, frame: Frame) {
-    // Begin drawing
-    // frame.win
-    model.windows.get(&frame.window_id()).map(|window| {
-        match &window.widget_ids {
-            WindowType::Editor(_, state) => {
-                let draw = app.draw();
-                draw.background().rgb(0.15, 0.15, 0.15);
-
-                let sampler = wgpu::SamplerBuilder::new()
-                    .address_mode(wgpu::AddressMode::ClampToEdge)
-                    .mag_filter(wgpu::FilterMode::Nearest)
-                    .into_descriptor();
-
-                let draw = draw.sampler(sampler);
-
-                let canvas = wgpu::Texture::from_image(app, &state.pixels);
-                draw.texture(&canvas)
-                    .wh(state.rect.wh())
-                    .xy(state.rect.xy());
-
-                draw.ellipse()
-                    .no_fill()
-                    .stroke(LinSrgb::new(0.0, 0.0, 0.0))
-                    .stroke_weight(1.0)
-                    .xy(app.mouse.position())
-                    .w_h(
-                        model.global_state.brush_size * model.global_state.scale,
-                        model.global_state.brush_size * model.global_state.scale,
-                    );
-                // println!("View Editor {:?}", state.rect);
-
-                // Write the result of our drawing to the window's frame.
-                draw.to_frame(app, &frame).unwrap();
-
-                // Draw the state of the `Ui` to the frame.
-                window.ui.draw_to_frame(app, &frame).unwrap();
+        Command::ExportAnimation(path) => {
+            let label = format!("Exporting {}", file_label(&path));
+            start_job(model, &label, |global, state| {
+                let mut path = export_path(state, &path, 1.0)?;
+                if path.extension().is_none() {
+                    path.set_extension("gif");
+                }
+                let delay = global.animation.delay.round() as u32;
+                let mut frames = export::animation::frames(&state.layers, delay);
+                for frame in &mut frames {
+                    frame.image = export_pixels(global, &frame.image);
+                }
+                let options = global.animation.clone();
+                Some(move |progress: &jobs::Progress| {
+                    export::animation::export_animation(&frames, &path, &options, progress)
+                        .map_err(|e| e.to_string())?;
+                    Ok(jobs::Output::Exported(path))
+                })
+            });
+        }
+        Command::ImportSpriteSheet(path) => match Window::open_sprite_sheet(
+            app,
+            &path,
+            &model.global_state.sprite_sheet,
+            model.global_state.import_alpha,
+        ) {
+            Ok(window) => {
+                model.global_state.focused_editor = Some(window.id);
+                model.windows.insert(window.id, window);
+            }
+            Err(e) => log::error!("Failed to import {}: {}", path.display(), e),
+        },
+        Command::ExportSpriteSheet(path) => {
+            let label = format!("Exporting {}", file_label(&path));
+            start_job(model, &label, |global, state| {
+                let path = export_path(state, &path, 1.0)?.with_extension("png");
+                let delay = global.animation.delay.round() as u32;
+                let mut frames = export::animation::frames(&state.layers, delay);
+                for frame in &mut frames {
+                    frame.image = export_pixels(global, &frame.image);
+                }
+                let options = global.sprite_sheet.clone();
+                Some(move |_: &jobs::Progress| {
+                    sprite_sheet::export(&frames, &path, &options).map_err(|e| e.to_string())?;
+                    Ok(jobs::Output::Exported(path))
+                })
+            });
+        }
+        Command::CaptureStamp => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                match &state.selection {
+                    Some(selection) => {
+                        model.global_state.stamp = Some(stamp::Stamp::capture(
+                            &state.layers.active().pixels,
+                            selection,
+                        ));
+                        model.global_state.mode = Mode::Stamp;
+                    }
+                    None => log::warn!("Select a region before using it as a brush"),
+                }
+            }
+        }
+        // Baked across the whole canvas so the texture lines up however it's clipped, then kept
+        // to the selection like a filter
+        Command::BakeNodeGraph => start_job(model, "Baking node graph", |global, state| {
+            let graph = global.node_graph.clone();
+            let (index, selection) = (state.layers.active, state.selection);
+            let pixels = state.layers.active().pixels.clone();
+            Some(move |_: &jobs::Progress| {
+                let baked = graph.bake(pixels.width(), pixels.height());
+                let baked = match selection {
+                    Some(selection) => selection.clip(&pixels, &baked),
+                    None => baked,
+                };
+                Ok(jobs::Output::Layer(index, baked))
+            })
+        }),
+        Command::ApplyFilter => start_job(model, "Applying filter", |global, state| {
+            let filter = global.filter.take()?;
+            let gpu = global.gpu_filters();
+            let (index, selection) = (state.layers.active, state.selection);
+            let pixels = state.layers.active().pixels.clone();
+            Some(move |progress: &jobs::Progress| {
+                let filtered = filter.apply(&pixels, gpu.as_deref());
+                progress.set(0.9);
+                let filtered = match selection {
+                    Some(selection) => selection.clip(&pixels, &filtered),
+                    None => filtered,
+                };
+                Ok(jobs::Output::Layer(index, filtered))
+            })
+        }),
+        Command::CancelFilter => model.global_state.filter = None,
+        Command::AnalyzeColors => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                model.global_state.color_report =
+                    Some(palette::ColorReport::new(&state.layers.composite()));
+            }
+        }
+        Command::Posterize => start_job(model, "Posterizing", |global, state| {
+            let options = global.posterize;
+            global.color_report = None;
+            // One palette for the whole canvas, so layers don't each pick their own
+            let canvas = state.layers.composite();
+            let layers: Vec<DynamicImage> = state
+                .layers
+                .layers
+                .iter()
+                .map(|layer| layer.pixels.clone())
+                .collect();
+            Some(move |progress: &jobs::Progress| {
+                let colors = options
+                    .quantizer
+                    .palette(&canvas, options.colors.round() as usize);
+                progress.set(0.3);
+                let mut reduced = Vec::with_capacity(layers.len());
+                for (i, pixels) in layers.iter().enumerate() {
+                    reduced.push(palette::remap(pixels, &colors));
+                    progress.set(0.3 + 0.7 * (i + 1) as f32 / layers.len() as f32);
+                }
+                Ok(jobs::Output::Layers(reduced))
+            })
+        }),
+        Command::LoadReference(path) => match formats::open(&path) {
+            Ok(reference) => {
+                model.global_state.reference = Some(reference.into());
+                for window in model.windows.values_mut() {
+                    if let WindowType::Editor(_, state) = &mut window.widget_ids {
+                        state.compare_preview = None;
+                    }
+                }
+            }
+            Err(e) => log::error!("Failed to load reference {}: {}", path.display(), e),
+        },
+        Command::LoadLut(path) => match filters::lut::Lut3d::load(&path) {
+            Ok(lut) => {
+                model.global_state.filter =
+                    Some(filters::Filter::Lut(filters::lut::LutGrade::new(lut)))
+            }
+            Err(e) => log::error!("Failed to load LUT {}: {}", path.display(), e),
+        },
+        Command::RunBatch(input, output) => {
+            let options = &model.global_state.batch_options;
+            let mut ops = Vec::new();
+            if options.scale != 1.0 {
+                ops.push(batch::BatchOp::Resize {
+                    scale: options.scale,
+                });
+            }
+            if let (true, Some(filter)) = (options.use_filter, &model.global_state.filter) {
+                ops.push(batch::BatchOp::Filter(filter.clone()));
+            }
+            if options.colors >= 2.0 {
+                ops.push(batch::BatchOp::Quantize {
+                    colors: options.colors as usize,
+                });
             }
-            WindowType::Workbench(_, _) => {
-                let draw = app.draw();
-                draw.background().rgb(0.15, 0.15, 0.15);
-                draw.to_frame(app, &frame).unwrap();
-                // println!("View workbench");
-
-                // println!("View Workbench");
 
-                window.ui.draw_to_frame(app, &frame).unwrap();
+            match batch::run(&input, output, ops) {
+                Ok(batch) => model.global_state.batch = Some(batch),
+                Err(e) => log::error!("Failed to start batch on {}: {}", input.display(), e),
             }
         }
-        Some(0)
-    });
+        Command::LoadWatermark(path) => match nannou::image::open(&path) {
+            Ok(image) => model.global_state.watermark.image = Some(image),
+            Err(e) => log::error!("Failed to load watermark {}: {}", path.display(), e),
+        },
+        Command::NewImage => {
+            let options = &model.global_state.new_image;
+            let window = Window::new_image(
+                app,
+                options.width as u32,
+                options.height as u32,
+                options.dpi,
+                options.background.color(model.global_state.brush_color),
+            );
+            model.global_state.focused_editor = Some(window.id);
+            model.windows.insert(window.id, window);
+        }
+        Command::ReprocessRaw => start_job(model, "Developing RAW", |global, state| {
+            let path = match &state.path {
+                Some(path) if raw::is_raw(path) => path.clone(),
+                _ => {
+                    log::warn!("The document wasn't opened from a camera RAW file");
+                    return None;
+                }
+            };
+            let options = global.raw.clone();
+            let (width, height) = (state.layers.width(), state.layers.height());
+            Some(move |progress: &jobs::Progress| {
+                let pixels = raw::decode(&path, &options).map_err(|e| e.to_string())?;
+                progress.set(0.8);
+                // The document may have been scaled since it was opened
+                let pixels = if pixels.dimensions() == (width, height) {
+                    pixels
+                } else {
+                    pixels.resize_exact(width, height, FilterType::Lanczos3)
+                };
+                let name = format!("RAW {:+.1} EV", options.exposure);
+                Ok(jobs::Output::NewLayer(
+                    name,
+                    DynamicImage::ImageRgba8(pixels.to_rgba8()),
+                ))
+            })
+        }),
+        Command::ScaleImage | Command::ResizeCanvas => {
+            let scale = matches!(command, Command::ScaleImage);
+            let label = if scale {
+                "Scaling image"
+            } else {
+                "Resizing canvas"
+            };
+            start_job(model, label, |global, state| {
+                let options = &global.resize;
+                let (width, height) = (options.width as u32, options.height as u32);
+                let (resample, anchor) = (options.resample, options.anchor);
+                let layers: Vec<DynamicImage> = state
+                    .layers
+                    .layers
+                    .iter()
+                    .map(|layer| layer.pixels.clone())
+                    .collect();
+                Some(move |progress: &jobs::Progress| {
+                    let mut resized = Vec::with_capacity(layers.len());
+                    for (i, pixels) in layers.iter().enumerate() {
+                        resized.push(if scale {
+                            resize::scale(pixels, width, height, resample)
+                        } else {
+                            resize::resize_canvas(pixels, width, height, anchor)
+                        });
+                        progress.set((i + 1) as f32 / layers.len() as f32);
+                    }
+                    Ok(jobs::Output::Layers(resized))
+                })
+            });
+        }
+        Command::Print => {
+            start_job(model, "Printing", |global, state| {
+                let image = state.layers.composite();
+                let (options, smooth) = (global.print.clone(), global.smooth_sampling);
+                Some(move |progress: &jobs::Progress| {
+                    print::print(&image, &options, smooth, progress).map_err(|e| e.to_string())?;
+                    Ok(jobs::Output::Printed)
+                })
+            });
+        }
+        Command::FocusDocument(id) => {
+            if let Some(window) = app.window(id) {
+                window.set_minimized(false);
+                model.global_state.focused_editor = Some(id);
+            }
+        }
+        Command::BrowseBackups => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                match &state.path {
+                    Some(path) if path.extension().map_or(false, |e| e == "ied") => {
+                        let versions = backup::versions(path);
+                        if versions.is_empty() {
+                            log::info!("No backups of {} yet", path.display());
+                        }
+                        model.global_state.backup_versions = Some((path.clone(), versions));
+                    }
+                    _ => log::warn!("Backups are only kept of documents saved as projects (.ied)"),
+                }
+            }
+        }
+        Command::RestoreBackup(path) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                match project::open(&path) {
+                    Ok((layers, metadata)) => {
+                        state.checkpoint();
+                        state.layers = layers;
+                        state.selection = metadata.selection;
+                        state.guides = metadata.guides;
+                        state.overlays = metadata.overlays;
+                        state.notes = metadata.notes;
+                        state.alpha_mode = metadata.alpha;
+                        state.selected_note = None;
+                        state.dpi = metadata.dpi;
+                        state.nine_slice_preview = None;
+                        state.filter_preview = None;
+                        state.invalidate();
+                    }
+                    Err(e) => log::error!("Failed to restore {}: {}", path.display(), e),
+                }
+            }
+        }
+        Command::OpenOutput(source) => {
+            let window = Window::new_output(app, source);
+            window.restore_placement(app, &model.global_state.config);
+            model.windows.insert(window.id, window);
+        }
+        Command::CloseOutput(id) => {
+            if matches!(
+                model.windows.get(&id),
+                Some(Window {
+                    widget_ids: WindowType::Output(_),
+                    ..
+                })
+            ) {
+                close_window(app, model, id);
+            }
+        }
+        Command::OpenProof(source) => {
+            let window = Window::new_proof(app, source);
+            window.restore_placement(app, &model.global_state.config);
+            model.windows.insert(window.id, window);
+        }
+        Command::CloseProof(id) => {
+            if let Some(Window {
+                widget_ids: WindowType::Proof(source),
+                ..
+            }) = close_window(app, model, id)
+            {
+                // Drop the proof unless another preview still shows it
+                let shown = model
+                    .windows
+                    .values()
+                    .any(|w| matches!(w.widget_ids, WindowType::Proof(s) if s == source));
+                if let (false, Some(state)) =
+                    (shown, editor_state(&mut model.windows, Some(source)))
+                {
+                    state.proof = None;
+                    state.proof_job = None;
+                }
+            }
+        }
+        Command::SetAlphaMode(mode) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.alpha_mode = mode;
+                state.proof = None;
+                state.settings_changed();
+            }
+        }
+        Command::Zoom(target) => {
+            let id = editor_id(&model.windows, model.global_state.focused_editor);
+            let window = id.and_then(|id| app.window(id)).map(|w| w.rect().wh());
+            if let (Some(state), Some(window)) = (editor_state(&mut model.windows, id), window) {
+                let from = state.view;
+                let to = match (target, &state.selection) {
+                    (zoom::Target::Fit, _) => from.framing(Vec2::ZERO, canvas_size(state), window),
+                    (zoom::Target::ActualSize, _) => from.zoomed(1.0, Vec2::ZERO),
+                    (zoom::Target::Scale(zoom), _) => from.zoomed(zoom, Vec2::ZERO),
+                    (zoom::Target::Rotate(degrees), _) => {
+                        from.rotated(degrees.to_radians(), Vec2::ZERO)
+                    }
+                    (zoom::Target::Selection, Some(selection)) => {
+                        let min = Vec2::new(selection.x as f32, selection.y as f32);
+                        let extent = Vec2::new(selection.width as f32, selection.height as f32);
+                        from.framing(min, min + extent, window)
+                    }
+                    (zoom::Target::Selection, None) => {
+                        log::warn!("Nothing is selected to zoom to");
+                        return;
+                    }
+                };
+                state.zoom = Some(zoom::Transition::new(from, to, app.time));
+            }
+        }
+        Command::SetOverlays(overlays) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.overlays = overlays;
+                state.settings_changed();
+            }
+        }
+        Command::Export(path) => {
+            let label = format!("Exporting {}", file_label(&path));
+            start_job(model, &label, |global, state| {
+                let mut path = export_path(state, &path, global.export.scale)?;
+                if path.extension().is_none() {
+                    path.set_extension(global.export.format.extension());
+                }
+                let canvas = export_pixels(global, &state.layers.composite());
+                let (options, alpha) = (global.export.clone(), state.alpha_mode);
+                Some(move |progress: &jobs::Progress| {
+                    let bytes = options.encode(&canvas, alpha).map_err(|e| e.to_string())?;
+                    progress.set(0.8);
+                    std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+                    Ok(jobs::Output::Exported(path))
+                })
+            });
+        }
+        Command::CloseDocument(id) => {
+            let (name, modified, path) = match model.windows.get(&id).map(|w| &w.widget_ids) {
+                Some(WindowType::Editor(_, state)) => {
+                    (state.name.clone(), state.modified, state.path.clone())
+                }
+                _ => return,
+            };
+            // Closing from the title bar has already taken the window away, so only a close from
+            // the Documents panel can be called off
+            let open = app.window(id).is_some();
+            let save = modified
+                && rfd::MessageDialog::new()
+                    .set_title("Unsaved changes")
+                    .set_description(&format!("Save the changes to {} before closing?", name))
+                    .set_buttons(rfd::MessageButtons::YesNo)
+                    .show();
+            if save {
+                match path.or_else(pick_save_file) {
+                    Some(path) => {
+                        // The save job keeps its own copy of the layers
+                        model.global_state.focused_editor = Some(id);
+                        run_command(app, model, Command::Save(path));
+                    }
+                    None if open => return,
+                    None => log::warn!("Closed {} without saving", name),
+                }
+            }
+
+            close_window(app, model, id);
+            if model.global_state.focused_editor == Some(id) {
+                model.global_state.focused_editor = None;
+            }
+        }
+        Command::Copy => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                model.global_state.clipboard = Some(clipboard::Clip::copy(
+                    &state.layers.active().pixels,
+                    state.selection.as_ref(),
+                ));
+            }
+        }
+        Command::Paste(transform) => {
+            if let (Some(state), Some(clip)) = (
+                editor_state(&mut model.windows, model.global_state.focused_editor),
+                &model.global_state.clipboard,
+            ) {
+                state.checkpoint();
+                // Into the current selection if there is one, otherwise back where it came from
+                let selection = state.selection;
+                let at = selection.map(|s| (s.x, s.y));
+                let layer = &mut state.layers.active_mut().pixels;
+                state.selection = clip.paste(layer, at, transform, selection.as_ref());
+                state.clear_previews();
+                match state.selection {
+                    Some(pasted) => state.mark_dirty_rect(dirty::DirtyRect {
+                        x0: pasted.x,
+                        y0: pasted.y,
+                        x1: pasted.x + pasted.width,
+                        y1: pasted.y + pasted.height,
+                    }),
+                    None => state.invalidate(),
+                }
+            }
+        }
+        Command::StrokeFx(fx) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                match state.last_stroke.take() {
+                    Some(mut path) if path.layer == state.layers.active => {
+                        state.checkpoint();
+                        path.apply(fx);
+                        path.replay(&mut state.layers.active_mut().pixels);
+                        state.last_stroke = Some(path);
+                        state.invalidate();
+                    }
+                    _ => log::warn!("Stroke effects apply to the last stroke on the active layer"),
+                }
+            }
+        }
+        Command::Undo => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.undo();
+            }
+        }
+        Command::ExportNineSlice(path) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                let path = match export_path(state, &path, 1.0) {
+                    Some(path) => path,
+                    None => return,
+                };
+                if let Err(e) = nine_slice::export(
+                    &state.layers.composite(),
+                    &model.global_state.nine_slice,
+                    &path,
+                ) {
+                    log::error!("Failed to export 9-slice to {}: {}", path.display(), e);
+                } else {
+                    post_export(&mut model.global_state, &path.with_extension("png"));
+                }
+            }
+        }
+        Command::MakeSeamless => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.checkpoint();
+                let layer = state.layers.active_mut();
+                layer.pixels =
+                    tiles::make_seamless(&layer.pixels, model.global_state.seam_blend as u32);
+                state.filter_preview = None;
+                state.invalidate();
+                // Show the seams wrapping so they can be healed and checked
+                model.global_state.tile_mode = true;
+                model.global_state.wrap_preview = true;
+            }
+        }
+        Command::CommitTransform => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.commit_transform(model.global_state.transform_smooth);
+            }
+        }
+        Command::CancelTransform => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                // Lifting was the last edit, so undoing it restores the pixels
+                if state.transform.is_some() {
+                    state.undo();
+                }
+            }
+        }
+        Command::ClearMeasurements => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.annotations.clear();
+                state.measurement = None;
+            }
+        }
+        Command::RemoveBackground => start_job(model, "Removing background", |global, state| {
+            let softness = global.cutout_softness;
+            let (index, selection) = (state.layers.active, state.selection);
+            let scribbles = state.scribbles.clone();
+            let pixels = state.layers.active().pixels.clone();
+            Some(move |progress: &jobs::Progress| {
+                let pixels =
+                    cutout::remove_background(&pixels, &scribbles, softness, selection, progress)?;
+                Ok(jobs::Output::Layer(index, pixels))
+            })
+        }),
+        Command::ClearScribbles => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                state.scribbles = Default::default();
+            }
+        }
+        Command::DivideGuides | Command::AddGuides | Command::ClearGuides => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                let options = &model.global_state.guides;
+                match command {
+                    Command::DivideGuides => {
+                        state.guides = guides::divide(
+                            state.layers.width(),
+                            state.layers.height(),
+                            options.columns as u32,
+                            options.rows as u32,
+                        );
+                    }
+                    Command::AddGuides => {
+                        for (text, orientation) in [
+                            (&options.vertical, guides::Orientation::Vertical),
+                            (&options.horizontal, guides::Orientation::Horizontal),
+                        ] {
+                            match guides::parse_positions(text) {
+                                Ok(positions) => {
+                                    state.guides.extend(positions.into_iter().map(|position| {
+                                        guides::Guide {
+                                            orientation,
+                                            position,
+                                        }
+                                    }))
+                                }
+                                Err(e) => log::error!("Invalid guide positions: {}", e),
+                            }
+                        }
+                    }
+                    _ => state.guides.clear(),
+                }
+            }
+        }
+        Command::SliceByGuides(path) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                let path = match export_path(state, &path, 1.0) {
+                    Some(path) => path,
+                    None => return,
+                };
+                match guides::export_slices(&state.layers.composite(), &state.guides, &path) {
+                    Ok(files) => {
+                        log::info!("Wrote {} slices", files.len());
+                        for file in files {
+                            post_export(&mut model.global_state, &file);
+                        }
+                    }
+                    Err(e) => log::error!("Failed to slice to {}: {}", path.display(), e),
+                }
+            }
+        }
+        Command::ExportChannelPack(path) => {
+            if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor)
+            {
+                let path = match export_path(state, &path, 1.0) {
+                    Some(path) => path,
+                    None => return,
+                };
+                // Packed maps are data rather than pictures, so no watermark
+                let packed =
+                    export::channel_pack::pack(&state.layers, &model.global_state.channel_pack);
+                if let Err(e) = save_image(&packed, &path) {
+                    log::error!("Failed to export channel pack to {}: {}", path.display(), e);
+                } else {
+                    post_export(&mut model.global_state, &path);
+                }
+            }
+        }
+    }
+}
+
+fn pick_image_file() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("Image", &["png", "jpg", "jpeg", "avif", "jxl"])
+        .add_filter("Project", &["ied"])
+        .add_filter("Camera RAW", &raw::EXTENSIONS)
+        .add_filter("OpenRaster", &["ora"])
+        .pick_file()
+}
+
+fn pick_save_file() -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter("PNG", &["png"])
+        .add_filter("Project", &["ied"])
+        .add_filter("JPEG", &["jpg", "jpeg"])
+        .add_filter("AVIF", &["avif"])
+        .add_filter("JPEG XL", &["jxl"])
+        .add_filter("OpenRaster", &["ora"])
+        .add_filter("SVG (raster layers)", &["svg"])
+        .add_filter("PDF", &["pdf"])
+        .save_file()
+}
+
+/// Writes `pixels` to `path`, picking PNG or JPEG from the extension.
+fn save_image(pixels: &DynamicImage, path: &std::path::Path) -> nannou::image::ImageResult<()> {
+    let ext = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    match ext.as_deref() {
+        // JPEG has no alpha channel
+        Some("jpg" | "jpeg") => DynamicImage::ImageRgb8(pixels.to_rgb8()).save(path),
+        _ => formats::save(pixels, path).unwrap_or_else(|| pixels.save(path)),
+    }
+}
+
+/// Expands `{token}` placeholders in an export path chosen by the user.
+fn export_path(state: &EditorState, path: &std::path::Path, scale: f32) -> Option<PathBuf> {
+    let ctx = export::naming::NameContext {
+        name: &state.name,
+        frame: 0,
+        layer: "canvas",
+        scale,
+        width: state.layers.width(),
+        height: state.layers.height(),
+    };
+    export::naming::expand_path(path, &ctx)
+        .map_err(|e| log::error!("Invalid export name {}: {}", path.display(), e))
+        .ok()
+}
+
+/// File name of `path` for job labels, which have to fit on the progress bar.
+fn file_label(path: &std::path::Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Runs the work `prepare` builds from the document Workbench commands apply to on a worker
+/// thread. Documents run one job at a time, so results always land on the pixels they started
+/// from unless the document was edited in between.
+fn start_job<F>(
+    model: &mut Model,
+    label: &str,
+    prepare: impl FnOnce(&mut GlobalState, &mut EditorState) -> Option<F>,
+) where
+    F: FnOnce(&jobs::Progress) -> Result<jobs::Output, String> + Send + 'static,
+{
+    let target = match editor_id(&model.windows, model.global_state.focused_editor) {
+        Some(target) => target,
+        None => return,
+    };
+    if let Some(job) = model.global_state.jobs.iter().find(|j| j.target == target) {
+        log::warn!("Wait for {} to finish first", job.label.to_lowercase());
+        return;
+    }
+    if let Some(WindowType::Editor(_, state)) =
+        model.windows.get_mut(&target).map(|w| &mut w.widget_ids)
+    {
+        if let Some(work) = prepare(&mut model.global_state, state) {
+            let job = jobs::Job::spawn(label, target, state.edits.get(), work);
+            model.global_state.jobs.push(job);
+        }
+    }
+}
+
+/// Applies the results of finished jobs to the documents they were started for.
+fn poll_jobs(model: &mut Model) {
+    let mut i = 0;
+    while i < model.global_state.jobs.len() {
+        // Results wait for the document's stroke to land
+        let target = model.global_state.jobs[i].target;
+        let stroking = match model.windows.get(&target).map(|w| &w.widget_ids) {
+            Some(WindowType::Editor(_, state)) => state.stroking(),
+            _ => false,
+        };
+        if stroking {
+            i += 1;
+            continue;
+        }
+        let result = match model.global_state.jobs[i].poll() {
+            Some(result) => result,
+            None => {
+                i += 1;
+                continue;
+            }
+        };
+        let job = model.global_state.jobs.remove(i);
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => {
+                log::error!("{} failed: {}", job.label, e);
+                continue;
+            }
+        };
+        if let jobs::Output::Exported(path) = &output {
+            post_export(&mut model.global_state, path);
+            continue;
+        }
+        if let jobs::Output::Printed = output {
+            log::info!("Sent to the printer");
+            continue;
+        }
+
+        let state = match model
+            .windows
+            .get_mut(&job.target)
+            .map(|w| &mut w.widget_ids)
+        {
+            Some(WindowType::Editor(_, state)) => state,
+            // Closed while the job ran
+            _ => continue,
+        };
+        let unchanged = state.edits.get() == job.edits;
+        match output {
+            jobs::Output::Saved(path) => {
+                model.global_state.config.add_recent(&path);
+                if let Some(name) = path.file_stem() {
+                    state.name = name.to_string_lossy().into_owned();
+                }
+                state.path = Some(path);
+                // Edits made during the save aren't in the file
+                if unchanged {
+                    state.modified = false;
+                }
+            }
+            _ if !unchanged => {
+                log::warn!(
+                    "{} was discarded, the document changed while it ran",
+                    job.label
+                );
+            }
+            jobs::Output::Layer(index, pixels) => {
+                state.checkpoint();
+                if let Some(layer) = state.layers.layers.get_mut(index) {
+                    layer.pixels = pixels;
+                }
+                state.filter_preview = None;
+                state.nine_slice_preview = None;
+                state.invalidate();
+            }
+            jobs::Output::NewLayer(name, pixels) => {
+                state.checkpoint();
+                state.layers.apply(layers::LayerOp::Add);
+                let layer = state.layers.active_mut();
+                layer.name = name;
+                layer.pixels = pixels;
+                state.invalidate();
+            }
+            jobs::Output::Layers(pixels) => {
+                state.checkpoint();
+                let size = (state.layers.width(), state.layers.height());
+                for (layer, pixels) in state.layers.layers.iter_mut().zip(pixels) {
+                    layer.pixels = pixels;
+                }
+                // A selection only makes sense on the canvas it was made on
+                if (state.layers.width(), state.layers.height()) != size {
+                    state.selection = None;
+                }
+                state.filter_preview = None;
+                state.nine_slice_preview = None;
+                state.invalidate();
+            }
+            jobs::Output::Exported(_) | jobs::Output::Printed | jobs::Output::Previews(_) => (),
+        }
+    }
+}
+
+/// Runs the configured post-export command, if any, on a freshly written file.
+fn post_export(global_state: &mut GlobalState, file: &std::path::Path) {
+    if !global_state.post_export_command.trim().is_empty() {
+        global_state
+            .hook_log
+            .run(&global_state.post_export_command, file);
+    }
+}
+
+/// The canvas as it should be written by exports, with export-only overlays applied.
+fn export_pixels(global_state: &GlobalState, pixels: &DynamicImage) -> DynamicImage {
+    if global_state.watermark.enabled {
+        global_state.watermark.apply(pixels)
+    } else {
+        pixels.clone()
+    }
+}
+
+/// Places the stamp in the tile grid cell under `pos`, once per cell while dragging.
+fn tile_stamp(global: &mut GlobalState, state: &mut EditorState, pos: Vec2) {
+    let stamp = match &global.stamp {
+        Some(stamp) => stamp,
+        None => return,
+    };
+    let cell = global.tile_size as u32;
+    let key = (pos / cell.max(1) as f32).floor();
+    if global.last_mouse == Some(key) {
+        return;
+    }
+
+    let (x, y) = stamp.place_in_cell(
+        &mut canvas::Canvas::new(
+            &mut state.layers.active_mut().pixels,
+            state.selection.as_ref(),
+        ),
+        pos.x as i32,
+        pos.y as i32,
+        cell,
+    );
+    let (w, h) = stamp.image.dimensions();
+    state.mark_dirty(x, y, w.max(h) as i32 * 2);
+    global.last_mouse = Some(key);
+}
+
+/// Distance the canvas moves per scroll step when panning.
+const PAN_STEP: f32 = 20.0;
+/// Degrees Ctrl+[ and Ctrl+] turn the view by.
+const VIEW_TURN: f32 = 15.0;
+
+/// How long the brush size HUD stays on the canvas after the last change.
+const BRUSH_HUD_DURATION: std::time::Duration = std::time::Duration::from_millis(1200);
+
+/// Grows or shrinks the brush by `steps` increments of about 10%, so small and large brushes
+/// both change at a usable rate.
+fn adjust_brush_size(global: &mut GlobalState, steps: f32) {
+    let step = (global.brush_size * 0.1).max(1.0);
+    global.brush_size = (global.brush_size + step * steps).round().clamp(1.0, 100.0);
+    global.brush_hud = Some(std::time::Instant::now());
+}
+
+/// Updates slower than this are reported to the console.
+const SLOW_UPDATE: std::time::Duration = std::time::Duration::from_millis(50);
+/// Least time between slow update reports, so a stretch of heavy work doesn't flood the console.
+const SLOW_UPDATE_REPORT: std::time::Duration = std::time::Duration::from_secs(5);
+
+fn update(app: &App, model: &mut Model, update: Update) {
+    let start = std::time::Instant::now();
+
+    if let Some(requests) = &model.open_requests {
+        model
+            .global_state
+            .commands
+            .extend(requests.try_iter().map(Command::Open));
+    }
+    poll_jobs(model);
+
+    if let Some(state) = editor_state(&mut model.windows, model.global_state.focused_editor) {
+        model.global_state.layer_list = (state.layers.info(), state.layers.active);
+        model.global_state.note_list = (state.notes.clone(), state.selected_note);
+        model.global_state.document_alpha = state.alpha_mode;
+        model.global_state.document_overlays = state.overlays;
+        model.global_state.document_size = (state.layers.width(), state.layers.height());
+        model.global_state.scale = state.view.zoom();
+        model.global_state.compare_stats =
+            state.compare_preview.as_ref().map(|(_, _, stats)| *stats);
+        model.global_state.histogram = state
+            .histogram
+            .as_ref()
+            .map(|(_, _, histogram)| histogram.clone());
+    }
+    model.global_state.documents = model
+        .windows
+        .iter()
+        .filter_map(|(id, window)| match &window.widget_ids {
+            WindowType::Editor(_, state) => Some((*id, state.name.clone())),
+            _ => None,
+        })
+        .collect();
+    // Windows are kept in a map, so sort to keep the list from shuffling
+    model.global_state.documents.sort_by_key(|(id, _)| *id);
+
+    let proofed: Vec<WindowId> = model
+        .windows
+        .values()
+        .filter_map(|w| match w.widget_ids {
+            WindowType::Proof(source) => Some(source),
+            _ => None,
+        })
+        .collect();
+
+    // Calling `set_widgets` allows us to instantiate some widgets.
+    for window in model.windows.values_mut() {
+        let ui = &mut window.ui.set_widgets();
+        match &mut window.widget_ids {
+            WindowType::Editor(_, state) => {
+                // Only set when the mode changes, as it is a request to the windowing system
+                let mode = model.global_state.mode;
+                if state.cursor_mode != Some(mode) {
+                    if let Some(editor) = app.window(window.id) {
+                        mode.cursor().apply(&editor);
+                    }
+                    state.cursor_mode = Some(mode);
+                }
+                if mode != Mode::Transform && !state.stroking() {
+                    state.commit_transform(model.global_state.transform_smooth);
+                }
+
+                state.poll_painters();
+
+                if let Some(transition) = &state.zoom {
+                    let (view, done) = transition.at(app.time);
+                    state.view = view;
+                    if done {
+                        state.zoom = None;
+                    }
+                }
+                let scale_factor = app.window(window.id).map_or(1.0, |w| w.scale_factor());
+                state.view = state
+                    .view
+                    .with_size(canvas_size(state))
+                    .with_scale_factor(scale_factor);
+
+                if model.global_state.tile_mode {
+                    state.refresh_tile_preview(window.id, model.global_state.tile_size as u32);
+                }
+
+                let slice = model.global_state.nine_slice;
+                let size = (
+                    model.global_state.nine_slice_size.0 as u32,
+                    model.global_state.nine_slice_size.1 as u32,
+                );
+                if model.global_state.nine_slice_mode
+                    && state
+                        .nine_slice_preview
+                        .as_ref()
+                        .map(|(s, sz, _)| (*s, *sz))
+                        != Some((slice, size))
+                {
+                    state.nine_slice_preview = Some((
+                        slice,
+                        size,
+                        slice
+                            .render(&state.layers.composite(), size.0, size.1)
+                            .into(),
+                    ));
+                }
+
+                if model.global_state.icon_preview {
+                    state.refresh_icon_previews(window.id);
+                }
+                if model.global_state.show_histogram {
+                    state.refresh_histogram();
+                }
+                if proofed.contains(&window.id) {
+                    state.refresh_proof(&model.global_state);
+                }
+                if let Some(reference) = &model.global_state.reference {
+                    state.refresh_compare_preview(reference);
+                }
+
+                match &model.global_state.filter {
+                    Some(filter) => {
+                        if state.filter_preview.as_ref().map(|(f, _)| f) != Some(filter) {
+                            let pixels = &state.layers.active().pixels;
+                            let mut preview =
+                                filter.apply(pixels, model.global_state.gpu_filters().as_deref());
+                            if let Some(selection) = &state.selection {
+                                preview = selection.clip(pixels, &preview);
+                            }
+                            state.filter_preview = Some((filter.clone(), preview));
+                            state.invalidate();
+                        }
+                    }
+                    None => {
+                        if state.filter_preview.take().is_some() {
+                            state.invalidate();
+                        }
+                    }
+                }
+            }
+            WindowType::Workbench(ids, _) => {
+                workbench::set_widgets(ids, ui, &mut model.global_state);
+            }
+            WindowType::Panel(ids, panel) => {
+                workbench::set_detached_widgets(
+                    ids,
+                    ui,
+                    &mut model.global_state,
+                    *panel,
+                    window.id,
+                );
+            }
+            WindowType::Output(_) | WindowType::Proof(_) => (),
+        }
+    }
+
+    poll_gamepad(app, model, update.since_last.as_secs_f32());
+    poll_midi(&mut model.global_state);
+
+    // Commands wait for strokes to land, as most of them edit layers a stroke worker paints on
+    let commands = if strokes_landing(&model.windows) {
+        Vec::new()
+    } else {
+        std::mem::take(&mut model.global_state.commands)
+    };
+    for command in commands {
+        crash::record_command(format!("{:?}", command));
+        run_command(app, model, command);
+    }
+
+    if model
+        .global_state
+        .autosave
+        .due(model.global_state.config.autosave_interval())
+    {
+        let documents = model
+            .windows
+            .values()
+            .filter_map(|window| match &window.widget_ids {
+                WindowType::Editor(_, state) if state.modified => Some((
+                    session::SessionDocument {
+                        name: state.name.clone(),
+                        path: state.path.clone(),
+                        file: format!("{}.ora", state.session_id),
+                    },
+                    state
+                        .autosave_stale
+                        .replace(false)
+                        .then(|| state.layers.clone()),
+                )),
+                _ => None,
+            })
+            .collect();
+        model.global_state.autosave.run(documents);
+    }
+
+    if model
+        .global_state
+        .backups
+        .due(&model.global_state.config.backup)
+    {
+        let global = &model.global_state;
+        let projects = model
+            .windows
+            .values()
+            .filter_map(|window| match &window.widget_ids {
+                WindowType::Editor(_, state) => {
+                    let path = state.path.as_ref().filter(|p| {
+                        p.extension()
+                            .map_or(false, |e| e.eq_ignore_ascii_case("ied"))
+                    })?;
+                    state.backup_stale.replace(false).then(|| {
+                        (
+                            path.clone(),
+                            state.layers.clone(),
+                            project::Metadata {
+                                selection: state.selection,
+                                dpi: state.dpi,
+                                guides: state.guides.clone(),
+                                overlays: state.overlays,
+                                notes: state.notes.clone(),
+                                alpha: state.alpha_mode,
+                                tools: project::ToolSettings::capture(global),
+                            },
+                        )
+                    })
+                }
+                _ => None,
+            })
+            .collect();
+        let count = global.config.backup.count;
+        model.global_state.backups.run(projects, count);
+    }
+
+    // Keep redrawing while the brush HUD is up so it disappears without further input, and while
+    // a selection's outline is animating
+    let hud_visible = model
+        .global_state
+        .brush_hud
+        .map_or(false, |t| t.elapsed() < BRUSH_HUD_DURATION);
+    let selecting = model.windows.values().any(
+        |w| matches!(&w.widget_ids, WindowType::Editor(_, state) if state.selection.is_some()),
+    );
+    // A change made during the icon preview's cooldown still has to reach it
+    let icons_pending = model.global_state.icon_preview
+        && model.windows.values().any(|w| match &w.widget_ids {
+            WindowType::Editor(_, state) => state
+                .icon_previews
+                .as_ref()
+                .map_or(false, |(_, revision, _)| *revision != state.revision.get()),
+            _ => false,
+        });
+    let histogram_pending = model.global_state.show_histogram
+        && model.windows.values().any(|w| match &w.widget_ids {
+            WindowType::Editor(_, state) => state
+                .histogram
+                .as_ref()
+                .map_or(true, |(_, revision, _)| *revision != state.revision.get()),
+            _ => false,
+        });
+    let proof_pending = model.windows.values().any(|w| match &w.widget_ids {
+        WindowType::Proof(source) => match model.windows.get(source).map(|w| &w.widget_ids) {
+            Some(WindowType::Editor(_, state)) => {
+                state.proof_job.is_some()
+                    || state.proof.as_ref().map_or(true, |(_, revision, _, _)| {
+                        *revision != state.revision.get()
+                    })
+            }
+            _ => false,
+        },
+        _ => false,
+    });
+    // Blinking needs frames without input
+    let blinking = model.global_state.reference.is_some()
+        && model.global_state.compare_view == compare::CompareView::Blink;
+    // Polled every frame so the progress bar moves and results land promptly
+    let working = !model.global_state.jobs.is_empty();
+    let zooming = model.windows.values().any(|w| match &w.widget_ids {
+        WindowType::Editor(_, state) => state.zoom.is_some(),
+        _ => false,
+    });
+    // Strokes land from the worker between input events
+    let painting = strokes_landing(&model.windows);
+    let animating = hud_visible
+        || selecting
+        || icons_pending
+        || histogram_pending
+        || proof_pending
+        || blinking
+        || working
+        || zooming
+        || painting;
+    app.set_loop_mode(if animating {
+        LoopMode::RefreshSync
+    } else {
+        LoopMode::Wait
+    });
+
+    let elapsed = start.elapsed();
+    if elapsed > SLOW_UPDATE {
+        let (reported, unreported) = &mut model.global_state.slow_updates;
+        if reported.map_or(true, |t| t.elapsed() >= SLOW_UPDATE_REPORT) {
+            match *unreported {
+                0 => log::warn!("Slow frame: update took {:?}", elapsed),
+                n => log::warn!("Slow frame: update took {:?}, {} more since", elapsed, n),
+            }
+            *reported = Some(std::time::Instant::now());
+            *unreported = 0;
+        } else {
+            *unreported += 1;
+        }
+    }
+}
+
+// Draw the state of your `Model` into the given `Frame` here.
+fn view(app: &App, model: &Model, frame: Frame) {
+    // Begin drawing
+    // frame.win
+    model.windows.get(&frame.window_id()).map(|window| {
+        match &window.widget_ids {
+            WindowType::Editor(_, state) => {
+                let draw = app.draw();
+                draw.background().color(model.global_state.config.theme.background());
+
+                let draw = draw.sampler(canvas_sampler(model.global_state.smooth_sampling));
+
+                // Only the regions changed since the last frame are re-uploaded
+                let canvas = state.canvas_texture(app, frame.device_queue_pair().queue());
+                draw.texture(&canvas)
+                    .wh(canvas_size(state) * state.view.scale())
+                    .xy(state.view.center())
+                    .rotate(state.view.rotation());
+
+                if let Some(transform) = &state.transform {
+                    draw_free_transform(&draw, app, state, transform, state.view.scale());
+                }
+
+                if let Some(reference) = &model.global_state.reference {
+                    draw_comparison(&draw, app, state, &model.global_state, reference);
+                }
+
+                if model.global_state.pixel_grid.visible(state.view.zoom()) {
+                    draw_pixel_grid(&draw, frame.rect(), state, &model.global_state.pixel_grid);
+                }
+
+                if model.global_state.tile_mode {
+                    draw_tile_grid(&draw, state, model.global_state.tile_size);
+
+                    if model.global_state.wrap_preview {
+                        draw_wrap_preview(&draw, state, &canvas);
+                    } else if let Some((_, _, _, preview)) = &state.tile_preview {
+                        // Shown at half the canvas zoom so the larger map fits beside the sheet
+                        let wh = Vec2::new(preview.width() as _, preview.height() as _)
+                            * state.view.scale()
+                            / 2.0;
+                        let beside = state.view.rect();
+                        draw.texture(&preview.view(app))
+                            .wh(wh)
+                            .x_y(beside.right() + 20.0 + wh.x / 2.0, beside.y());
+                    }
+                }
+
+                if let (Mode::Shape(shape), Some(anchor)) =
+                    (model.global_state.mode, state.shape_anchor)
+                {
+                    let pos = match shape {
+                        shapes::Shape::Line => line_end(app, state, &model.global_state, anchor),
+                        _ => snapped_canvas_position(app, state, &model.global_state),
+                    };
+                    draw_shape_preview(&draw, state, &model.global_state, shape, anchor, pos);
+                }
+
+                if let (Mode::Gradient, Some(anchor)) =
+                    (model.global_state.mode, state.shape_anchor)
+                {
+                    let pos = snapped_canvas_position(app, state, &model.global_state);
+                    draw_gradient_preview(&draw, state, &model.global_state.gradient, anchor, pos);
+                }
+
+                if model.global_state.mode == Mode::TileStamp {
+                    draw_tile_cursor(&draw, app, state, model.global_state.tile_size);
+                }
+
+                if model.global_state.perspective.visible {
+                    draw_perspective(&draw, state, &model.global_state.perspective);
+                }
+                draw_composition(&draw, state);
+                draw_guides(&draw, state);
+                draw_notes(&draw, state);
+                draw_symmetry_axes(&draw, state, model.global_state.symmetry);
+
+                for annotation in &state.annotations {
+                    draw_measurement(&draw, state, annotation);
+                }
+                // The measurement being dragged out, or else the last one taken
+                let measurement = match (model.global_state.mode, state.shape_anchor) {
+                    (Mode::Measure, Some(anchor)) => {
+                        let pos = snapped_canvas_position(app, state, &model.global_state);
+                        let measurement = measure::Measurement::new(anchor, pos);
+                        draw_measurement(&draw, state, &measurement);
+                        Some(measurement)
+                    }
+                    _ => state.measurement,
+                };
+
+                if let Some(selection) = &state.selection {
+                    draw_selection(&draw, state, selection, app.time);
+                }
+                if let Some(scissors) = &state.scissors {
+                    draw_scissors(&draw, state, scissors);
+                }
+                draw_scribbles(&draw, state);
+
+                if model.global_state.nine_slice_mode {
+                    draw_nine_slice_guides(&draw, state, &model.global_state.nine_slice);
+
+                    if let Some((_, _, preview)) = &state.nine_slice_preview {
+                        let wh = Vec2::new(preview.width() as _, preview.height() as _)
+                            * state.view.scale();
+                        let beside = state.view.rect();
+                        draw.texture(&preview.view(app))
+                            .wh(wh)
+                            .x_y(beside.left() - 20.0 - wh.x / 2.0, beside.y());
+                    }
+                }
+
+                match model.global_state.mode.cursor() {
+                    Cursor::BrushOutline => {
+                        draw_brush_outline(&draw, app, state, &model.global_state)
+                    }
+                    Cursor::Bucket => draw_bucket_cursor(&draw, app.mouse.position()),
+                    Cursor::System(_) => (),
+                }
+                if model.global_state.guides.rulers {
+                    draw_rulers(&draw, frame.rect(), state);
+                }
+                if let (true, Some((_, _, icons))) =
+                    (model.global_state.icon_preview, &state.icon_previews)
+                {
+                    let scale_factor = app
+                        .window(frame.window_id())
+                        .map_or(1.0, |w| w.scale_factor());
+                    draw_icon_previews(&draw, app, frame.rect(), scale_factor, icons);
+                }
+                if let Some((project, versions)) = &model.global_state.backup_versions {
+                    if state.path.as_ref() == Some(project) {
+                        draw_backup_versions(&draw, app, frame.rect(), versions);
+                    }
+                }
+                if let Some(measurement) = measurement {
+                    draw_status_bar(&draw, frame.rect(), &measurement.readout());
+                }
+                if let Some(changed) = model.global_state.brush_hud {
+                    if changed.elapsed() < BRUSH_HUD_DURATION {
+                        draw_brush_hud(&draw, frame.rect(), &model.global_state);
+                    }
+                }
+                // println!("View Editor {:?}", state.view);
+
+                // Write the result of our drawing to the window's frame.
+                draw.to_frame(app, &frame).unwrap();
+
+                // Draw the state of the `Ui` to the frame.
+                window.ui.draw_to_frame(app, &frame).unwrap();
+            }
+            // Only the canvas, fit to the window
+            WindowType::Output(source) => {
+                let draw = app.draw();
+                draw.background().color(model.global_state.config.theme.background());
+
+                if let Some(Window {
+                    widget_ids: WindowType::Editor(_, state),
+                    ..
+                }) = model.windows.get(source)
+                {
+                    let draw = draw.sampler(canvas_sampler(model.global_state.smooth_sampling));
+                    let canvas = state.canvas_texture(app, frame.device_queue_pair().queue());
+                    let size = Vec2::new(state.layers.width() as _, state.layers.height() as _);
+                    let fit = (frame.rect().wh() / size).min_element();
+                    draw.texture(&canvas).wh(size * fit);
+                }
+
+                draw.to_frame(app, &frame).unwrap();
+            }
+            // The canvas and the export as it will be written, side by side
+            WindowType::Proof(source) => {
+                let draw = app.draw();
+                draw.background().color(model.global_state.config.theme.background());
+
+                if let Some(Window {
+                    widget_ids: WindowType::Editor(_, state),
+                    ..
+                }) = model.windows.get(source)
+                {
+                    if let Some((_, _, options, proof)) = &state.proof {
+                        let size = Vec2::new(proof.image.width() as _, proof.image.height() as _);
+                        let window = frame.rect();
+                        let pane = Vec2::new(window.w() / 2.0 - 15.0, window.h() - 80.0);
+                        // Never enlarged, so compression artifacts show at their real size
+                        let wh = size * (pane / size).min_element().min(1.0);
+                        let canvas = state.canvas_texture(app, frame.device_queue_pair().queue());
+                        let exported = proof.image.view(app);
+
+                        let sampled = draw.sampler(canvas_sampler(false));
+                        for (texture, x, label) in [
+                            (&canvas, -window.w() / 4.0, "Original"),
+                            (&exported, window.w() / 4.0, "Exported"),
+                        ] {
+                            sampled.texture(texture).wh(wh).x_y(x, 0.0);
+                            draw.text(label)
+                                .color(WHITE)
+                                .font_size(14)
+                                .x_y(x, window.top() - 20.0);
+                        }
+                        draw_status_bar(&draw, window, &options.summary(proof));
+                    }
+                }
+
+                draw.to_frame(app, &frame).unwrap();
+            }
+            WindowType::Workbench(_, _) | WindowType::Panel(_, _) => {
+                let draw = app.draw();
+                draw.background().color(model.global_state.config.theme.background());
+                draw.to_frame(app, &frame).unwrap();
+                // println!("View workbench");
+
+                // println!("View Workbench");
+
+                window.ui.draw_to_frame(app, &frame).unwrap();
+            }
+        }
+        Some(0)
+    });
+}
+
+/// Sampler for drawing the canvas: hard pixel edges when zoomed in, unless smooth sampling is on.
+fn canvas_sampler(smooth: bool) -> wgpu::SamplerDescriptor<'static> {
+    wgpu::SamplerBuilder::new()
+        .address_mode(wgpu::AddressMode::ClampToEdge)
+        .mag_filter(if smooth {
+            wgpu::FilterMode::Linear
+        } else {
+            wgpu::FilterMode::Nearest
+        })
+        .into_descriptor()
+}
+
+/// The pixels a dab would paint under the pointer at the canvas zoom, with a dot marking the
+/// exact pointer position. Soft tips also get a fainter line where they reach half strength, so
+/// the falloff shows as well as the reach.
+fn draw_brush_outline(draw: &Draw, app: &App, state: &EditorState, global: &GlobalState) {
+    let pos = app.mouse.position();
+    let brush = global.brush.as_brush();
+    let center = canvas_position(app, state);
+    let outline = brush::footprint(brush, center, global.brush_size, 0.0);
+    let core = brush::footprint(brush, center, global.brush_size, 0.5);
+
+    let line = |(start, end): (Vec2, Vec2), color: Rgba, weight: f32| {
+        draw.line()
+            .start(canvas_to_screen(state, start))
+            .end(canvas_to_screen(state, end))
+            .color(color)
+            .weight(weight);
+    };
+    // A white line over a wider black one keeps the outline visible on any paint
+    for &edge in &outline {
+        line(edge, rgba(0.0, 0.0, 0.0, 1.0), 3.0);
+    }
+    for &edge in &outline {
+        line(edge, rgba(1.0, 1.0, 1.0, 1.0), 1.0);
+    }
+    if core != outline {
+        for &edge in &core {
+            line(edge, rgba(1.0, 1.0, 1.0, 0.5), 1.0);
+        }
+    }
+    draw.ellipse().color(BLACK).w_h(2.0, 2.0).xy(pos);
+}
+
+/// A small tipped paint bucket below and to the right of the crosshair.
+fn draw_bucket_cursor(draw: &Draw, pos: Point2) {
+    let center = pos + Vec2::new(14.0, -14.0);
+    draw.rect()
+        .color(WHITE)
+        .stroke(BLACK)
+        .stroke_weight(1.0)
+        .w_h(10.0, 12.0)
+        .rotate(std::f32::consts::FRAC_PI_4)
+        .xy(center);
+    // Paint spilling from the rim
+    draw.ellipse()
+        .color(BLACK)
+        .w_h(4.0, 6.0)
+        .xy(center + Vec2::new(-7.0, -5.0));
+}
+
+/// Brush size, opacity and flow readout with a preview circle at the canvas zoom, in the window's
+/// top-left corner.
+fn draw_brush_hud(draw: &Draw, window: Rect<f32>, global: &GlobalState) {
+    let box_size = 120.0;
+    let center = pt2(
+        window.left() + 10.0 + box_size / 2.0,
+        window.top() - 10.0 - box_size / 2.0,
+    );
+    let diameter = (global.brush_size * global.scale).min(box_size - 30.0);
+
+    draw.rect()
+        .color(rgba(0.0, 0.0, 0.0, 0.6))
+        .w_h(box_size, box_size)
+        .xy(center);
+    draw.ellipse()
+        .no_fill()
+        .stroke(WHITE)
+        .stroke_weight(1.0)
+        .w_h(diameter, diameter)
+        .xy(center + Vec2::new(0.0, 10.0));
+    draw.text(&format!(
+        "{} px  {:.0}% / {:.0}%",
+        global.brush_size,
+        global.brush_opacity * 100.0,
+        global.brush_flow * 100.0
+    ))
+    .color(WHITE)
+    .font_size(14)
+    .xy(center - Vec2::new(0.0, box_size / 2.0 - 12.0));
+}
+
+/// Screen position of a canvas position.
+fn canvas_to_screen(state: &EditorState, pos: Vec2) -> Point2 {
+    state.view.to_screen(pos)
+}
+
+/// The lifted pixels of a free transform where they would land, with the box, corner handles and
+/// rotation knob around them.
+fn draw_free_transform(
+    draw: &Draw,
+    app: &App,
+    state: &EditorState,
+    transform: &free_transform::FreeTransform,
+    scale: f32,
+) {
+    // Screen y points up, so the clockwise canvas rotation turns the other way
+    draw.texture(&transform.pixels.view(app))
+        .wh(transform.size() * transform.scale * scale)
+        .xy(canvas_to_screen(state, transform.center))
+        .rotate(state.view.rotation() - transform.angle);
+
+    let corners = transform.corners().map(|c| canvas_to_screen(state, c));
+    draw.polyline()
+        .weight(1.0)
+        .points(corners.iter().chain(&corners[..1]).copied())
+        .color(LinSrgb::new(0.3, 0.6, 1.0));
+    for corner in corners {
+        draw.rect()
+            .xy(corner)
+            .w_h(8.0, 8.0)
+            .color(LinSrgb::new(1.0, 1.0, 1.0))
+            .stroke(LinSrgb::new(0.3, 0.6, 1.0))
+            .stroke_weight(1.0);
+    }
+
+    let top = canvas_to_screen(
+        state,
+        transform.to_canvas(Vec2::new(0.0, -transform.size().y / 2.0)),
+    );
+    let knob = canvas_to_screen(state, transform.rotate_handle(scale));
+    draw.line()
+        .start(top)
+        .end(knob)
+        .weight(1.0)
+        .color(LinSrgb::new(0.3, 0.6, 1.0));
+    draw.ellipse()
+        .xy(knob)
+        .radius(5.0)
+        .color(LinSrgb::new(1.0, 1.0, 1.0))
+        .stroke(LinSrgb::new(0.3, 0.6, 1.0))
+        .stroke_weight(1.0);
+}
+
+/// The drag of a gradient in progress, as a line whose ends show the first and last colors.
+fn draw_gradient_preview(
+    draw: &Draw,
+    state: &EditorState,
+    gradient: &gradient::Gradient,
+    anchor: Vec2,
+    pos: Vec2,
+) {
+    let (start, end) = (
+        canvas_to_screen(state, anchor),
+        canvas_to_screen(state, pos),
+    );
+    draw.line()
+        .start(start)
+        .end(end)
+        .weight(1.0)
+        .color(rgba(1.0, 1.0, 1.0, 0.8));
+    for (xy, t) in [(start, 0.0), (end, 1.0)] {
+        let [r, g, b, a] = gradient.color_at(t);
+        draw.ellipse()
+            .color(rgba8(r, g, b, a))
+            .stroke(BLACK)
+            .stroke_weight(1.0)
+            .w_h(10.0, 10.0)
+            .xy(xy);
+    }
+}
+
+/// A measured line with its length written beside the middle.
+fn draw_measurement(draw: &Draw, state: &EditorState, measurement: &measure::Measurement) {
+    let (start, end) = (
+        canvas_to_screen(state, measurement.start),
+        canvas_to_screen(state, measurement.end),
+    );
+    let color = rgba(1.0, 0.9, 0.0, 0.9);
+    draw.line().start(start).end(end).weight(1.0).color(color);
+    for xy in [start, end] {
+        draw.ellipse().color(color).w_h(5.0, 5.0).xy(xy);
+    }
+    draw.text(&format!("{:.1} px", measurement.distance()))
+        .color(color)
+        .font_size(12)
+        .xy((start + end) / 2.0 + Vec2::new(0.0, 10.0));
+}
+
+/// One line of text on a dark strip along the bottom-left of the window.
+fn draw_status_bar(draw: &Draw, window: Rect<f32>, text: &str) {
+    let (width, height) = (420.0, 24.0);
+    let center = pt2(
+        window.left() + 10.0 + width / 2.0,
+        window.bottom() + 10.0 + height / 2.0,
+    );
+    draw.rect()
+        .color(rgba(0.0, 0.0, 0.0, 0.6))
+        .w_h(width, height)
+        .xy(center);
+    draw.text(text)
+        .color(WHITE)
+        .font_size(12)
+        .w_h(width - 10.0, height)
+        .left_justify()
+        .xy(center);
+}
+
+/// Overlay of the shape being dragged out, in the brush color.
+fn draw_shape_preview(
+    draw: &Draw,
+    state: &EditorState,
+    global: &GlobalState,
+    shape: shapes::Shape,
+    anchor: Vec2,
+    pos: Vec2,
+) {
+    let [r, g, b, a] = global.brush_color;
+    let color = rgba8(r, g, b, (a as f32 * global.brush_opacity) as u8);
+    let weight = global.brush_size * state.view.scale();
+    let (start, end) = (
+        canvas_to_screen(state, anchor),
+        canvas_to_screen(state, pos),
+    );
+    let center = (start + end) / 2.0;
+    // Measured along the canvas's axes, which turn with the view
+    let wh = (pos - anchor).abs() * state.view.scale();
+    let rotation = state.view.rotation();
+
+    match shape {
+        shapes::Shape::Line => {
+            draw.line()
+                .start(start)
+                .end(end)
+                .weight(weight)
+                .color(color);
+        }
+        shapes::Shape::Rectangle => {
+            draw.rect()
+                .no_fill()
+                .stroke(color)
+                .stroke_weight(weight)
+                .wh(wh)
+                .xy(center)
+                .rotate(rotation);
+        }
+        shapes::Shape::Ellipse => {
+            draw.ellipse()
+                .no_fill()
+                .stroke(color)
+                .stroke_weight(weight)
+                .wh(wh)
+                .xy(center)
+                .rotate(rotation);
+        }
+    }
+}
+
+/// Outlines the tile grid cell under the cursor.
+fn draw_tile_cursor(draw: &Draw, app: &App, state: &EditorState, tile_size: f32) {
+    let view = state.view;
+    let cell = (view.to_canvas(app.mouse.position()) / tile_size).floor() * tile_size;
+    let corners = [
+        cell,
+        cell + Vec2::new(tile_size, 0.0),
+        cell + Vec2::splat(tile_size),
+        cell + Vec2::new(0.0, tile_size),
+    ]
+    .map(|corner| view.to_screen(corner));
+
+    draw.polyline()
+        .weight(1.0)
+        .points(corners.iter().chain(&corners[..1]).copied())
+        .color(rgba(1.0, 0.8, 0.0, 0.9));
+}
+
+/// Draws each icon preview at its true pixel size on a light and a dark backdrop, in the window's
+/// bottom-right corner.
+fn draw_icon_previews(
+    draw: &Draw,
+    app: &App,
+    window: Rect<f32>,
+    scale_factor: f32,
+    icons: &[textures::Uploaded],
+) {
+    let padding = 8.0;
+    // Points per physical pixel, so a 16 px icon covers 16 screen pixels
+    let pixel = 1.0 / scale_factor;
+    let cell = |size: u32| size as f32 * pixel + padding;
+    let width: f32 = ICON_SIZES.iter().map(|s| cell(*s)).sum::<f32>() + padding;
+    let row_height = cell(*ICON_SIZES.last().unwrap());
+    let height = 2.0 * row_height + padding;
+    let corner = pt2(window.right() - 10.0 - width, window.bottom() + 10.0);
+
+    draw.rect()
+        .color(rgba(0.0, 0.0, 0.0, 0.6))
+        .w_h(width, height)
+        .xy(corner + Vec2::new(width, height) / 2.0);
+
+    let draw = draw.sampler(
+        wgpu::SamplerBuilder::new()
+            .mag_filter(wgpu::FilterMode::Nearest)
+            .min_filter(wgpu::FilterMode::Nearest)
+            .into_descriptor(),
+    );
+    let mut x = corner.x + padding;
+    for (size, icon) in ICON_SIZES.iter().zip(icons) {
+        let side = *size as f32 * pixel;
+        let texture = icon.view(app);
+        let wh = Vec2::new(icon.width() as _, icon.height() as _) * pixel;
+        for (row, backdrop) in [(1.0, rgb(0.95, 0.95, 0.95)), (0.0, rgb(0.1, 0.1, 0.1))] {
+            let center = pt2(
+                x + side / 2.0,
+                corner.y + padding + row * row_height + side / 2.0,
+            );
+            draw.rect().color(backdrop).w_h(side, side).xy(center);
+            draw.texture(&texture).wh(wh).xy(center);
+        }
+        x += cell(*size);
+    }
+}
+
+/// Thumbnails of the backed up versions down the right edge of the window, numbered like their
+/// restore buttons on the Workbench, newest at the top.
+fn draw_backup_versions(draw: &Draw, app: &App, window: Rect<f32>, versions: &[backup::Version]) {
+    let side = backup::THUMBNAIL_SIZE as f32;
+    let row_height = side + 24.0;
+    let x = window.right() - 10.0 - side / 2.0;
+    for (i, version) in versions.iter().enumerate() {
+        let top = window.top() - RULER_SIZE - 10.0 - i as f32 * row_height;
+        if top - row_height < window.bottom() {
+            break;
+        }
+        let center = pt2(x, top - side / 2.0);
+        draw.rect()
+            .color(rgba(0.0, 0.0, 0.0, 0.6))
+            .w_h(side + 8.0, row_height)
+            .xy(pt2(x, top - row_height / 2.0 + 4.0));
+        if let Some(thumbnail) = &version.thumbnail {
+            let wh = Vec2::new(thumbnail.width() as _, thumbnail.height() as _);
+            draw.texture(&thumbnail.view(app)).wh(wh).xy(center);
+        }
+        draw.text(&format!("{}. {}", i + 1, version.label()))
+            .color(WHITE)
+            .font_size(11)
+            .w(side + 40.0)
+            .xy(pt2(x, top - side - 10.0));
+    }
+}
+
+/// Covers the canvas with the difference heatmap, or with the reference every other half second
+/// when blinking. Both are lined up with the canvas at its top-left corner.
+fn draw_comparison(
+    draw: &Draw,
+    app: &App,
+    state: &EditorState,
+    global: &GlobalState,
+    reference: &textures::Uploaded,
+) {
+    let image = match global.compare_view {
+        compare::CompareView::Difference => match &state.compare_preview {
+            Some((_, heatmap, _)) => heatmap,
+            None => return,
+        },
+        compare::CompareView::Blink if (app.time * 2.0) as u32 % 2 == 1 => reference,
+        compare::CompareView::Blink => return,
+    };
+    // Pinned to the canvas's top left, as the images may differ in size
+    let view = state.view;
+    let size = Vec2::new(image.width() as _, image.height() as _);
+    draw.texture(&image.view(app))
+        .wh(size * view.scale())
+        .xy(view.to_screen(size / 2.0))
+        .rotate(view.rotation());
+}
+
+/// Draws the canvas again on all eight sides, dimmed, so edges that don't wrap stand out, and
+/// marks the middle lines Make Seamless moves the edges to.
+fn draw_wrap_preview(draw: &Draw, state: &EditorState, canvas: &wgpu::TextureView) {
+    let (view, size) = (state.view, canvas_size(state));
+    let wh = size * view.scale();
+    for (dx, dy) in (-1..=1).flat_map(|dx| (-1..=1).map(move |dy| (dx, dy))) {
+        if (dx, dy) == (0, 0) {
+            continue;
+        }
+        let xy = view.to_screen(size * Vec2::new(dx as f32 + 0.5, dy as f32 + 0.5));
+        draw.texture(canvas)
+            .wh(wh)
+            .xy(xy)
+            .rotate(view.rotation());
+        draw.rect()
+            .color(rgba(0.0, 0.0, 0.0, 0.35))
+            .wh(wh)
+            .xy(xy)
+            .rotate(view.rotation());
+    }
+
+    let color = rgba(0.0, 1.0, 1.0, 0.5);
+    let middle = size / 2.0;
+    draw.line()
+        .start(view.to_screen(Vec2::new(middle.x, 0.0)))
+        .end(view.to_screen(Vec2::new(middle.x, size.y)))
+        .weight(1.0)
+        .color(color);
+    draw.line()
+        .start(view.to_screen(Vec2::new(0.0, middle.y)))
+        .end(view.to_screen(Vec2::new(size.x, middle.y)))
+        .weight(1.0)
+        .color(color);
+}
+
+/// The perspective grid's lines, with a ring on each vanishing point to drag it by.
+fn draw_perspective(draw: &Draw, state: &EditorState, grid: &perspective::PerspectiveGrid) {
+    let size = canvas_size(state);
+    for (start, end) in grid.lines(size) {
+        draw.line()
+            .start(canvas_to_screen(state, start))
+            .end(canvas_to_screen(state, end))
+            .weight(1.0)
+            .color(rgba(1.0, 0.5, 0.2, 0.5));
+    }
+    for point in grid.vanishing_points(size) {
+        draw.ellipse()
+            .xy(canvas_to_screen(state, point))
+            .radius(SNAP_DISTANCE)
+            .no_fill()
+            .stroke(rgba(1.0, 0.5, 0.2, 1.0))
+            .stroke_weight(2.0);
+    }
+}
+
+/// The document's composition overlays, in canvas space so they follow the zoom.
+fn draw_composition(draw: &Draw, state: &EditorState) {
+    let size = canvas_size(state);
+    let color = rgba(1.0, 1.0, 1.0, 0.6);
+    for (start, end) in state.overlays.lines(size) {
+        draw.line()
+            .start(canvas_to_screen(state, start))
+            .end(canvas_to_screen(state, end))
+            .weight(1.0)
+            .color(color);
+    }
+    let spiral = state.overlays.spiral(size);
+    if !spiral.is_empty() {
+        draw.polyline()
+            .weight(1.0)
+            .points(spiral.into_iter().map(|point| canvas_to_screen(state, point)))
+            .color(color);
+    }
+}
+
+fn draw_guides(draw: &Draw, state: &EditorState) {
+    let view = state.view;
+    let color = rgba(0.0, 0.8, 1.0, 0.8);
+    for guide in state.guides.iter().chain(&state.dragged_guide) {
+        let position = guide.position as f32;
+        let (start, end) = match guide.orientation {
+            guides::Orientation::Vertical => (
+                view.to_screen(Vec2::new(position, 0.0)),
+                view.to_screen(Vec2::new(position, canvas_size(state).y)),
+            ),
+            guides::Orientation::Horizontal => (
+                view.to_screen(Vec2::new(0.0, position)),
+                view.to_screen(Vec2::new(canvas_size(state).x, position)),
+            ),
+        };
+        draw.line().start(start).end(end).weight(1.0).color(color);
+    }
+}
+
+/// Note markers, with the selected note's text beside its marker. TODOs are orange until checked
+/// off.
+fn draw_notes(draw: &Draw, state: &EditorState) {
+    for (i, note) in state.notes.iter().enumerate() {
+        let xy = canvas_to_screen(state, note.position());
+        let color = match (note.todo, note.done) {
+            (true, false) => rgba(1.0, 0.55, 0.1, 0.95),
+            (true, true) => rgba(0.6, 0.6, 0.6, 0.9),
+            (false, _) => rgba(1.0, 0.9, 0.3, 0.95),
+        };
+        let size = notes::MARKER_RADIUS * 2.0;
+        draw.ellipse()
+            .color(color)
+            .stroke(BLACK)
+            .stroke_weight(1.0)
+            .w_h(size, size)
+            .xy(xy);
+
+        if state.selected_note == Some(i) {
+            let (width, height) = (220.0, 60.0);
+            let center = xy + Vec2::new(size + width / 2.0, -height / 2.0);
+            draw.rect()
+                .color(rgba(0.0, 0.0, 0.0, 0.75))
+                .w_h(width, height)
+                .xy(center);
+            draw.text(&note.text)
+                .color(WHITE)
+                .font_size(12)
+                .w_h(width - 10.0, height - 10.0)
+                .left_justify()
+                .align_text_top()
+                .xy(center);
+        }
+    }
+}
+
+/// Lines along the axes strokes are mirrored across, or the spokes they are repeated around.
+fn draw_symmetry_axes(draw: &Draw, state: &EditorState, symmetry: symmetry::Symmetry) {
+    let (width, height) = (state.layers.width() as f32, state.layers.height() as f32);
+    let color = rgba(1.0, 0.4, 0.7, 0.8);
+    for (start, end) in symmetry.axes(width, height) {
+        draw.line()
+            .start(canvas_to_screen(state, start))
+            .end(canvas_to_screen(state, end))
+            .weight(1.0)
+            .color(color);
+    }
+}
+
+/// Lines between the canvas's pixels, limited to the part of the canvas inside the window.
+fn draw_pixel_grid(
+    draw: &Draw,
+    window: Rect<f32>,
+    state: &EditorState,
+    grid: &pixel_grid::PixelGrid,
+) {
+    let view = state.view;
+    // Canvas pixels around the part of the canvas in the window, top left and bottom right
+    let (start, end) = match view.visible(window) {
+        Some(visible) => visible,
+        None => return,
+    };
+    let [r, g, b, a] = grid.color;
+    let color = |i: u32| rgba8(r, g, b, if grid.is_major(i) { 255 } else { a });
+
+    for i in start.x.ceil() as u32..=end.x.floor() as u32 {
+        draw.line()
+            .start(view.to_screen(Vec2::new(i as f32, start.y)))
+            .end(view.to_screen(Vec2::new(i as f32, end.y)))
+            .weight(1.0)
+            .color(color(i));
+    }
+
+    // Rows count down from the top edge
+    for i in start.y.ceil() as u32..=end.y.floor() as u32 {
+        draw.line()
+            .start(view.to_screen(Vec2::new(start.x, i as f32)))
+            .end(view.to_screen(Vec2::new(end.x, i as f32)))
+            .weight(1.0)
+            .color(color(i));
+    }
+}
+
+/// Canvas pixel rulers along the window's top and left edges, labelled at a spacing that stays
+/// readable at the current zoom. The ticks are left off while the view is turned, as the canvas's
+/// rows and columns no longer run along the edges.
+fn draw_rulers(draw: &Draw, window: Rect<f32>, state: &EditorState) {
+    let view = state.view;
+    let scale = view.scale();
+    let background = rgba(0.1, 0.1, 0.1, 0.9);
+    let color = rgba(0.8, 0.8, 0.8, 1.0);
+    draw.rect()
+        .color(background)
+        .w_h(window.w(), RULER_SIZE)
+        .x_y(window.x(), window.top() - RULER_SIZE / 2.0);
+    draw.rect()
+        .color(background)
+        .w_h(RULER_SIZE, window.h() - RULER_SIZE)
+        .x_y(
+            window.left() + RULER_SIZE / 2.0,
+            window.y() - RULER_SIZE / 2.0,
+        );
+    if view.rotation() != 0.0 {
+        return;
+    }
+
+    // The smallest of 1, 2, 5, 10, 20, 50... pixels that puts labels at least 50 points apart
+    let mut step = 1.0;
+    for factor in [2.0, 2.5, 2.0].iter().cycle() {
+        if step * scale >= 50.0 {
+            break;
+        }
+        step *= factor;
+    }
+    let tick = |value: f32| (value / step).ceil() as i64;
+
+    // Along the top, canvas x grows to the right
+    let first = tick(view.to_canvas(pt2(window.left() + RULER_SIZE, 0.0)).x);
+    let last = tick(view.to_canvas(pt2(window.right(), 0.0)).x);
+    for i in first..last {
+        let value = i as f32 * step;
+        let x = view.to_screen(Vec2::new(value, 0.0)).x;
+        draw.line()
+            .start(pt2(x, window.top() - RULER_SIZE))
+            .end(pt2(x, window.top() - RULER_SIZE / 2.0))
+            .weight(1.0)
+            .color(color);
+        draw.text(&format!("{}", value))
+            .color(color)
+            .font_size(9)
+            .left_justify()
+            .w_h(step * scale, RULER_SIZE / 2.0)
+            .x_y(
+                x + 2.0 + step * scale / 2.0,
+                window.top() - RULER_SIZE / 4.0,
+            );
+    }
+
+    // Down the left, canvas y grows downwards
+    let first = tick(view.to_canvas(pt2(0.0, window.top() - RULER_SIZE)).y);
+    let last = tick(view.to_canvas(pt2(0.0, window.bottom())).y);
+    for i in first..last {
+        let value = i as f32 * step;
+        let y = view.to_screen(Vec2::new(0.0, value)).y;
+        draw.line()
+            .start(pt2(window.left() + RULER_SIZE / 2.0, y))
+            .end(pt2(window.left() + RULER_SIZE, y))
+            .weight(1.0)
+            .color(color);
+        draw.text(&format!("{}", value))
+            .color(color)
+            .font_size(9)
+            .w_h(RULER_SIZE, RULER_SIZE / 2.0)
+            .x_y(window.left() + RULER_SIZE / 2.0, y - RULER_SIZE / 4.0);
+    }
+}
+
+fn draw_tile_grid(draw: &Draw, state: &EditorState, tile_size: f32) {
+    let (view, size) = (state.view, canvas_size(state));
+    let step = tile_size.max(1.0);
+    let color = rgba(1.0, 0.0, 1.0, 0.5);
+
+    let mut x = step;
+    while x < size.x {
+        draw.line()
+            .start(view.to_screen(Vec2::new(x, 0.0)))
+            .end(view.to_screen(Vec2::new(x, size.y)))
+            .weight(1.0)
+            .color(color);
+        x += step;
+    }
+
+    let mut y = step;
+    while y < size.y {
+        draw.line()
+            .start(view.to_screen(Vec2::new(0.0, y)))
+            .end(view.to_screen(Vec2::new(size.x, y)))
+            .weight(1.0)
+            .color(color);
+        y += step;
+    }
+}
+
+/// Length of each dash in the selection outline, in screen pixels.
+const ANT_DASH: f32 = 4.0;
+
+/// The outline traced so far with the scissors, black under white so it shows on any colors.
+fn draw_scissors(draw: &Draw, state: &EditorState, scissors: &scissors::Scissors) {
+    let points: Vec<Point2> = scissors
+        .points()
+        .map(|pos| canvas_to_screen(state, pos))
+        .collect();
+    draw.polyline()
+        .weight(3.0)
+        .color(BLACK)
+        .points(points.iter().cloned());
+    draw.polyline().weight(1.0).color(WHITE).points(points);
+}
+
+/// The cutout scribbles at their brush width, translucent green over the foreground and red over
+/// the background.
+fn draw_scribbles(draw: &Draw, state: &EditorState) {
+    let px = state.view.scale();
+    for stroke in &state.scribbles.strokes {
+        let color = if stroke.background {
+            rgba(1.0, 0.2, 0.2, 0.5)
+        } else {
+            rgba(0.2, 1.0, 0.2, 0.5)
+        };
+        let points: Vec<Point2> = stroke
+            .points
+            .iter()
+            .map(|&pos| canvas_to_screen(state, pos))
+            .collect();
+        if points.len() == 1 {
+            draw.ellipse()
+                .xy(points[0])
+                .radius(stroke.radius * px)
+                .color(color);
+        } else {
+            draw.polyline()
+                .weight(stroke.radius * 2.0 * px)
+                .join_round()
+                .color(color)
+                .points(points);
+        }
+    }
+}
+
+/// Draws the selection as "marching ants": black dashes over a white outline, moving along the
+/// edge over `time`.
+fn draw_selection(draw: &Draw, state: &EditorState, selection: &selection::Selection, time: f32) {
+    let min = Vec2::new(selection.x as f32, selection.y as f32);
+    let max = min + Vec2::new(selection.width as f32, selection.height as f32);
+    let corners = [min, Vec2::new(max.x, min.y), max, Vec2::new(min.x, max.y)]
+        .map(|corner| canvas_to_screen(state, corner));
+
+    draw.polyline()
+        .weight(1.0)
+        .points(corners.iter().chain(&corners[..1]).copied())
+        .color(WHITE);
+
+    let phase = (time * 8.0) % (ANT_DASH * 2.0);
+    for (i, &start) in corners.iter().enumerate() {
+        let end = corners[(i + 1) % corners.len()];
+        let len = start.distance(end);
+        let dir = (end - start) / len.max(f32::EPSILON);
+
+        let mut t = phase - ANT_DASH * 2.0;
+        while t < len {
+            let (a, b) = (t.max(0.0), (t + ANT_DASH).min(len));
+            if b > a {
+                draw.line()
+                    .start(start + dir * a)
+                    .end(start + dir * b)
+                    .weight(1.0)
+                    .color(BLACK);
+            }
+            t += ANT_DASH * 2.0;
+        }
+    }
+}
+
+fn draw_nine_slice_guides(draw: &Draw, state: &EditorState, slice: &nine_slice::NineSlice) {
+    let slice = slice.clamped(state.layers.width(), state.layers.height());
+    let (view, size) = (state.view, canvas_size(state));
+    let color = rgba(0.0, 1.0, 1.0, 0.8);
+
+    // Measured inward from each edge
+    let near = Vec2::new(slice.left as f32, slice.top as f32);
+    let far = size - Vec2::new(slice.right as f32, slice.bottom as f32);
+    for x in [near.x, far.x] {
+        draw.line()
+            .start(view.to_screen(Vec2::new(x, 0.0)))
+            .end(view.to_screen(Vec2::new(x, size.y)))
+            .weight(1.0)
+            .color(color);
+    }
+
+    for y in [near.y, far.y] {
+        draw.line()
+            .start(view.to_screen(Vec2::new(0.0, y)))
+            .end(view.to_screen(Vec2::new(size.x, y)))
+            .weight(1.0)
+            .color(color);
+    }
+}
+
+/// Sets the brush color from the composited canvas under `pos`.
+fn pick_color(global_state: &mut GlobalState, state: &EditorState, pos: Vec2) {
+    let (x, y) = (pos.x.floor(), pos.y.floor());
+    if x >= 0.0
+        && y >= 0.0
+        && (x as u32) < state.layers.width()
+        && (y as u32) < state.layers.height()
+    {
+        global_state.brush_color = state.layers.composite_pixel(x as u32, y as u32).0;
+    }
+}
+
+fn heal_dab(
+    canvas: &mut canvas::Canvas,
+    snapshot: &DynamicImage,
+    source: Vec2,
+    pos: Vec2,
+    size: f32,
+) {
+    retouch::heal(
+        canvas,
+        snapshot,
+        (source.x, source.y),
+        pos.x,
+        pos.y,
+        size / 2.0,
+    );
+}
+
+/// Starts or finishes the active tool's action at the cursor, for the left mouse button or
+/// anything standing in for it.
+/// Applies gamepad input to the focused canvas: the stick warps the system cursor, so strokes go
+/// through the same cursor events as the mouse.
+fn poll_gamepad(app: &App, model: &mut Model, dt: f32) {
+    let global = &mut model.global_state;
+    let input = match &mut global.gamepad {
+        Some(gamepad) => gamepad.poll(global.mode, dt),
+        None => return,
+    };
+
+    if let Some(mode) = input.mode {
+        global.mode = mode;
+    }
+    if input.undo {
+        global.commands.push(Command::Undo);
+    }
+    if input.pressure.is_some() || input.button == Some(false) {
+        global.pressure = input.pressure;
+    }
+
+    let focused = match global.focused_editor {
+        Some(focused) => focused,
+        None => return,
+    };
+    if input.motion != Vec2::ZERO {
+        if let Some(window) = app.window(focused) {
+            let rect = window.rect();
+            let pos =
+                (app.mouse.position() + input.motion).clamp(rect.bottom_left(), rect.top_right());
+            window.set_cursor_position_points(pos.x - rect.left(), rect.top() - pos.y);
+        }
+    }
+    if let Some(pressed) = input.button {
+        if let Some(state) = editor_state(&mut model.windows, Some(focused)) {
+            canvas_button(app, &mut model.global_state, state, pressed);
+        }
+    }
+}
+
+/// Applies turned MIDI knobs to the parameters they are bound to, or binds the first one turned
+/// while learning.
+fn poll_midi(global: &mut GlobalState) {
+    let changes = match &global.midi {
+        Some(midi) => midi.poll(),
+        None => return,
+    };
+
+    for (cc, value) in changes {
+        if let Some(param) = global.midi_learn.take() {
+            global.config.midi.bind(param, cc);
+        }
+        match global.config.midi.param(cc) {
+            Some(param @ midi::MidiParam::BrushSize) => global.brush_size = param.value(value),
+            Some(param @ midi::MidiParam::BrushOpacity) => {
+                global.brush_opacity = param.value(value)
+            }
+            Some(param @ midi::MidiParam::Zoom) => {
+                let target = zoom::Target::Scale(param.value(value));
+                global.commands.push(Command::Zoom(target));
+            }
+            None => (),
+        }
+    }
+}
+
+fn canvas_button(app: &App, global: &mut GlobalState, state: &mut EditorState, pressed: bool) {
+    // Other tools edit the layers directly, so they wait for the last stroke to land. Strokes
+    // queue behind it.
+    let waits = !matches!(
+        global.mode,
+        Mode::Move
+            | Mode::Select
+            | Mode::Eyedropper
+            | Mode::Measure
+            | Mode::Note
+            | Mode::Paint
+            | Mode::Erase
+            | Mode::Smudge
+            | Mode::CloneStamp
+    );
+    if pressed && waits && state.stroking() {
+        log::warn!("Still finishing the last stroke");
+        return;
+    }
+    state.selected = pressed;
+    global.last_mouse = None;
+    state.clear_previews();
+    state.offset = app.mouse.position() - state.view.center();
+
+    if state.selected {
+        let pos = canvas_position(app, state);
+        match global.mode {
+            Mode::Move
+            | Mode::Select
+            | Mode::Eyedropper
+            | Mode::Measure
+            | Mode::Transform
+            | Mode::Note
+            | Mode::Scissors
+            | Mode::Cutout => (),
+            Mode::Heal | Mode::CloneStamp if app.keys.mods.alt() => (),
+            // Strokes checkpoint when their worker starts
+            Mode::Paint | Mode::Erase | Mode::Smudge | Mode::CloneStamp => (),
+            _ => state.checkpoint(),
+        }
+        match global.mode {
+            // Alt-click grabs the whole sprite under the cursor
+            Mode::Select if app.keys.mods.alt() => {
+                state.selection_anchor = None;
+                state.selection = (pos.x >= 0.0 && pos.y >= 0.0)
+                    .then(|| {
+                        selection::island(&state.layers.active().pixels, pos.x as u32, pos.y as u32)
+                    })
+                    .flatten();
+            }
+            Mode::Select => {
+                state.selection_anchor = Some(snapped_canvas_position(app, state, global));
+                state.selection = None;
+            }
+            Mode::Scissors => {
+                state.scissors = Some(scissors::Scissors::start(
+                    &state.layers.active().pixels,
+                    pos,
+                ));
+                state.selection = None;
+            }
+            Mode::Cutout => state
+                .scribbles
+                .begin(pos, global.brush_size, app.keys.mods.alt()),
+            Mode::TileStamp => {
+                // A selection doubles as the tile when no stamp was captured
+                if let (None, Some(selection)) = (&global.stamp, &state.selection) {
+                    global.stamp = Some(stamp::Stamp::capture(
+                        &state.layers.active().pixels,
+                        selection,
+                    ));
+                }
+                tile_stamp(global, state, pos);
+            }
+            Mode::Fill if pos.x >= 0.0 && pos.y >= 0.0 => {
+                fill::flood_fill(
+                    &mut canvas::Canvas::new(
+                        &mut state.layers.active_mut().pixels,
+                        state.selection.as_ref(),
+                    ),
+                    (pos.x as u32, pos.y as u32),
+                    global.brush_color,
+                    global.brush_opacity,
+                    global.fill_tolerance as u8,
+                );
+                // The fill can spread anywhere on the canvas
+                state.invalidate();
+            }
+            Mode::Shape(_) | Mode::Gradient | Mode::Measure => {
+                state.shape_anchor = Some(snapped_canvas_position(app, state, global))
+            }
+            Mode::CloneStamp if app.keys.mods.alt() => {
+                global.clone_source = Some(pos);
+                global.clone_offset = None;
+            }
+            Mode::Paint | Mode::Erase | Mode::Smudge | Mode::CloneStamp
+                if global.mode != Mode::CloneStamp || global.clone_source.is_some() =>
+            {
+                if let (Mode::CloneStamp, None, Some(source)) =
+                    (global.mode, global.clone_offset, global.clone_source)
+                {
+                    global.clone_offset = Some(source - pos);
+                }
+                state.end_stroke();
+                let path = stroke::StrokePath {
+                    // Filled in by the worker when the stroke has landed
+                    base: DynamicImage::new_rgba8(0, 0),
+                    layer: state.layers.active,
+                    samples: Vec::new(),
+                    brush: global.brush.clone(),
+                    dab: brush::Dab {
+                        size: global.brush_size,
+                        color: global.brush_color,
+                        opacity: global.brush_opacity,
+                        flow: global.brush_flow,
+                        blend: global.dab_blend(),
+                        spacing: global.brush_spacing,
+                        symmetry: global.symmetry,
+                    },
+                    pressure_size: global.pressure_size,
+                    pressure_opacity: global.pressure_opacity,
+                    selection: state.selection,
+                };
+                let layer = state.layers.active().id;
+                let stabilizer = global.stabilizer as usize;
+                state.painters.push_back(painter::Painter::new(layer, path, stabilizer));
+                state.poll_painters();
+            }
+            Mode::Stamp => {
+                if let Some(stamp) = &global.stamp {
+                    stamp.place(
+                        &mut canvas::Canvas::new(
+                            &mut state.layers.active_mut().pixels,
+                            state.selection.as_ref(),
+                        ),
+                        pos.x as i32,
+                        pos.y as i32,
+                        &global.stamp_jitter,
+                        &mut rand::thread_rng(),
+                    );
+                    let (w, h) = stamp.image.dimensions();
+                    state.mark_dirty(pos.x as i32, pos.y as i32, w.max(h) as i32);
+                    global.last_mouse = Some(pos);
+                }
+            }
+            Mode::Eyedropper => pick_color(global, state, pos),
+            Mode::Note => match notes::note_at(&state.notes, pos, state.view.scale()) {
+                Some(index) => state.selected_note = Some(index),
+                None if !global.note.text.trim().is_empty() => {
+                    state.notes.push(notes::Note {
+                        x: pos.x,
+                        y: pos.y,
+                        text: global.note.text.trim().to_string(),
+                        todo: global.note.todo,
+                        done: false,
+                    });
+                    state.selected_note = Some(state.notes.len() - 1);
+                    state.settings_changed();
+                }
+                None => state.selected_note = None,
+            },
+            Mode::Transform => {
+                if state.transform.is_none() {
+                    state.checkpoint();
+                    let selection = state.selection.take();
+                    state.transform = Some(free_transform::FreeTransform::lift(
+                        &mut state.layers.active_mut().pixels,
+                        selection.as_ref(),
+                    ));
+                    state.invalidate();
+                }
+                if let Some(transform) = &state.transform {
+                    state.transform_drag = transform
+                        .handle_at(pos, state.view.scale())
+                        .map(|handle| (handle, pos, transform.clone()));
+                }
+            }
+            Mode::RedEye => {
+                retouch::remove_red_eye(
+                    &mut canvas::Canvas::new(
+                        &mut state.layers.active_mut().pixels,
+                        state.selection.as_ref(),
+                    ),
+                    pos.x,
+                    pos.y,
+                    global.brush_size,
+                );
+                state.mark_dirty(pos.x as i32, pos.y as i32, global.brush_size.ceil() as i32);
+            }
+            Mode::Heal => {
+                if app.keys.mods.alt() {
+                    global.heal_source = Some(pos);
+                } else if let Some(source) = global.heal_source {
+                    let snapshot = state.layers.active().pixels.clone();
+                    heal_dab(
+                        &mut canvas::Canvas::new(
+                            &mut state.layers.active_mut().pixels,
+                            state.selection.as_ref(),
+                        ),
+                        &snapshot,
+                        source,
+                        pos,
+                        global.brush_size,
+                    );
+                    state.mark_dirty(pos.x as i32, pos.y as i32, global.brush_size.ceil() as i32);
+                    state.heal_stroke = Some((source - pos, snapshot));
+                }
+            }
+            _ => (),
+        }
+    } else {
+        state.selection_anchor = None;
+        if let Some(scissors) = state.scissors.take() {
+            state.selection = scissors.finish();
+        }
+        state.heal_stroke = None;
+        state.end_stroke();
+        state.transform_drag = None;
+
+        match (global.mode, state.shape_anchor.take()) {
+            (Mode::Shape(shape), Some(anchor)) => {
+                let pos = match shape {
+                    shapes::Shape::Line => line_end(app, state, global, anchor),
+                    _ => snapped_canvas_position(app, state, global),
+                };
+                shapes::draw(
+                    &mut canvas::Canvas::new(
+                        &mut state.layers.active_mut().pixels,
+                        state.selection.as_ref(),
+                    ),
+                    shape,
+                    anchor,
+                    pos,
+                    global.brush_size,
+                    global.brush_color,
+                    global.brush_opacity,
+                    global.shape_antialias,
+                );
+                state.mark_dirty_span(anchor, pos, global.brush_size);
+            }
+            (Mode::Gradient, Some(anchor)) => {
+                let pos = snapped_canvas_position(app, state, global);
+                global.gradient.fill(
+                    &mut canvas::Canvas::new(
+                        &mut state.layers.active_mut().pixels,
+                        state.selection.as_ref(),
+                    ),
+                    anchor,
+                    pos,
+                    global.brush_opacity,
+                );
+                // Gradients fill the whole selection, or the canvas without one
+                state.invalidate();
+            }
+            (Mode::Measure, Some(anchor)) => {
+                let pos = snapped_canvas_position(app, state, global);
+                let measurement = measure::Measurement::new(anchor, pos);
+                log::info!("{}", measurement.readout());
+                if global.keep_measurements {
+                    state.annotations.push(measurement);
+                }
+                state.measurement = Some(measurement);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Width of the rulers along the top and left of editor windows.
+const RULER_SIZE: f32 = 20.0;
+/// Screen distance within which the cursor snaps to, or picks up, a guide.
+const SNAP_DISTANCE: f32 = 6.0;
+
+/// The ruler under `mouse`. Guides dragged out of the top ruler are horizontal and ones from the
+/// left ruler are vertical.
+fn ruler_at(window: Rect<f32>, mouse: Point2) -> Option<guides::Orientation> {
+    if mouse.y > window.top() - RULER_SIZE {
+        Some(guides::Orientation::Horizontal)
+    } else if mouse.x < window.left() + RULER_SIZE {
+        Some(guides::Orientation::Vertical)
+    } else {
+        None
+    }
+}
+
+/// The perspective vanishing point under the cursor, which a left click with the Move tool picks
+/// up while the grid is shown.
+fn vanishing_point_grab(app: &App, state: &EditorState, global: &GlobalState) -> Option<usize> {
+    if global.mode != Mode::Move || !global.perspective.visible {
+        return None;
+    }
+    let pos = canvas_position(app, state);
+    global
+        .perspective
+        .find(pos, canvas_size(state), SNAP_DISTANCE / state.view.scale())
+}
+
+/// The guide a left click would pick up: a new one when the cursor is over a ruler, or with the
+/// Move tool the guide under the cursor along with its index.
+fn guide_grab(
+    app: &App,
+    id: WindowId,
+    state: &EditorState,
+    global: &GlobalState,
+) -> Option<(Option<usize>, guides::Guide)> {
+    let pos = canvas_position(app, state);
+    if global.guides.rulers {
+        let window = app.window(id)?.rect();
+        if let Some(orientation) = ruler_at(window, app.mouse.position()) {
+            let value = match orientation {
+                guides::Orientation::Vertical => pos.x,
+                guides::Orientation::Horizontal => pos.y,
+            };
+            let position = value.round().max(0.0) as u32;
+            return Some((
+                None,
+                guides::Guide {
+                    orientation,
+                    position,
+                },
+            ));
+        }
+    }
+    if global.mode != Mode::Move {
+        return None;
+    }
+    let index = guides::find(&state.guides, pos, SNAP_DISTANCE / state.view.scale())?;
+    Some((Some(index), state.guides[index]))
+}
+
+/// Position of the mouse in canvas coordinates, pulled onto guides near it when snapping is on.
+fn snapped_canvas_position(app: &App, state: &EditorState, global: &GlobalState) -> Vec2 {
+    let pos = canvas_position(app, state);
+    if global.guides.snap {
+        guides::snap(&state.guides, pos, SNAP_DISTANCE / state.view.scale())
+    } else {
+        pos
+    }
+}
+
+/// End of a line dragged from `anchor`: the snapped mouse position, pulled onto the perspective
+/// ray through `anchor` when the grid is shown with snapping on.
+fn line_end(app: &App, state: &EditorState, global: &GlobalState, anchor: Vec2) -> Vec2 {
+    let pos = snapped_canvas_position(app, state, global);
+    if global.perspective.visible && global.perspective.snap {
+        global.perspective.snap(anchor, pos, canvas_size(state))
+    } else {
+        pos
+    }
+}
+
+/// Width and height of the canvas in pixels.
+fn canvas_size(state: &EditorState) -> Vec2 {
+    Vec2::new(state.layers.width() as f32, state.layers.height() as f32)
 }
 
-pub fn translate_mouse_center(app: &nannou::App, rect: Rect<f32>) -> Point2 {
-    let pos = -(rect.xy() - Point2::new(app.mouse.x as _, app.mouse.y as _));
-    Point2::new(pos.x, pos.y)
+/// Position of the mouse in canvas pixel coordinates, with the origin at the top left.
+fn canvas_position(app: &App, state: &EditorState) -> Vec2 {
+    state.view.to_canvas(app.mouse.position())
 }
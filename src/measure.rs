@@ -0,0 +1,45 @@
+//! Measuring between two points on the canvas: the distance, the offset along each axis and the
+//! angle of the line joining them.
+
+use nannou::prelude::Vec2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Measurement {
+    pub start: Vec2,
+    pub end: Vec2,
+}
+
+impl Measurement {
+    pub fn new(start: Vec2, end: Vec2) -> Measurement {
+        Measurement { start, end }
+    }
+
+    /// Offset from the start to the end, in canvas pixels with y pointing down.
+    pub fn delta(&self) -> Vec2 {
+        self.end - self.start
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.delta().length()
+    }
+
+    /// Angle of the line in degrees, counter-clockwise from pointing right as seen on screen, in
+    /// (-180, 180].
+    pub fn angle(&self) -> f32 {
+        let delta = self.delta();
+        // Canvas y grows downwards, so flip it for the on-screen angle
+        (-delta.y).atan2(delta.x).to_degrees()
+    }
+
+    /// Distance, offsets and angle as shown in the status bar.
+    pub fn readout(&self) -> String {
+        let delta = self.delta();
+        format!(
+            "Distance: {:.1} px   dx: {:.0}   dy: {:.0}   Angle: {:.1}°",
+            self.distance(),
+            delta.x,
+            delta.y,
+            self.angle()
+        )
+    }
+}
@@ -0,0 +1,172 @@
+//! MIDI controller input: control change knobs and faders bound to numeric parameters, so a
+//! hardware controller can drive the brush and zoom live. Reading MIDI needs the `midi` feature;
+//! without it enabling MIDI input only logs a warning.
+
+use std::sync::mpsc::Receiver;
+
+use serde::{Deserialize, Serialize};
+
+use crate::viewport;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MidiParam {
+    BrushSize,
+    BrushOpacity,
+    Zoom,
+}
+
+impl MidiParam {
+    pub const ALL: [MidiParam; 3] = [
+        MidiParam::BrushSize,
+        MidiParam::BrushOpacity,
+        MidiParam::Zoom,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MidiParam::BrushSize => "Brush Size",
+            MidiParam::BrushOpacity => "Opacity",
+            MidiParam::Zoom => "Zoom",
+        }
+    }
+
+    /// The parameter's value with the knob at `value` (0-127), spanning the same range as its
+    /// Workbench slider.
+    pub fn value(&self, value: u8) -> f32 {
+        let t = value.min(127) as f32 / 127.0;
+        let (min, max) = match self {
+            MidiParam::BrushSize => (1.0, 100.0),
+            MidiParam::BrushOpacity => (0.0, 1.0),
+            MidiParam::Zoom => (viewport::MIN_ZOOM, viewport::MAX_ZOOM),
+        };
+        min + (max - min) * t
+    }
+}
+
+/// Which control change number drives each parameter.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiBindings {
+    /// Connect to a MIDI controller on startup.
+    pub enabled: bool,
+    /// Connect to the first input port whose name contains this, or the first port if empty.
+    pub port: String,
+    pub brush_size: Option<u8>,
+    pub brush_opacity: Option<u8>,
+    pub zoom: Option<u8>,
+}
+
+impl Default for MidiBindings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: String::new(),
+            // The first three knobs on most controllers
+            brush_size: Some(1),
+            brush_opacity: Some(2),
+            zoom: Some(3),
+        }
+    }
+}
+
+impl MidiBindings {
+    pub fn cc(&self, param: MidiParam) -> Option<u8> {
+        match param {
+            MidiParam::BrushSize => self.brush_size,
+            MidiParam::BrushOpacity => self.brush_opacity,
+            MidiParam::Zoom => self.zoom,
+        }
+    }
+
+    /// The parameter bound to control change `cc`.
+    pub fn param(&self, cc: u8) -> Option<MidiParam> {
+        MidiParam::ALL
+            .into_iter()
+            .find(|param| self.cc(*param) == Some(cc))
+    }
+
+    /// Binds `cc` to `param`, unbinding it from any other parameter.
+    pub fn bind(&mut self, param: MidiParam, cc: u8) {
+        for other in MidiParam::ALL {
+            if self.cc(other) == Some(cc) {
+                *self.slot(other) = None;
+            }
+        }
+        *self.slot(param) = Some(cc);
+    }
+
+    fn slot(&mut self, param: MidiParam) -> &mut Option<u8> {
+        match param {
+            MidiParam::BrushSize => &mut self.brush_size,
+            MidiParam::BrushOpacity => &mut self.brush_opacity,
+            MidiParam::Zoom => &mut self.zoom,
+        }
+    }
+}
+
+/// An open connection to a MIDI input port. Control changes arrive on the MIDI thread and are
+/// queued until the next [`MidiInput::poll`].
+pub struct MidiInput {
+    #[cfg(feature = "midi")]
+    _connection: midir::MidiInputConnection<()>,
+    changes: Receiver<(u8, u8)>,
+}
+
+impl MidiInput {
+    /// Connects to the port matching `port`. `None` if there is no such port or MIDI is
+    /// unavailable.
+    #[cfg(feature = "midi")]
+    pub fn connect(port: &str) -> Option<MidiInput> {
+        let input = midir::MidiInput::new("image_editor")
+            .map_err(|e| log::warn!("MIDI unavailable: {}", e))
+            .ok()?;
+        let ports = input.ports();
+        let found = ports
+            .iter()
+            .find(|p| input.port_name(p).map_or(false, |name| name.contains(port)));
+        let found = match found {
+            Some(found) => found,
+            None => {
+                log::warn!("No MIDI input port matching {:?}", port);
+                return None;
+            }
+        };
+        let name = input.port_name(found).unwrap_or_default();
+
+        let (sender, changes) = std::sync::mpsc::channel();
+        let connection = input
+            .connect(
+                found,
+                "image_editor-input",
+                move |_, message, _| {
+                    // Control change on any channel: status, controller number, value
+                    if let [status, cc, value] = *message {
+                        if status & 0xF0 == 0xB0 {
+                            let _ = sender.send((cc, value));
+                        }
+                    }
+                },
+                (),
+            )
+            .map_err(|e| log::warn!("Failed to connect to MIDI port {}: {}", name, e))
+            .ok()?;
+        log::info!("Listening to MIDI port {}", name);
+
+        Some(MidiInput {
+            _connection: connection,
+            changes,
+        })
+    }
+
+    #[cfg(not(feature = "midi"))]
+    pub fn connect(_port: &str) -> Option<MidiInput> {
+        log::warn!("MIDI input needs the editor to be built with the `midi` feature");
+        None
+    }
+
+    /// Control changes since the last poll, as (controller number, value) pairs, oldest first.
+    pub fn poll(&self) -> Vec<(u8, u8)> {
+        self.changes.try_iter().collect()
+    }
+}
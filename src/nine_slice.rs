@@ -0,0 +1,115 @@
+use std::io;
+use std::path::Path;
+
+use nannou::image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+use serde::{Deserialize, Serialize};
+
+/// Slice guides for a 9-slice sprite, in pixels measured inward from each edge.
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct NineSlice {
+    pub left: u32,
+    pub right: u32,
+    pub top: u32,
+    pub bottom: u32,
+}
+
+impl Default for NineSlice {
+    fn default() -> Self {
+        Self {
+            left: 8,
+            right: 8,
+            top: 8,
+            bottom: 8,
+        }
+    }
+}
+
+impl NineSlice {
+    /// Clamps the guides so the fixed corners never overlap on an image of the given size.
+    pub fn clamped(&self, width: u32, height: u32) -> NineSlice {
+        let left = self.left.min(width);
+        let top = self.top.min(height);
+        NineSlice {
+            left,
+            right: self.right.min(width - left),
+            top,
+            bottom: self.bottom.min(height - top),
+        }
+    }
+
+    /// Stretches `image` to `width` x `height`, keeping the corners fixed, stretching the edges
+    /// along one axis and the center along both.
+    pub fn render(&self, image: &DynamicImage, width: u32, height: u32) -> DynamicImage {
+        let (w, h) = image.dimensions();
+        let s = self.clamped(w, h);
+        // Corners are kept whole unless the target is smaller than them
+        let (left, right) = (s.left.min(width / 2), s.right.min(width - width / 2));
+        let (top, bottom) = (s.top.min(height / 2), s.bottom.min(height - height / 2));
+
+        let src_cols = [
+            (0, s.left),
+            (s.left, w - s.left - s.right),
+            (w - s.right, s.right),
+        ];
+        let src_rows = [
+            (0, s.top),
+            (s.top, h - s.top - s.bottom),
+            (h - s.bottom, s.bottom),
+        ];
+        let dst_cols = [
+            (0, left),
+            (left, width - left - right),
+            (width - right, right),
+        ];
+        let dst_rows = [
+            (0, top),
+            (top, height - top - bottom),
+            (height - bottom, bottom),
+        ];
+
+        let mut out = RgbaImage::new(width, height);
+        for ((sy, sh), (dy, dh)) in src_rows.iter().zip(dst_rows.iter()) {
+            for ((sx, sw), (dx, dw)) in src_cols.iter().zip(dst_cols.iter()) {
+                if *sw == 0 || *sh == 0 || *dw == 0 || *dh == 0 {
+                    continue;
+                }
+                let patch = image.crop_imm(*sx, *sy, *sw, *sh).resize_exact(
+                    *dw,
+                    *dh,
+                    imageops::FilterType::Nearest,
+                );
+                imageops::replace(&mut out, &patch.to_rgba8(), *dx, *dy);
+            }
+        }
+
+        DynamicImage::ImageRgba8(out)
+    }
+}
+
+#[derive(Serialize)]
+struct SliceMetadata<'a> {
+    image: &'a str,
+    width: u32,
+    height: u32,
+    slice: NineSlice,
+}
+
+/// Saves `image` to `path` as a PNG and writes the slice guides next to it as `<name>.json`.
+pub fn export(image: &DynamicImage, slice: &NineSlice, path: &Path) -> io::Result<()> {
+    let path = path.with_extension("png");
+    image
+        .save(&path)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    let metadata = SliceMetadata {
+        image: path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default(),
+        width: image.width(),
+        height: image.height(),
+        slice: slice.clamped(image.width(), image.height()),
+    };
+    let json = serde_json::to_string_pretty(&metadata)?;
+    std::fs::write(path.with_extension("json"), json)
+}
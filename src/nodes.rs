@@ -0,0 +1,273 @@
+//! Experimental procedural texture graph. Nodes are evaluated per pixel in normalized `0..1`
+//! coordinates, so the same graph can be baked at any resolution. Nodes refer to their inputs by
+//! index, and the Workbench Nodes panel edits them as a list.
+
+use nannou::image::{DynamicImage, RgbaImage};
+
+pub type NodeId = usize;
+
+#[derive(Clone)]
+pub enum Node {
+    /// Smooth value noise in grayscale. `scale` is the number of noise cells across the texture.
+    Noise { scale: f32, seed: u32 },
+    /// Linear gradient between two colors along `angle` (radians).
+    Gradient {
+        from: [f32; 4],
+        to: [f32; 4],
+        angle: f32,
+    },
+    /// Mixes two inputs. With `mask` set, its red channel is used as the per-pixel factor.
+    Blend {
+        a: NodeId,
+        b: NodeId,
+        factor: f32,
+        mask: Option<NodeId>,
+    },
+    /// Samples `input` through an offset, scale and rotation around the texture center.
+    Transform {
+        input: NodeId,
+        offset: (f32, f32),
+        scale: f32,
+        rotation: f32,
+    },
+}
+
+/// An input of a node, as edited in the Nodes panel.
+pub enum Input<'a> {
+    Required(&'a mut NodeId),
+    Optional(&'a mut Option<NodeId>),
+}
+
+impl Node {
+    /// One node of each kind, reading from `input` where they take inputs.
+    pub fn kinds(input: NodeId) -> [Node; 4] {
+        [
+            Node::Noise {
+                scale: 8.0,
+                seed: 1,
+            },
+            Node::Gradient {
+                from: [0.0, 0.0, 0.0, 1.0],
+                to: [1.0, 1.0, 1.0, 1.0],
+                angle: 0.0,
+            },
+            Node::Blend {
+                a: input,
+                b: input,
+                factor: 0.5,
+                mask: None,
+            },
+            Node::Transform {
+                input,
+                offset: (0.0, 0.0),
+                scale: 1.0,
+                rotation: 0.0,
+            },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Node::Noise { .. } => "Noise",
+            Node::Gradient { .. } => "Gradient",
+            Node::Blend { .. } => "Blend",
+            Node::Transform { .. } => "Transform",
+        }
+    }
+
+    /// The numeric settings with their labels and ranges, for sliders.
+    pub fn params_mut(&mut self) -> Vec<(&'static str, &mut f32, f32, f32)> {
+        use std::f32::consts::PI;
+        match self {
+            Node::Noise { scale, .. } => vec![("Scale", scale, 1.0, 64.0)],
+            Node::Gradient { angle, .. } => vec![("Angle", angle, 0.0, 2.0 * PI)],
+            Node::Blend { factor, .. } => vec![("Factor", factor, 0.0, 1.0)],
+            Node::Transform {
+                offset,
+                scale,
+                rotation,
+                ..
+            } => vec![
+                ("Offset X", &mut offset.0, -1.0, 1.0),
+                ("Offset Y", &mut offset.1, -1.0, 1.0),
+                ("Scale", scale, 0.1, 8.0),
+                ("Rotation", rotation, -PI, PI),
+            ],
+        }
+    }
+
+    pub fn inputs_mut(&mut self) -> Vec<(&'static str, Input)> {
+        match self {
+            Node::Noise { .. } | Node::Gradient { .. } => Vec::new(),
+            Node::Blend { a, b, mask, .. } => vec![
+                ("A", Input::Required(a)),
+                ("B", Input::Required(b)),
+                ("Mask", Input::Optional(mask)),
+            ],
+            Node::Transform { input, .. } => vec![("Input", Input::Required(input))],
+        }
+    }
+
+    fn inputs(&self) -> Vec<NodeId> {
+        match self {
+            Node::Noise { .. } | Node::Gradient { .. } => Vec::new(),
+            Node::Blend { a, b, mask, .. } => {
+                [Some(*a), Some(*b), *mask].into_iter().flatten().collect()
+            }
+            Node::Transform { input, .. } => vec![*input],
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct NodeGraph {
+    pub nodes: Vec<Node>,
+    pub output: NodeId,
+}
+
+impl Default for NodeGraph {
+    /// Cloudy noise tinted with a vertical sky gradient.
+    fn default() -> Self {
+        Self {
+            nodes: vec![
+                Node::Noise {
+                    scale: 8.0,
+                    seed: 1,
+                },
+                Node::Gradient {
+                    from: [0.2, 0.4, 0.9, 1.0],
+                    to: [0.8, 0.9, 1.0, 1.0],
+                    angle: std::f32::consts::FRAC_PI_2,
+                },
+                Node::Blend {
+                    a: 1,
+                    b: 0,
+                    factor: 0.35,
+                    mask: None,
+                },
+            ],
+            output: 2,
+        }
+    }
+}
+
+impl NodeGraph {
+    /// Removes node `id`, renumbering the ones after it. Refused while another node reads from
+    /// it or it is the last node left.
+    pub fn remove(&mut self, id: NodeId) -> Result<(), String> {
+        if self.nodes.len() == 1 {
+            return Err("the graph needs a node".to_string());
+        }
+        if let Some(user) = (0..self.nodes.len())
+            .find(|&other| other != id && self.nodes[other].inputs().contains(&id))
+        {
+            return Err(format!("node {} reads from it", user));
+        }
+
+        self.nodes.remove(id);
+        let renumber = |n: &mut NodeId| {
+            if *n > id {
+                *n -= 1;
+            }
+        };
+        for node in &mut self.nodes {
+            for (_, input) in node.inputs_mut() {
+                match input {
+                    Input::Required(n) => renumber(n),
+                    Input::Optional(Some(n)) => renumber(n),
+                    Input::Optional(None) => (),
+                }
+            }
+        }
+        if self.output == id {
+            self.output = self.nodes.len() - 1;
+        } else {
+            renumber(&mut self.output);
+        }
+        Ok(())
+    }
+
+    fn sample(&self, id: NodeId, x: f32, y: f32, depth: usize) -> [f32; 4] {
+        // Guard against cycles in hand-built graphs
+        if depth > self.nodes.len() {
+            return [0.0; 4];
+        }
+
+        match &self.nodes[id] {
+            Node::Noise { scale, seed } => {
+                let v = value_noise(x * scale, y * scale, *seed);
+                [v, v, v, 1.0]
+            }
+            Node::Gradient { from, to, angle } => {
+                let (sin, cos) = angle.sin_cos();
+                // Project onto the gradient direction, remapped so the corners span 0..1
+                let t = ((x - 0.5) * cos + (y - 0.5) * sin) / (cos.abs() + sin.abs()) + 0.5;
+                mix(*from, *to, t.clamp(0.0, 1.0))
+            }
+            Node::Blend { a, b, factor, mask } => {
+                let t = match mask {
+                    Some(mask) => self.sample(*mask, x, y, depth + 1)[0] * factor,
+                    None => *factor,
+                };
+                mix(
+                    self.sample(*a, x, y, depth + 1),
+                    self.sample(*b, x, y, depth + 1),
+                    t,
+                )
+            }
+            Node::Transform {
+                input,
+                offset,
+                scale,
+                rotation,
+            } => {
+                let (sin, cos) = (-rotation).sin_cos();
+                let (dx, dy) = ((x - 0.5 - offset.0) / scale, (y - 0.5 - offset.1) / scale);
+                let (sx, sy) = (dx * cos - dy * sin + 0.5, dx * sin + dy * cos + 0.5);
+                self.sample(*input, sx.rem_euclid(1.0), sy.rem_euclid(1.0), depth + 1)
+            }
+        }
+    }
+
+    /// Evaluates the output node for every pixel of a `width` x `height` image.
+    pub fn bake(&self, width: u32, height: u32) -> DynamicImage {
+        let img = RgbaImage::from_fn(width, height, |x, y| {
+            let color = self.sample(
+                self.output,
+                (x as f32 + 0.5) / width as f32,
+                (y as f32 + 0.5) / height as f32,
+                0,
+            );
+            nannou::image::Rgba(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+        });
+        DynamicImage::ImageRgba8(img)
+    }
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut out = a;
+    for i in 0..4 {
+        out[i] = a[i] + (b[i] - a[i]) * t;
+    }
+    out
+}
+
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32)
+        .wrapping_mul(374761393)
+        .wrapping_add((y as u32).wrapping_mul(668265263))
+        .wrapping_add(seed.wrapping_mul(2246822519));
+    h = (h ^ (h >> 13)).wrapping_mul(1274126177);
+    (h ^ (h >> 16)) as f32 / u32::MAX as f32
+}
+
+fn value_noise(x: f32, y: f32, seed: u32) -> f32 {
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, ty) = (smooth(x - x0 as f32), smooth(y - y0 as f32));
+
+    let top = hash(x0, y0, seed) + (hash(x0 + 1, y0, seed) - hash(x0, y0, seed)) * tx;
+    let bottom =
+        hash(x0, y0 + 1, seed) + (hash(x0 + 1, y0 + 1, seed) - hash(x0, y0 + 1, seed)) * tx;
+    top + (bottom - top) * ty
+}
@@ -0,0 +1,97 @@
+//! Notes pinned to canvas positions, for feedback left on a document. Notes can be plain remarks or
+//! TODO markers that get checked off. They are saved in projects but only ever drawn over the
+//! canvas, so exports never include them.
+
+use nannou::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// Radius of the markers drawn on the canvas, which is also how far they can be clicked from, in
+/// screen points.
+pub const MARKER_RADIUS: f32 = 6.0;
+/// Characters of a note shown on its button in the notes panel.
+const LABEL_LENGTH: usize = 24;
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Note {
+    /// Canvas pixel position.
+    pub x: f32,
+    pub y: f32,
+    pub text: String,
+    #[serde(default)]
+    pub todo: bool,
+    /// Whether a TODO has been checked off.
+    #[serde(default)]
+    pub done: bool,
+}
+
+impl Note {
+    pub fn position(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    /// The start of the text, with a checkbox in front of TODOs.
+    pub fn label(&self) -> String {
+        let mut text: String = self.text.chars().take(LABEL_LENGTH).collect();
+        if self.text.chars().count() > LABEL_LENGTH {
+            text.push_str("...");
+        }
+        match (self.todo, self.done) {
+            (true, true) => format!("[x] {}", text),
+            (true, false) => format!("[ ] {}", text),
+            (false, _) => text,
+        }
+    }
+}
+
+/// The note whose marker is under canvas position `pos` at canvas zoom `zoom`, topmost first.
+pub fn note_at(notes: &[Note], pos: Vec2, zoom: f32) -> Option<usize> {
+    let reach = MARKER_RADIUS / zoom;
+    notes
+        .iter()
+        .rposition(|note| note.position().distance(pos) <= reach)
+}
+
+/// Settings of the notes panel for the next note placed.
+#[derive(Default)]
+pub struct NoteOptions {
+    pub text: String,
+    pub todo: bool,
+}
+
+/// Changes made to the focused document's notes from the Workbench.
+#[derive(Clone, Copy, Debug)]
+pub enum NoteOp {
+    Select(usize),
+    Delete(usize),
+    ToggleDone(usize),
+    /// Deletes every checked off TODO.
+    ClearDone,
+}
+
+impl NoteOp {
+    /// Applies the change, keeping `selected` pointing at the same note.
+    pub fn apply(self, notes: &mut Vec<Note>, selected: &mut Option<usize>) {
+        match self {
+            NoteOp::Select(i) => *selected = (i < notes.len()).then(|| i),
+            NoteOp::Delete(i) if i < notes.len() => {
+                notes.remove(i);
+                *selected = match *selected {
+                    Some(s) if s == i => None,
+                    Some(s) if s > i => Some(s - 1),
+                    s => s,
+                };
+            }
+            NoteOp::ToggleDone(i) => {
+                if let Some(note) = notes.get_mut(i) {
+                    note.done = !note.done;
+                }
+            }
+            NoteOp::ClearDone => {
+                let kept = selected.and_then(|s| notes.get(s)).cloned();
+                notes.retain(|note| !(note.todo && note.done));
+                *selected = kept.and_then(|kept| notes.iter().position(|n| *n == kept));
+            }
+            NoteOp::Delete(_) => (),
+        }
+    }
+}
@@ -0,0 +1,264 @@
+//! OpenRaster (.ora), the layered format shared by Krita, GIMP and MyPaint: a zip of one PNG per
+//! layer and a `stack.xml` listing them top first with their opacity, visibility and blend mode.
+//! Nested stacks are flattened into the one layer list, and blend modes without an equivalent
+//! here are read as normal.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+use nannou::image::{
+    imageops, DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat, RgbaImage,
+};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::compositing::BlendMode;
+use crate::layers::{Layer, LayerStack};
+
+const MIMETYPE: &str = "image/openraster";
+/// Longest side of the thumbnail the format requires.
+const THUMBNAIL_SIZE: u32 = 256;
+
+fn composite_op(mode: BlendMode) -> &'static str {
+    match mode {
+        BlendMode::Normal => "svg:src-over",
+        BlendMode::Multiply => "svg:multiply",
+        BlendMode::Screen => "svg:screen",
+        BlendMode::Overlay => "svg:overlay",
+        BlendMode::Add => "svg:plus",
+    }
+}
+
+fn blend_mode(op: &str) -> BlendMode {
+    BlendMode::ALL
+        .into_iter()
+        .find(|mode| composite_op(*mode) == op)
+        .unwrap_or(BlendMode::Normal)
+}
+
+pub fn save(layers: &LayerStack, path: &Path) -> io::Result<()> {
+    write(layers, File::create(path)?)
+}
+
+fn write<W: Write + Seek>(layers: &LayerStack, file: W) -> io::Result<()> {
+    let mut zip = ZipWriter::new(file);
+
+    // The mimetype has to come first and uncompressed so the file can be identified
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(MIMETYPE.as_bytes())?;
+
+    let mut stack = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <image version=\"0.0.5\" w=\"{}\" h=\"{}\">\n<stack>\n",
+        layers.width(),
+        layers.height()
+    );
+    for (i, layer) in layers.layers.iter().enumerate().rev() {
+        let src = format!("data/layer{}.png", i);
+        stack.push_str(&format!(
+            "<layer name=\"{}\" src=\"{}\" x=\"0\" y=\"0\" opacity=\"{}\" visibility=\"{}\" \
+             composite-op=\"{}\"/>\n",
+            escape(&layer.name),
+            src,
+            layer.opacity,
+            if layer.visible { "visible" } else { "hidden" },
+            composite_op(layer.blend),
+        ));
+        write_png(&mut zip, &src, &layer.pixels)?;
+    }
+    stack.push_str("</stack>\n</image>\n");
+
+    zip.start_file("stack.xml", FileOptions::default())?;
+    zip.write_all(stack.as_bytes())?;
+
+    let merged = layers.composite();
+    write_png(&mut zip, "mergedimage.png", &merged)?;
+    let thumbnail = merged.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE);
+    write_png(&mut zip, "Thumbnails/thumbnail.png", &thumbnail)?;
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn write_png<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    name: &str,
+    image: &DynamicImage,
+) -> io::Result<()> {
+    let mut png = Vec::new();
+    image
+        .write_to(&mut png, ImageOutputFormat::Png)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    // PNG data is already compressed
+    zip.start_file(
+        name,
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(&png)
+}
+
+pub fn open(path: &Path) -> io::Result<LayerStack> {
+    read(File::open(path)?)
+}
+
+fn read<R: Read + Seek>(reader: R) -> io::Result<LayerStack> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut zip = ZipArchive::new(reader)?;
+
+    let mut stack = String::new();
+    zip.by_name("stack.xml")?.read_to_string(&mut stack)?;
+    let stack = roxmltree::Document::parse(&stack)
+        .map_err(|e| invalid(format!("stack.xml is not valid XML: {}", e)))?;
+
+    let image = stack.root_element();
+    if !image.has_tag_name("image") {
+        return Err(invalid("stack.xml has no image element".to_string()));
+    }
+    let size = |name| {
+        image
+            .attribute(name)
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|v| *v > 0)
+            .ok_or_else(|| invalid("image element has no size".to_string()))
+    };
+    let (width, height) = (size("w")?, size("h")?);
+
+    let mut layers = Vec::new();
+    // Document order lists the top layer first, nested stacks included
+    for node in image.descendants().filter(|n| n.has_tag_name("layer")) {
+        let src = match node.attribute("src") {
+            Some(src) => src,
+            None => continue,
+        };
+        let mut png = Vec::new();
+        zip.by_name(src)?.read_to_end(&mut png)?;
+        let pixels = nannou::image::load_from_memory_with_format(&png, ImageFormat::Png)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        // Layers may be smaller than the image and offset within it, and within their stacks
+        let position = |name| {
+            node.ancestors()
+                .filter_map(|n| n.attribute(name).and_then(|v| v.parse::<i64>().ok()))
+                .sum::<i64>()
+        };
+        let (x, y) = (position("x"), position("y"));
+        let mut canvas = RgbaImage::new(width, height);
+        let source = pixels.crop_imm(
+            (-x).max(0) as u32,
+            (-y).max(0) as u32,
+            pixels.width(),
+            pixels.height(),
+        );
+        if x < width as i64 && y < height as i64 {
+            imageops::replace(
+                &mut canvas,
+                &source.to_rgba8(),
+                x.max(0) as u32,
+                y.max(0) as u32,
+            );
+        }
+
+        let name = match node.attribute("name") {
+            Some(name) => name.to_string(),
+            None => format!("Layer {}", layers.len()),
+        };
+        let mut layer = Layer::new(name, DynamicImage::ImageRgba8(canvas));
+        layer.opacity = node
+            .attribute("opacity")
+            .and_then(|v| v.parse::<f32>().ok())
+            .unwrap_or(1.0)
+            .clamp(0.0, 1.0);
+        layer.visible = node.attribute("visibility") != Some("hidden");
+        layer.blend = node
+            .attribute("composite-op")
+            .map(blend_mode)
+            .unwrap_or(BlendMode::Normal);
+        layers.push(layer);
+    }
+
+    if layers.is_empty() {
+        return Err(invalid("stack.xml lists no layers".to_string()));
+    }
+    // stack.xml lists the top layer first
+    layers.reverse();
+    let active = layers.len() - 1;
+    Ok(LayerStack { layers, active })
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use nannou::image::Rgba;
+
+    #[test]
+    fn layers_round_trip() {
+        let mut layers = LayerStack::new(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+            3,
+            2,
+            Rgba([255, 255, 255, 255]),
+        )));
+        let mut top = Layer::new(
+            "Ink & \"paint\" <1>".to_string(),
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(3, 2, Rgba([10, 20, 30, 128]))),
+        );
+        top.opacity = 0.5;
+        top.visible = false;
+        top.blend = BlendMode::Multiply;
+        layers.layers.push(top);
+
+        let mut file = Cursor::new(Vec::new());
+        write(&layers, &mut file).unwrap();
+        file.set_position(0);
+        let opened = read(file).unwrap();
+
+        assert_eq!(opened.layers.len(), 2);
+        for (read, saved) in opened.layers.iter().zip(&layers.layers) {
+            assert_eq!(read.name, saved.name);
+            assert_eq!(read.opacity, saved.opacity);
+            assert_eq!(read.visible, saved.visible);
+            assert_eq!(read.blend, saved.blend);
+            assert_eq!(read.pixels.to_rgba8(), saved.pixels.to_rgba8());
+        }
+    }
+
+    #[test]
+    fn nested_stacks_are_flattened_with_their_offsets() {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba([1, 2, 3, 255])))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+        let stack = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <image w="4" h="4"><stack>
+              <stack x="1" y="2"><layer name="Inner" src="a.png" x="1"/></stack>
+              <layer name="Outer" src="a.png"/>
+            </stack></image>"#;
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file("stack.xml", FileOptions::default()).unwrap();
+        zip.write_all(stack.as_bytes()).unwrap();
+        zip.start_file("a.png", FileOptions::default()).unwrap();
+        zip.write_all(&png).unwrap();
+        let mut file = zip.finish().unwrap();
+        file.set_position(0);
+        let layers = read(file).unwrap();
+
+        let names: Vec<_> = layers.layers.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["Outer", "Inner"]);
+        let inner = layers.layers[1].pixels.to_rgba8();
+        assert_eq!(inner.get_pixel(2, 2).0, [1, 2, 3, 255]);
+        assert_eq!(inner.get_pixel(0, 0).0, [0, 0, 0, 0]);
+    }
+}
@@ -0,0 +1,222 @@
+//! Paint strokes rasterized off the UI thread. The event handler only queues where the pointer
+//! went along with the dab settings at that moment. A worker owns a copy of the layer for the
+//! length of the stroke, stamps the dabs into it and sends back the pixels it changed, which
+//! `update` merges into the layer. An expensive brush then trails the pointer for a moment
+//! instead of holding up input.
+//!
+//! The document's layers are locked until a stroke has landed: commands, finished jobs and undo
+//! wait for it, so nothing else changes the pixels the worker started from.
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+use nannou::image::{DynamicImage, GenericImage, GenericImageView, RgbaImage};
+use nannou::prelude::Vec2;
+
+use crate::brush::{self, BrushType, Dab};
+use crate::canvas::Canvas;
+use crate::dirty::DirtyRect;
+use crate::selection::Selection;
+use crate::stroke::{Stroke, StrokePath};
+
+/// A pointer position during a stroke and the dab settings it was sampled with.
+pub struct Sample {
+    /// Canvas position, already clamped to the canvas.
+    pub pos: Vec2,
+    pub dab: Dab,
+    pub pressure: f32,
+}
+
+/// Pixels the worker changed.
+pub struct Patch {
+    pub rect: DirtyRect,
+    pub pixels: RgbaImage,
+    /// Which pixels of `rect` the stroke painted, row by row. The others are left as they are.
+    pub painted: Vec<bool>,
+    /// Points the stroke passed through and their pressure, for the stroke's recorded path.
+    pub points: Vec<(Vec2, f32)>,
+}
+
+impl Patch {
+    /// Writes the painted pixels over `pixels`.
+    pub fn merge(&self, pixels: &mut DynamicImage) {
+        let (x0, y0) = (self.rect.x0, self.rect.y0);
+        for (x, y, pixel) in self.pixels.enumerate_pixels() {
+            if self.painted[(y * self.rect.width() + x) as usize] {
+                pixels.put_pixel(x0 + x, y0 + y, *pixel);
+            }
+        }
+    }
+}
+
+enum Message {
+    Patch(Patch),
+    /// The stroke is painted, along with the layer's pixels before it.
+    Done(RgbaImage),
+}
+
+/// A stroke on one layer, waiting for the layer or painting on it.
+pub struct Painter {
+    /// Id of the layer the stroke paints on.
+    pub layer: u64,
+    /// The recorded stroke, handed over once it has landed.
+    path: StrokePath,
+    stabilizer: usize,
+    samples: Option<Sender<Sample>>,
+    /// Samples for the worker, held here until it starts.
+    queued: Option<Receiver<Sample>>,
+    messages: Option<Receiver<Message>>,
+}
+
+impl Painter {
+    /// A stroke recorded into `path`, painting on layer `layer` once started.
+    pub fn new(layer: u64, path: StrokePath, stabilizer: usize) -> Painter {
+        let (samples, queued) = mpsc::channel();
+        Painter {
+            layer,
+            path,
+            stabilizer,
+            samples: Some(samples),
+            queued: Some(queued),
+            messages: None,
+        }
+    }
+
+    pub fn started(&self) -> bool {
+        self.messages.is_some()
+    }
+
+    /// Starts painting on `pixels`, the layer's pixels as they are now.
+    pub fn start(&mut self, pixels: DynamicImage) {
+        let queued = match self.queued.take() {
+            Some(queued) => queued,
+            None => return,
+        };
+        let (selection, brush) = (self.path.selection, self.path.brush.clone());
+        let stabilizer = self.stabilizer;
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || paint(pixels, selection, brush, stabilizer, queued, sender));
+        self.messages = Some(messages);
+    }
+
+    pub fn send(&self, sample: Sample) {
+        if let Some(samples) = &self.samples {
+            // Only fails if the worker panicked, which `poll` reports
+            let _ = samples.send(sample);
+        }
+    }
+
+    /// Ends the stroke. The worker still paints the samples queued so far.
+    pub fn end(&mut self) {
+        self.samples = None;
+    }
+
+    /// Patches painted since the last call, oldest first, and whether the worker is done.
+    pub fn poll(&mut self) -> (Vec<Patch>, bool) {
+        let messages = match &self.messages {
+            Some(messages) => messages,
+            None => return (Vec::new(), false),
+        };
+        let mut patches = Vec::new();
+        loop {
+            match messages.try_recv() {
+                Ok(Message::Patch(patch)) => {
+                    for &(point, pressure) in &patch.points {
+                        self.path.push(point, pressure);
+                    }
+                    patches.push(patch);
+                }
+                Ok(Message::Done(base)) => {
+                    self.path.base = DynamicImage::ImageRgba8(base);
+                    return (patches, true);
+                }
+                Err(TryRecvError::Empty) => return (patches, false),
+                Err(TryRecvError::Disconnected) => {
+                    log::error!("Stroke worker panicked");
+                    return (patches, true);
+                }
+            }
+        }
+    }
+
+    /// The recorded stroke, for stroke post-processing once it has landed.
+    pub fn into_path(self) -> StrokePath {
+        self.path
+    }
+}
+
+fn paint(
+    mut pixels: DynamicImage,
+    selection: Option<Selection>,
+    brush: BrushType,
+    stabilizer: usize,
+    samples: Receiver<Sample>,
+    messages: Sender<Message>,
+) {
+    let brush = brush.as_brush();
+    let mut stroke = Stroke::begin(&pixels);
+    let (width, height) = pixels.dimensions();
+    // Whatever queued up while the last batch was painting goes into one patch, so a slow brush
+    // catches up instead of falling further behind
+    while let Ok(first) = samples.recv() {
+        let mut rect: Option<DirtyRect> = None;
+        let mut points = Vec::new();
+        for sample in std::iter::once(first).chain(samples.try_iter()) {
+            let advanced = stroke.advance(sample.pos, stabilizer);
+            let mut canvas = Canvas::new(&mut pixels, selection.as_ref());
+            for pair in advanced.windows(2) {
+                brush::stroke_segment(
+                    &mut canvas,
+                    &mut stroke,
+                    brush,
+                    pair[0],
+                    pair[1],
+                    &sample.dab,
+                );
+            }
+
+            // Mirrored dabs land away from the pointer
+            let reach = brush.radius(sample.dab.size).ceil() as i32 + 1;
+            for &point in &advanced {
+                let mirrored = sample
+                    .dab
+                    .symmetry
+                    .positions(point, width as f32, height as f32);
+                for center in mirrored {
+                    let around =
+                        DirtyRect::around(center.x as i32, center.y as i32, reach, width, height);
+                    rect = Some(rect.map_or(around, |rect| rect.union(&around)));
+                }
+            }
+            points.extend(
+                advanced
+                    .iter()
+                    .skip(1)
+                    .map(|&point| (point, sample.pressure)),
+            );
+        }
+
+        let rect = match rect {
+            Some(rect) if !rect.is_empty() => rect,
+            _ => continue,
+        };
+        let patch = pixels
+            .view(rect.x0, rect.y0, rect.width(), rect.height())
+            .to_image();
+        // Pixels still as they were before the stroke belong to whatever else is on the layer
+        let painted = patch
+            .enumerate_pixels()
+            .map(|(x, y, pixel)| stroke.base().get_pixel(rect.x0 + x, rect.y0 + y) != pixel)
+            .collect();
+        let patch = Patch {
+            rect,
+            pixels: patch,
+            painted,
+            points,
+        };
+        if messages.send(Message::Patch(patch)).is_err() {
+            return;
+        }
+    }
+    let _ = messages.send(Message::Done(stroke.into_base()));
+}
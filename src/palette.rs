@@ -0,0 +1,342 @@
+use std::collections::HashMap;
+
+use nannou::image::{DynamicImage, RgbaImage};
+
+use crate::filters::par_map;
+
+/// Colors listed by a report when a document has at most this many.
+const REPORT_PALETTE_LIMIT: usize = 64;
+
+/// Picks up to `count` representative colors with median cut: the color box with the widest
+/// channel range is repeatedly split at its median until there are enough boxes.
+pub fn median_cut(image: &DynamicImage, count: usize) -> Vec<[u8; 4]> {
+    let mut boxes: Vec<Vec<[u8; 4]>> = vec![image.to_rgba8().pixels().map(|p| p.0).collect()];
+    if boxes[0].is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    let range = |colors: &[[u8; 4]], c: usize| {
+        let (min, max) = colors
+            .iter()
+            .fold((255, 0), |(lo, hi), p| (p[c].min(lo), p[c].max(hi)));
+        max - min
+    };
+
+    while boxes.len() < count {
+        let split = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, width) = (0..4)
+                    .map(|c| (c, range(b, c)))
+                    .max_by_key(|&(_, r)| r)
+                    .unwrap();
+                (i, channel, width)
+            })
+            .max_by_key(|&(_, _, width)| width);
+
+        match split {
+            Some((i, channel, width)) if width > 0 => {
+                let mut colors = boxes.swap_remove(i);
+                colors.sort_unstable_by_key(|p| p[channel]);
+                let upper = colors.split_off(colors.len() / 2);
+                boxes.push(colors);
+                boxes.push(upper);
+            }
+            // Every box is a single color already
+            _ => break,
+        }
+    }
+
+    boxes
+        .iter()
+        .map(|b| {
+            let mut sum = [0u64; 4];
+            for p in b {
+                for (total, &channel) in sum.iter_mut().zip(p) {
+                    *total += channel as u64;
+                }
+            }
+            sum.map(|s| (s / b.len() as u64) as u8)
+        })
+        .collect()
+}
+
+pub fn nearest(palette: &[[u8; 4]], color: [u8; 4]) -> [u8; 4] {
+    *palette
+        .iter()
+        .min_by_key(|p| {
+            (0..4)
+                .map(|c| (p[c] as i32 - color[c] as i32).pow(2))
+                .sum::<i32>()
+        })
+        .unwrap_or(&color)
+}
+
+/// Maps every pixel to its nearest color in `palette`.
+pub fn remap(image: &DynamicImage, palette: &[[u8; 4]]) -> DynamicImage {
+    let src = image.to_rgba8();
+    let out: RgbaImage = par_map(&src, |x, y| nearest(palette, src.get_pixel(x, y).0));
+    DynamicImage::ImageRgba8(out)
+}
+
+/// Reduces the image to at most `count` colors.
+pub fn quantize(image: &DynamicImage, count: usize) -> DynamicImage {
+    remap(image, &median_cut(image, count))
+}
+
+/// How many pixels have each color.
+fn color_counts(image: &DynamicImage) -> HashMap<[u8; 4], u64> {
+    let mut counts = HashMap::new();
+    for pixel in image.to_rgba8().pixels() {
+        *counts.entry(pixel.0).or_insert(0) += 1;
+    }
+    counts
+}
+
+#[derive(Default)]
+struct OctreeNode {
+    depth: u8,
+    parent: usize,
+    /// Channel sums and pixel count of every color below the node.
+    sum: [u64; 4],
+    pixels: u64,
+    children: [Option<usize>; 16],
+}
+
+/// Picks up to `count` colors with an octree: colors are sorted into a tree by their channel bits,
+/// then the nodes with the fewest pixels on the deepest level are folded into their parents until
+/// few enough leaves remain. Alpha counts as a fourth channel, so nodes have 16 children.
+pub fn octree(image: &DynamicImage, count: usize) -> Vec<[u8; 4]> {
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut nodes = vec![OctreeNode::default()];
+    for (color, pixels) in color_counts(image) {
+        let mut node = 0;
+        for depth in 0..8u8 {
+            let bit = 7 - depth;
+            let child = (0..4).fold(0, |i, c| i << 1 | ((color[c] >> bit) & 1) as usize);
+            node = match nodes[node].children[child] {
+                Some(next) => next,
+                None => {
+                    nodes.push(OctreeNode {
+                        depth: depth + 1,
+                        parent: node,
+                        ..Default::default()
+                    });
+                    nodes[node].children[child] = Some(nodes.len() - 1);
+                    nodes.len() - 1
+                }
+            };
+        }
+        let leaf = &mut nodes[node];
+        for (sum, channel) in leaf.sum.iter_mut().zip(color) {
+            *sum += channel as u64 * pixels;
+        }
+        leaf.pixels += pixels;
+    }
+
+    // Children always come after their parent, so one backwards pass totals every subtree
+    for i in (1..nodes.len()).rev() {
+        let (sum, pixels, parent) = (nodes[i].sum, nodes[i].pixels, nodes[i].parent);
+        for (total, channel) in nodes[parent].sum.iter_mut().zip(sum) {
+            *total += channel;
+        }
+        nodes[parent].pixels += pixels;
+    }
+
+    let mut leaves = nodes.iter().filter(|n| n.depth == 8).count();
+    'reduce: for depth in (0..8).rev() {
+        let mut level: Vec<usize> = (0..nodes.len())
+            .filter(|&i| nodes[i].depth == depth)
+            .collect();
+        level.sort_by_key(|&i| nodes[i].pixels);
+        for i in level {
+            if leaves <= count {
+                break 'reduce;
+            }
+            let children = nodes[i].children.iter().flatten().count();
+            nodes[i].children = [None; 16];
+            leaves = leaves + 1 - children;
+        }
+    }
+
+    let mut colors = Vec::new();
+    let mut stack = vec![0];
+    while let Some(i) = stack.pop() {
+        let node = &nodes[i];
+        if node.children.iter().all(Option::is_none) {
+            if node.pixels > 0 {
+                colors.push(node.sum.map(|s| (s / node.pixels) as u8));
+            }
+        } else {
+            stack.extend(node.children.iter().flatten());
+        }
+    }
+    colors
+}
+
+/// Algorithm picking the colors a document is posterized to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Quantizer {
+    MedianCut,
+    Octree,
+}
+
+impl Quantizer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Quantizer::MedianCut => "Median Cut",
+            Quantizer::Octree => "Octree",
+        }
+    }
+
+    pub fn next(&self) -> Quantizer {
+        match self {
+            Quantizer::MedianCut => Quantizer::Octree,
+            Quantizer::Octree => Quantizer::MedianCut,
+        }
+    }
+
+    /// Up to `count` colors representing `image`.
+    pub fn palette(&self, image: &DynamicImage, count: usize) -> Vec<[u8; 4]> {
+        match self {
+            Quantizer::MedianCut => median_cut(image, count),
+            Quantizer::Octree => octree(image, count),
+        }
+    }
+}
+
+/// Settings of the palette panel's posterize command.
+#[derive(Clone, Copy)]
+pub struct PosterizeOptions {
+    pub colors: f32,
+    pub quantizer: Quantizer,
+}
+
+impl Default for PosterizeOptions {
+    fn default() -> Self {
+        Self {
+            colors: 16.0,
+            quantizer: Quantizer::MedianCut,
+        }
+    }
+}
+
+/// What the colors of a document actually use.
+pub struct ColorReport {
+    /// Distinct RGBA values.
+    pub colors: usize,
+    /// Fewest bits per channel that reproduce every channel value exactly.
+    pub bits: u8,
+    pub grayscale: bool,
+    /// Whether any pixel is less than opaque.
+    pub alpha: bool,
+    /// Every color, most used first, if there are few enough to list.
+    pub palette: Vec<[u8; 4]>,
+}
+
+impl ColorReport {
+    pub fn new(image: &DynamicImage) -> ColorReport {
+        let counts = color_counts(image);
+
+        let mut used = [false; 256];
+        for color in counts.keys() {
+            for &v in color {
+                used[v as usize] = true;
+            }
+        }
+        // A value fits in n bits if scaling it down to n bits and back up again gives it back
+        let fits = |bits: u32| {
+            let max = (1u32 << bits) - 1;
+            (0..256u32).filter(|&v| used[v as usize]).all(|v| {
+                let reduced = (v * max + 127) / 255;
+                (reduced * 255 + max / 2) / max == v
+            })
+        };
+        let bits = (1..=8).find(|&bits| fits(bits)).unwrap_or(8) as u8;
+
+        let mut palette = Vec::new();
+        if counts.len() <= REPORT_PALETTE_LIMIT {
+            let mut colors: Vec<_> = counts.iter().collect();
+            colors.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            palette = colors.into_iter().map(|(color, _)| *color).collect();
+        }
+
+        ColorReport {
+            colors: counts.len(),
+            bits,
+            grayscale: counts.keys().all(|[r, g, b, _]| r == g && g == b),
+            alpha: counts.keys().any(|color| color[3] < 255),
+            palette,
+        }
+    }
+
+    /// One line summary for the palette panel.
+    pub fn summary(&self) -> String {
+        let mut text = format!("{} colors, {}-bit", self.colors, self.bits);
+        if self.grayscale {
+            text.push_str(", grayscale");
+        }
+        if self.alpha {
+            text.push_str(", alpha");
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::Rgba;
+
+    fn row(colors: &[[u8; 4]]) -> DynamicImage {
+        let mut image = RgbaImage::new(colors.len() as u32, 1);
+        for (x, color) in colors.iter().enumerate() {
+            image.put_pixel(x as u32, 0, Rgba(*color));
+        }
+        DynamicImage::ImageRgba8(image)
+    }
+
+    fn colors(image: &DynamicImage) -> Vec<[u8; 4]> {
+        image.to_rgba8().pixels().map(|p| p.0).collect()
+    }
+
+    #[test]
+    fn quantize_merges_close_colors() {
+        let image = row(&[
+            [0, 0, 0, 255],
+            [10, 0, 0, 255],
+            [200, 200, 200, 255],
+            [210, 200, 200, 255],
+        ]);
+        assert_eq!(
+            colors(&quantize(&image, 2)),
+            [
+                [5, 0, 0, 255],
+                [5, 0, 0, 255],
+                [205, 200, 200, 255],
+                [205, 200, 200, 255],
+            ]
+        );
+    }
+
+    #[test]
+    fn quantize_keeps_images_with_few_enough_colors() {
+        let image = row(&[[0, 0, 0, 255], [255, 0, 0, 128], [0, 0, 0, 255]]);
+        assert_eq!(colors(&quantize(&image, 4)), colors(&image));
+    }
+
+    #[test]
+    fn quantize_never_exceeds_the_count() {
+        let image = row(&(0..=255)
+            .map(|v| [v, 255 - v, v / 2, 255])
+            .collect::<Vec<_>>());
+        for count in [1, 3, 16] {
+            assert!(color_counts(&quantize(&image, count)).len() <= count);
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Perspective guides: rays fanning out from one, two or three vanishing points, drawn over the
+//! canvas to construct scenes against. The line tool can be pulled onto the ray through its
+//! start point, so edges drawn freehand still converge.
+
+use nannou::prelude::Vec2;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VanishingPoints {
+    /// Depth recedes to one point on the horizon. Fronts face the viewer, so the grid also has
+    /// vertical and horizontal lines.
+    One,
+    /// Two points on the horizon, with verticals kept upright.
+    Two,
+    /// Two points on the horizon and a third above or below it that verticals converge to.
+    Three,
+}
+
+impl VanishingPoints {
+    pub fn name(&self) -> &'static str {
+        match self {
+            VanishingPoints::One => "1-Point",
+            VanishingPoints::Two => "2-Point",
+            VanishingPoints::Three => "3-Point",
+        }
+    }
+
+    pub fn next(self) -> VanishingPoints {
+        match self {
+            VanishingPoints::One => VanishingPoints::Two,
+            VanishingPoints::Two => VanishingPoints::Three,
+            VanishingPoints::Three => VanishingPoints::One,
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            VanishingPoints::One => 1,
+            VanishingPoints::Two => 2,
+            VanishingPoints::Three => 3,
+        }
+    }
+}
+
+/// Settings of the perspective panel.
+pub struct PerspectiveGrid {
+    pub visible: bool,
+    pub points: VanishingPoints,
+    /// Vanishing points as fractions of the canvas size, so the grid carries over between
+    /// documents. Only the first `points` are used.
+    pub anchors: [Vec2; 3],
+    /// Rays drawn out of each vanishing point.
+    pub density: f32,
+    /// Pull the line tool onto the nearest perspective ray.
+    pub snap: bool,
+}
+
+impl Default for PerspectiveGrid {
+    fn default() -> Self {
+        Self {
+            visible: false,
+            points: VanishingPoints::Two,
+            anchors: [
+                Vec2::new(0.15, 0.4),
+                Vec2::new(0.85, 0.4),
+                Vec2::new(0.5, 1.2),
+            ],
+            density: 24.0,
+            snap: true,
+        }
+    }
+}
+
+impl PerspectiveGrid {
+    /// The vanishing points in use, in canvas pixels on a `size` canvas.
+    pub fn vanishing_points(&self, size: Vec2) -> impl Iterator<Item = Vec2> + '_ {
+        self.anchors[..self.points.count()]
+            .iter()
+            .map(move |&anchor| anchor * size)
+    }
+
+    /// Index of the vanishing point within `distance` of canvas position `pos`.
+    pub fn find(&self, pos: Vec2, size: Vec2, distance: f32) -> Option<usize> {
+        self.vanishing_points(size)
+            .position(|point| point.distance(pos) <= distance)
+    }
+
+    /// Moves vanishing point `index` to canvas position `pos`.
+    pub fn move_point(&mut self, index: usize, pos: Vec2, size: Vec2) {
+        self.anchors[index] = pos / size;
+    }
+
+    /// The grid's lines, clipped to the canvas: the horizon, the rays out of each vanishing point,
+    /// and the upright and level lines the vanishing points leave parallel.
+    pub fn lines(&self, size: Vec2) -> Vec<(Vec2, Vec2)> {
+        let points: Vec<Vec2> = self.vanishing_points(size).collect();
+        let reach = size.length() * 4.0;
+        let mut lines = Vec::new();
+
+        let horizon = match self.points {
+            VanishingPoints::One => Vec2::X,
+            _ => (points[1] - points[0]).normalize_or_zero(),
+        };
+        lines.push((points[0] - horizon * reach, points[0] + horizon * reach));
+
+        let rays = self.density.round().max(1.0) as usize;
+        for &point in &points {
+            for i in 0..rays {
+                let angle = i as f32 / rays as f32 * std::f32::consts::TAU;
+                lines.push((point, point + Vec2::new(angle.cos(), angle.sin()) * reach));
+            }
+        }
+
+        let spacing = |length: f32| length / (rays as f32 / 2.0).max(1.0);
+        if self.points != VanishingPoints::Three {
+            let step = spacing(size.x);
+            let mut x = step;
+            while x < size.x {
+                lines.push((Vec2::new(x, 0.0), Vec2::new(x, size.y)));
+                x += step;
+            }
+        }
+        if self.points == VanishingPoints::One {
+            let step = spacing(size.y);
+            let mut y = step;
+            while y < size.y {
+                lines.push((Vec2::new(0.0, y), Vec2::new(size.x, y)));
+                y += step;
+            }
+        }
+
+        lines
+            .into_iter()
+            .filter_map(|(a, b)| clip(a, b, size))
+            .collect()
+    }
+
+    /// `pos` moved onto whichever perspective direction through `start` is nearest the direction
+    /// from `start` to `pos`: towards a vanishing point, or upright and level where the grid
+    /// keeps those parallel.
+    pub fn snap(&self, start: Vec2, pos: Vec2, size: Vec2) -> Vec2 {
+        let drag = pos - start;
+        if drag.length() < f32::EPSILON {
+            return pos;
+        }
+
+        let mut directions: Vec<Vec2> = self
+            .vanishing_points(size)
+            .map(|point| (point - start).normalize_or_zero())
+            .filter(|direction| *direction != Vec2::ZERO)
+            .collect();
+        if self.points != VanishingPoints::Three {
+            directions.push(Vec2::Y);
+        }
+        if self.points == VanishingPoints::One {
+            directions.push(Vec2::X);
+        }
+
+        // Lines run both ways along a direction, so compare without the sign
+        directions
+            .into_iter()
+            .max_by(|a, b| a.dot(drag).abs().total_cmp(&b.dot(drag).abs()))
+            .map_or(pos, |direction| start + direction * direction.dot(drag))
+    }
+}
+
+/// The part of the segment from `a` to `b` on a `size` canvas, if any (Liang-Barsky).
+fn clip(a: Vec2, b: Vec2, size: Vec2) -> Option<(Vec2, Vec2)> {
+    let d = b - a;
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    for (p, q) in [
+        (-d.x, a.x),
+        (d.x, size.x - a.x),
+        (-d.y, a.y),
+        (d.y, size.y - a.y),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+        if t0 > t1 {
+            return None;
+        }
+    }
+    Some((a + d * t0, a + d * t1))
+}
@@ -0,0 +1,46 @@
+//! Grid over the canvas's pixel cells, shown once the canvas is zoomed in far enough for the
+//! cells to be told apart.
+
+/// Zoom from which the grid is drawn.
+pub const MIN_SCALE: f32 = 8.0;
+
+/// Spacings the stronger lines can be drawn at, 0 for none.
+const MAJOR_SPACINGS: [u32; 4] = [0, 8, 16, 32];
+
+pub struct PixelGrid {
+    pub enabled: bool,
+    /// Color of the lines between pixels. Every `major` pixels they are drawn opaque.
+    pub color: [u8; 4],
+    /// Pixels between highlighted lines, 0 for none.
+    pub major: u32,
+}
+
+impl Default for PixelGrid {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: [128, 128, 128, 96],
+            major: 16,
+        }
+    }
+}
+
+impl PixelGrid {
+    pub fn visible(&self, scale: f32) -> bool {
+        self.enabled && scale >= MIN_SCALE
+    }
+
+    /// The next highlight spacing, wrapping around.
+    pub fn next_major(&self) -> u32 {
+        let i = MAJOR_SPACINGS
+            .iter()
+            .position(|m| *m == self.major)
+            .map_or(0, |i| i + 1);
+        MAJOR_SPACINGS[i % MAJOR_SPACINGS.len()]
+    }
+
+    /// Whether the line at pixel boundary `i` is highlighted.
+    pub fn is_major(&self, i: u32) -> bool {
+        self.major > 0 && i % self.major == 0
+    }
+}
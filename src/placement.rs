@@ -0,0 +1,58 @@
+//! Saving and restoring where windows were on screen between sessions.
+
+use nannou::winit::dpi::{PhysicalPosition, PhysicalSize};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WindowPlacement {
+    /// Name of the monitor the window was on, if the platform reports one.
+    pub monitor: Option<String>,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Reads the current placement of `window`.
+pub fn capture(window: &nannou::window::Window) -> Option<WindowPlacement> {
+    let winit = window.winit_window();
+    let position = winit.outer_position().ok()?;
+    let size = winit.inner_size();
+    Some(WindowPlacement {
+        monitor: winit.current_monitor().and_then(|m| m.name()),
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Moves and resizes `window` to a saved placement. If the monitor it was on is no longer
+/// connected, or the position would be off every monitor, only the size is restored and the window
+/// keeps its default position.
+pub fn restore(window: &nannou::window::Window, placement: &WindowPlacement) {
+    let winit = window.winit_window();
+    winit.set_inner_size(PhysicalSize::new(
+        placement.width.max(100),
+        placement.height.max(100),
+    ));
+
+    let on_screen = winit.available_monitors().any(|monitor| {
+        let (pos, size) = (monitor.position(), monitor.size());
+        let named = match &placement.monitor {
+            Some(name) => monitor.name().as_ref() == Some(name),
+            None => true,
+        };
+        named
+            && placement.x >= pos.x
+            && placement.y >= pos.y
+            && placement.x < pos.x + size.width as i32
+            && placement.y < pos.y + size.height as i32
+    });
+
+    if on_screen {
+        winit.set_outer_position(PhysicalPosition::new(placement.x, placement.y));
+    } else {
+        log::info!("Saved window position is off-screen, using default placement");
+    }
+}
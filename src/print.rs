@@ -0,0 +1,177 @@
+//! Printing. The document is laid out on a page inside the margins, resampled to the chosen DPI,
+//! written to a temporary PDF and handed to the system print spooler.
+
+use std::io;
+use std::path::Path;
+use std::process::Command;
+
+use nannou::image::{imageops::FilterType, DynamicImage, GenericImageView};
+
+use crate::export::pdf;
+use crate::jobs::Progress;
+
+/// PDF points per millimetre.
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Paper {
+    A4,
+    Letter,
+}
+
+impl Paper {
+    pub fn next(&self) -> Paper {
+        match self {
+            Paper::A4 => Paper::Letter,
+            Paper::Letter => Paper::A4,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Paper::A4 => "A4",
+            Paper::Letter => "Letter",
+        }
+    }
+
+    /// Portrait width and height in millimetres.
+    fn size_mm(&self) -> (f32, f32) {
+        match self {
+            Paper::A4 => (210.0, 297.0),
+            Paper::Letter => (215.9, 279.4),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct PrintOptions {
+    pub paper: Paper,
+    pub landscape: bool,
+    pub dpi: f32,
+    pub margin_mm: f32,
+    /// Scale the image to fill the space inside the margins. Otherwise it prints at `dpi`
+    /// pixels per inch times `scale`.
+    pub fit: bool,
+    pub scale: f32,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            paper: Paper::A4,
+            landscape: false,
+            dpi: 300.0,
+            margin_mm: 10.0,
+            fit: true,
+            scale: 1.0,
+        }
+    }
+}
+
+/// Page size and where the image goes on it, in points from the bottom left corner.
+pub struct Layout {
+    pub page: (f32, f32),
+    pub image: (f32, f32, f32, f32),
+}
+
+impl PrintOptions {
+    /// Centers an image of `width` by `height` pixels inside the margins. Images larger than the
+    /// printable area are shrunk to fit.
+    pub fn layout(&self, width: u32, height: u32) -> Layout {
+        let (w, h) = self.paper.size_mm();
+        let (w, h) = if self.landscape { (h, w) } else { (w, h) };
+        let page = (w * POINTS_PER_MM, h * POINTS_PER_MM);
+        let margin = self.margin_mm * POINTS_PER_MM;
+        let area = (
+            (page.0 - 2.0 * margin).max(1.0),
+            (page.1 - 2.0 * margin).max(1.0),
+        );
+
+        let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+        let natural = 72.0 / self.dpi * self.scale;
+        let fit = (area.0 / width).min(area.1 / height);
+        let points_per_pixel = if self.fit { fit } else { natural.min(fit) };
+
+        let size = (width * points_per_pixel, height * points_per_pixel);
+        Layout {
+            page,
+            image: (
+                (page.0 - size.0) / 2.0,
+                (page.1 - size.1) / 2.0,
+                size.0,
+                size.1,
+            ),
+        }
+    }
+
+    /// Resamples `image` to the pixel size it covers on the page at `dpi`.
+    pub fn rasterize(&self, image: &DynamicImage, layout: &Layout, smooth: bool) -> DynamicImage {
+        let (_, _, w, h) = layout.image;
+        let (width, height) = (
+            (w / 72.0 * self.dpi).round().max(1.0) as u32,
+            (h / 72.0 * self.dpi).round().max(1.0) as u32,
+        );
+        // Pixel art keeps hard edges when printed large
+        let filter = if smooth {
+            FilterType::Lanczos3
+        } else {
+            FilterType::Nearest
+        };
+        image.resize_exact(width, height, filter)
+    }
+}
+
+/// Prints `image` on one page with the default printer.
+pub fn print(
+    image: &DynamicImage,
+    options: &PrintOptions,
+    smooth: bool,
+    progress: &Progress,
+) -> io::Result<()> {
+    let layout = options.layout(image.width(), image.height());
+    let pixels = options.rasterize(image, &layout, smooth);
+    progress.set(0.6);
+
+    let file = tempfile::Builder::new()
+        .prefix("image-editor-print-")
+        .suffix(".pdf")
+        .tempfile()?;
+    pdf::write_pages(
+        &[pdf::Page {
+            image: &pixels,
+            size: layout.page,
+            placement: layout.image,
+        }],
+        file.path(),
+    )?;
+    progress.set(0.9);
+    spool(file.path())?;
+    // `lp` has copied the file into the spool by the time it exits, but on Windows the document
+    // is printed by its viewer in the background and has to outlive this call
+    if cfg!(windows) {
+        file.keep().map_err(|e| e.error)?;
+    }
+    Ok(())
+}
+
+fn spool(path: &Path) -> io::Result<()> {
+    let status = if cfg!(windows) {
+        // A single-quoted PowerShell string takes everything literally but doubled quotes
+        let path = path.display().to_string().replace('\'', "''");
+        Command::new("powershell")
+            .args(["-NoProfile", "-NonInteractive", "-Command"])
+            .arg(format!("Start-Process -Verb Print -FilePath '{}'", path))
+            .status()?
+    } else {
+        Command::new("lp").arg(path).status()?
+    };
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("print spooler exited with {}", status),
+        ))
+    }
+}
@@ -0,0 +1,304 @@
+//! The editor's own project format (.ied), which keeps everything PNG export flattens away: the
+//! layer stack with names, opacity, visibility and blend modes, the selection, the canvas
+//! resolution, the guides and composition overlays, notes pinned to the canvas and the tool
+//! settings. It is a zip of one PNG per layer plus `project.toml` describing them.
+//!
+//! `project.toml` carries a format version. Fields added in later versions must have serde
+//! defaults so older files still load, and files from a newer version are refused rather than
+//! read wrongly.
+
+use std::fs::File;
+use std::io::{self, Read, Seek, Write};
+use std::path::Path;
+
+use nannou::image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+use serde::{Deserialize, Serialize};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::alpha::AlphaMode;
+use crate::compositing::BlendMode;
+use crate::composition::Overlays;
+use crate::guides::Guide;
+use crate::layers::{Layer, LayerStack};
+use crate::notes::Note;
+use crate::selection::Selection;
+use crate::{GlobalState, Mode};
+
+/// Format version written by this build.
+pub const VERSION: u32 = 1;
+
+const MANIFEST: &str = "project.toml";
+
+/// Everything saved alongside the layers.
+pub struct Metadata {
+    pub selection: Option<Selection>,
+    /// Canvas resolution in pixels per inch.
+    pub dpi: f32,
+    pub guides: Vec<Guide>,
+    pub overlays: Overlays,
+    /// Notes pinned to the canvas, kept out of exports.
+    pub notes: Vec<Note>,
+    /// How the document's exported images store alpha.
+    pub alpha: AlphaMode,
+    pub tools: ToolSettings,
+}
+
+/// Tool setup restored when a project is opened.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolSettings {
+    pub brush_size: f32,
+    pub brush_color: [u8; 4],
+    pub brush_opacity: f32,
+    pub brush_flow: f32,
+    pub brush_spacing: f32,
+    pub fill_tolerance: f32,
+    pub tile_size: f32,
+    // Last, as shape modes are written as a table
+    pub mode: Mode,
+}
+
+impl Default for ToolSettings {
+    fn default() -> Self {
+        Self {
+            brush_size: 1.0,
+            brush_color: [0, 0, 0, 255],
+            brush_opacity: 1.0,
+            brush_flow: 1.0,
+            brush_spacing: 0.1,
+            fill_tolerance: 32.0,
+            tile_size: 16.0,
+            mode: Mode::Paint,
+        }
+    }
+}
+
+impl ToolSettings {
+    pub fn capture(state: &GlobalState) -> ToolSettings {
+        ToolSettings {
+            brush_size: state.brush_size,
+            brush_color: state.brush_color,
+            brush_opacity: state.brush_opacity,
+            brush_flow: state.brush_flow,
+            brush_spacing: state.brush_spacing,
+            fill_tolerance: state.fill_tolerance,
+            tile_size: state.tile_size,
+            mode: state.mode,
+        }
+    }
+
+    pub fn apply(&self, state: &mut GlobalState) {
+        state.mode = self.mode;
+        state.brush_size = self.brush_size;
+        state.brush_color = self.brush_color;
+        state.brush_opacity = self.brush_opacity;
+        state.brush_flow = self.brush_flow;
+        state.brush_spacing = self.brush_spacing;
+        state.fill_tolerance = self.fill_tolerance;
+        state.tile_size = self.tile_size;
+    }
+}
+
+// TOML needs plain values ahead of tables, so keep new plain fields above `selection`
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    width: u32,
+    height: u32,
+    dpi: f32,
+    /// Index into `layers` of the layer being edited.
+    active: usize,
+    #[serde(default)]
+    alpha: AlphaMode,
+    selection: Option<Selection>,
+    #[serde(default)]
+    tools: ToolSettings,
+    #[serde(default)]
+    overlays: Overlays,
+    #[serde(default)]
+    guides: Vec<Guide>,
+    #[serde(default)]
+    notes: Vec<Note>,
+    /// Bottom layer first.
+    layers: Vec<LayerEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct LayerEntry {
+    name: String,
+    /// PNG in the archive holding the layer's pixels.
+    file: String,
+    opacity: f32,
+    visible: bool,
+    #[serde(default = "normal")]
+    blend: BlendMode,
+}
+
+fn normal() -> BlendMode {
+    BlendMode::Normal
+}
+
+pub fn save(layers: &LayerStack, metadata: &Metadata, path: &Path) -> io::Result<()> {
+    let mut zip = ZipWriter::new(File::create(path)?);
+    let mut entries = Vec::new();
+    for (i, layer) in layers.layers.iter().enumerate() {
+        let file = format!("layers/{}.png", i);
+        let mut png = Vec::new();
+        layer
+            .pixels
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        // PNG data is already compressed
+        zip.start_file(
+            &file,
+            FileOptions::default().compression_method(CompressionMethod::Stored),
+        )?;
+        zip.write_all(&png)?;
+
+        entries.push(LayerEntry {
+            name: layer.name.clone(),
+            file,
+            opacity: layer.opacity,
+            visible: layer.visible,
+            blend: layer.blend,
+        });
+    }
+
+    let manifest = Manifest {
+        version: VERSION,
+        width: layers.width(),
+        height: layers.height(),
+        dpi: metadata.dpi,
+        active: layers.active,
+        alpha: metadata.alpha,
+        selection: metadata.selection,
+        tools: metadata.tools.clone(),
+        overlays: metadata.overlays,
+        guides: metadata.guides.clone(),
+        notes: metadata.notes.clone(),
+        layers: entries,
+    };
+    let source =
+        toml::to_string_pretty(&manifest).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    zip.start_file(MANIFEST, FileOptions::default())?;
+    zip.write_all(source.as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
+
+pub fn open(path: &Path) -> io::Result<(LayerStack, Metadata)> {
+    read(File::open(path)?)
+}
+
+fn read<R: Read + Seek>(reader: R) -> io::Result<(LayerStack, Metadata)> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+    let mut zip = ZipArchive::new(reader)?;
+
+    let mut source = String::new();
+    zip.by_name(MANIFEST)?.read_to_string(&mut source)?;
+    let manifest: Manifest = toml::from_str(&source).map_err(|e| invalid(e.to_string()))?;
+    if manifest.version > VERSION {
+        return Err(invalid(format!(
+            "project format version {} is newer than this editor supports ({})",
+            manifest.version, VERSION
+        )));
+    }
+
+    let mut layers = Vec::new();
+    for entry in manifest.layers {
+        let mut png = Vec::new();
+        zip.by_name(&entry.file)?.read_to_end(&mut png)?;
+        let pixels = nannou::image::load_from_memory_with_format(&png, ImageFormat::Png)
+            .map_err(|e| invalid(e.to_string()))?;
+        if (pixels.width(), pixels.height()) != (manifest.width, manifest.height) {
+            return Err(invalid(format!("layer {} has the wrong size", entry.name)));
+        }
+
+        let mut layer = Layer::new(entry.name, DynamicImage::ImageRgba8(pixels.to_rgba8()));
+        layer.opacity = entry.opacity.clamp(0.0, 1.0);
+        layer.visible = entry.visible;
+        layer.blend = entry.blend;
+        layers.push(layer);
+    }
+
+    if layers.is_empty() {
+        return Err(invalid("project has no layers".to_string()));
+    }
+    let active = manifest.active.min(layers.len() - 1);
+    let metadata = Metadata {
+        selection: manifest.selection,
+        dpi: manifest.dpi,
+        guides: manifest.guides,
+        overlays: manifest.overlays,
+        notes: manifest.notes,
+        alpha: manifest.alpha,
+        tools: manifest.tools,
+    };
+    Ok((LayerStack { layers, active }, metadata))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use nannou::image::{Rgba, RgbaImage};
+
+    /// A project with one 2 x 2 layer described by `manifest`.
+    fn project(manifest: &str) -> Cursor<Vec<u8>> {
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(2, 2, Rgba([1, 2, 3, 255])))
+            .write_to(&mut png, ImageOutputFormat::Png)
+            .unwrap();
+
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        zip.start_file(MANIFEST, FileOptions::default()).unwrap();
+        zip.write_all(manifest.as_bytes()).unwrap();
+        zip.start_file("layers/0.png", FileOptions::default())
+            .unwrap();
+        zip.write_all(&png).unwrap();
+        let mut file = zip.finish().unwrap();
+        file.set_position(0);
+        file
+    }
+
+    fn manifest(version: u32) -> String {
+        format!(
+            r#"
+            version = {}
+            width = 2
+            height = 2
+            dpi = 72.0
+            active = 0
+
+            [[layers]]
+            name = "Background"
+            file = "layers/0.png"
+            opacity = 1.0
+            visible = true
+            "#,
+            version
+        )
+    }
+
+    #[test]
+    fn first_version_loads_with_defaults_for_later_fields() {
+        let (layers, metadata) = read(project(&manifest(1))).unwrap();
+        assert_eq!(layers.layers.len(), 1);
+        assert_eq!(layers.layers[0].blend, BlendMode::Normal);
+        assert_eq!(
+            layers.layers[0].pixels.to_rgba8().get_pixel(1, 1).0,
+            [1, 2, 3, 255]
+        );
+        assert!(metadata.guides.is_empty() && metadata.notes.is_empty());
+        assert_eq!(metadata.tools.mode, ToolSettings::default().mode);
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let error = read(project(&manifest(VERSION + 1))).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("newer"), "{}", error);
+    }
+}
@@ -0,0 +1,199 @@
+//! Camera RAW import through rawloader. The sensor data is demosaiced bilinearly, white balanced
+//! with the camera's coefficients, pushed by the chosen exposure, converted from the camera's
+//! colors to sRGB with its color matrix and gamma encoded.
+
+use std::path::Path;
+
+use nannou::image::error::{DecodingError, ImageFormatHint};
+use nannou::image::{DynamicImage, ImageError, ImageResult, RgbImage};
+use rawloader::RawImageData;
+use rayon::prelude::*;
+
+/// Extensions opened as camera RAW.
+pub const EXTENSIONS: [&str; 10] = [
+    "cr2", "crw", "nef", "arw", "dng", "orf", "rw2", "raf", "pef", "srw",
+];
+
+#[derive(Clone)]
+pub struct RawOptions {
+    /// Exposure adjustment in stops.
+    pub exposure: f32,
+    /// Apply the white balance the camera recorded.
+    pub white_balance: bool,
+}
+
+impl Default for RawOptions {
+    fn default() -> Self {
+        Self {
+            exposure: 0.0,
+            white_balance: true,
+        }
+    }
+}
+
+pub fn is_raw(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map_or(false, |e| EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+pub fn decode(path: &Path, options: &RawOptions) -> ImageResult<DynamicImage> {
+    let raw = rawloader::decode_file(path).map_err(|e| {
+        ImageError::Decoding(DecodingError::new(
+            ImageFormatHint::Name("RAW".to_string()),
+            e.to_string(),
+        ))
+    })?;
+    let (width, height, cpp) = (raw.width, raw.height, raw.cpp);
+    let data: Vec<f32> = match raw.data {
+        RawImageData::Integer(data) => data.into_iter().map(|v| v as f32).collect(),
+        RawImageData::Float(data) => data,
+    };
+
+    // Sensor values scaled to 0-1 between the black and white levels of their color
+    let level = |v: f32, c: usize| {
+        let (black, white) = (raw.blacklevels[c] as f32, raw.whitelevels[c] as f32);
+        ((v - black) / (white - black).max(1.0)).max(0.0)
+    };
+
+    let gain = 2f32.powf(options.exposure);
+    let balance = |c: usize| {
+        let (coeff, green) = (raw.wb_coeffs[c], raw.wb_coeffs[1]);
+        if options.white_balance && coeff.is_finite() && green.is_finite() && green > 0.0 {
+            coeff / green
+        } else {
+            1.0
+        }
+    };
+    let scale: [f32; 4] = [0, 1, 2, 3].map(|c| balance(c) * gain);
+    let matrix = camera_to_srgb(&raw.xyz_to_cam).unwrap_or_else(|| {
+        log::warn!("No color matrix for the {} {}", raw.make, raw.model);
+        IDENTITY
+    });
+
+    let [top, right, bottom, left] = raw.crops;
+    let (out_w, out_h) = (
+        width.saturating_sub(left + right).max(1),
+        height.saturating_sub(top + bottom).max(1),
+    );
+    let mut out = vec![0u8; out_w * out_h * 3];
+    out.par_chunks_mut(out_w * 3)
+        .enumerate()
+        .for_each(|(row, pixels)| {
+            let y = (row + top).min(height - 1);
+            for (col, pixel) in pixels.chunks_mut(3).enumerate() {
+                let x = (col + left).min(width - 1);
+                let mut sum = [0.0f32; 3];
+                let mut count = [0u32; 3];
+                if cpp == 1 {
+                    // Bilinear demosaic: average each color over the 3x3 neighbourhood
+                    for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+                        for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                            let c = raw.cfa.color_at(ny, nx);
+                            let v = level(data[ny * width + nx], c) * scale[c];
+                            // A fourth CFA color is a second green
+                            let channel = if c == 3 { 1 } else { c };
+                            sum[channel] += v;
+                            count[channel] += 1;
+                        }
+                    }
+                } else {
+                    for c in 0..3 {
+                        sum[c] = level(data[(y * width + x) * cpp + c], c) * scale[c];
+                        count[c] = 1;
+                    }
+                }
+
+                let camera = [0, 1, 2].map(|c| {
+                    if count[c] > 0 {
+                        sum[c] / count[c] as f32
+                    } else {
+                        0.0
+                    }
+                });
+                for (value, row) in pixel.iter_mut().zip(&matrix) {
+                    let v: f32 = row.iter().zip(&camera).map(|(m, v)| m * v).sum();
+                    *value = (srgb(v.clamp(0.0, 1.0)) * 255.0).round() as u8;
+                }
+            }
+        });
+
+    let image = RgbImage::from_raw(out_w as u32, out_h as u32, out)
+        .expect("buffer matches the output size");
+    Ok(DynamicImage::ImageRgb8(image))
+}
+
+const IDENTITY: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Linear sRGB (D65) to CIE XYZ.
+const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_456_4, 0.357_576_1, 0.180_437_5],
+    [0.212_672_9, 0.715_152_2, 0.072_175],
+    [0.019_333_9, 0.119_192, 0.950_304_1],
+];
+
+/// The matrix from white balanced camera colors to linear sRGB, worked out from the camera's XYZ
+/// to camera matrix the way dcraw does: sRGB to camera, each row scaled so white stays white,
+/// then inverted. `None` if rawloader doesn't know the camera's colors.
+fn camera_to_srgb(xyz_to_cam: &[[f32; 3]; 4]) -> Option<[[f32; 3]; 3]> {
+    let mut srgb_to_cam = [[0.0f32; 3]; 3];
+    for (i, row) in srgb_to_cam.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| xyz_to_cam[i][k] * SRGB_TO_XYZ[k][j]).sum();
+        }
+        let sum: f32 = row.iter().sum();
+        if sum.abs() < f32::EPSILON {
+            return None;
+        }
+        row.iter_mut().for_each(|v| *v /= sum);
+    }
+    invert(&srgb_to_cam)
+}
+
+fn invert(m: &[[f32; 3]; 3]) -> Option<[[f32; 3]; 3]> {
+    let det = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+        - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+        + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    if det.abs() < f32::EPSILON {
+        return None;
+    }
+    let mut inverse = [[0.0; 3]; 3];
+    for (i, row) in inverse.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            // The cofactor of m[j][i]; cycling the indices takes care of its sign
+            let (r0, r1, c0, c1) = ((j + 1) % 3, (j + 2) % 3, (i + 1) % 3, (i + 2) % 3);
+            *value = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / det;
+        }
+    }
+    Some(inverse)
+}
+
+/// Linear light to the sRGB transfer curve.
+fn srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_camera_needs_no_conversion() {
+        let [r, g, b] = invert(&SRGB_TO_XYZ).unwrap();
+        let matrix = camera_to_srgb(&[r, g, b, [0.0; 3]]).unwrap();
+        for (row, expected) in matrix.iter().zip(&IDENTITY) {
+            for (value, expected) in row.iter().zip(expected) {
+                assert!((value - expected).abs() < 1e-4, "{:?}", matrix);
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_camera_has_no_matrix() {
+        assert!(camera_to_srgb(&[[0.0; 3]; 4]).is_none());
+    }
+}
@@ -0,0 +1,93 @@
+//! Changing the size of a document, either by resampling its pixels or by growing or cropping
+//! the canvas around them.
+
+use nannou::image::{imageops, imageops::FilterType, DynamicImage, GenericImageView, RgbaImage};
+
+use crate::alpha;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Resample {
+    Nearest,
+    Bilinear,
+    Lanczos,
+}
+
+impl Resample {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Resample::Nearest => "Nearest",
+            Resample::Bilinear => "Bilinear",
+            Resample::Lanczos => "Lanczos",
+        }
+    }
+
+    pub fn next(&self) -> Resample {
+        match self {
+            Resample::Nearest => Resample::Bilinear,
+            Resample::Bilinear => Resample::Lanczos,
+            Resample::Lanczos => Resample::Nearest,
+        }
+    }
+
+    fn filter(&self) -> FilterType {
+        match self {
+            Resample::Nearest => FilterType::Nearest,
+            Resample::Bilinear => FilterType::Triangle,
+            Resample::Lanczos => FilterType::Lanczos3,
+        }
+    }
+}
+
+pub struct ResizeOptions {
+    pub width: f32,
+    pub height: f32,
+    pub resample: Resample,
+    /// Where the old canvas sits in the resized one: column and row of a 3x3 grid.
+    pub anchor: (u32, u32),
+}
+
+impl Default for ResizeOptions {
+    fn default() -> Self {
+        Self {
+            width: 256.0,
+            height: 256.0,
+            resample: Resample::Bilinear,
+            anchor: (1, 1),
+        }
+    }
+}
+
+/// Resamples the image to `width` by `height`, in premultiplied alpha so edges don't darken.
+pub fn scale(image: &DynamicImage, width: u32, height: u32, resample: Resample) -> DynamicImage {
+    alpha::resize_exact(image, width.max(1), height.max(1), resample.filter())
+}
+
+/// Places the image unscaled on a transparent `width` by `height` canvas, cropping whatever falls
+/// outside. `anchor` picks the edge or corner it is kept against.
+pub fn resize_canvas(
+    image: &DynamicImage,
+    width: u32,
+    height: u32,
+    anchor: (u32, u32),
+) -> DynamicImage {
+    let (width, height) = (width.max(1), height.max(1));
+    let offset = |new: u32, old: u32, anchor: u32| (new as i64 - old as i64) * anchor as i64 / 2;
+    let x = offset(width, image.width(), anchor.0);
+    let y = offset(height, image.height(), anchor.1);
+
+    // `replace` only takes positive offsets, so crop first when the image moves up or left
+    let source = image.crop_imm(
+        (-x).max(0) as u32,
+        (-y).max(0) as u32,
+        image.width(),
+        image.height(),
+    );
+    let mut canvas = RgbaImage::new(width, height);
+    imageops::replace(
+        &mut canvas,
+        &source.to_rgba8(),
+        x.max(0) as u32,
+        y.max(0) as u32,
+    );
+    DynamicImage::ImageRgba8(canvas)
+}
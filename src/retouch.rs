@@ -0,0 +1,96 @@
+use nannou::image::{DynamicImage, GenericImageView, Rgba};
+
+use crate::canvas::Canvas;
+
+/// Desaturates strongly red pixels within `radius` of `(cx, cy)`, replacing the red channel with
+/// the mean of green and blue.
+pub fn remove_red_eye(canvas: &mut Canvas, cx: f32, cy: f32, radius: f32) {
+    stamp_disc(canvas, cx, cy, radius, |_, _, mut pixel| {
+        let [r, g, b, _] = pixel.0;
+        let others = (g as f32 + b as f32) / 2.0;
+        (r as f32 > others * 1.5 && r > 60).then(|| {
+            pixel.0[0] = others as u8;
+            pixel
+        })
+    });
+}
+
+/// Mean color of the pixels within `radius` of `(cx, cy)`.
+fn mean(image: &DynamicImage, cx: f32, cy: f32, radius: f32) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+    let mut count = 0.0f32;
+    for (x, y) in disc(image, cx, cy, radius) {
+        for (s, c) in sum.iter_mut().zip(image.get_pixel(x, y).0) {
+            *s += c as f32;
+        }
+        count += 1.0;
+    }
+    sum.map(|s| s / count.max(1.0))
+}
+
+/// Heals a dab of `radius` at `(cx, cy)`: the texture around `source` (its deviation from the
+/// local mean) is laid over the mean color surrounding the destination, feathered towards the
+/// edge. `source` is read from `sample` so the stroke never picks up its own output.
+pub fn heal(
+    canvas: &mut Canvas,
+    sample: &DynamicImage,
+    (sx, sy): (f32, f32),
+    cx: f32,
+    cy: f32,
+    radius: f32,
+) {
+    // The ring around the dab carries the color the patch should blend into
+    let target = mean(sample, cx, cy, radius * 1.5);
+    let source = mean(sample, sx, sy, radius);
+
+    stamp_disc(canvas, cx, cy, radius, |x, y, mut pixel| {
+        let (ox, oy) = (x as f32 - cx + sx, y as f32 - cy + sy);
+        if ox < 0.0 || oy < 0.0 || ox >= sample.width() as f32 || oy >= sample.height() as f32 {
+            return None;
+        }
+
+        let dist = ((x as f32 - cx).powi(2) + (y as f32 - cy).powi(2)).sqrt();
+        let weight = (1.0 - dist / radius).clamp(0.0, 1.0);
+        let texture = sample.get_pixel(ox as u32, oy as u32).0;
+        for c in 0..3 {
+            let healed = texture[c] as f32 - source[c] + target[c];
+            let current = pixel.0[c] as f32;
+            pixel.0[c] = (current + (healed - current) * weight).clamp(0.0, 255.0) as u8;
+        }
+        Some(pixel)
+    });
+}
+
+/// Stamps `paint` on the canvas pixels within `radius` of `(cx, cy)`.
+fn stamp_disc<F>(canvas: &mut Canvas, cx: f32, cy: f32, radius: f32, mut paint: F)
+where
+    F: FnMut(u32, u32, Rgba<u8>) -> Option<Rgba<u8>>,
+{
+    let (x0, y0) = ((cx - radius).floor() as i32, (cy - radius).floor() as i32);
+    let (x1, y1) = ((cx + radius).ceil() as i32, (cy + radius).ceil() as i32);
+    let (width, height) = ((x1 - x0).max(0) as u32, (y1 - y0).max(0) as u32);
+    canvas.stamp(x0, y0, width, height, |x, y, pixel| {
+        if in_disc(x, y, cx, cy, radius) {
+            paint(x, y, pixel)
+        } else {
+            None
+        }
+    });
+}
+
+fn in_disc(x: u32, y: u32, cx: f32, cy: f32, radius: f32) -> bool {
+    (x as f32 + 0.5 - cx).powi(2) + (y as f32 + 0.5 - cy).powi(2) <= radius * radius
+}
+
+/// Canvas pixels within `radius` of `(cx, cy)`.
+fn disc(image: &DynamicImage, cx: f32, cy: f32, radius: f32) -> impl Iterator<Item = (u32, u32)> {
+    let (w, h) = image.dimensions();
+    let x0 = (cx - radius).floor().max(0.0) as u32;
+    let y0 = (cy - radius).floor().max(0.0) as u32;
+    let x1 = ((cx + radius).ceil().max(0.0) as u32).min(w);
+    let y1 = ((cy + radius).ceil().max(0.0) as u32).min(h);
+
+    (y0..y1)
+        .flat_map(move |y| (x0..x1).map(move |x| (x, y)))
+        .filter(move |&(x, y)| in_disc(x, y, cx, cy, radius))
+}
@@ -0,0 +1,226 @@
+//! Intelligent scissors: a lasso that clings to the strongest edges of the layer. Between anchors
+//! the outline takes the cheapest route through a cost map that is low along edges, and anchors
+//! are dropped on their own as the pointer travels. Selections are rectangles, so like the island
+//! selection the traced outline selects its bounds.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+
+use nannou::image::DynamicImage;
+use nannou::prelude::Vec2;
+use rayon::prelude::*;
+
+use crate::selection::Selection;
+
+/// Distance in pixels the pointer snaps to the strongest edge within.
+const SNAP_RADIUS: i32 = 4;
+/// Length in pixels the route to the pointer grows to before it is anchored.
+const ANCHOR_SPACING: usize = 40;
+/// How far in pixels a route may stray outside the box around its two ends.
+const SEARCH_MARGIN: i32 = 24;
+/// Cost of the strongest edge relative to flat areas, kept above zero so routes stay short.
+const EDGE_COST: f32 = 0.05;
+
+/// Gradient magnitude of a layer, scaled so the strongest edge is 1.
+pub struct EdgeMap {
+    width: i32,
+    height: i32,
+    strength: Vec<f32>,
+}
+
+impl EdgeMap {
+    /// Sobel gradient of the luminance, weighted by alpha so the outlines of sprites on
+    /// transparency count as edges.
+    pub fn new(image: &DynamicImage) -> EdgeMap {
+        let rgba = image.to_rgba8();
+        let (width, height) = (rgba.width() as i32, rgba.height() as i32);
+        let value: Vec<f32> = rgba
+            .pixels()
+            .map(|p| {
+                let [r, g, b, a] = p.0.map(|c| c as f32);
+                (0.299 * r + 0.587 * g + 0.114 * b) * a / 255.0
+            })
+            .collect();
+        let at = |x: i32, y: i32| {
+            value[(y.clamp(0, height - 1) * width + x.clamp(0, width - 1)) as usize]
+        };
+
+        let mut strength: Vec<f32> = (0..height)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..width).map(move |x| {
+                    let gx = at(x + 1, y - 1) + 2.0 * at(x + 1, y) + at(x + 1, y + 1)
+                        - at(x - 1, y - 1)
+                        - 2.0 * at(x - 1, y)
+                        - at(x - 1, y + 1);
+                    let gy = at(x - 1, y + 1) + 2.0 * at(x, y + 1) + at(x + 1, y + 1)
+                        - at(x - 1, y - 1)
+                        - 2.0 * at(x, y - 1)
+                        - at(x + 1, y - 1);
+                    (gx * gx + gy * gy).sqrt()
+                })
+            })
+            .collect();
+        let max = strength.iter().cloned().fold(0.0, f32::max);
+        if max > 0.0 {
+            strength.iter_mut().for_each(|s| *s /= max);
+        }
+
+        EdgeMap {
+            width,
+            height,
+            strength,
+        }
+    }
+
+    fn strength(&self, (x, y): (i32, i32)) -> f32 {
+        self.strength[(y * self.width + x) as usize]
+    }
+
+    /// The pixel on the strongest edge near canvas position `pos`, or the pixel under it if there
+    /// are no edges around.
+    fn snap(&self, pos: Vec2) -> (i32, i32) {
+        let x = (pos.x.floor() as i32).clamp(0, self.width - 1);
+        let y = (pos.y.floor() as i32).clamp(0, self.height - 1);
+        let mut best = ((x, y), self.strength((x, y)));
+        for ny in (y - SNAP_RADIUS).max(0)..=(y + SNAP_RADIUS).min(self.height - 1) {
+            for nx in (x - SNAP_RADIUS).max(0)..=(x + SNAP_RADIUS).min(self.width - 1) {
+                let s = self.strength((nx, ny));
+                if s > best.1 {
+                    best = ((nx, ny), s);
+                }
+            }
+        }
+        best.0
+    }
+
+    /// Cheapest 8-connected route from `from` to `to`, both included, found with Dijkstra's
+    /// algorithm inside the box around them.
+    fn route(&self, from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+        let x0 = (from.0.min(to.0) - SEARCH_MARGIN).max(0);
+        let y0 = (from.1.min(to.1) - SEARCH_MARGIN).max(0);
+        let x1 = (from.0.max(to.0) + SEARCH_MARGIN).min(self.width - 1);
+        let y1 = (from.1.max(to.1) + SEARCH_MARGIN).min(self.height - 1);
+        let w = x1 - x0 + 1;
+        let index = |(x, y): (i32, i32)| ((y - y0) * w + (x - x0)) as usize;
+        let point = |i: usize| (x0 + i as i32 % w, y0 + i as i32 / w);
+
+        let size = (w * (y1 - y0 + 1)) as usize;
+        let mut cost = vec![u32::MAX; size];
+        let mut previous = vec![usize::MAX; size];
+        let mut queue = BinaryHeap::new();
+        cost[index(from)] = 0;
+        queue.push(Reverse((0, index(from))));
+
+        while let Some(Reverse((c, i))) = queue.pop() {
+            if i == index(to) {
+                break;
+            }
+            if c > cost[i] {
+                continue;
+            }
+            let (x, y) = point(i);
+            for (dx, dy) in [
+                (-1, -1),
+                (0, -1),
+                (1, -1),
+                (-1, 0),
+                (1, 0),
+                (-1, 1),
+                (0, 1),
+                (1, 1),
+            ] {
+                let next = (x + dx, y + dy);
+                if next.0 < x0 || next.0 > x1 || next.1 < y0 || next.1 > y1 {
+                    continue;
+                }
+                let length = if dx != 0 && dy != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let step = 1.0 - self.strength(next) * (1.0 - EDGE_COST);
+                let c = c + (step * length * 1000.0) as u32;
+                let n = index(next);
+                if c < cost[n] {
+                    cost[n] = c;
+                    previous[n] = i;
+                    queue.push(Reverse((c, n)));
+                }
+            }
+        }
+
+        let mut route = vec![to];
+        let mut i = index(to);
+        while previous[i] != usize::MAX {
+            i = previous[i];
+            route.push(point(i));
+        }
+        route.reverse();
+        route
+    }
+}
+
+/// An outline being traced.
+pub struct Scissors {
+    edges: EdgeMap,
+    /// The outline up to the last anchor.
+    path: Vec<(i32, i32)>,
+    /// Route from the last anchor to the pointer.
+    live: Vec<(i32, i32)>,
+}
+
+impl Scissors {
+    /// Starts an outline on the edges of `image` at canvas position `pos`.
+    pub fn start(image: &DynamicImage, pos: Vec2) -> Scissors {
+        let edges = EdgeMap::new(image);
+        let start = edges.snap(pos);
+        Scissors {
+            edges,
+            path: vec![start],
+            live: Vec::new(),
+        }
+    }
+
+    /// Routes the outline to the pointer, anchoring it once the route gets long.
+    pub fn trace(&mut self, pos: Vec2) {
+        let last = self.path[self.path.len() - 1];
+        self.live = self.edges.route(last, self.edges.snap(pos));
+        if self.live.len() > ANCHOR_SPACING {
+            self.path.extend(self.live.drain(..).skip(1));
+        }
+    }
+
+    /// Canvas pixel centers along the outline, up to the pointer.
+    pub fn points(&self) -> impl Iterator<Item = Vec2> + '_ {
+        self.path
+            .iter()
+            .chain(self.live.iter().skip(1))
+            .map(|&(x, y)| Vec2::new(x as f32 + 0.5, y as f32 + 0.5))
+    }
+
+    /// Closes the outline back to its start and selects its bounds. `None` if nothing was traced.
+    pub fn finish(mut self) -> Option<Selection> {
+        self.path.extend(self.live.drain(..).skip(1));
+        let (first, last) = (self.path[0], self.path[self.path.len() - 1]);
+        self.path
+            .extend(self.edges.route(last, first).into_iter().skip(1));
+        if self.path.len() < 3 {
+            return None;
+        }
+
+        let (mut x0, mut y0, mut x1, mut y1) = (first.0, first.1, first.0, first.1);
+        for &(x, y) in &self.path {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+        Some(Selection {
+            x: x0 as u32,
+            y: y0 as u32,
+            width: (x1 - x0 + 1) as u32,
+            height: (y1 - y0 + 1) as u32,
+        })
+    }
+}
@@ -0,0 +1,86 @@
+use nannou::image::{imageops, DynamicImage, GenericImageView};
+use nannou::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+/// A rectangular region of the canvas in pixel coordinates, with the origin at the top left.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Selection {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Selection {
+    /// Builds the selection spanned by two canvas positions, clipped to a `width` x `height`
+    /// canvas. Both corner pixels are included.
+    pub fn from_corners(a: Vec2, b: Vec2, width: u32, height: u32) -> Option<Selection> {
+        let clip = |v: f32, max: u32| (v.floor().max(0.0) as u32).min(max);
+        let (x0, x1) = (clip(a.x.min(b.x), width), clip(a.x.max(b.x) + 1.0, width));
+        let (y0, y1) = (clip(a.y.min(b.y), height), clip(a.y.max(b.y) + 1.0, height));
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        Some(Selection {
+            x: x0,
+            y: y0,
+            width: x1 - x0,
+            height: y1 - y0,
+        })
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    /// Combines the result of an operation with the image it was applied to, keeping `edited`
+    /// inside the selection and `original` outside it.
+    pub fn clip(&self, original: &DynamicImage, edited: &DynamicImage) -> DynamicImage {
+        let mut out = original.to_rgba8();
+        let inside = edited
+            .crop_imm(self.x, self.y, self.width, self.height)
+            .to_rgba8();
+        imageops::replace(&mut out, &inside, self.x, self.y);
+        DynamicImage::ImageRgba8(out)
+    }
+}
+
+/// Selects the bounds of the "island" of non-transparent pixels connected to `(x, y)`, including
+/// diagonally, whatever their colors. Returns `None` when the pixel itself is transparent.
+pub fn island(image: &DynamicImage, x: u32, y: u32) -> Option<Selection> {
+    let (width, height) = image.dimensions();
+    let opaque = |x: u32, y: u32| image.get_pixel(x, y)[3] > 0;
+    if x >= width || y >= height || !opaque(x, y) {
+        return None;
+    }
+
+    let mut seen = vec![false; (width * height) as usize];
+    seen[(y * width + x) as usize] = true;
+    let mut queue = vec![(x, y)];
+    let (mut x0, mut y0, mut x1, mut y1) = (x, y, x, y);
+
+    while let Some((x, y)) = queue.pop() {
+        x0 = x0.min(x);
+        y0 = y0.min(y);
+        x1 = x1.max(x);
+        y1 = y1.max(y);
+
+        for ny in y.saturating_sub(1)..=(y + 1).min(height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(width - 1) {
+                let i = (ny * width + nx) as usize;
+                if !seen[i] && opaque(nx, ny) {
+                    seen[i] = true;
+                    queue.push((nx, ny));
+                }
+            }
+        }
+    }
+
+    Some(Selection {
+        x: x0,
+        y: y0,
+        width: x1 - x0 + 1,
+        height: y1 - y0 + 1,
+    })
+}
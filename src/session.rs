@@ -0,0 +1,154 @@
+//! Autosave of documents with unsaved changes, so work survives a crash or a forced quit. Each
+//! document is written as OpenRaster to the session directory on a background thread, alongside
+//! a `session.toml` manifest naming them. Saving a document or closing it drops it from the
+//! session, and once nothing unsaved is left the directory is removed.
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::crash;
+use crate::layers::LayerStack;
+use crate::ora;
+
+const MANIFEST: &str = "session.toml";
+
+/// Set while a background autosave is writing, so a slow one isn't overlapped by the next.
+static WRITING: AtomicBool = AtomicBool::new(false);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A new id for a document's session file, unique within this run.
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Directory the session is written to.
+pub fn session_dir() -> PathBuf {
+    dirs::data_local_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("image_editor")
+        .join("session")
+}
+
+/// A document in the session manifest.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SessionDocument {
+    pub name: String,
+    /// File the document was opened from or last saved to.
+    pub path: Option<PathBuf>,
+    /// OpenRaster file in the session directory holding its layers.
+    pub file: String,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct Manifest {
+    documents: Vec<SessionDocument>,
+}
+
+/// Timer deciding when the next autosave is due.
+#[derive(Default)]
+pub struct Autosave {
+    last: Option<Instant>,
+}
+
+impl Autosave {
+    /// Whether `interval` has passed since the last autosave.
+    pub fn due(&self, interval: Duration) -> bool {
+        self.last.map_or(true, |t| t.elapsed() >= interval) && !WRITING.load(Ordering::Acquire)
+    }
+
+    /// Writes the session on a background thread. Documents are given with their layers when
+    /// they changed since the last autosave, or without when their session file is current.
+    pub fn run(&mut self, documents: Vec<(SessionDocument, Option<LayerStack>)>) {
+        self.last = Some(Instant::now());
+        WRITING.store(true, Ordering::Release);
+        std::thread::spawn(move || {
+            if let Err(e) = write(documents) {
+                log::warn!("Autosave failed: {}", e);
+            }
+            WRITING.store(false, Ordering::Release);
+        });
+    }
+}
+
+fn write(documents: Vec<(SessionDocument, Option<LayerStack>)>) -> io::Result<()> {
+    let dir = session_dir();
+    if documents.is_empty() {
+        return clear();
+    }
+    std::fs::create_dir_all(&dir)?;
+
+    let mut manifest = Manifest::default();
+    for (document, layers) in documents {
+        if let Some(layers) = layers {
+            // Written aside and renamed so a crash mid-write leaves the previous copy intact
+            let partial = dir.join(format!("{}.partial", document.file));
+            ora::save(&layers, &partial)?;
+            std::fs::rename(&partial, dir.join(&document.file))?;
+        }
+        manifest.documents.push(document);
+    }
+
+    let source =
+        toml::to_string_pretty(&manifest).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    let partial = dir.join(format!("{}.partial", MANIFEST));
+    std::fs::write(&partial, source)?;
+    std::fs::rename(&partial, dir.join(MANIFEST))?;
+
+    // Files of documents that have since been saved or closed
+    for entry in std::fs::read_dir(&dir)? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name != MANIFEST && !manifest.documents.iter().any(|d| d.file == name) {
+            let _ = std::fs::remove_file(dir.join(name));
+        }
+    }
+    Ok(())
+}
+
+/// Documents left unsaved by the previous run.
+pub fn pending() -> Vec<SessionDocument> {
+    let path = session_dir().join(MANIFEST);
+    if !path.exists() {
+        return Vec::new();
+    }
+    match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|source| toml::from_str::<Manifest>(&source).map_err(|e| e.to_string()))
+    {
+        Ok(manifest) => manifest.documents,
+        Err(e) => {
+            log::warn!("Ignoring invalid session {}: {}", path.display(), e);
+            Vec::new()
+        }
+    }
+}
+
+/// Reads the layers of a document from the previous session.
+pub fn restore(document: &SessionDocument) -> io::Result<LayerStack> {
+    ora::open(&session_dir().join(&document.file))
+}
+
+/// Moves the session file of a document that failed to restore into the recovery directory, where
+/// later autosaves won't remove it. Returns its new path.
+pub fn set_aside(document: &SessionDocument) -> io::Result<PathBuf> {
+    let stamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let dir = crash::recovery_dir();
+    std::fs::create_dir_all(&dir)?;
+    let to = dir.join(format!("unrestored-{}-{}", stamp, document.file));
+    std::fs::rename(session_dir().join(&document.file), &to)?;
+    Ok(to)
+}
+
+/// Removes the session, discarding any unsaved work in it.
+pub fn clear() -> io::Result<()> {
+    match std::fs::remove_dir_all(session_dir()) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,109 @@
+//! Line, rectangle and ellipse outlines drawn with the brush size and color.
+
+use line_drawing::{Bresenham, XiaolinWu};
+use nannou::image::Rgba;
+use nannou::prelude::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::canvas::Canvas;
+use crate::layers::over;
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum Shape {
+    Line,
+    /// Axis-aligned rectangle spanned by the two points.
+    Rectangle,
+    /// Ellipse inscribed in the rectangle spanned by the two points.
+    Ellipse,
+}
+
+/// Per-pixel coverage of a shape. Segments take the maximum rather than adding up, so overlapping
+/// parts of the outline don't blend twice.
+struct Coverage {
+    width: u32,
+    height: u32,
+    values: Vec<f32>,
+}
+
+impl Coverage {
+    fn new(width: u32, height: u32) -> Coverage {
+        Coverage {
+            width,
+            height,
+            values: vec![0.0; (width * height) as usize],
+        }
+    }
+
+    /// Covers a `size` x `size` square centered on `(x, y)`.
+    fn dab(&mut self, x: i32, y: i32, size: i32, value: f32) {
+        let half = size / 2;
+        for py in y - half..y - half + size.max(1) {
+            for px in x - half..x - half + size.max(1) {
+                if px < 0 || py < 0 || px >= self.width as i32 || py >= self.height as i32 {
+                    continue;
+                }
+                let v = &mut self.values[(py as u32 * self.width + px as u32) as usize];
+                *v = v.max(value);
+            }
+        }
+    }
+
+    fn line(&mut self, a: Vec2, b: Vec2, size: i32, antialias: bool) {
+        if antialias {
+            for ((x, y), value) in XiaolinWu::<f32, i32>::new((a.x, a.y), (b.x, b.y)) {
+                self.dab(x, y, size, value);
+            }
+        } else {
+            let (a, b) = (a.round(), b.round());
+            for (x, y) in Bresenham::<i32>::new((a.x as i32, a.y as i32), (b.x as i32, b.y as i32))
+            {
+                self.dab(x, y, size, 1.0);
+            }
+        }
+    }
+}
+
+/// Draws `shape` between canvas positions `a` and `b` onto `canvas`, with lines `size` pixels
+/// wide.
+#[allow(clippy::too_many_arguments)]
+pub fn draw(
+    canvas: &mut Canvas,
+    shape: Shape,
+    a: Vec2,
+    b: Vec2,
+    size: f32,
+    color: [u8; 4],
+    opacity: f32,
+    antialias: bool,
+) {
+    let mut coverage = Coverage::new(canvas.width(), canvas.height());
+    let size = size.round().max(1.0) as i32;
+
+    match shape {
+        Shape::Line => coverage.line(a, b, size, antialias),
+        Shape::Rectangle => {
+            let corners = [a, Vec2::new(b.x, a.y), b, Vec2::new(a.x, b.y)];
+            for i in 0..4 {
+                coverage.line(corners[i], corners[(i + 1) % 4], size, antialias);
+            }
+        }
+        Shape::Ellipse => {
+            let center = (a + b) / 2.0;
+            let radius = (b - a).abs() / 2.0;
+            // Enough segments that each is only a couple of pixels long
+            let segments = ((radius.x + radius.y) * std::f32::consts::PI / 2.0).max(16.0) as usize;
+            let point = |i: usize| {
+                let t = i as f32 / segments as f32 * std::f32::consts::TAU;
+                center + Vec2::new(radius.x * t.cos(), radius.y * t.sin())
+            };
+            for i in 0..segments {
+                coverage.line(point(i), point(i + 1), size, antialias);
+            }
+        }
+    }
+
+    canvas.fill(|x, y, pixel| {
+        let value = coverage.values[(y * coverage.width + x) as usize];
+        (value > 0.0).then(|| over(pixel, Rgba(color), value * opacity))
+    });
+}
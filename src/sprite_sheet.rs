@@ -0,0 +1,196 @@
+//! Sprite sheets: a grid of animation frames in one image. Importing cuts a sheet into layers, one
+//! per cell, which are then the frames of an animation export. Exporting packs the visible layers
+//! back into a sheet, optionally with a JSON file listing where each frame is.
+
+use std::io;
+use std::path::Path;
+
+use nannou::image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+use serde::Serialize;
+
+use crate::export::animation::Frame;
+
+#[derive(Clone)]
+pub struct SpriteSheetOptions {
+    /// Imported sheets are cut into cells of `cell_width` x `cell_height` rather than into
+    /// `grid_columns` x `grid_rows` cells.
+    pub by_size: bool,
+    pub cell_width: f32,
+    pub cell_height: f32,
+    pub grid_columns: f32,
+    pub grid_rows: f32,
+    /// Imported cells with nothing in them are dropped rather than kept as blank frames.
+    pub skip_empty: bool,
+    /// Columns of an exported sheet, or 0 to keep it about as tall as it is wide.
+    pub columns: f32,
+    /// Transparent pixels around each exported frame.
+    pub padding: f32,
+    /// Exported sheets get a JSON file of frame rects next to them.
+    pub metadata: bool,
+}
+
+impl Default for SpriteSheetOptions {
+    fn default() -> Self {
+        Self {
+            by_size: true,
+            cell_width: 32.0,
+            cell_height: 32.0,
+            grid_columns: 4.0,
+            grid_rows: 4.0,
+            skip_empty: false,
+            columns: 0.0,
+            padding: 0.0,
+            metadata: true,
+        }
+    }
+}
+
+/// Cuts `sheet` into cells, left to right and top to bottom. Blank cells are kept as frames, since
+/// an animation may pause on one, unless `skip_empty` is set for sheets that leave the end of the
+/// last row empty.
+pub fn slice(sheet: &DynamicImage, options: &SpriteSheetOptions) -> Vec<RgbaImage> {
+    let (width, height) = sheet.dimensions();
+    let (cell_width, cell_height) = if options.by_size {
+        (options.cell_width as u32, options.cell_height as u32)
+    } else {
+        (
+            width / (options.grid_columns as u32).max(1),
+            height / (options.grid_rows as u32).max(1),
+        )
+    };
+    let (cell_width, cell_height) = (cell_width.clamp(1, width), cell_height.clamp(1, height));
+
+    let mut cells = Vec::new();
+    for row in 0..height / cell_height {
+        for column in 0..width / cell_width {
+            let cell = sheet
+                .view(
+                    column * cell_width,
+                    row * cell_height,
+                    cell_width,
+                    cell_height,
+                )
+                .to_image();
+            if !options.skip_empty || cell.pixels().any(|pixel| pixel.0[3] > 0) {
+                cells.push(cell);
+            }
+        }
+    }
+    cells
+}
+
+/// Where a frame went in a packed sheet, in pixels.
+#[derive(Serialize)]
+struct FrameRect<'a> {
+    name: &'a str,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    /// Milliseconds the frame is shown.
+    delay: u32,
+}
+
+#[derive(Serialize)]
+struct SheetMetadata<'a> {
+    image: &'a str,
+    width: u32,
+    height: u32,
+    frames: Vec<FrameRect<'a>>,
+}
+
+/// Packs `frames` into a grid and saves it to `path` as a PNG, along with `<name>.json` if
+/// `options.metadata` is set.
+pub fn export(frames: &[Frame], path: &Path, options: &SpriteSheetOptions) -> io::Result<()> {
+    if frames.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no visible layers to pack",
+        ));
+    }
+    let (cell_width, cell_height) = frames[0].image.dimensions();
+    let columns = match options.columns as u32 {
+        0 => (frames.len() as f32).sqrt().ceil() as u32,
+        columns => columns.min(frames.len() as u32),
+    };
+    let rows = (frames.len() as u32 + columns - 1) / columns;
+    let padding = options.padding as u32;
+
+    let mut sheet = RgbaImage::new(
+        columns * (cell_width + padding) + padding,
+        rows * (cell_height + padding) + padding,
+    );
+    let mut rects = Vec::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let (column, row) = (i as u32 % columns, i as u32 / columns);
+        let x = padding + column * (cell_width + padding);
+        let y = padding + row * (cell_height + padding);
+        imageops::replace(&mut sheet, &frame.image.to_rgba8(), x, y);
+        rects.push(FrameRect {
+            name: &frame.name,
+            x,
+            y,
+            width: cell_width,
+            height: cell_height,
+            delay: frame.delay,
+        });
+    }
+
+    let path = path.with_extension("png");
+    sheet
+        .save(&path)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    if !options.metadata {
+        return Ok(());
+    }
+
+    let metadata = SheetMetadata {
+        image: path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default(),
+        width: sheet.width(),
+        height: sheet.height(),
+        frames: rects,
+    };
+    let json = serde_json::to_string_pretty(&metadata)?;
+    std::fs::write(path.with_extension("json"), json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nannou::image::Rgba;
+
+    /// A 3 x 1 sheet of 2 px cells with the middle one blank.
+    fn sheet() -> DynamicImage {
+        let mut sheet = RgbaImage::new(6, 2);
+        for (x, _, pixel) in sheet.enumerate_pixels_mut() {
+            if !(2..4).contains(&x) {
+                *pixel = Rgba([255, 0, 0, 255]);
+            }
+        }
+        DynamicImage::ImageRgba8(sheet)
+    }
+
+    fn options(skip_empty: bool) -> SpriteSheetOptions {
+        SpriteSheetOptions {
+            cell_width: 2.0,
+            cell_height: 2.0,
+            skip_empty,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn blank_cells_are_kept_as_frames() {
+        let cells = slice(&sheet(), &options(false));
+        assert_eq!(cells.len(), 3);
+        assert!(cells[1].pixels().all(|p| p.0[3] == 0));
+    }
+
+    #[test]
+    fn blank_cells_can_be_skipped() {
+        assert_eq!(slice(&sheet(), &options(true)).len(), 2);
+    }
+}
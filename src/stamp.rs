@@ -0,0 +1,83 @@
+use nannou::image::{imageops, DynamicImage, Pixel, RgbaImage};
+use rand::Rng;
+
+use crate::canvas::Canvas;
+use crate::selection::Selection;
+
+/// Random variation applied each time a stamp is placed.
+#[derive(Clone, Copy, Default)]
+pub struct StampJitter {
+    /// Rotate by a random multiple of 90 degrees.
+    pub rotate: bool,
+    /// Randomly mirror horizontally and vertically.
+    pub flip: bool,
+}
+
+/// Pixels captured from a selection that can be painted repeatedly like a brush.
+pub struct Stamp {
+    pub image: RgbaImage,
+}
+
+impl Stamp {
+    pub fn capture(image: &DynamicImage, selection: &Selection) -> Stamp {
+        Stamp {
+            image: image
+                .crop_imm(selection.x, selection.y, selection.width, selection.height)
+                .to_rgba8(),
+        }
+    }
+
+    /// The stamp with a random rotation/flip picked according to `jitter`.
+    pub fn variant(&self, jitter: &StampJitter, rng: &mut impl Rng) -> RgbaImage {
+        let mut image = match jitter.rotate.then(|| rng.gen_range(0..4)).unwrap_or(0) {
+            1 => imageops::rotate90(&self.image),
+            2 => imageops::rotate180(&self.image),
+            3 => imageops::rotate270(&self.image),
+            _ => self.image.clone(),
+        };
+        if jitter.flip && rng.gen::<bool>() {
+            imageops::flip_horizontal_in_place(&mut image);
+        }
+        if jitter.flip && rng.gen::<bool>() {
+            imageops::flip_vertical_in_place(&mut image);
+        }
+        image
+    }
+
+    /// Blends a variant of the stamp onto `canvas` centered on `(x, y)`.
+    pub fn place(
+        &self,
+        canvas: &mut Canvas,
+        x: i32,
+        y: i32,
+        jitter: &StampJitter,
+        rng: &mut impl Rng,
+    ) {
+        let stamp = self.variant(jitter, rng);
+        let (left, top) = (x - stamp.width() as i32 / 2, y - stamp.height() as i32 / 2);
+        blend_onto(canvas, &stamp, left, top);
+    }
+
+    /// Blends the stamp unchanged onto `canvas` with its top-left corner at the origin of the
+    /// `cell` x `cell` grid cell containing `(x, y)`. Returns that origin.
+    pub fn place_in_cell(&self, canvas: &mut Canvas, x: i32, y: i32, cell: u32) -> (i32, i32) {
+        let cell = cell.max(1) as i32;
+        let origin = (x.div_euclid(cell) * cell, y.div_euclid(cell) * cell);
+        blend_onto(canvas, &self.image, origin.0, origin.1);
+        origin
+    }
+
+    /// Distance the cursor has to travel between stamps while dragging.
+    pub fn spacing(&self) -> f32 {
+        (self.image.width().max(self.image.height()) as f32).max(1.0)
+    }
+}
+
+/// Blends `image` onto `canvas` with its top-left corner at `(left, top)`.
+fn blend_onto(canvas: &mut Canvas, image: &RgbaImage, left: i32, top: i32) {
+    canvas.stamp(left, top, image.width(), image.height(), |x, y, mut dst| {
+        let pixel = image.get_pixel((x as i32 - left) as u32, (y as i32 - top) as u32);
+        dst.blend(pixel);
+        Some(dst)
+    });
+}
@@ -0,0 +1,372 @@
+//! Paint strokes with separate opacity and flow. Each dab builds up per-pixel coverage at the
+//! brush flow, and pixels are always recomputed from the layer as it was when the stroke began,
+//! so overlapping dabs within one stroke never exceed the brush opacity.
+//!
+//! Input positions are averaged over a short window to steady the hand, and a Catmull-Rom spline
+//! is drawn through the smoothed points, so fast strokes curve instead of showing straight
+//! segments between mouse events.
+//!
+//! The path of a stroke is recorded alongside, so the stroke can be redrawn afterwards with
+//! post-processing such as tapering.
+
+use nannou::image::{DynamicImage, Rgba, RgbaImage};
+use nannou::prelude::Vec2;
+
+use crate::brush::{self, BrushType, Dab};
+use crate::canvas::Canvas;
+use crate::compositing::{blend, BlendMode};
+use crate::layers::over;
+use crate::selection::Selection;
+
+pub struct Stroke {
+    base: RgbaImage,
+    coverage: Vec<f32>,
+    /// Distance travelled since the last dab.
+    pub travel: f32,
+    /// Latest raw input positions, for the stabilizer.
+    input: Vec<Vec2>,
+    /// The last smoothed points the spline passes through.
+    points: Vec<Vec2>,
+    /// Colors a smudge stroke is carrying, picked up by its first dab.
+    pub smudge: Option<brush::Smudge>,
+}
+
+impl Stroke {
+    /// Starts a stroke on a layer with the given pixels.
+    pub fn begin(pixels: &DynamicImage) -> Stroke {
+        let base = pixels.to_rgba8();
+        let coverage = vec![0.0; (base.width() * base.height()) as usize];
+        Stroke {
+            base,
+            coverage,
+            travel: f32::MAX,
+            input: Vec::new(),
+            points: Vec::new(),
+            smudge: None,
+        }
+    }
+
+    /// The layer's pixels before the stroke.
+    pub fn base(&self) -> &RgbaImage {
+        &self.base
+    }
+
+    pub fn into_base(self) -> RgbaImage {
+        self.base
+    }
+
+    /// Takes the next input position and returns the points to draw through, starting at the
+    /// end of the previous call. The position is averaged with up to `stabilizer` previous ones
+    /// first. The first call returns the start point twice so it gets a dab.
+    pub fn advance(&mut self, pos: Vec2, stabilizer: usize) -> Vec<Vec2> {
+        self.input.push(pos);
+        if self.input.len() > stabilizer + 1 {
+            self.input.drain(..self.input.len() - stabilizer - 1);
+        }
+        let smoothed = self.input.iter().sum::<Vec2>() / self.input.len() as f32;
+
+        let points = match self.points[..] {
+            [] => vec![smoothed, smoothed],
+            [.., p1] if p1.distance(smoothed) < f32::EPSILON => return Vec::new(),
+            [p1] => vec![p1, smoothed],
+            [.., p0, p1] => {
+                // The next point isn't known yet, so the segment ends heading straight at the
+                // new point.
+                let steps = (p1.distance(smoothed) / SPLINE_STEP).ceil().max(1.0) as usize;
+                (0..=steps)
+                    .map(|step| catmull_rom(p0, p1, smoothed, smoothed, step as f32 / steps as f32))
+                    .collect()
+            }
+        };
+
+        self.points.push(smoothed);
+        if self.points.len() > 2 {
+            self.points.remove(0);
+        }
+        points
+    }
+
+    /// Adds a dab of strength `amount` (0-1, already scaled by flow) at a pixel and returns the
+    /// pixel's total coverage so far.
+    pub fn dab(&mut self, x: u32, y: u32, amount: f32) -> f32 {
+        let c = &mut self.coverage[(y * self.base.width() + x) as usize];
+        *c += amount.clamp(0.0, 1.0) * (1.0 - *c);
+        *c
+    }
+
+    /// The stroke's color at a pixel: `color` blended over the original pixel at its coverage,
+    /// capped at `opacity`.
+    pub fn paint(&self, x: u32, y: u32, color: [u8; 4], opacity: f32, mode: BlendMode) -> Rgba<u8> {
+        let coverage = self.coverage[(y * self.base.width() + x) as usize];
+        blend(
+            *self.base.get_pixel(x, y),
+            Rgba(color),
+            coverage * opacity,
+            mode,
+        )
+    }
+
+    /// The original pixel `offset` away laid over the original pixel at its coverage, capped at
+    /// `opacity`. Both are read from the layer as it was when the stroke began, so the stroke
+    /// never copies its own paint. `None` when the source is off the canvas.
+    pub fn clone_stamp(&self, x: u32, y: u32, offset: Vec2, opacity: f32) -> Option<Rgba<u8>> {
+        let (sx, sy) = ((x as f32 + offset.x).round(), (y as f32 + offset.y).round());
+        if sx < 0.0 || sy < 0.0 || sx >= self.base.width() as f32 || sy >= self.base.height() as f32
+        {
+            return None;
+        }
+        let coverage = self.coverage[(y * self.base.width() + x) as usize];
+        Some(over(
+            *self.base.get_pixel(x, y),
+            *self.base.get_pixel(sx as u32, sy as u32),
+            coverage * opacity,
+        ))
+    }
+
+    /// The original pixel with its alpha reduced by its coverage, capped at `opacity`.
+    pub fn erase(&self, x: u32, y: u32, opacity: f32) -> Rgba<u8> {
+        let coverage = self.coverage[(y * self.base.width() + x) as usize];
+        let mut pixel = *self.base.get_pixel(x, y);
+        pixel.0[3] = (pixel.0[3] as f32 * (1.0 - coverage * opacity)).round() as u8;
+        pixel
+    }
+}
+
+/// One cursor sample of a stroke.
+#[derive(Clone, Copy, Debug)]
+pub struct StrokeSample {
+    pub pos: Vec2,
+    /// Pen pressure, 1 for the mouse.
+    pub pressure: f32,
+    /// Extra size factor applied by post-processing, such as tapering.
+    pub scale: f32,
+}
+
+/// Post-processing that can be applied to the last stroke.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StrokeFx {
+    /// Thins both ends of the stroke to a point.
+    Taper,
+    /// Raises every sample's pressure to this power: above 1 needs a firmer press, below 1 a
+    /// lighter one.
+    PressureCurve(f32),
+    /// Drops samples that don't change the stroke's shape, then refits a smooth curve through
+    /// the rest.
+    Simplify,
+}
+
+/// Fraction of the stroke's length at each end that `StrokeFx::Taper` thins.
+const TAPER_LENGTH: f32 = 0.2;
+/// Largest deviation in pixels `StrokeFx::Simplify` ignores.
+const SIMPLIFY_TOLERANCE: f32 = 1.5;
+/// Pixels between the points `Stroke::advance` places along the spline.
+const SPLINE_STEP: f32 = 2.0;
+
+/// The path and settings of the last stroke, kept so it can be redrawn with post-processing
+/// until the next operation.
+pub struct StrokePath {
+    /// Pixels of the layer before the stroke.
+    pub base: DynamicImage,
+    /// Index of the layer painted on.
+    pub layer: usize,
+    pub samples: Vec<StrokeSample>,
+    pub brush: BrushType,
+    pub dab: Dab,
+    pub pressure_size: bool,
+    pub pressure_opacity: bool,
+    pub selection: Option<Selection>,
+}
+
+impl StrokePath {
+    pub fn push(&mut self, pos: Vec2, pressure: f32) {
+        self.samples.push(StrokeSample {
+            pos,
+            pressure,
+            scale: 1.0,
+        });
+    }
+
+    pub fn apply(&mut self, fx: StrokeFx) {
+        match fx {
+            StrokeFx::Taper => {
+                let lengths = self.lengths();
+                let total = lengths.last().copied().unwrap_or(0.0).max(f32::EPSILON);
+                for (sample, length) in self.samples.iter_mut().zip(lengths) {
+                    let from_end = length.min(total - length) / total;
+                    sample.scale *= (from_end / TAPER_LENGTH).min(1.0);
+                }
+            }
+            StrokeFx::PressureCurve(power) => {
+                for sample in &mut self.samples {
+                    sample.pressure = sample.pressure.clamp(0.0, 1.0).powf(power);
+                }
+            }
+            StrokeFx::Simplify => {
+                let kept = simplify(&self.samples, SIMPLIFY_TOLERANCE);
+                self.samples = refit(&kept);
+            }
+        }
+    }
+
+    /// Distance along the path at each sample.
+    fn lengths(&self) -> Vec<f32> {
+        let mut total = 0.0;
+        let mut lengths = Vec::with_capacity(self.samples.len());
+        for (i, sample) in self.samples.iter().enumerate() {
+            if i > 0 {
+                total += sample.pos.distance(self.samples[i - 1].pos);
+            }
+            lengths.push(total);
+        }
+        lengths
+    }
+
+    /// Redraws the stroke over its base pixels into `pixels`.
+    pub fn replay(&self, pixels: &mut DynamicImage) {
+        *pixels = self.base.clone();
+        let mut stroke = Stroke::begin(pixels);
+        let mut canvas = Canvas::new(pixels, self.selection.as_ref());
+
+        for pair in self.samples.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let mut dab = self.dab.clone();
+            if self.pressure_size {
+                dab.size *= b.pressure;
+            }
+            if self.pressure_opacity {
+                dab.opacity *= b.pressure;
+            }
+            dab.size = (dab.size * b.scale).max(1.0);
+
+            brush::stroke_segment(
+                &mut canvas,
+                &mut stroke,
+                self.brush.as_brush(),
+                a.pos,
+                b.pos,
+                &dab,
+            );
+        }
+    }
+}
+
+/// Ramer-Douglas-Peucker: keeps only the samples that deviate from a straight line between their
+/// neighbours by more than `tolerance`.
+fn simplify(samples: &[StrokeSample], tolerance: f32) -> Vec<StrokeSample> {
+    if samples.len() < 3 {
+        return samples.to_vec();
+    }
+
+    let (first, last) = (samples[0], samples[samples.len() - 1]);
+    let line = last.pos - first.pos;
+    let distance = |p: Vec2| {
+        if line.length() < f32::EPSILON {
+            p.distance(first.pos)
+        } else {
+            (line.perp_dot(p - first.pos) / line.length()).abs()
+        }
+    };
+    let (index, max) = samples[1..samples.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (i + 1, distance(s.pos)))
+        .fold((0, 0.0), |best, d| if d.1 > best.1 { d } else { best });
+
+    if max <= tolerance {
+        return vec![first, last];
+    }
+    let mut kept = simplify(&samples[..=index], tolerance);
+    kept.pop();
+    kept.extend(simplify(&samples[index..], tolerance));
+    kept
+}
+
+/// Resamples a Catmull-Rom spline through `samples` at about one sample per pixel.
+fn refit(samples: &[StrokeSample]) -> Vec<StrokeSample> {
+    if samples.len() < 3 {
+        return samples.to_vec();
+    }
+
+    let at = |i: isize| samples[i.clamp(0, samples.len() as isize - 1) as usize];
+    let mut out = Vec::new();
+    for i in 0..samples.len() as isize - 1 {
+        let (p0, p1, p2, p3) = (at(i - 1), at(i), at(i + 1), at(i + 2));
+        let steps = (p1.pos.distance(p2.pos).ceil() as usize).max(1);
+        for step in 0..steps {
+            let t = step as f32 / steps as f32;
+            out.push(StrokeSample {
+                pos: catmull_rom(p0.pos, p1.pos, p2.pos, p3.pos, t),
+                pressure: p1.pressure + (p2.pressure - p1.pressure) * t,
+                scale: p1.scale + (p2.scale - p1.scale) * t,
+            });
+        }
+    }
+    out.push(samples[samples.len() - 1]);
+    out
+}
+
+/// Point `t` (0-1) of the way along the Catmull-Rom segment from `p1` to `p2`.
+fn catmull_rom(p0: Vec2, p1: Vec2, p2: Vec2, p3: Vec2, t: f32) -> Vec2 {
+    let (t2, t3) = (t * t, t * t * t);
+    0.5 * (2.0 * p1
+        + (p2 - p0) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples(points: &[(f32, f32)]) -> Vec<StrokeSample> {
+        points
+            .iter()
+            .map(|&(x, y)| StrokeSample {
+                pos: Vec2::new(x, y),
+                pressure: 1.0,
+                scale: 1.0,
+            })
+            .collect()
+    }
+
+    fn positions(samples: &[StrokeSample]) -> Vec<(f32, f32)> {
+        samples.iter().map(|s| (s.pos.x, s.pos.y)).collect()
+    }
+
+    #[test]
+    fn simplify_drops_samples_along_a_line() {
+        let line = samples(&[(0.0, 0.0), (1.0, 0.5), (2.0, -0.5), (3.0, 0.0), (4.0, 0.0)]);
+        assert_eq!(positions(&simplify(&line, 1.0)), [(0.0, 0.0), (4.0, 0.0)]);
+    }
+
+    #[test]
+    fn simplify_keeps_corners() {
+        let corner = samples(&[(0.0, 0.0), (2.0, 0.0), (4.0, 0.0), (4.0, 2.0), (4.0, 4.0)]);
+        assert_eq!(
+            positions(&simplify(&corner, 1.0)),
+            [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0)]
+        );
+    }
+
+    #[test]
+    fn refit_passes_through_its_samples_about_a_pixel_apart() {
+        let kept = samples(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        let refitted = refit(&kept);
+        assert_eq!(refitted.len(), 21);
+        for sample in &kept {
+            assert!(refitted.iter().any(|s| s.pos.distance(sample.pos) < 1e-4));
+        }
+        for pair in refitted.windows(2) {
+            assert!(pair[0].pos.distance(pair[1].pos) <= 1.5);
+        }
+    }
+
+    #[test]
+    fn refit_blends_pressure_between_samples() {
+        let mut kept = samples(&[(0.0, 0.0), (4.0, 0.0), (8.0, 0.0)]);
+        kept[2].pressure = 0.0;
+        let refitted = refit(&kept);
+        assert_eq!(refitted[6].pressure, 0.5);
+        assert_eq!(refitted.last().map(|s| s.pressure), Some(0.0));
+    }
+}
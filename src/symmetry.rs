@@ -0,0 +1,95 @@
+//! Symmetry painting: every dab is repeated mirrored across axes through the canvas center, or
+//! rotated around it.
+
+use nannou::prelude::Vec2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Symmetry {
+    Off,
+    /// Mirrored left to right, across the vertical center line.
+    Horizontal,
+    /// Mirrored top to bottom, across the horizontal center line.
+    Vertical,
+    /// Mirrored across both center lines, giving four copies.
+    Both,
+    /// This many copies spaced evenly around the center.
+    Radial(u32),
+}
+
+/// Copies a new radial symmetry starts with.
+pub const DEFAULT_SPOKES: u32 = 6;
+
+impl Symmetry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Symmetry::Off => "Off",
+            Symmetry::Horizontal => "Horizontal",
+            Symmetry::Vertical => "Vertical",
+            Symmetry::Both => "Both",
+            Symmetry::Radial(_) => "Radial",
+        }
+    }
+
+    pub fn next(&self) -> Symmetry {
+        match self {
+            Symmetry::Off => Symmetry::Horizontal,
+            Symmetry::Horizontal => Symmetry::Vertical,
+            Symmetry::Vertical => Symmetry::Both,
+            Symmetry::Both => Symmetry::Radial(DEFAULT_SPOKES),
+            Symmetry::Radial(_) => Symmetry::Off,
+        }
+    }
+
+    /// Every position a dab at `pos` is stamped at on a `width` x `height` canvas, `pos` first.
+    pub fn positions(&self, pos: Vec2, width: f32, height: f32) -> Vec<Vec2> {
+        let center = Vec2::new(width, height) / 2.0;
+        let flip_x = Vec2::new(2.0 * center.x - pos.x, pos.y);
+        let flip_y = Vec2::new(pos.x, 2.0 * center.y - pos.y);
+        match *self {
+            Symmetry::Off => vec![pos],
+            Symmetry::Horizontal => vec![pos, flip_x],
+            Symmetry::Vertical => vec![pos, flip_y],
+            Symmetry::Both => vec![pos, flip_x, flip_y, 2.0 * center - pos],
+            Symmetry::Radial(spokes) => {
+                let (offset, spokes) = (pos - center, spokes.max(1));
+                (0..spokes)
+                    .map(|i| {
+                        let angle = i as f32 * std::f32::consts::TAU / spokes as f32;
+                        let (sin, cos) = angle.sin_cos();
+                        center
+                            + Vec2::new(
+                                offset.x * cos - offset.y * sin,
+                                offset.x * sin + offset.y * cos,
+                            )
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    /// Lines across a `width` x `height` canvas showing the axes, in canvas pixels.
+    pub fn axes(&self, width: f32, height: f32) -> Vec<(Vec2, Vec2)> {
+        let center = Vec2::new(width, height) / 2.0;
+        let vertical = (Vec2::new(center.x, 0.0), Vec2::new(center.x, height));
+        let horizontal = (Vec2::new(0.0, center.y), Vec2::new(width, center.y));
+        match *self {
+            Symmetry::Off => Vec::new(),
+            Symmetry::Horizontal => vec![vertical],
+            Symmetry::Vertical => vec![horizontal],
+            Symmetry::Both => vec![vertical, horizontal],
+            // A spoke from the center to the edge of the canvas's inscribed circle for each copy
+            Symmetry::Radial(spokes) => {
+                let (radius, spokes) = (center.x.min(center.y), spokes.max(1));
+                (0..spokes)
+                    .map(|i| {
+                        let angle = i as f32 * std::f32::consts::TAU / spokes as f32;
+                        (
+                            center,
+                            center + Vec2::new(angle.cos(), angle.sin()) * radius,
+                        )
+                    })
+                    .collect()
+            }
+        }
+    }
+}
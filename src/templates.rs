@@ -0,0 +1,98 @@
+//! New document templates: named canvas sizes and resolutions offered by the New Image panel. The
+//! built-in ones are read from `templates/default.toml`. Every `.toml` file in the `templates`
+//! folder of the config directory adds more in the same format, and templates saved from the
+//! panel go to `user.toml` there.
+
+use std::io;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+const BUILTIN: &str = include_str!("../templates/default.toml");
+/// File in the templates folder that templates saved from the panel are added to.
+const USER_FILE: &str = "user.toml";
+
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct Template {
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    /// Resolution in pixels per inch.
+    #[serde(default = "default_dpi")]
+    pub dpi: f32,
+}
+
+fn default_dpi() -> f32 {
+    72.0
+}
+
+impl Template {
+    /// Name and size, as shown on the template's button.
+    pub fn label(&self) -> String {
+        format!("{} ({} x {})", self.name, self.width, self.height)
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct TemplateFile {
+    #[serde(default, rename = "template")]
+    templates: Vec<Template>,
+}
+
+impl TemplateFile {
+    fn parse(source: &str) -> Result<TemplateFile, String> {
+        toml::from_str(source).map_err(|e| e.to_string())
+    }
+}
+
+pub fn dir() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("image_editor").join("templates"))
+}
+
+/// The built-in templates followed by the user's. Files that can't be read are skipped with a
+/// warning.
+pub fn load() -> Vec<Template> {
+    let mut templates = TemplateFile::parse(BUILTIN)
+        .expect("built-in templates are valid")
+        .templates;
+
+    let mut paths: Vec<PathBuf> = match dir().map(std::fs::read_dir) {
+        Some(Ok(entries)) => entries
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().map_or(false, |e| e == "toml"))
+            .collect(),
+        _ => return templates,
+    };
+    // Listed in a stable order regardless of the file system
+    paths.sort();
+    for path in paths {
+        match std::fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| TemplateFile::parse(&source))
+        {
+            Ok(file) => templates.extend(file.templates),
+            Err(e) => log::warn!("Ignoring invalid templates {}: {}", path.display(), e),
+        }
+    }
+    templates
+}
+
+/// Adds a template to the user's templates file, replacing any there with the same name.
+pub fn save(template: &Template) -> io::Result<()> {
+    let dir =
+        dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+    let path = dir.join(USER_FILE);
+    let mut file = match std::fs::read_to_string(&path) {
+        Ok(source) => TemplateFile::parse(&source)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => TemplateFile::default(),
+        Err(e) => return Err(e),
+    };
+    file.templates.retain(|t| t.name != template.name);
+    file.templates.push(template.clone());
+
+    std::fs::create_dir_all(&dir)?;
+    let source =
+        toml::to_string_pretty(&file).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+    std::fs::write(path, source)
+}
@@ -0,0 +1,133 @@
+//! Images drawn every frame, kept on the GPU between frames. Building a texture in `view` uploads
+//! the whole image each frame, and its new texture view also misses nannou's renderer, which
+//! keys bind groups on the view, so every frame allocated a fresh bind group per image as well.
+//! An `Uploaded` image is uploaded on first draw and hands out the same view until it is
+//! replaced, from any window.
+//!
+//! Previews are replaced often at the same size, so the texture of a dropped `Uploaded` goes into
+//! a pool keyed by size and is written over by the next image of that size instead of allocating
+//! a new one. Every pooled texture is RGBA8 sRGB, so the size is the whole key.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ops::Deref;
+
+use nannou::image::{DynamicImage, RgbaImage};
+use nannou::prelude::*;
+
+/// Most textures pooled per size, so a burst of one size doesn't hold on to memory for good.
+const POOL_LIMIT: usize = 4;
+const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+thread_local! {
+    /// Textures of dropped `Uploaded` images, by width and height.
+    static POOL: RefCell<HashMap<[u32; 2], Vec<wgpu::Texture>>> = RefCell::new(HashMap::new());
+}
+
+/// An image along with its texture, created the first time it is drawn. Derefs to the image.
+pub struct Uploaded {
+    image: DynamicImage,
+    texture: RefCell<Option<(wgpu::Texture, wgpu::TextureView)>>,
+}
+
+impl Uploaded {
+    pub fn new(image: DynamicImage) -> Uploaded {
+        Uploaded {
+            image,
+            texture: RefCell::new(None),
+        }
+    }
+
+    /// The texture view to draw the image with, uploading it on first use.
+    pub fn view(&self, app: &App) -> wgpu::TextureView {
+        self.texture
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                let texture = upload(app, &self.image.to_rgba8());
+                let view = texture.view().build();
+                (texture, view)
+            })
+            .1
+            .clone()
+    }
+}
+
+/// A texture holding `image`, taken from the pool if one of its size is free.
+fn upload(app: &App, image: &RgbaImage) -> wgpu::Texture {
+    let window = app.main_window();
+    let device_queue = window.device_queue_pair();
+    let size = [image.width(), image.height()];
+    let pooled = POOL.with(|pool| pool.borrow_mut().get_mut(&size).and_then(Vec::pop));
+    let texture = pooled.unwrap_or_else(|| {
+        wgpu::TextureBuilder::new()
+            .size(size)
+            .format(FORMAT)
+            .usage(wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST)
+            .build(device_queue.device())
+    });
+    write(device_queue.queue(), &texture, [0, 0], image);
+    texture
+}
+
+/// Writes `pixels` into `texture` with their top left corner at `origin`.
+pub fn write(queue: &wgpu::Queue, texture: &wgpu::Texture, origin: [u32; 2], pixels: &RgbaImage) {
+    queue.write_texture(
+        wgpu::ImageCopyTexture {
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d {
+                x: origin[0],
+                y: origin[1],
+                z: 0,
+            },
+            aspect: wgpu::TextureAspect::All,
+        },
+        pixels,
+        wgpu::ImageDataLayout {
+            offset: 0,
+            bytes_per_row: std::num::NonZeroU32::new(4 * pixels.width()),
+            rows_per_image: std::num::NonZeroU32::new(pixels.height()),
+        },
+        wgpu::Extent3d {
+            width: pixels.width(),
+            height: pixels.height(),
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+impl Drop for Uploaded {
+    fn drop(&mut self) {
+        if let Some((texture, _)) = self.texture.get_mut().take() {
+            POOL.with(|pool| {
+                let mut pool = pool.borrow_mut();
+                let free = pool.entry(texture.size()).or_default();
+                if free.len() < POOL_LIMIT {
+                    free.push(texture);
+                }
+            });
+        }
+    }
+}
+
+/// Clones get their own texture on first draw, as a texture goes back to the pool when the
+/// `Uploaded` holding it is dropped.
+impl Clone for Uploaded {
+    fn clone(&self) -> Uploaded {
+        Uploaded::new(self.image.clone())
+    }
+}
+
+impl Deref for Uploaded {
+    type Target = DynamicImage;
+
+    fn deref(&self) -> &DynamicImage {
+        &self.image
+    }
+}
+
+impl From<DynamicImage> for Uploaded {
+    fn from(image: DynamicImage) -> Uploaded {
+        Uploaded::new(image)
+    }
+}
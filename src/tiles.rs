@@ -0,0 +1,173 @@
+use nannou::image::{imageops, DynamicImage, GenericImageView, RgbaImage};
+
+use crate::canvas::Canvas;
+use crate::selection::Selection;
+
+/// Number of whole tiles across and down a sheet.
+pub fn grid_size(image: &DynamicImage, tile_size: u32) -> (u32, u32) {
+    (image.width() / tile_size, image.height() / tile_size)
+}
+
+enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+fn edge(image: &DynamicImage, tile_size: u32, index: u32, edge: Edge) -> Vec<[u8; 4]> {
+    let (cols, _) = grid_size(image, tile_size);
+    let (tx, ty) = ((index % cols) * tile_size, (index / cols) * tile_size);
+    let last = tile_size - 1;
+
+    (0..tile_size)
+        .map(|i| {
+            let (x, y) = match edge {
+                Edge::Top => (i, 0),
+                Edge::Bottom => (i, last),
+                Edge::Left => (0, i),
+                Edge::Right => (last, i),
+            };
+            image.get_pixel(tx + x, ty + y).0
+        })
+        .collect()
+}
+
+fn edge_cost(a: &[[u8; 4]], b: &[[u8; 4]]) -> u32 {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| (*a as i32 - *b as i32).unsigned_abs())
+                .sum::<u32>()
+        })
+        .sum()
+}
+
+/// Lays tiles from the sheet out on a `map_cols` x `map_rows` map, picking for each cell the tile
+/// whose left and top edges best continue its already placed neighbours. Returns the map as tile
+/// indices in row-major order.
+pub fn arrange(image: &DynamicImage, tile_size: u32, map_cols: u32, map_rows: u32) -> Vec<u32> {
+    let (cols, rows) = grid_size(image, tile_size);
+    let count = cols * rows;
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let edges: Vec<_> = (0..count)
+        .map(|i| {
+            (
+                edge(image, tile_size, i, Edge::Top),
+                edge(image, tile_size, i, Edge::Bottom),
+                edge(image, tile_size, i, Edge::Left),
+                edge(image, tile_size, i, Edge::Right),
+            )
+        })
+        .collect();
+
+    let mut map: Vec<u32> = Vec::with_capacity((map_cols * map_rows) as usize);
+    for cell in 0..map_cols * map_rows {
+        let (x, y) = (cell % map_cols, cell / map_cols);
+        let left = (x > 0).then(|| map[(cell - 1) as usize]);
+        let top = (y > 0).then(|| map[(cell - map_cols) as usize]);
+
+        // Rotate the starting candidate so equally good tiles don't collapse into one repeated tile
+        let tile = (0..count)
+            .map(|i| (i + cell) % count)
+            .min_by_key(|&i| {
+                let (t, _, l, _) = &edges[i as usize];
+                left.map_or(0, |n| edge_cost(&edges[n as usize].3, l))
+                    + top.map_or(0, |n| edge_cost(&edges[n as usize].1, t))
+            })
+            .unwrap();
+        map.push(tile);
+    }
+
+    map
+}
+
+/// Renders an arrangement map built by [`arrange`] into an image.
+pub fn render_arrangement(
+    image: &DynamicImage,
+    tile_size: u32,
+    map_cols: u32,
+    map_rows: u32,
+) -> DynamicImage {
+    let (cols, _) = grid_size(image, tile_size);
+    let mut out = RgbaImage::new(map_cols * tile_size, map_rows * tile_size);
+
+    for (cell, tile) in arrange(image, tile_size, map_cols, map_rows)
+        .into_iter()
+        .enumerate()
+    {
+        let cell = cell as u32;
+        let src = image.crop_imm(
+            (tile % cols) * tile_size,
+            (tile / cols) * tile_size,
+            tile_size,
+            tile_size,
+        );
+        imageops::replace(
+            &mut out,
+            &src.to_rgba8(),
+            (cell % map_cols) * tile_size,
+            (cell / map_cols) * tile_size,
+        );
+    }
+
+    DynamicImage::ImageRgba8(out)
+}
+
+/// Shifts the contents of `image` by `(dx, dy)`, wrapping what falls off one edge around to the
+/// opposite one. With a selection, only the selected area is shifted and wraps at its edges.
+pub fn wrap_shift(image: &mut DynamicImage, dx: i32, dy: i32, selection: Option<&Selection>) {
+    let area = selection.copied().unwrap_or(Selection {
+        x: 0,
+        y: 0,
+        width: image.width(),
+        height: image.height(),
+    });
+    let (w, h) = (area.width as i32, area.height as i32);
+    let source = image.crop_imm(area.x, area.y, area.width, area.height);
+    let (left, top) = (area.x as i32, area.y as i32);
+    Canvas::new(image, selection).stamp(left, top, area.width, area.height, |x, y, _| {
+        let sx = (x as i32 - left - dx).rem_euclid(w) as u32;
+        let sy = (y as i32 - top - dy).rem_euclid(h) as u32;
+        Some(source.get_pixel(sx, sy))
+    });
+}
+
+/// Offsets `image` by half its size so its edges meet in a cross through the middle, where they
+/// can be retouched, while the untouched middle wraps around to the edges. With `blend` above
+/// zero the original is cross-faded back in over that many pixels either side of the cross,
+/// which hides the seams without touching the edges.
+pub fn make_seamless(image: &DynamicImage, blend: u32) -> DynamicImage {
+    let (w, h) = image.dimensions();
+    let mut shifted = DynamicImage::ImageRgba8(image.to_rgba8());
+    wrap_shift(&mut shifted, (w / 2) as i32, (h / 2) as i32, None);
+    // The fade must stay clear of the edges for them to keep wrapping
+    let blend = blend.min(w.min(h) / 2) as f32;
+    if blend < 1.0 {
+        return shifted;
+    }
+
+    let (shifted, original) = (shifted.to_rgba8(), image.to_rgba8());
+    // How much of the original shows at `d` pixels from a seam: all of it on the seam, easing
+    // out to none at `blend`
+    let weight = |d: f32| {
+        let t = (1.0 - d / blend).clamp(0.0, 1.0);
+        t * t * (3.0 - 2.0 * t)
+    };
+    let out = RgbaImage::from_fn(w, h, |x, y| {
+        let m = weight((x as f32 + 0.5 - (w / 2) as f32).abs())
+            .max(weight((y as f32 + 0.5 - (h / 2) as f32).abs()));
+        let (s, o) = (shifted.get_pixel(x, y), original.get_pixel(x, y));
+        let mut px = *s;
+        for c in 0..4 {
+            px[c] = (s[c] as f32 + (o[c] as f32 - s[c] as f32) * m).round() as u8;
+        }
+        px
+    });
+    DynamicImage::ImageRgba8(out)
+}
@@ -0,0 +1,47 @@
+//! The tool registry: every canvas mode with the labels its Workbench buttons show. Tool buttons
+//! are generated from this list, so a new mode only has to be added here to get one.
+
+use crate::shapes::Shape;
+use crate::Mode;
+
+pub struct Tool {
+    pub mode: Mode,
+    pub name: &'static str,
+    /// Label in the compact strip.
+    pub short_name: &'static str,
+    /// Also shown in the compact strip, which only has room for the common tools.
+    pub compact: bool,
+}
+
+const fn tool(mode: Mode, name: &'static str, short_name: &'static str, compact: bool) -> Tool {
+    Tool {
+        mode,
+        name,
+        short_name,
+        compact,
+    }
+}
+
+pub const TOOLS: [Tool; 21] = [
+    tool(Mode::Move, "Move", "Mv", true),
+    tool(Mode::Paint, "Paint", "Pt", true),
+    tool(Mode::Erase, "Erase", "Er", true),
+    tool(Mode::Select, "Select", "Sel", true),
+    tool(Mode::Scissors, "Scissors", "Sc", false),
+    tool(Mode::Cutout, "Cutout", "Cut", false),
+    tool(Mode::Stamp, "Stamp", "St", true),
+    tool(Mode::RedEye, "Red Eye", "Eye", true),
+    tool(Mode::Heal, "Heal", "Hl", true),
+    tool(Mode::CloneStamp, "Clone Stamp", "Cl", false),
+    tool(Mode::Smudge, "Smudge", "Sm", false),
+    tool(Mode::Eyedropper, "Eyedropper", "Pick", true),
+    tool(Mode::Fill, "Fill", "Fill", true),
+    tool(Mode::Shape(Shape::Line), "Line", "Ln", false),
+    tool(Mode::Shape(Shape::Rectangle), "Rectangle", "Rct", false),
+    tool(Mode::Shape(Shape::Ellipse), "Ellipse", "Ell", false),
+    tool(Mode::Gradient, "Gradient", "Grd", false),
+    tool(Mode::Measure, "Measure", "Ms", false),
+    tool(Mode::Transform, "Transform", "Tf", false),
+    tool(Mode::Note, "Note", "Nt", false),
+    tool(Mode::TileStamp, "Tile Stamp", "Tile", false),
+];
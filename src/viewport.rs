@@ -0,0 +1,417 @@
+//! The mapping between window coordinates and canvas pixels, shared by every tool and overlay.
+//! Window coordinates are nannou's: points from the window's center with y up. Canvas coordinates
+//! are pixels from the canvas's top left with y down.
+//!
+//! A view is where the canvas's center sits in the window, the zoom, and how far the canvas is
+//! turned around its center. The center and zoom are held as integers, so panning and zooming
+//! never pile up rounding, and every tool converts a position the same way in any frame. The zoom
+//! counts physical pixels, so 100% shows one canvas pixel per screen pixel on high-DPI displays
+//! too.
+
+use std::f64::consts::{PI, TAU};
+
+use nannou::prelude::{Rect, Vec2};
+
+/// Steps per physical pixel the center is held to.
+const SUBPIXELS: f64 = 256.0;
+/// Steps per unit the zoom is held to.
+const ZOOM_STEPS: f64 = 65536.0;
+/// Share of the window a framed region fills, leaving a margin around it.
+const FRAME_FILL: f32 = 0.9;
+/// Smallest zoom, low enough to fit a large photo into a small window.
+pub const MIN_ZOOM: f32 = 0.01;
+/// Largest zoom.
+pub const MAX_ZOOM: f32 = 100.0;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Viewport {
+    /// Window position of the canvas's center, in 1/`SUBPIXELS` physical pixels.
+    center: [i64; 2],
+    /// Physical pixels per canvas pixel, in 1/`ZOOM_STEPS`.
+    zoom: i64,
+    /// Counterclockwise turn of the canvas on screen, in radians.
+    rotation: f32,
+    /// Physical pixels per point in the window.
+    scale_factor: f32,
+    /// Canvas size in pixels.
+    size: Vec2,
+}
+
+impl Viewport {
+    /// A `size` canvas in the middle of the window, upright, one canvas pixel per screen pixel.
+    pub fn new(size: Vec2) -> Viewport {
+        Viewport {
+            center: [0, 0],
+            zoom: ZOOM_STEPS as i64,
+            rotation: 0.0,
+            scale_factor: 1.0,
+            size,
+        }
+    }
+
+    /// Physical pixels per canvas pixel.
+    pub fn zoom(&self) -> f32 {
+        (self.zoom as f64 / ZOOM_STEPS) as f32
+    }
+
+    /// Points per canvas pixel, what canvas distances are drawn at.
+    pub fn scale(&self) -> f32 {
+        self.zoom() / self.scale_factor
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    /// Window point the canvas's center is drawn at.
+    pub fn center(&self) -> Vec2 {
+        let (x, y) = self.center_pixels();
+        let scale_factor = self.scale_factor as f64;
+        Vec2::new((x / scale_factor) as f32, (y / scale_factor) as f32)
+    }
+
+    /// Canvas position of window point `point`.
+    pub fn to_canvas(&self, point: Vec2) -> Vec2 {
+        let (cx, cy) = self.center_pixels();
+        let scale_factor = self.scale_factor as f64;
+        let offset = (
+            point.x as f64 * scale_factor - cx,
+            point.y as f64 * scale_factor - cy,
+        );
+        let (x, y) = turn(offset, -self.rotation as f64);
+        let zoom = self.zoom as f64 / ZOOM_STEPS;
+        Vec2::new(
+            (self.size.x as f64 / 2.0 + x / zoom) as f32,
+            (self.size.y as f64 / 2.0 - y / zoom) as f32,
+        )
+    }
+
+    /// Window point of canvas position `pos`.
+    pub fn to_screen(&self, pos: Vec2) -> Vec2 {
+        let zoom = self.zoom as f64 / ZOOM_STEPS;
+        let offset = (
+            (pos.x as f64 - self.size.x as f64 / 2.0) * zoom,
+            (self.size.y as f64 / 2.0 - pos.y as f64) * zoom,
+        );
+        let (x, y) = turn(offset, self.rotation as f64);
+        let (cx, cy) = self.center_pixels();
+        let scale_factor = self.scale_factor as f64;
+        Vec2::new(
+            ((cx + x) / scale_factor) as f32,
+            ((cy + y) / scale_factor) as f32,
+        )
+    }
+
+    /// Window points of the canvas's corners, starting at its top left and going around it.
+    pub fn corners(&self) -> [Vec2; 4] {
+        [
+            Vec2::ZERO,
+            Vec2::new(self.size.x, 0.0),
+            self.size,
+            Vec2::new(0.0, self.size.y),
+        ]
+        .map(|corner| self.to_screen(corner))
+    }
+
+    /// The part of the window the canvas covers, as an upright rectangle around it.
+    pub fn rect(&self) -> Rect {
+        let corners = self.corners();
+        let min = corners.iter().fold(corners[0], |min, &c| min.min(c));
+        let max = corners.iter().fold(corners[0], |max, &c| max.max(c));
+        Rect::from_corners(min, max)
+    }
+
+    /// Whether window point `point` is over the canvas.
+    pub fn contains(&self, point: Vec2) -> bool {
+        let pos = self.to_canvas(point);
+        pos.x >= 0.0 && pos.y >= 0.0 && pos.x < self.size.x && pos.y < self.size.y
+    }
+
+    /// The smallest and largest canvas positions showing in `window`, within the canvas.
+    pub fn visible(&self, window: Rect) -> Option<(Vec2, Vec2)> {
+        let corners = [
+            window.top_left(),
+            window.top_right(),
+            window.bottom_right(),
+            window.bottom_left(),
+        ]
+        .map(|corner| self.to_canvas(corner));
+        let min = corners.iter().fold(corners[0], |min, &c| min.min(c));
+        let max = corners.iter().fold(corners[0], |max, &c| max.max(c));
+        let (min, max) = (min.max(Vec2::ZERO), max.min(self.size));
+        if min.x < max.x && min.y < max.y {
+            Some((min, max))
+        } else {
+            None
+        }
+    }
+
+    /// The view moved by `delta` points.
+    pub fn panned(&self, delta: Vec2) -> Viewport {
+        let scale_factor = self.scale_factor as f64;
+        let step = |value: f32| (value as f64 * scale_factor * SUBPIXELS).round() as i64;
+        Viewport {
+            center: [
+                self.center[0] + step(delta.x),
+                self.center[1] + step(delta.y),
+            ],
+            ..*self
+        }
+    }
+
+    /// The view with the canvas's center at window point `point`.
+    pub fn centered_at(&self, point: Vec2) -> Viewport {
+        self.panned(point - self.center())
+    }
+
+    /// The view moved so canvas position `pos` is at window point `point`.
+    pub fn pinned(&self, pos: Vec2, point: Vec2) -> Viewport {
+        self.panned(point - self.to_screen(pos))
+    }
+
+    /// The view at `zoom` physical pixels per canvas pixel, around the canvas's center.
+    pub fn with_zoom(&self, zoom: f32) -> Viewport {
+        Viewport {
+            zoom: zoom_steps(zoom),
+            ..*self
+        }
+    }
+
+    /// The view at `zoom` that keeps the canvas position under window point `anchor` in place.
+    pub fn zoomed(&self, zoom: f32, anchor: Vec2) -> Viewport {
+        let pos = self.to_canvas(anchor);
+        self.with_zoom(zoom).pinned(pos, anchor)
+    }
+
+    /// The view turned by `angle` radians counterclockwise around window point `anchor`.
+    pub fn rotated(&self, angle: f32, anchor: Vec2) -> Viewport {
+        let pos = self.to_canvas(anchor);
+        Viewport {
+            rotation: (self.rotation as f64 + angle as f64).rem_euclid(TAU) as f32,
+            ..*self
+        }
+        .pinned(pos, anchor)
+    }
+
+    /// The view of a canvas resized to `size`, with its center where it was.
+    pub fn with_size(&self, size: Vec2) -> Viewport {
+        Viewport { size, ..*self }
+    }
+
+    /// The view in a window with `scale_factor` physical pixels per point. The canvas stays at
+    /// the same point in the window and at the same zoom in physical pixels.
+    pub fn with_scale_factor(&self, scale_factor: f32) -> Viewport {
+        let ratio = scale_factor as f64 / self.scale_factor as f64;
+        let step = |value: i64| (value as f64 * ratio).round() as i64;
+        Viewport {
+            center: [step(self.center[0]), step(self.center[1])],
+            scale_factor,
+            ..*self
+        }
+    }
+
+    /// The upright view with canvas region `min`..`max` centered and whole in a `window` sized
+    /// window, within `MIN_ZOOM`..`MAX_ZOOM`.
+    pub fn framing(&self, min: Vec2, max: Vec2, window: Vec2) -> Viewport {
+        let extent = (max - min).max(Vec2::ONE);
+        let zoom = (window * self.scale_factor * FRAME_FILL / extent)
+            .min_element()
+            .clamp(MIN_ZOOM, MAX_ZOOM);
+        Viewport {
+            zoom: zoom_steps(zoom),
+            rotation: 0.0,
+            ..*self
+        }
+        .pinned((min + max) / 2.0, Vec2::ZERO)
+    }
+
+    /// The view `t` (0-1) of the way to `to`. The canvas position at the window's center glides
+    /// straight across, the zoom changes by the same factor each step, as a linear zoom looks
+    /// like it speeds up, and the canvas turns the short way round.
+    pub fn toward(&self, to: &Viewport, t: f32) -> Viewport {
+        let focus = self.to_canvas(Vec2::ZERO).lerp(to.to_canvas(Vec2::ZERO), t);
+        let zoom = self.zoom() * (to.zoom() / self.zoom()).powf(t);
+        let turn = (to.rotation as f64 - self.rotation as f64 + PI).rem_euclid(TAU) - PI;
+        Viewport {
+            zoom: zoom_steps(zoom),
+            rotation: (self.rotation as f64 + turn * t as f64).rem_euclid(TAU) as f32,
+            ..*to
+        }
+        .pinned(focus, Vec2::ZERO)
+    }
+
+    fn center_pixels(&self) -> (f64, f64) {
+        (
+            self.center[0] as f64 / SUBPIXELS,
+            self.center[1] as f64 / SUBPIXELS,
+        )
+    }
+}
+
+fn zoom_steps(zoom: f32) -> i64 {
+    ((zoom as f64 * ZOOM_STEPS).round() as i64).max(1)
+}
+
+/// `offset` turned counterclockwise by `angle` radians.
+fn turn((x, y): (f64, f64), angle: f64) -> (f64, f64) {
+    let (sin, cos) = angle.sin_cos();
+    (x * cos - y * sin, x * sin + y * cos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn views() -> Vec<Viewport> {
+        let view = Viewport::new(Vec2::new(640.0, 480.0));
+        vec![
+            view,
+            view.panned(Vec2::new(123.4, -56.7)),
+            view.zoomed(7.3, Vec2::new(40.0, 30.0)),
+            view.rotated(0.6, Vec2::new(-100.0, 20.0)).with_zoom(3.0),
+            view.with_scale_factor(2.0)
+                .zoomed(0.5, Vec2::new(10.0, 10.0)),
+            view.with_scale_factor(1.5)
+                .rotated(-2.2, Vec2::ZERO)
+                .panned(Vec2::new(-3.3, 9.9)),
+        ]
+    }
+
+    /// Within rounding to the center's steps.
+    fn close(a: Vec2, b: Vec2) -> bool {
+        a.distance(b) < 0.01
+    }
+
+    #[test]
+    fn screen_canvas_screen_round_trips() {
+        for view in views() {
+            for point in [
+                Vec2::ZERO,
+                Vec2::new(300.0, -200.0),
+                Vec2::new(-512.5, 384.25),
+            ] {
+                let back = view.to_screen(view.to_canvas(point));
+                assert!(
+                    close(back, point),
+                    "{:?}: {} came back as {}",
+                    view,
+                    point,
+                    back
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn canvas_screen_canvas_round_trips() {
+        for view in views() {
+            for pos in [Vec2::ZERO, Vec2::new(640.0, 480.0), Vec2::new(17.5, 301.25)] {
+                let back = view.to_canvas(view.to_screen(pos));
+                assert!(
+                    close(back, pos),
+                    "{:?}: {} came back as {}",
+                    view,
+                    pos,
+                    back
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn upright_view_lines_up_with_the_window() {
+        let view = Viewport::new(Vec2::new(100.0, 50.0)).with_zoom(2.0);
+        assert_eq!(view.to_screen(Vec2::ZERO), Vec2::new(-100.0, 50.0));
+        assert_eq!(
+            view.to_canvas(Vec2::new(100.0, -50.0)),
+            Vec2::new(100.0, 50.0)
+        );
+        assert_eq!(view.rect(), Rect::from_x_y_w_h(0.0, 0.0, 200.0, 100.0));
+    }
+
+    #[test]
+    fn zoom_counts_physical_pixels() {
+        let view = Viewport::new(Vec2::new(100.0, 100.0)).with_scale_factor(2.0);
+        assert_eq!(view.zoom(), 1.0);
+        assert_eq!(view.scale(), 0.5);
+        assert_eq!(view.rect().w(), 50.0);
+    }
+
+    #[test]
+    fn scale_factor_keeps_the_canvas_in_place() {
+        let view = Viewport::new(Vec2::new(100.0, 100.0)).panned(Vec2::new(20.0, -10.0));
+        let moved = view.with_scale_factor(2.0);
+        assert!(close(moved.center(), view.center()));
+    }
+
+    #[test]
+    fn zooming_keeps_the_anchor_in_place() {
+        let anchor = Vec2::new(70.0, -35.0);
+        for view in views() {
+            let zoomed = view.zoomed(4.0, anchor);
+            assert!(close(zoomed.to_canvas(anchor), view.to_canvas(anchor)));
+            let turned = view.rotated(1.0, anchor);
+            assert!(close(turned.to_canvas(anchor), view.to_canvas(anchor)));
+        }
+    }
+
+    #[test]
+    fn panning_back_returns_exactly() {
+        for view in views() {
+            let mut moved = view;
+            for _ in 0..1000 {
+                moved = moved.panned(Vec2::new(0.3, -0.7));
+            }
+            for _ in 0..1000 {
+                moved = moved.panned(Vec2::new(-0.3, 0.7));
+            }
+            assert_eq!(moved, view);
+        }
+    }
+
+    #[test]
+    fn quarter_turn_swaps_the_axes() {
+        let view =
+            Viewport::new(Vec2::new(10.0, 10.0)).rotated(std::f32::consts::FRAC_PI_2, Vec2::ZERO);
+        // The canvas's top right corner turns to the top left
+        assert!(close(
+            view.to_screen(Vec2::new(10.0, 0.0)),
+            Vec2::new(-5.0, 5.0)
+        ));
+        assert!(view.contains(Vec2::new(4.0, -4.0)));
+        assert!(!view.contains(Vec2::new(6.0, 0.0)));
+    }
+
+    #[test]
+    fn framing_centers_the_region() {
+        let view = Viewport::new(Vec2::new(400.0, 300.0)).rotated(0.4, Vec2::ZERO);
+        let framed = view.framing(
+            Vec2::new(100.0, 100.0),
+            Vec2::new(200.0, 150.0),
+            Vec2::new(800.0, 600.0),
+        );
+        assert_eq!(framed.rotation(), 0.0);
+        assert!((framed.zoom() - 7.2).abs() < 1e-4);
+        assert!(close(framed.to_canvas(Vec2::ZERO), Vec2::new(150.0, 125.0)));
+    }
+
+    #[test]
+    fn framing_zooms_out_to_fit_a_large_canvas() {
+        let size = Vec2::new(4000.0, 3000.0);
+        let framed = Viewport::new(size).framing(Vec2::ZERO, size, Vec2::new(800.0, 600.0));
+        assert!((framed.zoom() - 0.18).abs() < 1e-4);
+        for corner in [Vec2::ZERO, size] {
+            let on_screen = framed.to_screen(corner);
+            assert!(on_screen.x.abs() <= 400.0 && on_screen.y.abs() <= 300.0);
+        }
+    }
+
+    #[test]
+    fn transitions_end_at_their_target() {
+        let from = Viewport::new(Vec2::new(400.0, 300.0)).rotated(6.0, Vec2::ZERO);
+        let to = from.framing(Vec2::ZERO, Vec2::new(40.0, 30.0), Vec2::new(800.0, 600.0));
+        assert!((from.toward(&to, 1.0).zoom() - to.zoom()).abs() < 1e-4);
+        assert!(close(from.toward(&to, 1.0).center(), to.center()));
+        assert!(close(from.toward(&to, 0.0).center(), from.center()));
+    }
+}
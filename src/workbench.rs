@@ -0,0 +1,3031 @@
+//! Workbench panels. Each panel sets its widgets below the previous visible panel, so which
+//! panels are shown is controlled by the active workspace.
+
+use nannou::prelude::WindowId;
+use nannou_conrod::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    brush::{BrushType, Textured},
+    clipboard::PasteTransform,
+    compositing::BrushBlend,
+    console,
+    curve_editor::CurveEditor,
+    export::{channel_pack::Source, options::ExportFormat},
+    filters,
+    gamepad::Gamepad,
+    gradient::ColorStop,
+    histogram::Histogram,
+    layers::LayerOp,
+    midi::{MidiInput, MidiParam},
+    nodes,
+    notes::NoteOp,
+    pick_image_file, pick_save_file, pixel_grid,
+    stroke::StrokeFx,
+    symmetry::Symmetry,
+    templates,
+    tools::{Tool, TOOLS},
+    viewport, zoom, Command, GlobalState, Mode, WorkbenchIds,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Panel {
+    Canvas,
+    NewImage,
+    Documents,
+    Print,
+    Raw,
+    Resize,
+    Color,
+    Brush,
+    Gradient,
+    Layers,
+    Clipboard,
+    Video,
+    SpriteSheet,
+    ChannelPack,
+    Tiles,
+    NineSlice,
+    Guides,
+    Perspective,
+    Composition,
+    Notes,
+    Palette,
+    Stamp,
+    Nodes,
+    Filters,
+    Compare,
+    Batch,
+    Watermark,
+    Hooks,
+    Console,
+    Settings,
+}
+
+impl Panel {
+    pub const ALL: [Panel; 30] = [
+        Panel::Canvas,
+        Panel::NewImage,
+        Panel::Documents,
+        Panel::Print,
+        Panel::Raw,
+        Panel::Resize,
+        Panel::Color,
+        Panel::Brush,
+        Panel::Gradient,
+        Panel::Layers,
+        Panel::Clipboard,
+        Panel::Video,
+        Panel::SpriteSheet,
+        Panel::ChannelPack,
+        Panel::Tiles,
+        Panel::NineSlice,
+        Panel::Guides,
+        Panel::Perspective,
+        Panel::Composition,
+        Panel::Notes,
+        Panel::Palette,
+        Panel::Stamp,
+        Panel::Nodes,
+        Panel::Filters,
+        Panel::Compare,
+        Panel::Batch,
+        Panel::Watermark,
+        Panel::Hooks,
+        Panel::Console,
+        Panel::Settings,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Panel::Canvas => "Canvas",
+            Panel::NewImage => "New Image",
+            Panel::Documents => "Documents",
+            Panel::Print => "Print",
+            Panel::Raw => "Camera RAW",
+            Panel::Resize => "Resize",
+            Panel::Color => "Color",
+            Panel::Brush => "Brush",
+            Panel::Gradient => "Gradient",
+            Panel::Layers => "Layers",
+            Panel::Clipboard => "Clipboard",
+            Panel::Video => "Video",
+            Panel::SpriteSheet => "Sprite Sheet",
+            Panel::ChannelPack => "Channel Pack",
+            Panel::Tiles => "Tiles",
+            Panel::NineSlice => "9-Slice",
+            Panel::Guides => "Guides",
+            Panel::Perspective => "Perspective",
+            Panel::Composition => "Composition",
+            Panel::Notes => "Notes",
+            Panel::Palette => "Palette",
+            Panel::Stamp => "Stamp",
+            Panel::Nodes => "Nodes",
+            Panel::Filters => "Filters",
+            Panel::Compare => "Compare",
+            Panel::Batch => "Batch",
+            Panel::Watermark => "Watermark",
+            Panel::Hooks => "Hooks",
+            Panel::Console => "Console",
+            Panel::Settings => "Settings",
+        }
+    }
+}
+
+fn slider(val: f32, min: f32, max: f32) -> widget::Slider<'static, f32> {
+    widget::Slider::new(val, min, max)
+        .w_h(200.0, 30.0)
+        .label_font_size(15)
+        .rgb(0.3, 0.3, 0.3)
+        .label_rgb(1.0, 1.0, 1.0)
+        .border(0.0)
+}
+
+/// Sets the widgets of every panel visible in the current workspace that hasn't been detached
+/// into its own window.
+pub fn set_widgets(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    if state.config.compact {
+        compact_strip(ids, ui, state);
+        return;
+    }
+
+    workspace_panel(ids, ui, state);
+
+    if ids.panel_headers.len() < Panel::ALL.len() {
+        ids.panel_headers
+            .resize(Panel::ALL.len(), &mut ui.widget_id_generator());
+    }
+
+    let mut first = true;
+    for (i, panel) in Panel::ALL.into_iter().enumerate() {
+        if !state.panels.contains(&panel) || state.detached.contains(&panel) {
+            continue;
+        }
+
+        // Panel header, dragged out of the window to detach the panel. The first one goes under
+        // the workspace row, whose last widget is not left-aligned.
+        let header = if first {
+            widget::Button::new().down_from(ids.workspace_button, 20.0)
+        } else {
+            widget::Button::new().down(20.0)
+        };
+        first = false;
+        header
+            .w_h(200.0, 24.0)
+            .label_font_size(12)
+            .label(panel.name())
+            .set(ids.panel_headers[i], ui);
+        // Dragging a header out of the window detaches the panel where it is dropped
+        let dragged = ui.widget_input(ids.panel_headers[i]).drags().left().next().is_some();
+        let [x, y] = ui.global_input().current.mouse.xy;
+        if dragged && (x.abs() > ui.win_w / 2.0 || y.abs() > ui.win_h / 2.0) {
+            state.commands.push(Command::DetachPanel(panel, Some([x, y])));
+        }
+
+        set_panel(panel, ids, ui, state);
+    }
+
+    job_progress(ids, ui, state, first);
+}
+
+/// The running jobs under the panels, or under the workspace row if no panel is docked. Several
+/// jobs share the bar of the oldest.
+fn job_progress(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &GlobalState, first: bool) {
+    let job = match state.jobs.first() {
+        Some(job) => job,
+        None => return,
+    };
+    let label = match state.jobs.len() {
+        1 => job.label.clone(),
+        n => format!("{} (+{} more)", job.label, n - 1),
+    };
+
+    let background = widget::Rectangle::fill([200.0, 24.0]).rgb(0.1, 0.1, 0.1);
+    let background = if first {
+        background.down_from(ids.workspace_button, 20.0)
+    } else {
+        background.down(20.0)
+    };
+    background.set(ids.job_progress, ui);
+    widget::Rectangle::fill([(200.0 * job.progress as f64).max(1.0), 24.0])
+        .mid_left_of(ids.job_progress)
+        .rgb(0.5, 0.7, 1.0)
+        .set(ids.job_progress_bar, ui);
+    widget::Text::new(&label)
+        .middle_of(ids.job_progress)
+        .font_size(12)
+        .rgb(1.0, 1.0, 1.0)
+        .set(ids.job_progress_label, ui);
+}
+
+/// Sets the widgets of a panel shown in its own window.
+pub fn set_detached_widgets(
+    ids: &mut WorkbenchIds,
+    ui: &mut UiCell,
+    state: &mut GlobalState,
+    panel: Panel,
+    window: WindowId,
+) {
+    for _click in widget::Button::new()
+        .top_left_with_margin(20.0)
+        .w_h(200.0, 24.0)
+        .label_font_size(12)
+        .label(&format!("{} (dock)", panel.name()))
+        .set(ids.dock_button, ui)
+    {
+        state.commands.push(Command::DockPanel(window));
+    }
+
+    set_panel(panel, ids, ui, state);
+}
+
+fn set_panel(panel: Panel, ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    match panel {
+        Panel::Canvas => canvas_panel(ids, ui, state),
+        Panel::NewImage => new_image_panel(ids, ui, state),
+        Panel::Documents => documents_panel(ids, ui, state),
+        Panel::Print => print_panel(ids, ui, state),
+        Panel::Raw => raw_panel(ids, ui, state),
+        Panel::Resize => resize_panel(ids, ui, state),
+        Panel::Color => color_panel(ids, ui, state),
+        Panel::Brush => brush_panel(ids, ui, state),
+        Panel::Gradient => gradient_panel(ids, ui, state),
+        Panel::Layers => layers_panel(ids, ui, state),
+        Panel::Clipboard => clipboard_panel(ids, ui, state),
+        Panel::Video => video_panel(ids, ui, state),
+        Panel::SpriteSheet => sprite_sheet_panel(ids, ui, state),
+        Panel::ChannelPack => channel_pack_panel(ids, ui, state),
+        Panel::Tiles => tiles_panel(ids, ui, state),
+        Panel::NineSlice => nine_slice_panel(ids, ui, state),
+        Panel::Guides => guides_panel(ids, ui, state),
+        Panel::Perspective => perspective_panel(ids, ui, state),
+        Panel::Composition => composition_panel(ids, ui, state),
+        Panel::Notes => notes_panel(ids, ui, state),
+        Panel::Palette => palette_panel(ids, ui, state),
+        Panel::Stamp => stamp_panel(ids, ui, state),
+        Panel::Nodes => nodes_panel(ids, ui, state),
+        Panel::Filters => filters_panel(ids, ui, state),
+        Panel::Compare => compare_panel(ids, ui, state),
+        Panel::Batch => batch_panel(ids, ui, state),
+        Panel::Watermark => watermark_panel(ids, ui, state),
+        Panel::Hooks => hooks_panel(ids, ui, state),
+        Panel::Console => console_panel(ids, ui, state),
+        Panel::Settings => settings_panel(ids, ui, state),
+    }
+}
+
+/// A button selecting a tool, highlighted while the tool is active.
+fn tool_button(label: &str, active: bool) -> widget::Button<widget::button::Flat> {
+    let button = widget::Button::new().label(label);
+    if active {
+        button.rgb(0.5, 0.7, 1.0)
+    } else {
+        button
+    }
+}
+
+/// The collapsed Workbench: a single row of small tool buttons, undo, and a button to expand
+/// back to the full panels.
+fn compact_strip(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let compact_tools: Vec<&Tool> = TOOLS.iter().filter(|tool| tool.compact).collect();
+    if ids.compact_tools.len() < compact_tools.len() {
+        ids.compact_tools
+            .resize(compact_tools.len(), &mut ui.widget_id_generator());
+    }
+
+    for _click in widget::Button::new()
+        .top_left_with_margin(10.0)
+        .w_h(40.0, 30.0)
+        .label_font_size(12)
+        .label("<>")
+        .set(ids.compact_button, ui)
+    {
+        state.config.compact = false;
+        state.commands.push(Command::UpdateWorkbenchWindow);
+    }
+
+    for (i, tool) in compact_tools.into_iter().enumerate() {
+        for _click in tool_button(tool.short_name, state.mode == tool.mode)
+            .right(5.0)
+            .w_h(40.0, 30.0)
+            .label_font_size(12)
+            .set(ids.compact_tools[i], ui)
+        {
+            state.mode = tool.mode;
+        }
+    }
+
+    for _click in widget::Button::new()
+        .right(5.0)
+        .w_h(40.0, 30.0)
+        .label_font_size(12)
+        .label("Undo")
+        .set(ids.undo_button, ui)
+    {
+        state.commands.push(Command::Undo);
+    }
+
+    pin_button(ids, ui, state);
+}
+
+/// Toggles keeping the Workbench above other windows.
+fn pin_button(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .right(5.0)
+        .w_h(40.0, 30.0)
+        .label_font_size(12)
+        .label(if state.config.always_on_top {
+            "Unpin"
+        } else {
+            "Pin"
+        })
+        .set(ids.pin_button, ui)
+    {
+        state.config.always_on_top = !state.config.always_on_top;
+        state.commands.push(Command::UpdateWorkbenchWindow);
+    }
+}
+
+/// Cycles through the configured workspace presets.
+fn workspace_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .top_left_with_margin(20.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Workspace: {}", state.config.workspace))
+        .set(ids.workspace_button, ui)
+    {
+        let presets = &state.config.presets;
+        let next = presets
+            .iter()
+            .position(|p| p.name == state.config.workspace)
+            .map_or(0, |i| (i + 1) % presets.len().max(1));
+        if let Some(preset) = presets.get(next).cloned() {
+            preset.apply(state);
+        }
+    }
+
+    for _click in widget::Button::new()
+        .right(10.0)
+        .w_h(40.0, 30.0)
+        .label_font_size(12)
+        .label("><")
+        .set(ids.compact_button, ui)
+    {
+        state.config.compact = true;
+        state.commands.push(Command::UpdateWorkbenchWindow);
+    }
+
+    pin_button(ids, ui, state);
+}
+
+/// Scale, file access, brush size and tool selection.
+fn canvas_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    if let Some(value) = slider(state.scale, viewport::MIN_ZOOM, viewport::MAX_ZOOM)
+        .down(10.0)
+        .label("Scale")
+        .set(ids.scale, ui)
+    {
+        state.commands.push(Command::Zoom(zoom::Target::Scale(value)));
+    }
+
+    for _click in widget::Button::new()
+        .right_from(ids.scale, 10.0)
+        .w_h(80.0, 30.0)
+        .label("Open...")
+        .set(ids.open_button, ui)
+    {
+        if let Some(path) = pick_image_file() {
+            state.commands.push(Command::Open(path));
+        }
+    }
+
+    for _click in widget::Button::new()
+        .right(10.0)
+        .w_h(80.0, 30.0)
+        .label("Save As...")
+        .set(ids.save_button, ui)
+    {
+        if let Some(path) = pick_save_file() {
+            state.commands.push(Command::Save(path));
+        }
+    }
+
+    for _click in widget::Button::new()
+        .right(10.0)
+        .w_h(80.0, 30.0)
+        .label("Undo")
+        .set(ids.undo_button, ui)
+    {
+        state.commands.push(Command::Undo);
+    }
+
+    for (target, label, id) in [
+        (zoom::Target::Fit, "Fit", ids.zoom_fit_button),
+        (zoom::Target::ActualSize, "100%", ids.zoom_actual_button),
+        (zoom::Target::Selection, "Selection", ids.zoom_selection_button),
+    ] {
+        let button = widget::Button::new()
+            .w_h(60.0, 30.0)
+            .label_font_size(12)
+            .label(label);
+        let button = if target == zoom::Target::Fit {
+            button.down_from(ids.scale, 10.0)
+        } else {
+            button.right(10.0)
+        };
+        for _click in button.set(id, ui) {
+            state.commands.push(Command::Zoom(target));
+        }
+    }
+
+    if let Some(value) = slider(state.brush_size, 1.0, 100.0)
+        .down_from(ids.zoom_fit_button, 10.0)
+        .label("Brush Size")
+        .set(ids.brush_size, ui)
+    {
+        state.brush_size = value;
+    }
+
+    widget::Text::new(format!("{}", state.brush_size).as_str())
+        .right_from(ids.brush_size, 10.0)
+        .set(ids.brush_size_labels, ui);
+
+    if ids.tool_buttons.len() < TOOLS.len() {
+        ids.tool_buttons
+            .resize(TOOLS.len(), &mut ui.widget_id_generator());
+    }
+    for (i, tool) in TOOLS.iter().enumerate() {
+        let button = tool_button(tool.name, state.mode == tool.mode);
+        let button = if i == 0 {
+            button.down_from(ids.brush_size, 10.0)
+        } else {
+            button
+        };
+        for _click in button.set(ids.tool_buttons[i], ui) {
+            state.mode = tool.mode;
+        }
+    }
+
+    for value in widget::Toggle::new(state.shape_antialias)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Anti-aliased Shapes")
+        .set(ids.shape_antialias_toggle, ui)
+    {
+        state.shape_antialias = value;
+    }
+
+    for value in widget::Toggle::new(state.pressure_size)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Pressure Sets Size")
+        .set(ids.pressure_size_toggle, ui)
+    {
+        state.pressure_size = value;
+    }
+
+    for value in widget::Toggle::new(state.pressure_opacity)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Pressure Sets Opacity")
+        .set(ids.pressure_opacity_toggle, ui)
+    {
+        state.pressure_opacity = value;
+    }
+
+    if let Some(value) = slider(state.fill_tolerance, 0.0, 255.0)
+        .down(10.0)
+        .label(&format!("Fill Tolerance: {}", state.fill_tolerance))
+        .set(ids.fill_tolerance, ui)
+    {
+        state.fill_tolerance = value.round();
+    }
+
+    let label = format!("Cutout Softness: {:.2}", state.cutout_softness);
+    if let Some(value) = slider(state.cutout_softness, 0.0, 1.0)
+        .down(10.0)
+        .label(&label)
+        .set(ids.cutout_softness, ui)
+    {
+        state.cutout_softness = value;
+    }
+
+    if widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Remove Background")
+        .set(ids.remove_background_button, ui)
+        .was_clicked()
+    {
+        state.commands.push(Command::RemoveBackground);
+    }
+
+    if widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Clear Scribbles")
+        .set(ids.clear_scribbles_button, ui)
+        .was_clicked()
+    {
+        state.commands.push(Command::ClearScribbles);
+    }
+
+    for value in widget::Toggle::new(state.icon_preview)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Icon Preview")
+        .set(ids.icon_preview_toggle, ui)
+    {
+        state.icon_preview = value;
+    }
+
+    for value in widget::Toggle::new(state.keep_measurements)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Keep Measurements")
+        .set(ids.keep_measurements_toggle, ui)
+    {
+        state.keep_measurements = value;
+    }
+
+    for value in widget::Toggle::new(state.config.gamepad)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Gamepad Input")
+        .set(ids.gamepad_toggle, ui)
+    {
+        state.config.gamepad = value;
+        state.gamepad = value.then(Gamepad::new);
+    }
+
+    for value in widget::Toggle::new(state.config.midi.enabled)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("MIDI Controller")
+        .set(ids.midi_toggle, ui)
+    {
+        state.config.midi.enabled = value;
+        state.midi = value
+            .then(|| MidiInput::connect(&state.config.midi.port))
+            .flatten();
+        state.midi_learn = None;
+    }
+
+    if state.midi.is_some() {
+        if ids.midi_learn_buttons.len() < MidiParam::ALL.len() {
+            ids.midi_learn_buttons
+                .resize(MidiParam::ALL.len(), &mut ui.widget_id_generator());
+        }
+        for (i, param) in MidiParam::ALL.into_iter().enumerate() {
+            let label = match (state.midi_learn == Some(param), state.config.midi.cc(param)) {
+                (true, _) => format!("{}: turn a knob...", param.name()),
+                (false, Some(cc)) => format!("{}: CC {}", param.name(), cc),
+                (false, None) => format!("{}: unbound", param.name()),
+            };
+            for _click in tool_button(&label, state.midi_learn == Some(param))
+                .down(10.0)
+                .w_h(200.0, 30.0)
+                .set(ids.midi_learn_buttons[i], ui)
+            {
+                state.midi_learn = (state.midi_learn != Some(param)).then(|| param);
+            }
+        }
+    }
+
+    if widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Clear Measurements")
+        .set(ids.clear_measurements_button, ui)
+        .was_clicked()
+    {
+        state.commands.push(Command::ClearMeasurements);
+    }
+
+    // widget::Tabs::new(&[(ids.move_mode_button, "Move"), (
+    //     ids.paint_mode_button,
+    //     "Paint",
+    // )]);
+    // .set(ids.modes, ui);
+}
+
+/// Open documents. Clicking one makes it the document the other panels work on. The active one
+/// can be rolled back to a backed up version or mirrored in a stream output window.
+fn documents_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    if ids.document_buttons.len() < state.documents.len() {
+        ids.document_buttons
+            .resize(state.documents.len(), &mut ui.widget_id_generator());
+    }
+
+    // The focused document, or the one commands fall back to before any has been focused
+    let active = state
+        .focused_editor
+        .filter(|id| state.documents.iter().any(|(d, _)| d == id))
+        .or_else(|| state.documents.first().map(|(id, _)| *id));
+
+    for (i, (id, name)) in state.documents.iter().enumerate() {
+        let button = widget::Button::new()
+            .down(if i == 0 { 10.0 } else { 4.0 })
+            .w_h(200.0, 24.0)
+            .label_font_size(12)
+            .label(name);
+        let button = if Some(*id) == active {
+            button.rgb(0.5, 0.7, 1.0)
+        } else {
+            button
+        };
+        for _click in button.set(ids.document_buttons[i], ui) {
+            state.commands.push(Command::FocusDocument(*id));
+        }
+    }
+
+    if let Some(id) = active {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label("Close Document")
+            .set(ids.document_close, ui)
+        {
+            state.commands.push(Command::CloseDocument(id));
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(&format!("Document Alpha: {}", state.document_alpha.name()))
+            .set(ids.document_alpha_button, ui)
+        {
+            state
+                .commands
+                .push(Command::SetAlphaMode(state.document_alpha.next()));
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label("Restore Version...")
+            .set(ids.backup_browse_button, ui)
+        {
+            state.commands.push(Command::BrowseBackups);
+        }
+
+        if let Some((_, versions)) = &state.backup_versions {
+            if ids.backup_buttons.len() < versions.len() {
+                ids.backup_buttons
+                    .resize(versions.len(), &mut ui.widget_id_generator());
+            }
+            for (i, version) in versions.iter().enumerate() {
+                for _click in widget::Button::new()
+                    .down(4.0)
+                    .w_h(200.0, 24.0)
+                    .label_font_size(12)
+                    .label(&format!("{}. {}", i + 1, version.label()))
+                    .set(ids.backup_buttons[i], ui)
+                {
+                    state
+                        .commands
+                        .push(Command::RestoreBackup(version.path.clone()));
+                }
+            }
+            if widget::Button::new()
+                .down(4.0)
+                .w_h(200.0, 24.0)
+                .label_font_size(12)
+                .label("Close Versions")
+                .set(ids.backup_close_button, ui)
+                .was_clicked()
+            {
+                state.backup_versions = None;
+            }
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label("Stream Output Window")
+            .set(ids.open_output_button, ui)
+        {
+            state.commands.push(Command::OpenOutput(id));
+        }
+
+        export_options(ids, ui, state, id);
+    }
+
+    // Applies to image files opened from here on; projects remember their own mode
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Import Alpha: {}", state.import_alpha.name()))
+        .set(ids.import_alpha_button, ui)
+    {
+        state.import_alpha = state.import_alpha.next();
+    }
+}
+
+/// Format, compression, scale and palette of exports, with the soft proof preview.
+fn export_options(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState, id: WindowId) {
+    let options = &mut state.export;
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Export Format: {}", options.format.name()))
+        .set(ids.export_format_button, ui)
+    {
+        options.format = options.format.next();
+    }
+
+    if options.format == ExportFormat::Png {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(&format!("PNG Color: {}", options.png_color.name()))
+            .set(ids.export_png_color, ui)
+        {
+            options.png_color = options.png_color.next();
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(&format!("Compression: {}", options.png_compression.name()))
+            .set(ids.export_png_compression, ui)
+        {
+            options.png_compression = options.png_compression.next();
+        }
+    }
+
+    if options.format == ExportFormat::WebP {
+        for value in widget::Toggle::new(options.webp_lossless)
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label("Lossless")
+            .set(ids.export_webp_lossless, ui)
+        {
+            options.webp_lossless = value;
+        }
+    }
+
+    let lossy = match options.format {
+        ExportFormat::Png => false,
+        ExportFormat::Jpeg => true,
+        ExportFormat::WebP => !options.webp_lossless,
+    };
+    if lossy {
+        if let Some(value) = slider(options.quality, 1.0, 100.0)
+            .down(10.0)
+            .label(&format!("Quality: {}", options.quality.round()))
+            .set(ids.export_quality, ui)
+        {
+            options.quality = value.round();
+        }
+    }
+
+    if let Some(value) = slider(options.scale, 0.1, 4.0)
+        .down(10.0)
+        .label(&format!("Export Scale: {:.2}x", options.scale))
+        .set(ids.export_scale, ui)
+    {
+        options.scale = value;
+    }
+
+    let colors = match options.colors.round() as usize {
+        0 => "Colors: All".to_string(),
+        n => format!("Colors: {}", n),
+    };
+    if let Some(value) = slider(options.colors, 0.0, 256.0)
+        .down(10.0)
+        .label(&colors)
+        .set(ids.export_colors, ui)
+    {
+        options.colors = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Export Preview")
+        .set(ids.export_preview_button, ui)
+    {
+        state.commands.push(Command::OpenProof(id));
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Export...")
+        .set(ids.export_button, ui)
+    {
+        let format = state.export.format;
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter(format.name(), &[format.extension()])
+            .save_file()
+        {
+            state.commands.push(Command::Export(path));
+        }
+    }
+}
+
+/// Paper, margins and resolution for printing, with a preview of where the document lands on
+/// the page.
+fn print_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let options = &mut state.print;
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Paper: {}", options.paper.name()))
+        .set(ids.print_paper, ui)
+    {
+        options.paper = options.paper.next();
+    }
+
+    for value in widget::Toggle::new(options.landscape)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Landscape")
+        .set(ids.print_landscape, ui)
+    {
+        options.landscape = value;
+    }
+
+    if let Some(value) = slider(options.dpi, 72.0, 600.0)
+        .down(10.0)
+        .label(&format!("DPI: {:.0}", options.dpi))
+        .set(ids.print_dpi, ui)
+    {
+        options.dpi = value.round();
+    }
+
+    if let Some(value) = slider(options.margin_mm, 0.0, 50.0)
+        .down(10.0)
+        .label(&format!("Margin: {:.0} mm", options.margin_mm))
+        .set(ids.print_margin, ui)
+    {
+        options.margin_mm = value.round();
+    }
+
+    for value in widget::Toggle::new(options.fit)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Fit to Page")
+        .set(ids.print_fit, ui)
+    {
+        options.fit = value;
+    }
+
+    if !options.fit {
+        if let Some(value) = slider(options.scale, 0.1, 4.0)
+            .down(10.0)
+            .label(&format!("Scale: {:.0}%", options.scale * 100.0))
+            .set(ids.print_scale, ui)
+        {
+            options.scale = value;
+        }
+    }
+
+    // The page, the area inside the margins and the document, scaled down
+    let (width, height) = state.document_size;
+    let layout = options.layout(width, height);
+    let k = 200.0 / layout.page.0.max(layout.page.1) as f64;
+    let margin = options.margin_mm as f64 * 72.0 / 25.4 * k;
+    let page = (layout.page.0 as f64 * k, layout.page.1 as f64 * k);
+    widget::Rectangle::fill([page.0, page.1])
+        .rgb(1.0, 1.0, 1.0)
+        .down(10.0)
+        .set(ids.print_page, ui);
+    widget::Rectangle::outline([
+        (page.0 - 2.0 * margin).max(1.0),
+        (page.1 - 2.0 * margin).max(1.0),
+    ])
+    .middle_of(ids.print_page)
+    .rgb(0.6, 0.6, 0.6)
+    .set(ids.print_area, ui);
+    widget::Rectangle::fill([layout.image.2 as f64 * k, layout.image.3 as f64 * k])
+        .middle_of(ids.print_page)
+        .rgb(0.5, 0.7, 1.0)
+        .set(ids.print_image, ui);
+
+    for _click in widget::Button::new()
+        .down_from(ids.print_page, 10.0)
+        .w_h(200.0, 30.0)
+        .label("Print")
+        .set(ids.print_button, ui)
+    {
+        state.commands.push(Command::Print);
+    }
+}
+
+/// Development settings for camera RAW files, used when one is opened. Reprocessing decodes the
+/// current document's RAW file again with changed settings.
+fn raw_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    if let Some(value) = slider(state.raw.exposure, -3.0, 3.0)
+        .down(10.0)
+        .label(&format!("Exposure: {:+.1} EV", state.raw.exposure))
+        .set(ids.raw_exposure, ui)
+    {
+        state.raw.exposure = (value * 10.0).round() / 10.0;
+    }
+
+    for value in widget::Toggle::new(state.raw.white_balance)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Camera White Balance")
+        .set(ids.raw_white_balance, ui)
+    {
+        state.raw.white_balance = value;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Reprocess RAW")
+        .set(ids.raw_reprocess, ui)
+    {
+        state.commands.push(Command::ReprocessRaw);
+    }
+}
+
+/// New document size, applied either by resampling the layers or by resizing the canvas around
+/// them at the chosen anchor.
+fn resize_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let options = &mut state.resize;
+    if let Some(value) = slider(options.width, 1.0, 4096.0)
+        .down(10.0)
+        .label(&format!("Width: {}", options.width))
+        .set(ids.resize_width, ui)
+    {
+        options.width = value.round();
+    }
+
+    if let Some(value) = slider(options.height, 1.0, 4096.0)
+        .down(10.0)
+        .label(&format!("Height: {}", options.height))
+        .set(ids.resize_height, ui)
+    {
+        options.height = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Use Current Size")
+        .set(ids.resize_current, ui)
+    {
+        let (width, height) = state.document_size;
+        options.width = width as f32;
+        options.height = height as f32;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Filter: {}", options.resample.name()))
+        .set(ids.resize_resample, ui)
+    {
+        options.resample = options.resample.next();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Scale Image")
+        .set(ids.scale_image_button, ui)
+    {
+        state.commands.push(Command::ScaleImage);
+    }
+
+    // Anchor for canvas resizing, as a 3x3 grid
+    if ids.resize_anchors.len() < 9 {
+        ids.resize_anchors.resize(9, &mut ui.widget_id_generator());
+    }
+    for i in 0..9 {
+        let anchor = (i as u32 % 3, i as u32 / 3);
+        let button = widget::Button::new().w_h(64.0, 24.0);
+        let button = match i {
+            0 => button.down(20.0),
+            _ if i % 3 == 0 => button.down_from(ids.resize_anchors[i - 3], 4.0),
+            _ => button.right(4.0),
+        };
+        let button = if anchor == options.anchor {
+            button.rgb(0.5, 0.7, 1.0)
+        } else {
+            button
+        };
+        for _click in button.set(ids.resize_anchors[i], ui) {
+            options.anchor = anchor;
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down_from(ids.resize_anchors[6], 10.0)
+        .w_h(200.0, 30.0)
+        .label("Resize Canvas")
+        .set(ids.resize_canvas_button, ui)
+    {
+        state.commands.push(Command::ResizeCanvas);
+    }
+}
+
+/// Size and background of a blank canvas, opened in a new editor window.
+fn new_image_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let options = &mut state.new_image;
+    if ids.new_image_templates.len() < options.templates.len() {
+        ids.new_image_templates
+            .resize(options.templates.len(), &mut ui.widget_id_generator());
+    }
+
+    // Picking a template fills in the size and resolution
+    for (template, id) in options.templates.iter().zip(ids.new_image_templates.iter()) {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(&template.label())
+            .set(*id, ui)
+        {
+            options.width = template.width as f32;
+            options.height = template.height as f32;
+            options.dpi = template.dpi;
+            options.template_name = template.name.clone();
+        }
+    }
+
+    if let Some(value) = slider(options.width, 1.0, 4096.0)
+        .down(10.0)
+        .label(&format!("Width: {}", options.width))
+        .set(ids.new_image_width, ui)
+    {
+        options.width = value.round();
+    }
+
+    if let Some(value) = slider(options.height, 1.0, 4096.0)
+        .down(10.0)
+        .label(&format!("Height: {}", options.height))
+        .set(ids.new_image_height, ui)
+    {
+        options.height = value.round();
+    }
+
+    if let Some(value) = slider(options.dpi, 36.0, 600.0)
+        .down(10.0)
+        .label(&format!("Resolution: {} ppi", options.dpi))
+        .set(ids.new_image_dpi, ui)
+    {
+        options.dpi = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Background: {}", options.background.name()))
+        .set(ids.new_image_background, ui)
+    {
+        options.background = options.background.next();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Create")
+        .set(ids.new_image_button, ui)
+    {
+        state.commands.push(Command::NewImage);
+    }
+
+    let options = &mut state.new_image;
+    for event in widget::TextBox::new(&options.template_name)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .font_size(15)
+        .set(ids.new_image_template_name, ui)
+    {
+        if let widget::text_box::Event::Update(name) = event {
+            options.template_name = name;
+        }
+    }
+
+    if widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Save as Template")
+        .set(ids.new_image_save_template, ui)
+        .was_clicked()
+    {
+        let name = options.template_name.trim();
+        if name.is_empty() {
+            log::warn!("Name the template before saving it");
+        } else {
+            let template = templates::Template {
+                name: name.to_string(),
+                width: options.width as u32,
+                height: options.height as u32,
+                dpi: options.dpi,
+            };
+            match templates::save(&template) {
+                Ok(()) => options.templates = templates::load(),
+                Err(e) => log::error!("Failed to save template {}: {}", template.name, e),
+            }
+        }
+    }
+}
+
+/// Brush color as RGBA sliders with a swatch.
+fn color_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let [r, g, b, a] = state.brush_color;
+    widget::Rectangle::fill([200.0, 30.0])
+        .rgba(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+        .down(10.0)
+        .set(ids.color_swatch, ui);
+
+    for (i, (id, label)) in [
+        (ids.color_red, "Red"),
+        (ids.color_green, "Green"),
+        (ids.color_blue, "Blue"),
+        (ids.color_alpha, "Alpha"),
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if let Some(value) = slider(state.brush_color[i] as f32, 0.0, 255.0)
+            .down(10.0)
+            .label(label)
+            .set(id, ui)
+        {
+            state.brush_color[i] = value.round() as u8;
+        }
+    }
+
+    if let Some(value) = slider(state.brush_opacity, 0.0, 1.0)
+        .down(10.0)
+        .label(&format!("Opacity: {:.0}%", state.brush_opacity * 100.0))
+        .set(ids.brush_opacity, ui)
+    {
+        state.brush_opacity = value;
+    }
+
+    if let Some(value) = slider(state.brush_flow, 0.0, 1.0)
+        .down(10.0)
+        .label(&format!("Flow: {:.0}%", state.brush_flow * 100.0))
+        .set(ids.brush_flow, ui)
+    {
+        state.brush_flow = value;
+    }
+}
+
+/// Brush tip picker, blend mode, dab spacing and stroke stabilizer. The textured tip is made from
+/// the captured stamp.
+fn brush_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let choices = [
+        BrushType::HardRound.name(),
+        BrushType::SoftRound.name(),
+        BrushType::Square.name(),
+        BrushType::Pencil.name(),
+        "Textured (from stamp)",
+    ];
+    let active = match state.brush {
+        BrushType::HardRound => 0,
+        BrushType::SoftRound => 1,
+        BrushType::Square => 2,
+        BrushType::Pencil => 3,
+        BrushType::Textured(_) => 4,
+    };
+
+    if ids.brush_type_buttons.len() < choices.len() {
+        ids.brush_type_buttons
+            .resize(choices.len(), &mut ui.widget_id_generator());
+    }
+
+    for (i, name) in choices.into_iter().enumerate() {
+        let button = widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(name);
+        let button = if i == active {
+            button.rgb(0.5, 0.7, 1.0)
+        } else {
+            button
+        };
+        for _click in button.set(ids.brush_type_buttons[i], ui) {
+            state.brush = match i {
+                0 => BrushType::HardRound,
+                1 => BrushType::SoftRound,
+                2 => BrushType::Square,
+                3 => BrushType::Pencil,
+                _ => match &state.stamp {
+                    Some(stamp) => BrushType::Textured(Textured::from_stamp(stamp)),
+                    None => {
+                        log::warn!("Capture a stamp to use as the brush tip");
+                        continue;
+                    }
+                },
+            };
+        }
+    }
+
+    let blends: Vec<&str> = BrushBlend::ALL.iter().map(|b| b.name()).collect();
+    let selected = BrushBlend::ALL.iter().position(|b| *b == state.brush_blend);
+    if let Some(i) = widget::DropDownList::new(&blends, selected)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .max_visible_items(BrushBlend::ALL.len())
+        .set(ids.brush_blend, ui)
+    {
+        state.brush_blend = BrushBlend::ALL[i];
+    }
+
+    if let Some(value) = slider(state.brush_spacing, 0.05, 2.0)
+        .down(10.0)
+        .label(&format!("Spacing: {:.0}%", state.brush_spacing * 100.0))
+        .set(ids.brush_spacing, ui)
+    {
+        state.brush_spacing = value;
+    }
+
+    if let Some(value) = slider(state.stabilizer, 0.0, 20.0)
+        .down(10.0)
+        .label(&format!("Stabilizer: {:.0}", state.stabilizer))
+        .set(ids.stabilizer, ui)
+    {
+        state.stabilizer = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Symmetry: {}", state.symmetry.name()))
+        .set(ids.symmetry_button, ui)
+    {
+        state.symmetry = state.symmetry.next();
+    }
+
+    if let Symmetry::Radial(spokes) = &mut state.symmetry {
+        if let Some(value) = slider(*spokes as f32, 2.0, 16.0)
+            .down(10.0)
+            .label(&format!("Copies: {}", spokes))
+            .set(ids.symmetry_spokes, ui)
+        {
+            *spokes = value.round() as u32;
+        }
+    }
+
+    let effects = [
+        ("Taper Last Stroke", StrokeFx::Taper),
+        ("Softer Pressure", StrokeFx::PressureCurve(0.5)),
+        ("Firmer Pressure", StrokeFx::PressureCurve(2.0)),
+        ("Simplify Last Stroke", StrokeFx::Simplify),
+    ];
+    if ids.stroke_fx_buttons.len() < effects.len() {
+        ids.stroke_fx_buttons
+            .resize(effects.len(), &mut ui.widget_id_generator());
+    }
+
+    for (i, (label, fx)) in effects.into_iter().enumerate() {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(label)
+            .set(ids.stroke_fx_buttons[i], ui)
+        {
+            state.commands.push(Command::StrokeFx(fx));
+        }
+    }
+}
+
+/// Gradient type and color stops. Clicking a stop's swatch gives it the brush color.
+fn gradient_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let gradient = &mut state.gradient;
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Type: {}", gradient.kind.name()))
+        .set(ids.gradient_kind, ui)
+    {
+        gradient.kind = gradient.kind.next();
+    }
+
+    let count = gradient.stops.len();
+    if ids.gradient_stop_colors.len() < count {
+        let generator = &mut ui.widget_id_generator();
+        ids.gradient_stop_colors.resize(count, generator);
+        ids.gradient_stop_positions.resize(count, generator);
+        ids.gradient_stop_removes.resize(count, generator);
+    }
+
+    let mut removed = None;
+    for (i, stop) in gradient.stops.iter_mut().enumerate() {
+        let [r, g, b, a] = stop.color.map(|c| c as f32 / 255.0);
+        let swatch = if i == 0 {
+            widget::Button::new().down(10.0)
+        } else {
+            widget::Button::new().down_from(ids.gradient_stop_colors[i - 1], 4.0)
+        };
+        for _click in swatch
+            .w_h(30.0, 30.0)
+            .rgba(r, g, b, a)
+            .set(ids.gradient_stop_colors[i], ui)
+        {
+            stop.color = state.brush_color;
+        }
+
+        if let Some(value) = slider(stop.position, 0.0, 1.0)
+            .right(8.0)
+            .w_h(120.0, 30.0)
+            .label(&format!("{:.0}%", stop.position * 100.0))
+            .set(ids.gradient_stop_positions[i], ui)
+        {
+            stop.position = value;
+        }
+
+        // A gradient needs two ends
+        if count > 2 {
+            for _click in widget::Button::new()
+                .right(8.0)
+                .w_h(30.0, 30.0)
+                .label("x")
+                .set(ids.gradient_stop_removes[i], ui)
+            {
+                removed = Some(i);
+            }
+        }
+    }
+    if let Some(i) = removed {
+        gradient.stops.remove(i);
+    }
+
+    for _click in widget::Button::new()
+        .down_from(ids.gradient_stop_colors[count - 1], 10.0)
+        .w_h(200.0, 30.0)
+        .label("Add Stop")
+        .set(ids.gradient_add_stop, ui)
+    {
+        gradient.stops.push(ColorStop {
+            position: 0.5,
+            color: state.brush_color,
+        });
+    }
+}
+
+/// Layer list, top layer first, with controls for the active layer.
+fn layers_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let mut ops = Vec::new();
+
+    for (id, label, op) in [
+        (ids.layer_add, "Add", LayerOp::Add),
+        (ids.layer_delete, "Del", LayerOp::Delete),
+        (ids.layer_up, "Up", LayerOp::MoveUp),
+        (ids.layer_down, "Down", LayerOp::MoveDown),
+    ] {
+        let button = widget::Button::new().w_h(44.0, 30.0).label(label);
+        let button = if id == ids.layer_add {
+            button.down(10.0)
+        } else {
+            button.right(8.0)
+        };
+        if button.set(id, ui).was_clicked() {
+            ops.push(op);
+        }
+    }
+
+    let (layers, active) = &state.layer_list;
+    if let Some(layer) = layers.get(*active) {
+        for value in widget::Toggle::new(layer.visible)
+            .down_from(ids.layer_add, 10.0)
+            .w_h(200.0, 30.0)
+            .label("Visible")
+            .set(ids.layer_visible, ui)
+        {
+            ops.push(LayerOp::SetVisible(value));
+        }
+
+        if let Some(value) = slider(layer.opacity, 0.0, 1.0)
+            .down(10.0)
+            .label("Opacity")
+            .set(ids.layer_opacity, ui)
+        {
+            ops.push(LayerOp::SetOpacity(value));
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(&format!("Blend: {}", layer.blend.name()))
+            .set(ids.layer_blend, ui)
+        {
+            ops.push(LayerOp::SetBlend(layer.blend.next()));
+        }
+    }
+
+    if ids.layer_buttons.len() < layers.len() {
+        ids.layer_buttons
+            .resize(layers.len(), &mut ui.widget_id_generator());
+    }
+    for (i, layer) in layers.iter().enumerate().rev() {
+        let button = widget::Button::new()
+            .down(4.0)
+            .w_h(200.0, 24.0)
+            .label_font_size(12)
+            .label(&layer.name);
+        let button = if i == *active {
+            button.rgb(0.4, 0.5, 0.8)
+        } else {
+            button
+        };
+        if button.set(ids.layer_buttons[i], ui).was_clicked() {
+            ops.push(LayerOp::Select(i));
+        }
+    }
+
+    state.commands.extend(ops.into_iter().map(Command::Layer));
+}
+
+/// Video of the canvas, and animated GIF and APNG export with a frame per visible layer.
+fn video_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    if let Some(value) = slider(state.video.fps, 1.0, 60.0)
+        .down(10.0)
+        .label("Video FPS")
+        .set(ids.video_fps, ui)
+    {
+        state.video.fps = value.round();
+    }
+
+    if let Some(value) = slider(state.video.scale, 0.25, 16.0)
+        .down(10.0)
+        .label("Video Scale")
+        .set(ids.video_scale, ui)
+    {
+        // Quarter steps, so halving a large canvas is as easy as doubling a small one
+        state.video.scale = (value * 4.0).round() / 4.0;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Export Video")
+        .set(ids.export_video_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("MP4", &["mp4"])
+            .add_filter("WebM", &["webm"])
+            .save_file()
+        {
+            state.commands.push(Command::ExportVideo(path));
+        }
+    }
+
+    let options = &mut state.animation;
+    if let Some(value) = slider(options.delay, 10.0, 1000.0)
+        .down(10.0)
+        .label(&format!("Frame Delay: {} ms", options.delay))
+        .set(ids.animation_delay, ui)
+    {
+        options.delay = (value / 10.0).round() * 10.0;
+    }
+
+    let plays = match options.plays.round() as u32 {
+        0 => "Plays: Forever".to_string(),
+        n => format!("Plays: {}", n),
+    };
+    if let Some(value) = slider(options.plays, 0.0, 20.0)
+        .down(10.0)
+        .label(&plays)
+        .set(ids.animation_plays, ui)
+    {
+        options.plays = value.round();
+    }
+
+    if let Some(value) = slider(options.colors, 2.0, 256.0)
+        .down(10.0)
+        .label(&format!("GIF Colors: {}", options.colors))
+        .set(ids.animation_colors, ui)
+    {
+        options.colors = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Export Animation")
+        .set(ids.export_animation_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("GIF", &["gif"])
+            .add_filter("APNG", &["png", "apng"])
+            .save_file()
+        {
+            state.commands.push(Command::ExportAnimation(path));
+        }
+    }
+}
+
+/// Cutting a sprite sheet into layers, and packing the visible layers into one.
+fn sprite_sheet_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let options = &mut state.sprite_sheet;
+    for value in widget::Toggle::new(options.by_size)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(if options.by_size {
+            "Slice: Cell Size"
+        } else {
+            "Slice: Cell Count"
+        })
+        .set(ids.sprite_by_size, ui)
+    {
+        options.by_size = value;
+    }
+
+    if options.by_size {
+        if let Some(value) = slider(options.cell_width, 1.0, 512.0)
+            .down(10.0)
+            .label(&format!("Cell Width: {}", options.cell_width))
+            .set(ids.sprite_cell_width, ui)
+        {
+            options.cell_width = value.round();
+        }
+        if let Some(value) = slider(options.cell_height, 1.0, 512.0)
+            .down(10.0)
+            .label(&format!("Cell Height: {}", options.cell_height))
+            .set(ids.sprite_cell_height, ui)
+        {
+            options.cell_height = value.round();
+        }
+    } else {
+        if let Some(value) = slider(options.grid_columns, 1.0, 64.0)
+            .down(10.0)
+            .label(&format!("Columns: {}", options.grid_columns))
+            .set(ids.sprite_grid_columns, ui)
+        {
+            options.grid_columns = value.round();
+        }
+        if let Some(value) = slider(options.grid_rows, 1.0, 64.0)
+            .down(10.0)
+            .label(&format!("Rows: {}", options.grid_rows))
+            .set(ids.sprite_grid_rows, ui)
+        {
+            options.grid_rows = value.round();
+        }
+    }
+
+    for value in widget::Toggle::new(options.skip_empty)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Skip Empty Cells")
+        .set(ids.sprite_skip_empty, ui)
+    {
+        options.skip_empty = value;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Import Sprite Sheet")
+        .set(ids.import_sprite_sheet_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Image", &["png", "jpg", "jpeg", "avif", "jxl"])
+            .pick_file()
+        {
+            state.commands.push(Command::ImportSpriteSheet(path));
+        }
+    }
+
+    let options = &mut state.sprite_sheet;
+    let columns = match options.columns as u32 {
+        0 => "Sheet Columns: Auto".to_string(),
+        n => format!("Sheet Columns: {}", n),
+    };
+    if let Some(value) = slider(options.columns, 0.0, 64.0)
+        .down(10.0)
+        .label(&columns)
+        .set(ids.sprite_columns, ui)
+    {
+        options.columns = value.round();
+    }
+
+    if let Some(value) = slider(options.padding, 0.0, 16.0)
+        .down(10.0)
+        .label(&format!("Padding: {} px", options.padding))
+        .set(ids.sprite_padding, ui)
+    {
+        options.padding = value.round();
+    }
+
+    for value in widget::Toggle::new(options.metadata)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Frame Rects JSON")
+        .set(ids.sprite_metadata, ui)
+    {
+        options.metadata = value;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Export Sprite Sheet")
+        .set(ids.export_sprite_sheet_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .save_file()
+        {
+            state.commands.push(Command::ExportSpriteSheet(path));
+        }
+    }
+}
+
+/// Picks the layer or composite channel each output channel of a packed texture is read from.
+fn channel_pack_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    if ids.channel_pack_sources.len() < 4 {
+        ids.channel_pack_sources
+            .resize(4, &mut ui.widget_id_generator());
+        ids.channel_pack_channels
+            .resize(4, &mut ui.widget_id_generator());
+    }
+
+    let layers = &state.layer_list.0;
+    for (i, output) in state.channel_pack.outputs.iter_mut().enumerate() {
+        let source = match output.source {
+            Source::Composite => "Composite".to_string(),
+            Source::Layer(l) => layers
+                .get(l)
+                .map_or_else(|| format!("Layer {}", l), |layer| layer.name.clone()),
+            Source::Black => "Black".to_string(),
+            Source::White => "White".to_string(),
+        };
+        let button = if i == 0 {
+            widget::Button::new().down(10.0)
+        } else {
+            widget::Button::new().down_from(ids.channel_pack_sources[i - 1], 4.0)
+        };
+        for _click in button
+            .w_h(140.0, 30.0)
+            .label_font_size(12)
+            .label(&format!("{}: {}", ["R", "G", "B", "A"][i], source))
+            .set(ids.channel_pack_sources[i], ui)
+        {
+            output.source = output.source.next(layers.len());
+        }
+
+        if !output.source.is_constant() {
+            for _click in widget::Button::new()
+                .right(8.0)
+                .w_h(52.0, 30.0)
+                .label_font_size(12)
+                .label(output.channel.name())
+                .set(ids.channel_pack_channels[i], ui)
+            {
+                output.channel = output.channel.next();
+            }
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down_from(ids.channel_pack_sources[3], 10.0)
+        .w_h(200.0, 30.0)
+        .label("Export Packed...")
+        .set(ids.channel_pack_export, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .save_file()
+        {
+            state.commands.push(Command::ExportChannelPack(path));
+        }
+    }
+}
+
+fn tiles_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for value in widget::Toggle::new(state.tile_mode)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Tile Mode")
+        .set(ids.tile_mode_toggle, ui)
+    {
+        state.tile_mode = value;
+    }
+
+    if let Some(value) = slider(state.tile_size, 4.0, 64.0)
+        .down(10.0)
+        .label("Tile Size")
+        .set(ids.tile_size, ui)
+    {
+        state.tile_size = value.round();
+    }
+
+    if let Some(value) = slider(state.wrap_step, 1.0, 64.0)
+        .down(10.0)
+        .label(&format!("Alt+Arrow Shift: {} px", state.wrap_step))
+        .set(ids.wrap_step, ui)
+    {
+        state.wrap_step = value.round();
+    }
+
+    if let Some(value) = slider(state.seam_blend, 0.0, 64.0)
+        .down(10.0)
+        .label(&format!("Seam Blend: {} px", state.seam_blend))
+        .set(ids.seam_blend, ui)
+    {
+        state.seam_blend = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Make Seamless")
+        .set(ids.make_seamless_button, ui)
+    {
+        state.commands.push(Command::MakeSeamless);
+    }
+
+    for value in widget::Toggle::new(state.wrap_preview)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Wrap Preview")
+        .set(ids.wrap_preview_toggle, ui)
+    {
+        state.wrap_preview = value;
+    }
+
+    for _click in tool_button("Tile Stamp", state.mode == Mode::TileStamp)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .set(ids.tile_stamp_button, ui)
+    {
+        state.mode = Mode::TileStamp;
+    }
+
+    let grid = &mut state.pixel_grid;
+    for value in widget::Toggle::new(grid.enabled)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Pixel Grid (from {}x)", pixel_grid::MIN_SCALE))
+        .set(ids.pixel_grid_toggle, ui)
+    {
+        grid.enabled = value;
+    }
+
+    // Takes the brush color, like the gradient stops
+    let [r, g, b, a] = grid.color.map(|c| c as f32 / 255.0);
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .rgba(r, g, b, a)
+        .label("Grid Color: Use Brush Color")
+        .set(ids.pixel_grid_color, ui)
+    {
+        grid.color = state.brush_color;
+    }
+
+    let grid = &mut state.pixel_grid;
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&match grid.major {
+            0 => "Highlight: Off".to_string(),
+            major => format!("Highlight Every {} px", major),
+        })
+        .set(ids.pixel_grid_major, ui)
+    {
+        grid.major = grid.next_major();
+    }
+}
+
+/// Text of the next note placed with the Note tool, and the focused document's notes. Clicking a
+/// note selects it, showing its text on the canvas.
+fn notes_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for event in widget::TextBox::new(&state.note.text)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .font_size(15)
+        .set(ids.note_text, ui)
+    {
+        if let widget::text_box::Event::Update(value) = event {
+            state.note.text = value;
+        }
+    }
+
+    for value in widget::Toggle::new(state.note.todo)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("TODO")
+        .set(ids.note_todo, ui)
+    {
+        state.note.todo = value;
+    }
+
+    let (notes, selected) = &state.note_list;
+    if ids.note_buttons.len() < notes.len() {
+        ids.note_buttons
+            .resize(notes.len(), &mut ui.widget_id_generator());
+    }
+    for (i, note) in notes.iter().enumerate() {
+        let button = widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label_font_size(12)
+            .label(&note.label());
+        let button = if *selected == Some(i) {
+            button.rgb(0.5, 0.7, 1.0)
+        } else {
+            button
+        };
+        if button.set(ids.note_buttons[i], ui).was_clicked() {
+            state.commands.push(Command::Note(NoteOp::Select(i)));
+        }
+    }
+
+    if let Some((i, note)) = selected.and_then(|i| Some((i, notes.get(i)?))) {
+        if note.todo {
+            for _value in widget::Toggle::new(note.done)
+                .down(10.0)
+                .w_h(200.0, 30.0)
+                .label("Done")
+                .set(ids.note_done, ui)
+            {
+                state.commands.push(Command::Note(NoteOp::ToggleDone(i)));
+            }
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label("Delete Note")
+            .set(ids.note_delete, ui)
+        {
+            state.commands.push(Command::Note(NoteOp::Delete(i)));
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Clear Done TODOs")
+        .set(ids.note_clear_done, ui)
+    {
+        state.commands.push(Command::Note(NoteOp::ClearDone));
+    }
+}
+
+/// Colors the document uses, with swatches to pick from when there are few, and posterizing to a
+/// smaller palette.
+fn palette_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Analyze Colors")
+        .set(ids.palette_analyze, ui)
+    {
+        state.commands.push(Command::AnalyzeColors);
+    }
+
+    if let Some(report) = &state.color_report {
+        widget::Text::new(&report.summary())
+            .down(10.0)
+            .font_size(12)
+            .rgb(1.0, 1.0, 1.0)
+            .set(ids.palette_report, ui);
+
+        // Rows of eight, clicking one makes it the brush color
+        if ids.palette_swatches.len() < report.palette.len() {
+            ids.palette_swatches
+                .resize(report.palette.len(), &mut ui.widget_id_generator());
+        }
+        for (i, &[r, g, b, a]) in report.palette.iter().enumerate() {
+            let swatch = widget::Button::new().w_h(25.0, 25.0).rgba(
+                r as f32 / 255.0,
+                g as f32 / 255.0,
+                b as f32 / 255.0,
+                a as f32 / 255.0,
+            );
+            let swatch = match i {
+                0 => swatch.down(10.0),
+                i if i % 8 == 0 => swatch.down_from(ids.palette_swatches[i - 8], 0.0),
+                _ => swatch.right(0.0),
+            };
+            for _click in swatch.set(ids.palette_swatches[i], ui) {
+                state.brush_color = [r, g, b, a];
+            }
+        }
+    }
+
+    let target = state.posterize.colors;
+    let label = format!("Target Colors: {}", target.round());
+    let colors = slider(target, 2.0, 256.0).label(&label);
+    // Swatch rows end on the right, so line up under the first one
+    let colors = match &state.color_report {
+        Some(report) if !report.palette.is_empty() => {
+            let last_row = (report.palette.len() - 1) / 8 * 8;
+            colors.down_from(ids.palette_swatches[last_row], 10.0)
+        }
+        _ => colors.down(10.0),
+    };
+    if let Some(value) = colors.set(ids.palette_colors, ui) {
+        state.posterize.colors = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Method: {}", state.posterize.quantizer.name()))
+        .set(ids.palette_method, ui)
+    {
+        state.posterize.quantizer = state.posterize.quantizer.next();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Posterize to {} Colors", target.round()))
+        .set(ids.palette_posterize, ui)
+    {
+        state.commands.push(Command::Posterize);
+    }
+}
+
+/// Vanishing point guides. The points are dragged on the canvas with the Move tool.
+fn perspective_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let grid = &mut state.perspective;
+    for value in widget::Toggle::new(grid.visible)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Perspective Grid")
+        .set(ids.perspective_toggle, ui)
+    {
+        grid.visible = value;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(grid.points.name())
+        .set(ids.perspective_points, ui)
+    {
+        grid.points = grid.points.next();
+    }
+
+    if let Some(value) = slider(grid.density, 4.0, 96.0)
+        .down(10.0)
+        .label(&format!("Rays: {}", grid.density))
+        .set(ids.perspective_density, ui)
+    {
+        grid.density = value.round();
+    }
+
+    for value in widget::Toggle::new(grid.snap)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Snap Lines to Rays")
+        .set(ids.perspective_snap, ui)
+    {
+        grid.snap = value;
+    }
+}
+
+/// Overlays of the focused document to compose against.
+fn composition_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let mut overlays = state.document_overlays;
+    for value in widget::Toggle::new(overlays.thirds)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Rule of Thirds")
+        .set(ids.composition_thirds, ui)
+    {
+        overlays.thirds = value;
+    }
+
+    for value in widget::Toggle::new(overlays.golden_spiral)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Golden Spiral")
+        .set(ids.composition_spiral, ui)
+    {
+        overlays.golden_spiral = value;
+    }
+
+    if overlays.golden_spiral {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(&format!("Spiral: {}", overlays.spiral_corner.name()))
+            .set(ids.composition_spiral_corner, ui)
+        {
+            overlays.spiral_corner = overlays.spiral_corner.next();
+        }
+    }
+
+    for value in widget::Toggle::new(overlays.center)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Center Cross")
+        .set(ids.composition_center, ui)
+    {
+        overlays.center = value;
+    }
+
+    for value in widget::Toggle::new(overlays.safe_areas)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Safe Areas")
+        .set(ids.composition_safe_areas, ui)
+    {
+        overlays.safe_areas = value;
+    }
+
+    if overlays != state.document_overlays {
+        state.document_overlays = overlays;
+        state.commands.push(Command::SetOverlays(overlays));
+    }
+}
+
+/// Guides from equal divisions or typed positions, and slice export along them.
+fn guides_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let options = &mut state.guides;
+    if let Some(value) = slider(options.columns, 1.0, 32.0)
+        .down(10.0)
+        .label(&format!("Columns: {}", options.columns))
+        .set(ids.guide_columns, ui)
+    {
+        options.columns = value.round();
+    }
+
+    if let Some(value) = slider(options.rows, 1.0, 32.0)
+        .down(10.0)
+        .label(&format!("Rows: {}", options.rows))
+        .set(ids.guide_rows, ui)
+    {
+        options.rows = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Divide Equally")
+        .set(ids.guide_divide, ui)
+    {
+        state.commands.push(Command::DivideGuides);
+    }
+
+    for (label_id, label, box_id, text) in [
+        (
+            ids.guide_vertical_label,
+            "Vertical guides at x (px)",
+            ids.guide_vertical,
+            &mut options.vertical,
+        ),
+        (
+            ids.guide_horizontal_label,
+            "Horizontal guides at y (px)",
+            ids.guide_horizontal,
+            &mut options.horizontal,
+        ),
+    ] {
+        widget::Text::new(label)
+            .down(10.0)
+            .font_size(12)
+            .rgb(1.0, 1.0, 1.0)
+            .set(label_id, ui);
+
+        for event in widget::TextBox::new(text)
+            .down(4.0)
+            .w_h(200.0, 30.0)
+            .font_size(15)
+            .set(box_id, ui)
+        {
+            if let widget::text_box::Event::Update(value) = event {
+                *text = value;
+            }
+        }
+    }
+
+    let options = &mut state.guides;
+    for (id, label, value) in [
+        (ids.guide_rulers, "Rulers", &mut options.rulers),
+        (ids.guide_snap, "Snap to Guides", &mut options.snap),
+    ] {
+        for toggled in widget::Toggle::new(*value)
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(label)
+            .set(id, ui)
+        {
+            *value = toggled;
+        }
+    }
+
+    for (id, label, command) in [
+        (ids.guide_add, "Add Guides", Command::AddGuides),
+        (ids.guide_clear, "Clear Guides", Command::ClearGuides),
+    ] {
+        if widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(label)
+            .set(id, ui)
+            .was_clicked()
+        {
+            state.commands.push(command);
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Slice by Guides...")
+        .set(ids.guide_slice, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .save_file()
+        {
+            state.commands.push(Command::SliceByGuides(path));
+        }
+    }
+}
+
+fn nine_slice_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for value in widget::Toggle::new(state.nine_slice_mode)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("9-Slice")
+        .set(ids.nine_slice_toggle, ui)
+    {
+        state.nine_slice_mode = value;
+    }
+
+    let slice = &mut state.nine_slice;
+    for (id, label, value) in [
+        (ids.nine_slice_left, "Slice Left", &mut slice.left),
+        (ids.nine_slice_right, "Slice Right", &mut slice.right),
+        (ids.nine_slice_top, "Slice Top", &mut slice.top),
+        (ids.nine_slice_bottom, "Slice Bottom", &mut slice.bottom),
+    ] {
+        if let Some(v) = slider(*value as f32, 0.0, 128.0)
+            .down(10.0)
+            .label(label)
+            .set(id, ui)
+        {
+            *value = v.round() as u32;
+        }
+    }
+
+    if let Some(value) = slider(state.nine_slice_size.0, 8.0, 512.0)
+        .down(10.0)
+        .label("Preview Width")
+        .set(ids.nine_slice_width, ui)
+    {
+        state.nine_slice_size.0 = value.round();
+    }
+
+    if let Some(value) = slider(state.nine_slice_size.1, 8.0, 512.0)
+        .down(10.0)
+        .label("Preview Height")
+        .set(ids.nine_slice_height, ui)
+    {
+        state.nine_slice_size.1 = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Export 9-Slice")
+        .set(ids.export_nine_slice_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("PNG", &["png"])
+            .save_file()
+        {
+            state.commands.push(Command::ExportNineSlice(path));
+        }
+    }
+}
+
+fn stamp_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Use Selection as Brush")
+        .set(ids.capture_stamp_button, ui)
+    {
+        state.commands.push(Command::CaptureStamp);
+    }
+
+    for value in widget::Toggle::new(state.stamp_jitter.rotate)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Stamp Rotate Jitter")
+        .set(ids.stamp_rotate_toggle, ui)
+    {
+        state.stamp_jitter.rotate = value;
+    }
+
+    for value in widget::Toggle::new(state.stamp_jitter.flip)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Stamp Flip Jitter")
+        .set(ids.stamp_flip_toggle, ui)
+    {
+        state.stamp_jitter.flip = value;
+    }
+}
+
+/// Copy, plus paste variants that mirror or rotate the clip on the way in, and finishing a free
+/// transform.
+fn clipboard_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Copy")
+        .set(ids.copy_button, ui)
+    {
+        state.commands.push(Command::Copy);
+    }
+
+    if ids.paste_buttons.len() < PasteTransform::ALL.len() {
+        ids.paste_buttons
+            .resize(PasteTransform::ALL.len(), &mut ui.widget_id_generator());
+    }
+
+    for (i, transform) in PasteTransform::ALL.into_iter().enumerate() {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .w_h(200.0, 30.0)
+            .label(transform.name())
+            .set(ids.paste_buttons[i], ui)
+        {
+            state.commands.push(Command::Paste(transform));
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Commit Transform")
+        .set(ids.commit_transform_button, ui)
+    {
+        state.commands.push(Command::CommitTransform);
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Cancel Transform")
+        .set(ids.cancel_transform_button, ui)
+    {
+        state.commands.push(Command::CancelTransform);
+    }
+
+    for value in widget::Toggle::new(state.transform_smooth)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Smooth Transform")
+        .set(ids.transform_smooth_toggle, ui)
+    {
+        state.transform_smooth = value;
+    }
+}
+
+fn nodes_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let graph = &mut state.node_graph;
+    let count = graph.nodes.len();
+    if ids.node_labels.len() < count {
+        let generator = &mut ui.widget_id_generator();
+        ids.node_labels.resize(count, generator);
+        ids.node_removes.resize(count, generator);
+        ids.node_params.resize(count * NODE_PARAMS, generator);
+        ids.node_inputs.resize(count * NODE_INPUTS, generator);
+        ids.node_actions.resize(count * NODE_ACTIONS, generator);
+    }
+    if ids.node_add_buttons.len() < 4 {
+        ids.node_add_buttons
+            .resize(4, &mut ui.widget_id_generator());
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!(
+            "Output: {} {}",
+            graph.output,
+            graph.nodes[graph.output].name()
+        ))
+        .set(ids.node_output, ui)
+    {
+        graph.output = (graph.output + 1) % count;
+    }
+
+    let mut removed = None;
+    for (i, node) in graph.nodes.iter_mut().enumerate() {
+        widget::Text::new(&format!("{} {}", i, node.name()))
+            .down(14.0)
+            .w(150.0)
+            .font_size(14)
+            .rgb(1.0, 1.0, 1.0)
+            .set(ids.node_labels[i], ui);
+        for _click in widget::Button::new()
+            .right(20.0)
+            .w_h(30.0, 24.0)
+            .label("x")
+            .set(ids.node_removes[i], ui)
+        {
+            removed = Some(i);
+        }
+
+        // Each row goes under the last, not under the remove button beside the label
+        let mut above = ids.node_labels[i];
+        for (j, (label, value, min, max)) in node.params_mut().into_iter().enumerate() {
+            let id = ids.node_params[i * NODE_PARAMS + j];
+            if let Some(v) = slider(*value, min, max)
+                .down_from(above, 6.0)
+                .label(label)
+                .set(id, ui)
+            {
+                *value = v;
+            }
+            above = id;
+        }
+
+        // Inputs step through the nodes, and optional ones through none as well
+        for (j, (label, input)) in node.inputs_mut().into_iter().enumerate() {
+            let text = match &input {
+                nodes::Input::Required(n) => format!("{}: {}", label, n),
+                nodes::Input::Optional(Some(n)) => format!("{}: {}", label, n),
+                nodes::Input::Optional(None) => format!("{}: none", label),
+            };
+            let id = ids.node_inputs[i * NODE_INPUTS + j];
+            let clicked = widget::Button::new()
+                .down_from(above, 6.0)
+                .w_h(200.0, 24.0)
+                .label_font_size(12)
+                .label(&text)
+                .set(id, ui)
+                .was_clicked();
+            if clicked {
+                match input {
+                    nodes::Input::Required(n) => *n = (*n + 1) % count,
+                    nodes::Input::Optional(n) => {
+                        *n = match *n {
+                            None => Some(0),
+                            Some(n) if n + 1 < count => Some(n + 1),
+                            Some(_) => None,
+                        }
+                    }
+                }
+            }
+            above = id;
+        }
+
+        let actions: &[&str] = match node {
+            nodes::Node::Noise { .. } => &["New Seed"],
+            nodes::Node::Gradient { .. } => &["From: Brush Color", "To: Brush Color"],
+            _ => &[],
+        };
+        for (j, label) in actions.iter().enumerate() {
+            let id = ids.node_actions[i * NODE_ACTIONS + j];
+            for _click in widget::Button::new()
+                .down_from(above, 6.0)
+                .w_h(200.0, 24.0)
+                .label_font_size(12)
+                .label(label)
+                .set(id, ui)
+            {
+                let color = state.brush_color.map(|c| c as f32 / 255.0);
+                match (&mut *node, j) {
+                    (nodes::Node::Noise { seed, .. }, _) => *seed = seed.wrapping_add(1),
+                    (nodes::Node::Gradient { from, .. }, 0) => *from = color,
+                    (nodes::Node::Gradient { to, .. }, _) => *to = color,
+                    _ => (),
+                }
+            }
+            above = id;
+        }
+    }
+    if let Some(i) = removed {
+        if let Err(e) = graph.remove(i) {
+            log::warn!("Can't remove node {}: {}", i, e);
+        }
+    }
+
+    // New nodes read from the output, so they can be chained onto it
+    for (i, node) in nodes::Node::kinds(graph.output).into_iter().enumerate() {
+        let button = widget::Button::new()
+            .w_h(44.0, 24.0)
+            .label_font_size(10)
+            .label(node.name());
+        let button = if i == 0 {
+            button.down(14.0)
+        } else {
+            button.right(8.0)
+        };
+        for _click in button.set(ids.node_add_buttons[i], ui) {
+            graph.nodes.push(node.clone());
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down_from(ids.node_add_buttons[0], 10.0)
+        .label("Bake Node Graph")
+        .set(ids.bake_nodes_button, ui)
+    {
+        state.commands.push(Command::BakeNodeGraph);
+    }
+}
+
+/// Widget ids reserved for each node in the Nodes panel.
+const NODE_PARAMS: usize = 4;
+const NODE_INPUTS: usize = 3;
+const NODE_ACTIONS: usize = 2;
+
+/// Filter pickers, plus the parameters of the filter being previewed and the canvas histogram,
+/// which follows the preview.
+fn filters_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for (id, label, filter) in [
+        (
+            ids.gaussian_blur_button,
+            "Gaussian Blur",
+            filters::Filter::GaussianBlur(Default::default()),
+        ),
+        (
+            ids.box_blur_button,
+            "Box Blur",
+            filters::Filter::BoxBlur(Default::default()),
+        ),
+        (
+            ids.brightness_contrast_button,
+            "Brightness/Contrast",
+            filters::Filter::BrightnessContrast(Default::default()),
+        ),
+        (
+            ids.saturation_button,
+            "Saturation",
+            filters::Filter::Saturation(Default::default()),
+        ),
+        (
+            ids.levels_button,
+            "Levels",
+            filters::Filter::Levels(Default::default()),
+        ),
+        (
+            ids.threshold_button,
+            "Threshold",
+            filters::Filter::Threshold(Default::default()),
+        ),
+        (
+            ids.curves_button,
+            "Curves",
+            filters::Filter::Curves(Default::default()),
+        ),
+    ] {
+        for _click in widget::Button::new().down(10.0).label(label).set(id, ui) {
+            state.filter = Some(filter.clone());
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Unsharp Mask")
+        .set(ids.unsharp_mask_button, ui)
+    {
+        state.filter = Some(filters::Filter::UnsharpMask(Default::default()));
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("High Pass")
+        .set(ids.high_pass_button, ui)
+    {
+        state.filter = Some(filters::Filter::HighPass(Default::default()));
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Median")
+        .set(ids.median_button, ui)
+    {
+        state.filter = Some(filters::Filter::Median(Default::default()));
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Despeckle")
+        .set(ids.despeckle_button, ui)
+    {
+        state.filter = Some(filters::Filter::Despeckle(Default::default()));
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Bilateral Denoise")
+        .set(ids.bilateral_button, ui)
+    {
+        state.filter = Some(filters::Filter::Bilateral(Default::default()));
+    }
+
+    for (id, label, filter) in [
+        (
+            ids.chromatic_aberration_button,
+            "Chromatic Aberration",
+            filters::Filter::ChromaticAberration(Default::default()),
+        ),
+        (
+            ids.vignette_button,
+            "Vignette",
+            filters::Filter::Vignette(Default::default()),
+        ),
+        (
+            ids.grain_button,
+            "Film Grain",
+            filters::Filter::Grain(Default::default()),
+        ),
+        (
+            ids.normal_map_button,
+            "Normal Map",
+            filters::Filter::NormalMap(Default::default()),
+        ),
+    ] {
+        for _click in widget::Button::new().down(10.0).label(label).set(id, ui) {
+            state.filter = Some(filter.clone());
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Load LUT...")
+        .set(ids.load_lut_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Cube LUT", &["cube"])
+            .pick_file()
+        {
+            state.commands.push(Command::LoadLut(path));
+        }
+    }
+
+    for value in widget::Toggle::new(state.config.gpu_filters)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(if state.gpu.is_some() {
+            "GPU Filters"
+        } else {
+            "GPU Filters (unavailable)"
+        })
+        .set(ids.gpu_filters_toggle, ui)
+    {
+        state.config.gpu_filters = value;
+    }
+
+    for value in widget::Toggle::new(state.show_histogram)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Histogram")
+        .set(ids.histogram_toggle, ui)
+    {
+        state.show_histogram = value;
+    }
+
+    if let (true, Some(histogram)) = (state.show_histogram, &state.histogram) {
+        histogram_plot(ids, ui, histogram);
+    }
+
+    if let Some(filter) = &mut state.filter {
+        let params = filter.as_filter_mut().params_mut();
+        if ids.filter_params.len() < params.len() {
+            ids.filter_params
+                .resize(params.len(), &mut ui.widget_id_generator());
+        }
+        for (i, (label, value, min, max)) in params.into_iter().enumerate() {
+            if let Some(v) = slider(*value, min, max)
+                .down(10.0)
+                .label(label)
+                .set(ids.filter_params[i], ui)
+            {
+                *value = v;
+            }
+        }
+
+        if let filters::Filter::NormalMap(normal_map) = filter {
+            for value in widget::Toggle::new(normal_map.invert_y)
+                .down(10.0)
+                .w_h(200.0, 30.0)
+                .label("Invert Y (DirectX)")
+                .set(ids.normal_map_invert_y, ui)
+            {
+                normal_map.invert_y = value;
+            }
+        }
+
+        if let filters::Filter::Curves(curves) = filter {
+            curves_editor(ids, ui, curves, &mut state.curve_channel);
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .label("Apply Filter")
+            .set(ids.apply_filter_button, ui)
+        {
+            state.commands.push(Command::ApplyFilter);
+        }
+
+        for _click in widget::Button::new()
+            .down(10.0)
+            .label("Cancel Filter")
+            .set(ids.cancel_filter_button, ui)
+        {
+            state.commands.push(Command::CancelFilter);
+        }
+    }
+}
+
+/// The curve of one channel at a time, with buttons to switch channels and to reset the curve.
+fn curves_editor(
+    ids: &mut WorkbenchIds,
+    ui: &mut UiCell,
+    curves: &mut filters::curves::Curves,
+    channel: &mut filters::curves::Channel,
+) {
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Channel: {}", channel.name()))
+        .set(ids.curve_channel_button, ui)
+    {
+        *channel = channel.next();
+    }
+
+    let (r, g, b) = match channel {
+        filters::curves::Channel::Rgb => (0.9, 0.9, 0.9),
+        filters::curves::Channel::Red => (0.9, 0.2, 0.2),
+        filters::curves::Channel::Green => (0.2, 0.8, 0.2),
+        filters::curves::Channel::Blue => (0.3, 0.4, 1.0),
+    };
+    let curve = curves.curve_mut(*channel);
+    if let Some(edited) = CurveEditor::new(curve)
+        .down(10.0)
+        .w_h(200.0, 200.0)
+        .rgb(r, g, b)
+        .set(ids.curve_editor, ui)
+    {
+        *curve = edited;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Reset Channel")
+        .set(ids.curve_reset_button, ui)
+    {
+        *curve = Default::default();
+    }
+}
+
+/// Red, green, blue and luminance counts overlaid on one plot. Counts are square-rooted so a few
+/// dominant values don't flatten the rest.
+fn histogram_plot(ids: &mut WorkbenchIds, ui: &mut UiCell, histogram: &Histogram) {
+    widget::Rectangle::fill([200.0, 100.0])
+        .down(10.0)
+        .rgb(0.1, 0.1, 0.1)
+        .set(ids.histogram_background, ui);
+
+    let channels = [
+        (&histogram.red, (0.9, 0.2, 0.2)),
+        (&histogram.green, (0.2, 0.8, 0.2)),
+        (&histogram.blue, (0.3, 0.4, 1.0)),
+        (&histogram.luminance, (0.9, 0.9, 0.9)),
+    ];
+    if ids.histogram_plots.len() < channels.len() {
+        ids.histogram_plots
+            .resize(channels.len(), &mut ui.widget_id_generator());
+    }
+    let peak = (histogram.peak().max(1) as f32).sqrt();
+    for (i, (counts, (r, g, b))) in channels.into_iter().enumerate() {
+        widget::PlotPath::new(0usize, 255usize, 0.0, peak, |v: usize| {
+            (counts[v.min(255)] as f32).sqrt()
+        })
+        .w_h(200.0, 100.0)
+        .x_y_relative_to(ids.histogram_background, 0.0, 0.0)
+        .rgb(r, g, b)
+        .thickness(1.0)
+        .set(ids.histogram_plots[i], ui);
+    }
+}
+
+/// Reference image the document is compared against, how the comparison is shown and how much
+/// differs.
+fn compare_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Load Reference...")
+        .set(ids.compare_load, ui)
+    {
+        if let Some(path) = pick_image_file() {
+            state.commands.push(Command::LoadReference(path));
+        }
+    }
+
+    if state.reference.is_none() {
+        return;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("View: {}", state.compare_view.name()))
+        .set(ids.compare_view, ui)
+    {
+        state.compare_view = state.compare_view.next();
+    }
+
+    let stats = match state.compare_stats {
+        Some(stats) if stats.differing == 0 => "Identical".to_string(),
+        Some(stats) => format!("{} px differ, max {}", stats.differing, stats.max),
+        None => "Comparing...".to_string(),
+    };
+    widget::Text::new(&stats)
+        .down(10.0)
+        .font_size(12)
+        .rgb(1.0, 1.0, 1.0)
+        .set(ids.compare_stats, ui);
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Clear Reference")
+        .set(ids.compare_clear, ui)
+    {
+        state.reference = None;
+        state.compare_stats = None;
+    }
+}
+
+fn batch_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let options = &mut state.batch_options;
+    if let Some(value) = slider(options.scale, 0.1, 4.0)
+        .down(10.0)
+        .label("Batch Scale")
+        .set(ids.batch_scale, ui)
+    {
+        options.scale = (value * 10.0).round() / 10.0;
+    }
+
+    for value in widget::Toggle::new(options.use_filter)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Batch Uses Filter")
+        .set(ids.batch_filter_toggle, ui)
+    {
+        options.use_filter = value;
+    }
+
+    if let Some(value) = slider(options.colors, 0.0, 256.0)
+        .down(10.0)
+        .label("Batch Colors")
+        .set(ids.batch_colors, ui)
+    {
+        options.colors = value.round();
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Run Batch...")
+        .set(ids.run_batch_button, ui)
+    {
+        let input = rfd::FileDialog::new()
+            .set_title("Input Folder")
+            .pick_folder();
+        let output = input.as_ref().and_then(|_| {
+            rfd::FileDialog::new()
+                .set_title("Output Folder")
+                .pick_folder()
+        });
+        if let (Some(input), Some(output)) = (input, output) {
+            state.commands.push(Command::RunBatch(input, output));
+        }
+    }
+
+    if let Some(batch) = &mut state.batch {
+        batch.poll();
+        widget::Text::new(&format!("Batch: {}/{}", batch.done.len(), batch.total))
+            .down(10.0)
+            .font_size(12)
+            .rgb(1.0, 1.0, 1.0)
+            .set(ids.batch_progress, ui);
+
+        // Most recent files first
+        let recent: Vec<_> = batch.done.iter().rev().take(5).collect();
+        if ids.batch_results.len() < recent.len() {
+            ids.batch_results
+                .resize(recent.len(), &mut ui.widget_id_generator());
+        }
+        for (i, entry) in recent.into_iter().enumerate() {
+            let name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+            let line = match &entry.result {
+                Ok(()) => format!("{}: done", name),
+                Err(e) => format!("{}: {}", name, e),
+            };
+            widget::Text::new(&line)
+                .down(4.0)
+                .font_size(12)
+                .rgb(1.0, 1.0, 1.0)
+                .set(ids.batch_results[i], ui);
+        }
+    }
+}
+
+fn watermark_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    let watermark = &mut state.watermark;
+    for value in widget::Toggle::new(watermark.enabled)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Watermark Exports")
+        .set(ids.watermark_toggle, ui)
+    {
+        watermark.enabled = value;
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label("Watermark Image...")
+        .set(ids.watermark_image_button, ui)
+    {
+        if let Some(path) = rfd::FileDialog::new()
+            .add_filter("Image", &["png", "jpg", "jpeg"])
+            .pick_file()
+        {
+            state.commands.push(Command::LoadWatermark(path));
+        }
+    }
+
+    let watermark = &mut state.watermark;
+    for event in widget::TextBox::new(&watermark.text)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .font_size(15)
+        .set(ids.watermark_text, ui)
+    {
+        if let widget::text_box::Event::Update(text) = event {
+            watermark.text = text;
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .label(&format!("Watermark: {}", watermark.anchor.name()))
+        .set(ids.watermark_anchor_button, ui)
+    {
+        watermark.anchor = watermark.anchor.next();
+    }
+
+    if let Some(value) = slider(watermark.opacity, 0.0, 1.0)
+        .down(10.0)
+        .label("Watermark Opacity")
+        .set(ids.watermark_opacity, ui)
+    {
+        watermark.opacity = value;
+    }
+
+    if let Some(value) = slider(watermark.margin, 0.0, 64.0)
+        .down(10.0)
+        .label("Watermark Margin")
+        .set(ids.watermark_margin, ui)
+    {
+        watermark.margin = value.round();
+    }
+}
+
+fn hooks_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    widget::Text::new("Post-export command ({file} = path)")
+        .down(10.0)
+        .font_size(12)
+        .rgb(1.0, 1.0, 1.0)
+        .set(ids.post_export_command_label, ui);
+
+    for event in widget::TextBox::new(&state.post_export_command)
+        .down(4.0)
+        .w_h(200.0, 30.0)
+        .font_size(15)
+        .set(ids.post_export_command, ui)
+    {
+        if let widget::text_box::Event::Update(text) = event {
+            state.post_export_command = text;
+        }
+    }
+
+    let hook_log = &mut state.hook_log;
+    hook_log.poll();
+    let recent = &hook_log.lines[hook_log.lines.len().saturating_sub(5)..];
+    if ids.hook_log_lines.len() < recent.len() {
+        ids.hook_log_lines
+            .resize(recent.len(), &mut ui.widget_id_generator());
+    }
+    for (i, line) in recent.iter().enumerate() {
+        widget::Text::new(line)
+            .down(4.0)
+            .font_size(12)
+            .rgb(0.8, 0.8, 0.8)
+            .set(ids.hook_log_lines[i], ui);
+    }
+}
+
+fn console_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for value in widget::Toggle::new(state.console_visible)
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Console")
+        .set(ids.console_toggle, ui)
+    {
+        state.console_visible = value;
+    }
+
+    if state.console_visible {
+        for _click in widget::Button::new()
+            .down(10.0)
+            .label(&format!("Show: {}", state.console_level))
+            .set(ids.console_level_button, ui)
+        {
+            state.console_level = match state.console_level {
+                log::LevelFilter::Error => log::LevelFilter::Warn,
+                log::LevelFilter::Warn => log::LevelFilter::Info,
+                log::LevelFilter::Info => log::LevelFilter::Debug,
+                _ => log::LevelFilter::Error,
+            };
+        }
+
+        console::with_recent(state.console_level, 10, |entries| {
+            if ids.console_lines.len() < entries.len() {
+                ids.console_lines
+                    .resize(entries.len(), &mut ui.widget_id_generator());
+            }
+            for (i, entry) in entries.iter().enumerate() {
+                let (r, g, b) = match entry.level {
+                    log::Level::Error => (1.0, 0.4, 0.4),
+                    log::Level::Warn => (1.0, 0.8, 0.3),
+                    _ => (0.8, 0.8, 0.8),
+                };
+                widget::Text::new(&format!(
+                    "[{}] {}: {}",
+                    entry.level, entry.target, entry.message
+                ))
+                .down(4.0)
+                .w(400.0)
+                .font_size(12)
+                .rgb(r, g, b)
+                .set(ids.console_lines[i], ui);
+            }
+        });
+    }
+}
+
+/// Preferences kept in the config file, and the files opened or saved lately.
+fn settings_panel(ids: &mut WorkbenchIds, ui: &mut UiCell, state: &mut GlobalState) {
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label(&format!("Theme: {}", state.config.theme.name()))
+        .set(ids.settings_theme, ui)
+    {
+        state.config.theme = state.config.theme.next();
+    }
+
+    let label = format!("Autosave Every: {}s", state.config.autosave_seconds.round());
+    if let Some(value) = slider(state.config.autosave_seconds, 5.0, 600.0)
+        .down(10.0)
+        .label(&label)
+        .set(ids.settings_autosave, ui)
+    {
+        state.config.autosave_seconds = value.round();
+    }
+
+    widget::Text::new("Recent Files")
+        .down(10.0)
+        .font_size(12)
+        .rgb(1.0, 1.0, 1.0)
+        .set(ids.settings_recent_label, ui);
+
+    let recent = state.config.recent_files.clone();
+    if ids.settings_recent.len() < recent.len() {
+        ids.settings_recent
+            .resize(recent.len(), &mut ui.widget_id_generator());
+    }
+    for (i, path) in recent.into_iter().enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        for _click in widget::Button::new()
+            .down(4.0)
+            .w_h(200.0, 24.0)
+            .label_font_size(12)
+            .label(&name)
+            .set(ids.settings_recent[i], ui)
+        {
+            state.commands.push(Command::Open(path.clone()));
+        }
+    }
+
+    for _click in widget::Button::new()
+        .down(10.0)
+        .w_h(200.0, 30.0)
+        .label("Clear Recent Files")
+        .set(ids.settings_clear_recent, ui)
+    {
+        state.config.recent_files.clear();
+    }
+}
@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use crate::workbench::Panel;
+use crate::{GlobalState, Mode};
+
+/// A named Workbench layout and tool setup for a kind of work.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct WorkspacePreset {
+    pub name: String,
+    pub panels: Vec<Panel>,
+    pub mode: Mode,
+    /// Use linear filtering when drawing the zoomed canvas instead of hard pixel edges.
+    pub smooth_sampling: bool,
+    pub tile_grid: bool,
+    pub tile_size: f32,
+}
+
+impl WorkspacePreset {
+    pub fn builtin() -> Vec<WorkspacePreset> {
+        vec![
+            WorkspacePreset {
+                name: "Painting".to_string(),
+                panels: vec![
+                    Panel::Canvas,
+                    Panel::NewImage,
+                    Panel::Documents,
+                    Panel::Print,
+                    Panel::Color,
+                    Panel::Brush,
+                    Panel::Gradient,
+                    Panel::Layers,
+                    Panel::Clipboard,
+                    Panel::Stamp,
+                    Panel::Filters,
+                    Panel::Perspective,
+                    Panel::Composition,
+                    Panel::Console,
+                    Panel::Settings,
+                ],
+                mode: Mode::Paint,
+                smooth_sampling: true,
+                tile_grid: false,
+                tile_size: 16.0,
+            },
+            WorkspacePreset {
+                name: "Pixel Art".to_string(),
+                panels: vec![
+                    Panel::Canvas,
+                    Panel::NewImage,
+                    Panel::Documents,
+                    Panel::Print,
+                    Panel::Resize,
+                    Panel::Color,
+                    Panel::Palette,
+                    Panel::Brush,
+                    Panel::Layers,
+                    Panel::Clipboard,
+                    Panel::Tiles,
+                    Panel::NineSlice,
+                    Panel::Guides,
+                    Panel::Stamp,
+                    Panel::Video,
+                    Panel::SpriteSheet,
+                    Panel::ChannelPack,
+                    Panel::Compare,
+                    Panel::Settings,
+                ],
+                mode: Mode::Paint,
+                smooth_sampling: false,
+                tile_grid: true,
+                tile_size: 16.0,
+            },
+            WorkspacePreset {
+                name: "Photo".to_string(),
+                panels: vec![
+                    Panel::Canvas,
+                    Panel::NewImage,
+                    Panel::Documents,
+                    Panel::Print,
+                    Panel::Raw,
+                    Panel::Resize,
+                    Panel::Layers,
+                    Panel::Composition,
+                    Panel::Filters,
+                    Panel::Compare,
+                    Panel::Batch,
+                    Panel::Watermark,
+                    Panel::Hooks,
+                    Panel::Settings,
+                ],
+                mode: Mode::Move,
+                smooth_sampling: true,
+                tile_grid: false,
+                tile_size: 16.0,
+            },
+        ]
+    }
+
+    /// Brings presets saved by an older version up to date. Panels that weren't in `seen`, every
+    /// panel there was when the presets were saved, are added to the saved presets whose
+    /// built-in version has them, and built-in presets missing by name are added whole. Panels
+    /// the user took out of a preset stay out.
+    pub fn merge(saved: &mut Vec<WorkspacePreset>, seen: &[Panel]) {
+        for builtin in WorkspacePreset::builtin() {
+            let preset = match saved.iter_mut().find(|p| p.name == builtin.name) {
+                Some(preset) => preset,
+                None => {
+                    saved.push(builtin);
+                    continue;
+                }
+            };
+            for panel in builtin.panels {
+                if !seen.contains(&panel) && !preset.panels.contains(&panel) {
+                    preset.panels.push(panel);
+                }
+            }
+        }
+    }
+
+    /// Switches the editor over to this preset and remembers it in the config.
+    pub fn apply(self, state: &mut GlobalState) {
+        state.panels = self.panels;
+        state.mode = self.mode;
+        state.smooth_sampling = self.smooth_sampling;
+        state.tile_mode = self.tile_grid;
+        state.tile_size = self.tile_size;
+
+        state.config.workspace = self.name;
+        if let Err(e) = state.config.save() {
+            log::warn!("Failed to save config: {}", e);
+        }
+    }
+}
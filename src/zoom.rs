@@ -0,0 +1,41 @@
+//! Eased jumps of the view, so fitting the canvas, going to 100%, zooming to the selection or
+//! turning the canvas glides there instead of snapping.
+
+use crate::viewport::Viewport;
+
+/// Length of a transition in seconds.
+const DURATION: f32 = 0.25;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Target {
+    /// The whole canvas in the window, upright.
+    Fit,
+    /// One canvas pixel per screen pixel, around the current center.
+    ActualSize,
+    /// This many physical pixels per canvas pixel, around the current center.
+    Scale(f32),
+    Selection,
+    /// Turned by this many degrees counterclockwise around the window's center.
+    Rotate(f32),
+}
+
+pub struct Transition {
+    from: Viewport,
+    to: Viewport,
+    /// App time the transition started at.
+    start: f32,
+}
+
+impl Transition {
+    pub fn new(from: Viewport, to: Viewport, start: f32) -> Transition {
+        Transition { from, to, start }
+    }
+
+    /// The view at app time `now`, and whether the transition is over.
+    pub fn at(&self, now: f32) -> (Viewport, bool) {
+        let t = ((now - self.start) / DURATION).clamp(0.0, 1.0);
+        // Ease out, so the view responds at once and settles gently
+        let eased = 1.0 - (1.0 - t).powi(3);
+        (self.from.toward(&self.to, eased), t >= 1.0)
+    }
+}